@@ -0,0 +1,93 @@
+/// What /allinvoo buys until the user picks something else with /ticker.
+pub const DEFAULT_TICKER: &str = "VOO";
+
+/// Parses `VOO 80 VXUS 20` into (ticker, percent) pairs that add up to 100.
+pub fn parse_targets(words: &[&str]) -> Option<Vec<(String, i64)>> {
+    if !words.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut targets: Vec<(String, i64)> = Vec::new();
+    for pair in words.chunks(2) {
+        let ticker = pair[0].to_ascii_uppercase();
+        let valid_ticker = (1..=10).contains(&ticker.len())
+            && ticker
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.');
+        let percent = pair[1].trim_end_matches('%').parse::<i64>().ok()?;
+        if !valid_ticker || !(1..=100).contains(&percent) || targets.iter().any(|t| t.0 == ticker) {
+            return None;
+        }
+        targets.push((ticker, percent));
+    }
+    (targets.iter().map(|t| t.1).sum::<i64>() == 100).then_some(targets)
+}
+
+/// Parses `/ticker set` arguments: one ticker (all of it), or ticker and
+/// percent pairs like /target takes.
+pub fn parse_tickers(words: &[&str]) -> Option<Vec<(String, i64)>> {
+    match words {
+        [ticker] => parse_targets(&[ticker, "100"]),
+        _ => parse_targets(words),
+    }
+}
+
+/// `VOO`, or `VTI 60% + VXUS 40%`
+pub fn label(tickers: &[(String, i64)]) -> String {
+    match tickers {
+        [(ticker, 100)] => ticker.clone(),
+        _ => tickers
+            .iter()
+            .map(|(ticker, percent)| format!("{ticker} {percent}%"))
+            .collect::<Vec<_>>()
+            .join(" + "),
+    }
+}
+
+/// Splits `amount` across the target tickers so that, after investing, the
+/// portfolio is as close to the targets as new money alone can get it (no
+/// selling). Each ticker gets a share of its shortfall; the parts always sum
+/// to `amount` exactly.
+pub fn split_contribution(
+    holdings: &[(String, i64)],
+    targets: &[(String, i64)],
+    amount: i64,
+) -> Vec<(String, i64)> {
+    let total_after = holdings.iter().map(|h| h.1).sum::<i64>() + amount;
+    let deficits: Vec<i64> = targets
+        .iter()
+        .map(|(ticker, percent)| {
+            let held = holdings
+                .iter()
+                .filter(|h| &h.0 == ticker)
+                .map(|h| h.1)
+                .sum::<i64>();
+            (total_after * percent / 100 - held).max(0)
+        })
+        .collect();
+    let total_deficit: i64 = deficits.iter().sum();
+    if total_deficit == 0 {
+        return targets.iter().map(|t| (t.0.clone(), 0)).collect();
+    }
+
+    let mut parts: Vec<i64> = deficits
+        .iter()
+        .map(|d| (amount as i128 * *d as i128 / total_deficit as i128) as i64)
+        .collect();
+    // Hand out the cents lost to rounding, biggest shortfall first.
+    let mut order: Vec<usize> = (0..parts.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(deficits[i]));
+    let mut left = amount - parts.iter().sum::<i64>();
+    for i in order.into_iter().cycle() {
+        if left == 0 {
+            break;
+        }
+        parts[i] += 1;
+        left -= 1;
+    }
+
+    targets
+        .iter()
+        .zip(parts)
+        .map(|(t, cents)| (t.0.clone(), cents))
+        .collect()
+}
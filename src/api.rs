@@ -0,0 +1,273 @@
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse},
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use crate::db::{Cursor, Db};
+use crate::kind::EntryKind;
+
+/// What a token may do. Each scope includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Scope {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "read" => Some(Scope::Read),
+            "write" => Some(Scope::Write),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+/// A fresh random token and the hash to store for it.
+pub fn new_token() -> (String, String) {
+    let token = format!("voo_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let hash = hash_token(&token);
+    (token, hash)
+}
+
+/// The secret part of a /publicpage URL. Unlike API tokens it only ever
+/// shows aggregates, so it's kept as is to be shown again.
+pub fn new_page_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Where a public page is served: under `PUBLIC_URL` when set, else just
+/// the path on the `API_LISTEN` server.
+pub fn page_url(token: &str) -> String {
+    let base = std::env::var("PUBLIC_URL").unwrap_or_default();
+    format!("{}/p/{token}", base.trim_end_matches('/'))
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+type ApiError = (StatusCode, &'static str);
+
+/// Resolves the bearer token to the ledger its owner records into, if it's
+/// valid and allows `needed`.
+async fn authorize(db: &Db, headers: &HeaderMap, needed: Scope) -> Result<Uuid, ApiError> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or((StatusCode::UNAUTHORIZED, "missing bearer token"))?;
+    let owner = db
+        .authenticate_api_token(&hash_token(token.trim()))
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::UNAUTHORIZED, "invalid or expired token"))?;
+    match Scope::parse(&owner.scope) {
+        Some(scope) if scope >= needed => Ok(db
+            .shared_ledger_id(owner.user_id)
+            .await
+            .map_err(internal)?
+            .unwrap_or(owner.user_id)),
+        _ => Err((StatusCode::FORBIDDEN, "token scope too narrow")),
+    }
+}
+
+fn internal(err: anyhow::Error) -> ApiError {
+    eprintln!("api error: {err:?}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+}
+
+/// Serves the REST API on `addr` until the process exits.
+pub async fn serve(addr: SocketAddr, db: Db) -> Result<()> {
+    let app = Router::new()
+        .route("/api/me/total", get(total))
+        .route("/api/me/summary", get(summary))
+        .route("/api/me/entries", get(entries).post(add_entry))
+        .route("/api/admin/stats", get(stats))
+        .route("/p/:token", get(public_page))
+        .with_state(db);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn total(State(db): State<Db>, headers: HeaderMap) -> Result<Json<Value>, ApiError> {
+    let user = authorize(&db, &headers, Scope::Read).await?;
+    let current = db.total_cents(user).await.map_err(internal)?;
+    let invested = db.history_total_cents(user).await.map_err(internal)?;
+    Ok(Json(json!({
+        "total_cents": current,
+        "invested_cents": invested,
+    })))
+}
+
+/// Flat numbers for dashboards (e.g. a Home Assistant RESTful sensor).
+async fn summary(State(db): State<Db>, headers: HeaderMap) -> Result<Json<Value>, ApiError> {
+    let user = authorize(&db, &headers, Scope::Read).await?;
+    let summary = crate::events::Summary::load(&db, user)
+        .await
+        .map_err(internal)?;
+    Ok(Json(summary.to_json()))
+}
+
+#[derive(Deserialize)]
+struct EntriesQuery {
+    limit: Option<i64>,
+}
+
+async fn entries(
+    State(db): State<Db>,
+    headers: HeaderMap,
+    Query(q): Query<EntriesQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let user = authorize(&db, &headers, Scope::Read).await?;
+    let limit = q.limit.unwrap_or(50).clamp(1, 500);
+    let entries = db
+        .last_entries(user, limit, None, Cursor::Newest)
+        .await
+        .map_err(internal)?;
+    Ok(Json(Value::Array(
+        entries
+            .into_iter()
+            .map(|e| {
+                json!({
+                    "amount_cents": e.amount_cents,
+                    "kind": e.kind,
+                    "reason": e.reason,
+                    "class": e.class,
+                    "occurred_at": e.occurred_at,
+                })
+            })
+            .collect(),
+    )))
+}
+
+#[derive(Deserialize)]
+struct NewEntry {
+    amount_cents: i64,
+    /// `save` (positive amounts) or `adjust` (any sign).
+    kind: String,
+    reason: Option<String>,
+}
+
+async fn add_entry(
+    State(db): State<Db>,
+    headers: HeaderMap,
+    Json(entry): Json<NewEntry>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let user = authorize(&db, &headers, Scope::Write).await?;
+    let kind = match (EntryKind::parse(&entry.kind), entry.amount_cents) {
+        (Some(kind @ EntryKind::Save), n) if n > 0 => kind,
+        (Some(kind @ EntryKind::Adjust), n) if n != 0 => kind,
+        _ => {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "kind must be save (amount > 0) or adjust (amount != 0)",
+            ))
+        }
+    };
+    if crate::quota::exceeded(&db, user, 1)
+        .await
+        .map_err(internal)?
+        .is_some()
+    {
+        return Err((StatusCode::TOO_MANY_REQUESTS, "entry quota reached"));
+    }
+    db.add_entry(
+        user,
+        entry.amount_cents,
+        kind,
+        entry.reason.clone(),
+        None,
+        None,
+    )
+    .await
+    .map_err(internal)?;
+    crate::events::entry_added(&db, user, entry.amount_cents, kind, entry.reason.as_deref()).await;
+    let total = db.total_cents(user).await.map_err(internal)?;
+    Ok((StatusCode::CREATED, Json(json!({ "total_cents": total }))))
+}
+
+async fn stats(State(db): State<Db>, headers: HeaderMap) -> Result<Json<Value>, ApiError> {
+    authorize(&db, &headers, Scope::Admin).await?;
+    let counts = db.table_counts().await.map_err(internal)?;
+    Ok(Json(Value::Object(
+        counts.into_iter().map(|(t, n)| (t, json!(n))).collect(),
+    )))
+}
+
+/// A /publicpage: the streak and % to goal and nothing else, so no name,
+/// amount or entry ever leaves through it. Unknown and revoked tokens look
+/// the same.
+async fn public_page(
+    State(db): State<Db>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = db
+        .public_page_owner(&token)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "no such page"))?;
+    let offset = crate::user_offset(&db, user).await.map_err(internal)?;
+    let days: Vec<time::Date> = db
+        .save_days(user, offset.whole_minutes().into())
+        .await
+        .map_err(internal)?
+        .iter()
+        .filter_map(|d| crate::parse_date(d))
+        .collect();
+    let today = time::OffsetDateTime::now_utc().to_offset(offset).date();
+    let goal_percent = crate::events::Summary::load(&db, user)
+        .await
+        .map_err(internal)?
+        .goal_percent;
+
+    let mut lines = vec![
+        format!(
+            "🔥 {}-day saving streak",
+            crate::streaks::daily(&days, today)
+        ),
+        format!("📅 {} weeks in a row", crate::streaks::weekly(&days, today)),
+    ];
+    if let Some(percent) = goal_percent {
+        lines.push(format!("🎯 {percent}% of the way to the goal"));
+    }
+    let body = lines
+        .iter()
+        .map(|l| format!("<p>{l}</p>"))
+        .collect::<String>();
+    let html = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width\">\
+         <meta name=\"robots\" content=\"noindex\">\
+         <title>Saving streak</title></head>\
+         <body style=\"font-family:sans-serif;font-size:1.5em;text-align:center\">\
+         {body}</body></html>"
+    );
+    Ok((
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::HeaderName::from_static("x-robots-tag"), "noindex"),
+        ],
+        Html(html),
+    ))
+}
@@ -0,0 +1,189 @@
+use anyhow::{bail, Result};
+use std::{env, fs, path::PathBuf};
+use teloxide::prelude::*;
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::db::Db;
+use crate::s3::Bucket;
+
+/// Days between two automatic backups.
+const BACKUP_EVERY_DAYS: i64 = 7;
+
+/// Remote copy of each verified backup, for hosts whose disk doesn't survive
+/// a redeploy.
+struct Remote {
+    bucket: Bucket,
+    prefix: String,
+    /// How many backups to keep in the bucket; older ones are deleted.
+    keep: usize,
+}
+
+/// Where automatic backups go and who hears about failures. Backups are off
+/// unless `BACKUP_DIR` or an S3 bucket is configured.
+pub struct Config {
+    dir: PathBuf,
+    remote: Option<Remote>,
+    admin_chat: Option<ChatId>,
+    /// Also upload each verified backup to the admin chat.
+    send_to_admin: bool,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Option<Self>> {
+        let remote = Bucket::from_env()?.map(|bucket| Remote {
+            bucket,
+            prefix: env::var("S3_PREFIX").unwrap_or_else(|_| "backups/".into()),
+            keep: env::var("BACKUP_KEEP")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8),
+        });
+        let dir = match env::var("BACKUP_DIR").ok().filter(|d| !d.is_empty()) {
+            Some(dir) => dir.into(),
+            // VACUUM INTO needs a file; stage it somewhere disposable.
+            None if remote.is_some() => env::temp_dir().join("voo-backups"),
+            None => return Ok(None),
+        };
+        let admin_chat = env::var("ADMIN_CHAT_ID")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(ChatId);
+        Ok(Some(Self {
+            dir,
+            remote,
+            admin_chat,
+            send_to_admin: env::var("BACKUP_TO_ADMIN").is_ok_and(|v| v == "1" || v == "true"),
+        }))
+    }
+}
+
+/// Takes a backup if the newest one is at least a week old, then verifies it.
+/// Meant to be called once a day.
+#[tracing::instrument(skip_all)]
+pub async fn run_weekly(bot: &Bot, db: &Db, cfg: &Config) -> Result<()> {
+    let today = OffsetDateTime::now_utc().date();
+    let mut remote_keys = match &cfg.remote {
+        Some(remote) => remote.bucket.list(&remote.prefix).await?,
+        None => Vec::new(),
+    };
+    let remote_latest = remote_keys.iter().filter_map(|k| backup_date(k)).max();
+    if latest_backup(cfg)?
+        .max(remote_latest)
+        .is_some_and(|d| today - d < Duration::days(BACKUP_EVERY_DAYS))
+    {
+        return Ok(());
+    }
+    fs::create_dir_all(&cfg.dir)?;
+    let path = cfg.dir.join(format!("bot-{today}.db"));
+    let expected = match db.backup_into(&path).await {
+        Ok(counts) => counts,
+        Err(err) => {
+            discard(&path);
+            return Err(err);
+        }
+    };
+
+    if let Err(err) = verify(&path, &expected).await {
+        // Left in place, it would pass for this week's backup and hold off
+        // the retry until next week.
+        discard(&path);
+        let text = format!("⚠️ Backup {} failed verification: {err}", path.display());
+        return alert(bot, cfg, text).await;
+    }
+
+    if let (true, Some(chat)) = (cfg.send_to_admin, cfg.admin_chat) {
+        let name = format!("bot-{today}.db");
+        if let Err(err) =
+            crate::render::send_file(bot, chat, &name, fs::read(&path)?, "🗄 Weekly backup").await
+        {
+            eprintln!("backup: sending to admin chat failed: {err:?}");
+        }
+    }
+
+    let Some(remote) = &cfg.remote else {
+        return Ok(());
+    };
+    let key = format!("{}bot-{today}.db", remote.prefix);
+    if let Err(err) = remote.bucket.put(&key, fs::read(&path)?).await {
+        return alert(bot, cfg, format!("⚠️ Uploading backup {key} failed: {err}")).await;
+    }
+    remote_keys.push(key);
+    remote_keys.retain(|k| backup_date(k).is_some());
+    remote_keys.sort();
+    remote_keys.dedup();
+    let stale = remote_keys.len().saturating_sub(remote.keep.max(1));
+    for old in &remote_keys[..stale] {
+        if let Err(err) = remote.bucket.delete(old).await {
+            eprintln!("backup: pruning {old} failed: {err:?}");
+        }
+    }
+    Ok(())
+}
+
+async fn alert(bot: &Bot, cfg: &Config, text: String) -> Result<()> {
+    eprintln!("backup: {text}");
+    if let Some(chat) = cfg.admin_chat {
+        bot.send_message(chat, text).await?;
+    }
+    Ok(())
+}
+
+/// Removes a snapshot that can't be trusted; a partial file may be all
+/// there is.
+fn discard(path: &std::path::Path) {
+    if let Err(err) = fs::remove_file(path) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            eprintln!("backup: removing {} failed: {err:?}", path.display());
+        }
+    }
+}
+
+/// Opens the snapshot read-only, checks its integrity and that every table
+/// has the rows the live database had when it was taken.
+#[tracing::instrument(skip_all)]
+async fn verify(path: &std::path::Path, live: &[(String, i64)]) -> Result<()> {
+    let copy = Db::open_read_only(path).await?;
+    let check = copy.integrity_check().await;
+    let snap = copy.table_counts().await;
+    copy.0.close().await;
+    let check = check?;
+    if check != ["ok"] {
+        bail!("integrity_check: {}", check.join("; "));
+    }
+    let snap = snap?;
+    let mismatches: Vec<String> = live
+        .iter()
+        .filter_map(|(table, n)| {
+            let m = snap.iter().find(|s| &s.0 == table).map(|s| s.1);
+            (m != Some(*n)).then(|| {
+                let m = m.map(|m| m.to_string()).unwrap_or_else(|| "missing".into());
+                format!("{table} live {n} vs backup {m}")
+            })
+        })
+        .collect();
+    if !mismatches.is_empty() {
+        bail!("row counts differ: {}", mismatches.join(", "));
+    }
+    Ok(())
+}
+
+/// Date of the newest `bot-YYYY-MM-DD.db` in the backup directory.
+fn latest_backup(cfg: &Config) -> Result<Option<Date>> {
+    let entries = match fs::read_dir(&cfg.dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut latest = None;
+    for entry in entries {
+        let name = entry?.file_name();
+        latest = latest.max(name.to_str().and_then(backup_date));
+    }
+    Ok(latest)
+}
+
+/// Date of a `bot-YYYY-MM-DD.db` file name or object key.
+fn backup_date(name: &str) -> Option<Date> {
+    let file = name.rsplit('/').next()?;
+    crate::parse_date(file.strip_prefix("bot-")?.strip_suffix(".db")?)
+}
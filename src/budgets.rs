@@ -0,0 +1,230 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use time::{macros::format_description, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::db::{Budget, Db, SavingProgress};
+use crate::stats::{Period, Span};
+use crate::{category_of, format_cents};
+
+/// Percentages of the `/budget 300` saving target that each get a message
+/// the first time the month's saves reach them.
+pub const SAVING_ALERTS: [i64; 3] = [50, 90, 100];
+
+/// `saved 150.00 of 300.00 (50%)`
+pub fn saving_line(target_cents: i64, saved_cents: i64) -> String {
+    format!(
+        "saved {} of {} ({}%)",
+        format_cents(saved_cents),
+        format_cents(target_cents),
+        saved_cents.max(0) * 100 / target_cents.max(1)
+    )
+}
+
+/// The highest alert in [`SAVING_ALERTS`] that `p` reached but wasn't sent.
+pub fn saving_alert_due(p: &SavingProgress) -> Option<i64> {
+    SAVING_ALERTS
+        .into_iter()
+        .rev()
+        .find(|pct| p.saved_cents * 100 >= pct * p.target_cents)
+        .filter(|pct| *pct > p.alerted_pct)
+}
+
+/// An envelope as it stands in one month.
+pub struct Envelope {
+    pub budget: Budget,
+    pub spent_cents: i64,
+}
+
+impl Envelope {
+    pub fn available_cents(&self) -> i64 {
+        self.budget.amount_cents + self.budget.carry_cents
+    }
+
+    pub fn line(&self) -> String {
+        let b = &self.budget;
+        let mut line = format!(
+            "#{}: {} / {}",
+            b.category,
+            format_cents(self.spent_cents),
+            format_cents(self.available_cents())
+        );
+        if b.carry_cents > 0 {
+            line.push_str(&format!(
+                " ({} + {} rolled over)",
+                format_cents(b.amount_cents),
+                format_cents(b.carry_cents)
+            ));
+        }
+        let left = self.available_cents() - self.spent_cents;
+        if left >= 0 {
+            line.push_str(&format!(", {} left", format_cents(left)));
+        } else {
+            line.push_str(&format!(", over by {}", format_cents(-left)));
+        }
+        if b.rollover {
+            line.push_str(" ↻");
+        }
+        line
+    }
+}
+
+/// Spending per category (first `#hashtag` of the reason) in a `YYYY-MM` month.
+pub async fn spending_by_category(
+    db: &Db,
+    user_id: Uuid,
+    month: &str,
+) -> Result<Vec<(String, i64)>> {
+    Ok(by_category(db.spending_by_reason(user_id, month).await?))
+}
+
+fn by_category(by_reason: Vec<(Option<String>, i64)>) -> Vec<(String, i64)> {
+    let mut by_category: Vec<(String, i64)> = Vec::new();
+    for (reason, spent) in by_reason {
+        let category = category_of(reason.as_deref());
+        match by_category.iter_mut().find(|c| c.0 == category) {
+            Some(c) => c.1 += spent,
+            None => by_category.push((category, spent)),
+        }
+    }
+    by_category
+}
+
+/// Budget against actual spending per envelope over a week or month that
+/// has ended, for the scheduler's summaries; `None` without envelopes. A
+/// week's budget is its days' share of each month's envelope.
+pub async fn variance(
+    db: &Db,
+    user_id: Uuid,
+    span: Span,
+    period: &Period,
+) -> Result<Option<String>> {
+    // (category, budget, actual)
+    let mut rows: Vec<(String, i64, i64)> = Vec::new();
+    if span == Span::Month {
+        for e in for_month(db, user_id, &period.label).await? {
+            rows.push((
+                e.budget.category.clone(),
+                e.available_cents(),
+                e.spent_cents,
+            ));
+        }
+    } else {
+        // Days of the week falling in each month.
+        let mut months: Vec<(Period, i64)> = Vec::new();
+        let mut day = period.start;
+        while day < period.end {
+            match months.last_mut() {
+                Some((m, days)) if day < m.end => *days += 1,
+                _ => months.push((Span::Month.containing(day).unwrap_or(period.clone()), 1)),
+            }
+            day = day.next_day().unwrap_or(period.end);
+        }
+        for (month, days) in &months {
+            let month_days = (month.end - month.start).whole_days().max(1);
+            for e in for_month(db, user_id, &month.label).await? {
+                let budget = (e.available_cents() * days + month_days / 2) / month_days;
+                match rows.iter_mut().find(|r| r.0 == e.budget.category) {
+                    Some(r) => r.1 += budget,
+                    None => rows.push((e.budget.category.clone(), budget, 0)),
+                }
+            }
+        }
+        let spent = by_category(
+            db.spending_by_reason_between(user_id, &period.start_str(), &period.end_str())
+                .await?,
+        );
+        for row in &mut rows {
+            row.2 = spent.iter().find(|c| c.0 == row.0).map_or(0, |c| c.1);
+        }
+    }
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let mut lines = vec![format!("📊 Budgets, {}:", period.label)];
+    for (category, budget, actual) in &rows {
+        lines.push(format!(
+            "#{category}: budget {}, actual {}, {}",
+            format_cents(*budget),
+            format_cents(*actual),
+            delta(*budget, *actual)
+        ));
+    }
+    let (budget, actual) = rows.iter().fold((0, 0), |(b, a), r| (b + r.1, a + r.2));
+    lines.push(format!(
+        "Total: budget {}, actual {}, {}",
+        format_cents(budget),
+        format_cents(actual),
+        delta(budget, actual)
+    ));
+    Ok(Some(lines.join("\n")))
+}
+
+/// `12.10 over` or `5.00 under`
+fn delta(budget: i64, actual: i64) -> String {
+    if actual > budget {
+        format!("{} over", format_cents(actual - budget))
+    } else {
+        format!("{} under", format_cents(budget - actual))
+    }
+}
+
+/// Active envelopes for a `YYYY-MM` month. Months without a row yet are
+/// filled in from the previous one, carrying unused budget forward for
+/// rollover envelopes, and stored once the month has started.
+pub async fn for_month(db: &Db, user_id: Uuid, month: &str) -> Result<Vec<Envelope>> {
+    let this_month = OffsetDateTime::now_utc().format(format_description!("[year]-[month]"))?;
+    let mut spent: HashMap<String, Vec<(String, i64)>> = HashMap::new();
+    let mut envelopes = Vec::new();
+    for mut budget in db.latest_budgets(user_id, month).await? {
+        while budget.month.as_str() < month {
+            let spent_cents = spent_in(db, user_id, &mut spent, &budget).await?;
+            budget.carry_cents = if budget.rollover {
+                (budget.amount_cents + budget.carry_cents - spent_cents).max(0)
+            } else {
+                0
+            };
+            budget.month = next_month(&budget.month);
+            if budget.month <= this_month {
+                db.put_budget(user_id, &budget).await?;
+            }
+        }
+        if budget.amount_cents == 0 && budget.carry_cents == 0 {
+            continue;
+        }
+        let spent_cents = spent_in(db, user_id, &mut spent, &budget).await?;
+        envelopes.push(Envelope {
+            budget,
+            spent_cents,
+        });
+    }
+    Ok(envelopes)
+}
+
+async fn spent_in(
+    db: &Db,
+    user_id: Uuid,
+    cache: &mut HashMap<String, Vec<(String, i64)>>,
+    budget: &Budget,
+) -> Result<i64> {
+    if !cache.contains_key(&budget.month) {
+        let by_category = spending_by_category(db, user_id, &budget.month).await?;
+        cache.insert(budget.month.clone(), by_category);
+    }
+    Ok(cache[&budget.month]
+        .iter()
+        .find(|c| c.0 == budget.category)
+        .map_or(0, |c| c.1))
+}
+
+fn next_month(month: &str) -> String {
+    let (year, m) = month.split_once('-').unwrap_or((month, "01"));
+    let year: i32 = year.parse().unwrap_or(1970);
+    let m: u8 = m.parse().unwrap_or(1);
+    if m >= 12 {
+        format!("{}-01", year + 1)
+    } else {
+        format!("{year}-{:02}", m + 1)
+    }
+}
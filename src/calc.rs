@@ -0,0 +1,271 @@
+//! Arithmetic in typed amounts: `/save 3*4.50 coffees`,
+//! `/adjust -(12.99+4.50)`. Numbers are read like any typed amount (see
+//! /decimal) and the arithmetic is exact; only the result is rounded to
+//! the cent, half away from zero.
+
+/// An exact value while evaluating: `num / den`, `den` always positive.
+#[derive(Debug, Clone, Copy)]
+struct Ratio {
+    num: i128,
+    den: i128,
+}
+
+impl Ratio {
+    fn new(num: i128, den: i128) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        let g = gcd(num.unsigned_abs(), den.unsigned_abs()).max(1) as i128;
+        let sign = if den < 0 { -1 } else { 1 };
+        Some(Ratio {
+            num: sign * num / g,
+            den: sign * den / g,
+        })
+    }
+
+    fn add(self, o: Self) -> Option<Self> {
+        Ratio::new(
+            self.num
+                .checked_mul(o.den)?
+                .checked_add(o.num.checked_mul(self.den)?)?,
+            self.den.checked_mul(o.den)?,
+        )
+    }
+
+    fn mul(self, o: Self) -> Option<Self> {
+        Ratio::new(self.num.checked_mul(o.num)?, self.den.checked_mul(o.den)?)
+    }
+
+    fn div(self, o: Self) -> Option<Self> {
+        Ratio::new(self.num.checked_mul(o.den)?, self.den.checked_mul(o.num)?)
+    }
+
+    fn neg(self) -> Self {
+        Ratio {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+
+    fn cents(self) -> Option<i64> {
+        let scaled = self.num.checked_mul(100)?;
+        let half = self.den / 2;
+        let rounded = if scaled < 0 {
+            (scaled - half) / self.den
+        } else {
+            (scaled + half) / self.den
+        };
+        i64::try_from(rounded).ok()
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Number(&'a str),
+    Op(char),
+    Open,
+    Close,
+}
+
+fn is_expression_char(c: char) -> bool {
+    c.is_ascii_digit() || " .,+-*/×÷()".contains(c)
+}
+
+fn tokens(expr: &str) -> Option<Vec<Token<'_>>> {
+    let mut out = Vec::new();
+    let mut chars = expr.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            ' ' => {}
+            '(' => out.push(Token::Open),
+            ')' => out.push(Token::Close),
+            '+' | '-' | '*' | '/' => out.push(Token::Op(c)),
+            '×' => out.push(Token::Op('*')),
+            '÷' => out.push(Token::Op('/')),
+            c if c.is_ascii_digit() || c == '.' || c == ',' => {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, d)) = chars.peek() {
+                    if !(d.is_ascii_digit() || d == '.' || d == ',') {
+                        break;
+                    }
+                    end = j + d.len_utf8();
+                    chars.next();
+                }
+                out.push(Token::Number(&expr[i..end]));
+            }
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+/// Recursive descent over `+ -`, then `* /`, then signs and parentheses.
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    comma: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn sum(&mut self) -> Option<Ratio> {
+        let mut value = self.product()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek() {
+            self.pos += 1;
+            let rhs = self.product()?;
+            value = value.add(if op == '-' { rhs.neg() } else { rhs })?;
+        }
+        Some(value)
+    }
+
+    fn product(&mut self) -> Option<Ratio> {
+        let mut value = self.factor()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.peek() {
+            self.pos += 1;
+            let rhs = self.factor()?;
+            value = if op == '*' {
+                value.mul(rhs)?
+            } else {
+                value.div(rhs)?
+            };
+        }
+        Some(value)
+    }
+
+    fn factor(&mut self) -> Option<Ratio> {
+        let token = self.peek()?;
+        self.pos += 1;
+        match token {
+            Token::Op('-') => Some(self.factor()?.neg()),
+            Token::Op('+') => self.factor(),
+            Token::Number(n) => Ratio::new(crate::parse_cents(n, self.comma).ok()?.into(), 100),
+            Token::Open => {
+                let value = self.sum()?;
+                (self.peek() == Some(Token::Close)).then_some(())?;
+                self.pos += 1;
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The value of `expr` in cents, if it is well-formed arithmetic.
+pub fn eval_cents(expr: &str, comma: bool) -> Option<i64> {
+    let mut parser = Parser {
+        tokens: tokens(expr)?,
+        pos: 0,
+        comma,
+    };
+    let value = parser.sum()?;
+    (parser.pos == parser.tokens.len()).then_some(())?;
+    value.cents()
+}
+
+/// An expression at the start of `input` and its value in cents, with the
+/// text after it. Plain amounts like `12.50` or `-3` aren't expressions:
+/// there must be an operator between two operands or parentheses.
+pub fn leading(input: &str, comma: bool) -> Option<(&str, i64, &str)> {
+    let input = input.trim_start();
+    let prefix_len = input
+        .char_indices()
+        .find(|(_, c)| !is_expression_char(*c))
+        .map_or(input.len(), |(i, _)| i);
+    let prefix = &input[..prefix_len];
+    // Longest first, then shorter ones ending at a space, so a date or a
+    // number in the reason doesn't spoil the expression before it.
+    let mut ends: Vec<usize> = prefix.match_indices(' ').map(|(i, _)| i).collect();
+    if input[prefix_len..]
+        .chars()
+        .next()
+        .is_none_or(char::is_whitespace)
+    {
+        ends.push(prefix_len);
+    }
+    for end in ends.into_iter().rev() {
+        let expr = prefix[..end].trim();
+        let operands = tokens(expr)?;
+        let arithmetic = operands.windows(2).any(|w| {
+            matches!(
+                w,
+                [Token::Number(_) | Token::Close, Token::Op(_)] | [_, Token::Open]
+            )
+        }) || operands.first() == Some(&Token::Open);
+        if !arithmetic {
+            continue;
+        }
+        if let Some(cents) = eval_cents(expr, comma) {
+            return Some((expr, cents, input[end..].trim()));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence_and_parentheses() {
+        assert_eq!(eval_cents("2+3*4", false), Some(1_400));
+        assert_eq!(eval_cents("(2+3)*4", false), Some(2_000));
+        assert_eq!(eval_cents("10-4/2", false), Some(800));
+        assert_eq!(eval_cents("2×3÷4", false), Some(150));
+        assert_eq!(eval_cents("-(12.99+4.50)", false), Some(-1_749));
+        assert_eq!(eval_cents("1,5*2", true), Some(300));
+    }
+
+    #[test]
+    fn malformed_or_undefined_is_none() {
+        assert_eq!(eval_cents("5/0", false), None);
+        assert_eq!(eval_cents("5/(2-2)", false), None);
+        assert_eq!(eval_cents("(1+2", false), None);
+        assert_eq!(eval_cents("1+", false), None);
+        assert_eq!(eval_cents("1 2", false), None);
+    }
+
+    #[test]
+    fn only_the_result_is_rounded_half_away_from_zero() {
+        assert_eq!(eval_cents("1/8", false), Some(13));
+        assert_eq!(eval_cents("-1/8", false), Some(-13));
+        assert_eq!(eval_cents("10/3", false), Some(333));
+        assert_eq!(eval_cents("10/3*3", false), Some(1_000));
+    }
+
+    #[test]
+    fn leading_splits_off_the_reason() {
+        assert_eq!(
+            leading("3*4.50 coffees", false),
+            Some(("3*4.50", 1_350, "coffees"))
+        );
+        assert_eq!(
+            leading("-(12.99+4.50) refund", false),
+            Some(("-(12.99+4.50)", -1_749, "refund"))
+        );
+        // A number in the reason doesn't spoil the expression before it.
+        assert_eq!(
+            leading("2*3 lunch 2 people", false),
+            Some(("2*3", 600, "lunch 2 people"))
+        );
+        assert_eq!(leading("5/0 oops", false), None);
+    }
+
+    #[test]
+    fn plain_amounts_and_dates_are_not_expressions() {
+        assert_eq!(leading("12.50 lunch", false), None);
+        assert_eq!(leading("-3", false), None);
+        assert_eq!(leading("12 2024-01-05", false), None);
+        assert_eq!(eval_cents("12.50", false), Some(1_250));
+    }
+}
@@ -0,0 +1,162 @@
+//! Category suggestions learned from the user's own entries: each word of
+//! a categorized reason counts toward that category (see the
+//! category_tokens table), and a new uncategorized reason gets the category
+//! its words went with most.
+
+use anyhow::Result;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use uuid::Uuid;
+
+use crate::category_of;
+use crate::db::Db;
+
+/// Words too common to say anything about a category.
+const STOPWORDS: [&str; 10] = [
+    "the", "and", "for", "with", "from", "some", "this", "that", "our", "your",
+];
+
+/// A guess needs this many past matches, and this share of all of them.
+const MIN_HITS: i64 = 2;
+const MIN_SHARE_PERCENT: i64 = 60;
+
+/// /autocategory: offer the category with a button, tag right away with an
+/// undo button, or neither.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Suggest,
+    Apply,
+    Off,
+}
+
+impl Mode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "suggest" => Some(Mode::Suggest),
+            "apply" => Some(Mode::Apply),
+            "off" => Some(Mode::Off),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mode::Suggest => "suggest",
+            Mode::Apply => "apply",
+            Mode::Off => "off",
+        }
+    }
+}
+
+/// The words of a reason that are worth learning from: lowercased, without
+/// hashtags, numbers, stopwords or anything shorter than 3 letters.
+pub fn tokens(reason: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    for word in reason.split_whitespace() {
+        if word.starts_with('#') {
+            continue;
+        }
+        let word = word
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
+        let useful = word.chars().count() >= 3
+            && !word.chars().all(|c| c.is_ascii_digit())
+            && !STOPWORDS.contains(&word.as_str());
+        if useful && !tokens.contains(&word) {
+            tokens.push(word);
+        }
+    }
+    tokens
+}
+
+/// A category picked for a new entry; `applied` once it is in the reason.
+#[derive(Debug, Clone)]
+pub struct Guess {
+    pub category: String,
+    pub applied: bool,
+}
+
+/// Looks for a confident category for an uncategorized `reason`. In
+/// apply mode the reason comes back with the `#category` appended.
+pub async fn prepare(
+    db: &Db,
+    uuid: Uuid,
+    reason: Option<String>,
+) -> Result<(Option<String>, Option<Guess>)> {
+    let mode = Mode::parse(&db.autocategory(uuid).await?).unwrap_or(Mode::Suggest);
+    let Some(text) = reason.as_deref() else {
+        return Ok((reason, None));
+    };
+    if mode == Mode::Off || category_of(Some(text)) != "uncategorized" {
+        return Ok((reason, None));
+    }
+    let hits = db.category_hits(uuid, &tokens(text)).await?;
+    let total: i64 = hits.iter().map(|(_, n)| n).sum();
+    let Some((category, top)) = hits.into_iter().next() else {
+        return Ok((reason, None));
+    };
+    // Long categories wouldn't fit the 64-byte callback data.
+    if top < MIN_HITS || top * 100 < total * MIN_SHARE_PERCENT || category.len() > 40 {
+        return Ok((reason, None));
+    }
+    let applied = mode == Mode::Apply;
+    let reason = if applied {
+        Some(with_category(text, &category))
+    } else {
+        reason
+    };
+    Ok((reason, Some(Guess { category, applied })))
+}
+
+pub fn with_category(reason: &str, category: &str) -> String {
+    format!("{reason} #{category}")
+}
+
+/// `reason` without its `#category` word.
+pub fn without_category(reason: &str, category: &str) -> String {
+    reason
+        .split_whitespace()
+        .filter(|w| {
+            !w.strip_prefix('#')
+                .is_some_and(|t| t.eq_ignore_ascii_case(category))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Callback data is `cat:{entry id}:{+|-}{category}`: add or remove it.
+fn keyboard(entry_id: i64, guess: &Guess) -> InlineKeyboardMarkup {
+    let button = if guess.applied {
+        InlineKeyboardButton::callback("↩️ Undo", format!("cat:{entry_id}:-{}", guess.category))
+    } else {
+        InlineKeyboardButton::callback(
+            format!("🏷 Tag #{}", guess.category),
+            format!("cat:{entry_id}:+{}", guess.category),
+        )
+    };
+    InlineKeyboardMarkup::new([[button]])
+}
+
+/// Sends an entry's confirmation, mentioning the guess with its button,
+/// through the outbox.
+pub async fn reply(
+    bot: &Bot,
+    db: &Db,
+    chat_id: ChatId,
+    mut text: String,
+    entry_id: i64,
+    guess: Option<Guess>,
+) -> Result<()> {
+    let Some(guess) = guess else {
+        return crate::outbox::send(bot, db, chat_id, text, None).await;
+    };
+    if guess.applied {
+        text.push_str(&format!(
+            "\n🏷 Tagged #{} from similar entries.",
+            guess.category
+        ));
+    } else {
+        text.push_str(&format!("\n🏷 Looks like #{}?", guess.category));
+    }
+    crate::outbox::send(bot, db, chat_id, text, Some(keyboard(entry_id, &guess))).await
+}
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Result};
+use image::{ImageOutputFormat, RgbImage};
+use plotters::prelude::*;
+use std::io::Cursor;
+use time::{Date, Duration};
+
+const WIDTH: u32 = 1000;
+const HEIGHT: u32 = 600;
+
+/// Renders a dated series of cent amounts as a PNG line chart.
+pub fn line_chart(title: &str, points: &[(Date, i64)]) -> Result<Vec<u8>> {
+    let first = points.first().ok_or_else(|| anyhow!("nothing to plot"))?.0;
+    // Plot against day offsets; plotters only knows chrono dates.
+    let series: Vec<(i64, f64)> = points
+        .iter()
+        .map(|(d, cents)| ((*d - first).whole_days(), *cents as f64 / 100.0))
+        .collect();
+    let x_max = series.last().map(|p| p.0).unwrap_or(0).max(1);
+    let (y_min, y_max) = y_range(series.iter().map(|p| p.1));
+
+    let mut buf = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buf, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 28))
+            .margin(20)
+            .x_label_area_size(40)
+            .y_label_area_size(80)
+            .build_cartesian_2d(0..x_max, y_min..y_max)?;
+        chart
+            .configure_mesh()
+            .x_labels(6)
+            .x_label_formatter(&|x| (first + Duration::days(*x)).to_string())
+            .y_label_formatter(&|y| format!("{y:.0}"))
+            .draw()?;
+        chart.draw_series(
+            AreaSeries::new(series.iter().copied(), y_min, BLUE.mix(0.15)).border_style(BLUE),
+        )?;
+        root.present()?;
+    }
+    encode_png(buf, WIDTH, HEIGHT)
+}
+
+const CELL: i32 = 16;
+const GAP: i32 = 3;
+const HEATMAP_LEFT: i32 = 40;
+const HEATMAP_TOP: i32 = 70;
+const EMPTY: RGBColor = RGBColor(235, 237, 240);
+const GREENS: [RGBColor; 4] = [
+    RGBColor(155, 233, 168),
+    RGBColor(64, 196, 99),
+    RGBColor(48, 161, 78),
+    RGBColor(33, 110, 57),
+];
+const REDS: [RGBColor; 4] = [
+    RGBColor(255, 200, 200),
+    RGBColor(250, 140, 140),
+    RGBColor(230, 80, 80),
+    RGBColor(180, 30, 30),
+];
+
+/// GitHub-style calendar of the 53 weeks ending at `end`: one cell per day,
+/// green for net saving, red for net spending, darker for bigger amounts.
+pub fn heatmap(title: &str, days: &[(Date, i64)], end: Date) -> Result<Vec<u8>> {
+    let weeks = 53;
+    let start = end
+        - Duration::days(end.weekday().number_days_from_monday() as i64)
+        - Duration::weeks(weeks - 1);
+    let width = (HEATMAP_LEFT + weeks as i32 * (CELL + GAP) + 20) as u32;
+    let height = (HEATMAP_TOP + 7 * (CELL + GAP) + 20) as u32;
+    let max = days.iter().map(|d| d.1.abs()).max().unwrap_or(0).max(1);
+
+    let mut buf = vec![0u8; (width * height * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buf, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+        root.draw(&Text::new(title, (HEATMAP_LEFT, 15), ("sans-serif", 22)))?;
+        let label = ("sans-serif", 13).into_font().color(&BLACK.mix(0.6));
+        for (row, name) in [(0, "Mon"), (2, "Wed"), (4, "Fri")] {
+            let y = HEATMAP_TOP + row * (CELL + GAP) + 2;
+            root.draw(&Text::new(name, (5, y), label.clone()))?;
+        }
+
+        let mut day = start;
+        let mut last_month = None;
+        while day <= end {
+            let col = ((day - start).whole_days() / 7) as i32;
+            let row = day.weekday().number_days_from_monday() as i32;
+            let x = HEATMAP_LEFT + col * (CELL + GAP);
+            let y = HEATMAP_TOP + row * (CELL + GAP);
+            if row == 0 && last_month != Some(day.month()) {
+                last_month = Some(day.month());
+                let name = format!("{:?}", day.month());
+                root.draw(&Text::new(
+                    name[..3].to_string(),
+                    (x, HEATMAP_TOP - 18),
+                    label.clone(),
+                ))?;
+            }
+            let net = days.iter().find(|d| d.0 == day).map(|d| d.1).unwrap_or(0);
+            let color = match net {
+                0 => EMPTY,
+                n => {
+                    let level = ((n.abs() * 4 + max - 1) / max).clamp(1, 4) as usize - 1;
+                    if n > 0 {
+                        GREENS[level]
+                    } else {
+                        REDS[level]
+                    }
+                }
+            };
+            root.draw(&Rectangle::new(
+                [(x, y), (x + CELL, y + CELL)],
+                color.filled(),
+            ))?;
+            day = day.next_day().ok_or_else(|| anyhow!("date overflow"))?;
+        }
+        root.present()?;
+    }
+    encode_png(buf, width, height)
+}
+
+/// Donut chart of (label, cents) slices, each labelled with its amount.
+pub fn pie(title: &str, slices: &[(String, i64)]) -> Result<Vec<u8>> {
+    let sizes: Vec<f64> = slices.iter().map(|s| s.1 as f64).collect();
+    let labels: Vec<String> = slices
+        .iter()
+        .map(|(name, cents)| format!("{name} {}", crate::format_cents(*cents)))
+        .collect();
+    let colors: Vec<RGBColor> = (0..slices.len())
+        .map(|i| {
+            let (r, g, b) = Palette99::COLORS[i % Palette99::COLORS.len()];
+            RGBColor(r, g, b)
+        })
+        .collect();
+
+    let mut buf = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buf, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let root = root.titled(title, ("sans-serif", 28))?;
+        let (w, h) = root.dim_in_pixel();
+        let center = (w as i32 / 2, h as i32 / 2);
+        let radius = h as f64 * 0.35;
+        let mut pie = Pie::new(&center, &radius, &sizes, &colors, &labels);
+        pie.donut_hole(radius * 0.5);
+        pie.label_style(("sans-serif", 18).into_font().color(&BLACK));
+        pie.percentages(("sans-serif", 14).into_font().color(&WHITE));
+        root.draw(&pie)?;
+        root.present()?;
+    }
+    encode_png(buf, WIDTH, HEIGHT)
+}
+
+/// Pixels per QR module, and the quiet-zone width in modules.
+const QR_SCALE: u32 = 10;
+const QR_QUIET: u32 = 4;
+
+/// Renders `text` as a black-on-white QR code PNG.
+pub fn qr(text: &str) -> Result<Vec<u8>> {
+    let code = qrcode::QrCode::new(text.as_bytes())?;
+    let modules = code.width() as u32;
+    let colors = code.to_colors();
+    let size = (modules + 2 * QR_QUIET) * QR_SCALE;
+    let mut buf = vec![255u8; (size * size * 3) as usize];
+    for (i, color) in colors.iter().enumerate() {
+        if *color == qrcode::Color::Light {
+            continue;
+        }
+        let (mx, my) = (i as u32 % modules + QR_QUIET, i as u32 / modules + QR_QUIET);
+        for y in my * QR_SCALE..(my + 1) * QR_SCALE {
+            let row = (y * size + mx * QR_SCALE) as usize * 3;
+            buf[row..row + QR_SCALE as usize * 3].fill(0);
+        }
+    }
+    encode_png(buf, size, size)
+}
+
+/// Pads the value range so flat or single-point series still get a visible axis.
+fn y_range(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    let min = min.min(0.0);
+    let pad = ((max - min) * 0.1).max(1.0);
+    (min, max + pad)
+}
+
+fn encode_png(buf: Vec<u8>, width: u32, height: u32) -> Result<Vec<u8>> {
+    let img = RgbImage::from_raw(width, height, buf).ok_or_else(|| anyhow!("bad image buffer"))?;
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, ImageOutputFormat::Png)?;
+    Ok(out.into_inner())
+}
@@ -0,0 +1,172 @@
+//! `/close`: a month-end checklist walked through with inline buttons.
+//! Uncategorized spending is reviewed first. Then budget variances are
+//! confirmed and the stash can optionally be invested. The run ends with
+//! one archived statement per month.
+
+use anyhow::Result;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use uuid::Uuid;
+
+use crate::db::Db;
+use crate::stats::Period;
+use crate::{
+    allocation, budgets, category_of, format_cents, format_signed_cents, invest_tickers, stats,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Review,
+    Budgets,
+    Invest,
+}
+
+impl Step {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "review" => Some(Step::Review),
+            "budgets" => Some(Step::Budgets),
+            "invest" => Some(Step::Invest),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Step::Review => "review",
+            Step::Budgets => "budgets",
+            Step::Invest => "invest",
+        }
+    }
+}
+
+/// Buttons for a step. Callback data is `close:{month}:{next|invest|skip|no}`.
+pub fn keyboard(month: &str, step: Step) -> InlineKeyboardMarkup {
+    let button = |label: &str, action: &str| {
+        InlineKeyboardButton::callback(label, format!("close:{month}:{action}"))
+    };
+    let row = match step {
+        Step::Review => vec![button("▶️ Continue", "next"), button("✖️ Cancel", "no")],
+        Step::Budgets => vec![button("✅ Confirm", "next"), button("✖️ Cancel", "no")],
+        Step::Invest => vec![
+            button("📈 Invest all", "invest"),
+            button("⏭ Skip", "skip"),
+            button("✖️ Cancel", "no"),
+        ],
+    };
+    InlineKeyboardMarkup::new([row])
+}
+
+/// The message shown for `step` of closing `month`.
+pub async fn prompt(db: &Db, uuid: Uuid, month: &Period, step: Step) -> Result<String> {
+    let mut lines = Vec::new();
+    match step {
+        Step::Review => {
+            lines.push(format!(
+                "🧾 Closing {}: step 1 of 3, uncategorized spending",
+                month.label
+            ));
+            let uncategorized = uncategorized(db, uuid, month).await?;
+            if uncategorized.is_empty() {
+                lines.push("Every spending entry has a #category. 👍".into());
+            } else {
+                for e in &uncategorized {
+                    lines.push(format!(
+                        "#{} {} {}{}",
+                        e.id,
+                        e.occurred_at.get(..10).unwrap_or(&e.occurred_at),
+                        format_signed_cents(e.amount_cents),
+                        e.reason
+                            .as_ref()
+                            .map(|r| format!(" — {r}"))
+                            .unwrap_or_default()
+                    ));
+                }
+                lines.push(
+                    "\nAdd a #category with /edit {id} {amount} #category reason, then continue."
+                        .into(),
+                );
+            }
+        }
+        Step::Budgets => {
+            lines.push(format!(
+                "🧾 Closing {}: step 2 of 3, budget variances",
+                month.label
+            ));
+            let envelopes = budgets::for_month(db, uuid, &month.label).await?;
+            if envelopes.is_empty() {
+                lines.push("No budgets for this month.".into());
+            } else {
+                lines.extend(envelopes.iter().map(|e| e.line()));
+            }
+        }
+        Step::Invest => {
+            lines.push(format!("🧾 Closing {}: step 3 of 3, invest", month.label));
+            let tickers = invest_tickers(db, uuid).await?;
+            lines.push(format!(
+                "{} is still uninvested. Put it all in {} now?",
+                format_cents(db.total_cents(uuid).await?),
+                allocation::label(&tickers)
+            ));
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+async fn uncategorized(db: &Db, uuid: Uuid, month: &Period) -> Result<Vec<crate::db::Entry>> {
+    let filter = crate::db::EntryFilter {
+        after: Some(month.start_str()),
+        before: Some(month.end_str()),
+        ..Default::default()
+    };
+    let mut entries = db.matching_entries(uuid, &filter).await?;
+    entries.retain(|e| e.amount_cents < 0 && category_of(e.reason.as_deref()) == "uncategorized");
+    entries.reverse();
+    Ok(entries)
+}
+
+/// The archived statement: the month's flows, budgets and every entry.
+pub async fn statement(
+    db: &Db,
+    uuid: Uuid,
+    month: &Period,
+    invested_at_close: Option<i64>,
+) -> Result<String> {
+    let summary = db
+        .period_summary(uuid, &month.start_str(), &month.end_str())
+        .await?;
+    let mut lines = vec![
+        format!("Monthly statement for {}", month.label),
+        String::new(),
+        format!("Saved: {}", format_cents(summary.saved)),
+        format!("Spent: {}", format_cents(summary.spent)),
+        format!("Invested: {}", format_cents(summary.invested)),
+    ];
+    if let Some(rate) = stats::savings_rate(&summary) {
+        lines.push(format!("Savings rate: {rate}%"));
+    }
+    if let Some(moved) = invested_at_close {
+        lines.push(format!("Invested at close: {}", format_cents(moved)));
+    }
+    let envelopes = budgets::for_month(db, uuid, &month.label).await?;
+    if !envelopes.is_empty() {
+        lines.push("\nBudgets:".into());
+        lines.extend(envelopes.iter().map(|e| e.line()));
+    }
+    lines.push("\nEntries:".into());
+    let rows = db
+        .ledger_between(uuid, &month.start_str(), &month.end_str())
+        .await?;
+    if rows.is_empty() {
+        lines.push("(none)".into());
+    }
+    for r in rows {
+        lines.push(format!(
+            "{} {} [{}]{}",
+            r.occurred_at.get(..10).unwrap_or(&r.occurred_at),
+            format_signed_cents(r.amount_cents),
+            r.kind,
+            r.reason.map(|r| format!(" — {r}")).unwrap_or_default()
+        ));
+    }
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,79 @@
+//! /currency: how a user's amounts are written in replies. The ledger is
+//! cents of whatever currency the user saves in; this only picks the
+//! symbol, where it goes and the decimal mark.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Currency {
+    pub code: &'static str,
+    symbol: &'static str,
+    /// `$12.34` rather than `12,34 €`.
+    symbol_first: bool,
+    decimal_comma: bool,
+}
+
+impl Currency {
+    /// Bare numbers, as before /currency existed.
+    pub const PLAIN: Currency = Currency::new("", "", true, false);
+
+    pub const KNOWN: [Currency; 15] = [
+        Currency::new("USD", "$", true, false),
+        Currency::new("EUR", "€", false, true),
+        Currency::new("GBP", "£", true, false),
+        Currency::new("CHF", "CHF ", true, false),
+        Currency::new("CAD", "CA$", true, false),
+        Currency::new("AUD", "A$", true, false),
+        Currency::new("NZD", "NZ$", true, false),
+        Currency::new("CNY", "¥", true, false),
+        Currency::new("INR", "₹", true, false),
+        Currency::new("SEK", "kr", false, true),
+        Currency::new("NOK", "kr", false, true),
+        Currency::new("DKK", "kr.", false, true),
+        Currency::new("PLN", "zł", false, true),
+        Currency::new("CZK", "Kč", false, true),
+        Currency::new("BRL", "R$", true, true),
+    ];
+
+    const fn new(
+        code: &'static str,
+        symbol: &'static str,
+        symbol_first: bool,
+        decimal_comma: bool,
+    ) -> Self {
+        Currency {
+            code,
+            symbol,
+            symbol_first,
+            decimal_comma,
+        }
+    }
+
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::KNOWN
+            .into_iter()
+            .find(|c| c.code.eq_ignore_ascii_case(code.trim()))
+    }
+
+    /// `-$12.34`, `12,34 €`
+    pub fn format(self, cents: i64) -> String {
+        let sign = if cents < 0 { "-" } else { "" };
+        let number = crate::format_cents(cents.abs());
+        let number = if self.decimal_comma {
+            number.replace('.', ",")
+        } else {
+            number
+        };
+        if self.symbol.is_empty() {
+            format!("{sign}{number}")
+        } else if self.symbol_first {
+            format!("{sign}{}{number}", self.symbol)
+        } else {
+            format!("{sign}{number} {}", self.symbol)
+        }
+    }
+
+    /// `+$12.34`, `-$12.34`: [`Currency::format`] with the sign always shown.
+    pub fn format_signed(self, cents: i64) -> String {
+        let sign = if cents < 0 { "" } else { "+" };
+        format!("{sign}{}", self.format(cents))
+    }
+}
@@ -1,23 +1,514 @@
 use anyhow::Result;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use futures_util::{stream::BoxStream, StreamExt};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePoolOptions};
 use sqlx::{Row, SqlitePool};
 use std::{fs, path::Path, str::FromStr};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use uuid::Uuid;
 
+use crate::kind::EntryKind;
+
 #[derive(Debug, Clone)]
 pub struct Db(pub SqlitePool);
 
+const RECURRING_SELECT: &str = "SELECT id, user_id, chat_id, amount_cents, reason, every,
+        start_on, runs, next_on
+    FROM recurring_entries";
+
+const REMINDER_SELECT: &str = "SELECT r.user_id, r.chat_id, r.weekday, r.minute_of_day,
+        r.last_sent_on, COALESCE(s.utc_offset_minutes, 0) AS utc_offset_minutes
+    FROM reminders r
+    LEFT JOIN user_settings s ON s.user_id = r.user_id";
+
+/// Active goal check-ins, shaped like [`REMINDER_SELECT`] rows. A /shared
+/// ledger's goal keeps its creator's /timezone.
+const GOAL_CHECKIN_SELECT: &str = "SELECT c.user_id, c.chat_id, c.weekday, c.minute_of_day,
+        c.last_sent_on, COALESCE(s.utc_offset_minutes, 0) AS utc_offset_minutes
+    FROM goal_checkins c
+    JOIN goals g ON g.user_id = c.user_id
+    LEFT JOIN shared_ledgers l ON l.ledger_id = c.user_id
+    LEFT JOIN user_settings s ON s.user_id = COALESCE(l.created_by, c.user_id)
+    WHERE c.paused = 0";
+
+/// /shared ledgers' pseudo-users take Telegram ids from here down, past
+/// any real user, group or channel id (`-100…` channels reach about
+/// -2 × 10¹²).
+const SHARED_LEDGER_TG_IDS: i64 = -4_000_000_000_000;
+
+/// Who gets a ledger's direct messages, as (ledger_id, tg_user_id): a
+/// person's own ledger goes to them, a /shared one to each member who
+/// hasn't turned /nudges off. A group pot goes to nobody.
+const LEDGER_RECIPIENTS: &str = "(
+    SELECT id AS ledger_id, tg_user_id FROM users WHERE kind IS NULL
+    UNION ALL
+    SELECT m.ledger_id, p.tg_user_id
+    FROM shared_members m
+    JOIN users p ON p.id = m.user_id
+    LEFT JOIN user_settings ps ON ps.user_id = p.id
+    WHERE COALESCE(ps.nudges, 1) = 1
+)";
+
+/// The uninvested entries an /allinvoo takes: `whole` ones by id, plus
+/// `split`, an entry of which only the given cents are invested while the
+/// rest stays in current. `cents` is the total taken.
+#[derive(Debug, Clone, Default)]
+pub struct InvestSelection {
+    pub whole: Vec<i64>,
+    pub split: Option<(i64, i64)>,
+    pub cents: i64,
+}
+
 /// A single ledger entry (moved to module scope so Rust is happy)
 #[derive(Debug, Clone)]
 pub struct Entry {
+    pub id: i64,
+    pub amount_cents: i64,
+    pub kind: EntryKind,
+    pub reason: Option<String>,
+    pub class: Option<String>,
+    pub occurred_at: String,
+}
+
+/// Everything known about one entry, for /show.
+#[derive(Debug, Clone)]
+pub struct EntryDetail {
+    pub entry: Entry,
+    pub created_at: String,
+    /// When /allinvoo moved it to history; `None` while still uninvested.
+    pub archived_at: Option<String>,
+    pub source_chat_id: Option<i64>,
+    pub source_message_id: Option<i32>,
+    /// Earlier versions from /edit, oldest first.
+    pub edits: Vec<EntryEdit>,
+    /// The amount and currency it was typed in, if converted.
+    pub original: Option<(i64, String)>,
+    /// Set on both legs of a /transfer.
+    pub transfer_id: Option<i64>,
+}
+
+/// One row of the full ledger for /export. `batch` is the /allinvoo time
+/// that archived the entry, if any.
+#[derive(Debug, Clone)]
+pub struct LedgerRow {
+    pub occurred_at: String,
+    pub amount_cents: i64,
+    pub kind: EntryKind,
+    pub reason: Option<String>,
+    pub batch: Option<String>,
+}
+
+/// Where a page of [`Db::last_entries`] starts: the newest entries, or
+/// those just older (`Before`) or newer (`After`) than an entry id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cursor {
+    Newest,
+    Before(i64),
+    After(i64),
+}
+
+/// Newest entries whose reason matched a /search, out of `matches` in
+/// total netting `net_cents`. `id` is how /show finds the entry; archived
+/// entries from before ids were kept have none.
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    pub matches: i64,
+    pub net_cents: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub id: Option<i64>,
+    pub occurred_at: String,
+    pub amount_cents: i64,
+    pub kind: EntryKind,
+    pub reason: Option<String>,
+    pub invested: bool,
+}
+
+/// An invested entry for /historylog, with the totals of the whole
+/// /allinvoo batch it went out in.
+#[derive(Debug, Clone)]
+pub struct ArchivedEntry {
+    pub batch_id: i64,
+    pub archived_at: String,
+    pub strategy: Option<String>,
+    pub id: Option<i64>,
+    pub occurred_at: String,
+    pub amount_cents: i64,
+    pub kind: EntryKind,
+    pub reason: Option<String>,
+    pub batch_cents: i64,
+    pub batch_entries: i64,
+}
+
+/// One /allinvoo run for /history: `running_cents` is everything invested
+/// up to and including it.
+#[derive(Debug, Clone)]
+pub struct Investment {
+    pub archived_at: String,
+    pub strategy: Option<String>,
+    pub cents: i64,
+    pub running_cents: i64,
+}
+
+/// A message waiting in the outbox; `keyboard` is the inline keyboard as
+/// JSON, and `attempts` counts the sends tried so far.
+#[derive(Debug, Clone)]
+pub struct QueuedMessage {
+    pub id: i64,
+    pub chat_id: i64,
+    pub text: String,
+    pub keyboard: Option<String>,
+    pub attempts: i64,
+}
+
+/// Shares bought by a strategy's /allinvoo runs (`None` is the main
+/// bucket), in millionths of a share.
+#[derive(Debug, Clone)]
+pub struct Holding {
+    pub strategy: Option<String>,
+    pub ticker: String,
+    pub micro_shares: i64,
+    pub cost_cents: i64,
+}
+
+/// One ticker bought by one /allinvoo run.
+#[derive(Debug, Clone)]
+pub struct Lot {
+    pub ticker: String,
+    pub micro_shares: i64,
+    pub cost_cents: i64,
+    /// RFC3339
+    pub bought_at: String,
+    /// The share price paid.
+    pub price_cents: i64,
+    /// USD per unit of the user's currency when bought; see `add_holding`.
+    pub fx_rate: Option<f64>,
+}
+
+/// A /close run for one `YYYY-MM` month. `statement` is set once the
+/// checklist is done.
+#[derive(Debug, Clone)]
+pub struct MonthClose {
+    /// `review`, `budgets` or `invest` while in progress; see `close::Step`.
+    pub step: String,
+    pub statement: Option<String>,
+    pub closed_at: Option<String>,
+}
+
+/// An entry waiting to be inserted by a confirmed /import.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NewEntry {
+    pub amount_cents: i64,
+    pub kind: EntryKind,
+    pub reason: Option<String>,
+    pub occurred_at: String,
+}
+
+/// What an entry looked like before one /edit.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EntryEdit {
+    pub amount_cents: i64,
+    pub reason: Option<String>,
+    pub edited_at: String,
+}
+
+/// Which uninvested entries /purge should match; `None` fields match all.
+#[derive(Debug, Clone, Default)]
+pub struct EntryFilter {
+    /// Case-insensitive substring of the reason.
+    pub reason: Option<String>,
+    pub kind: Option<EntryKind>,
+    /// `YYYY-MM-DD`, exclusive.
+    pub before: Option<String>,
+    /// `YYYY-MM-DD`, inclusive.
+    pub after: Option<String>,
+    /// RFC 3339; only entries recorded at or after this. Set by /immutable.
+    pub recorded_after: Option<String>,
+}
+
+/// A future-dated entry that doesn't count toward totals until `due_on`.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub id: i64,
+    pub user_id: Uuid,
+    pub chat_id: i64,
+    pub amount_cents: i64,
+    pub reason: Option<String>,
+    pub due_on: String,
+}
+
+/// A /recurring standing order. Run `runs` is due on `next_on`.
+#[derive(Debug, Clone)]
+pub struct Recurring {
+    pub id: i64,
+    pub user_id: Uuid,
+    pub chat_id: i64,
+    pub amount_cents: i64,
+    pub reason: Option<String>,
+    /// `daily`, `weekly`, `biweekly` or `monthly`; see `recurring::Every`.
+    pub every: String,
+    pub start_on: String,
+    pub runs: i64,
+    pub next_on: String,
+}
+
+/// A save proposed from a voice note or photo, waiting for the user to
+/// confirm it.
+#[derive(Debug, Clone)]
+pub struct Pending {
+    pub amount_cents: i64,
+    pub reason: Option<String>,
+    pub source: String,
+    /// The photo or voice note it was read from.
+    pub file_id: Option<String>,
+}
+
+/// A feature flag as stored: its /flag rollout, if one was set, and how
+/// many users are forced on or off.
+#[derive(Debug, Clone)]
+pub struct FlagRow {
+    pub flag: String,
+    pub percent: Option<i64>,
+    pub forced_on: i64,
+    pub forced_off: i64,
+}
+
+/// What /globalstats reports about the whole instance.
+#[derive(Debug, Clone)]
+pub struct InstanceStats {
+    pub users: i64,
+    pub active_7d: i64,
+    pub active_30d: i64,
+    pub db_bytes: i64,
+    /// (`YYYY-MM-DD`, entries created that day), days without entries left out.
+    pub entries_per_day: Vec<(String, i64)>,
+}
+
+/// An /allinvoo waiting for its ✅; `expired` once it is older than the
+/// cutoff given to [`Db::take_pending_invest`].
+#[derive(Debug, Clone)]
+pub struct PendingInvest {
+    pub amount_cents: i64,
+    pub strategy: Option<String>,
+    pub expired: bool,
+}
+
+/// A /transfer waiting for the recipient's ✅; `expired` once it is older
+/// than the cutoff given to [`Db::take_pending_transfer`].
+#[derive(Debug, Clone)]
+pub struct PendingTransfer {
+    pub from_user_id: Uuid,
+    /// Who sent it, to tell them how it went; the sender's ledger may be a
+    /// /shared one.
+    pub from_tg_user_id: i64,
+    pub from_name: String,
     pub amount_cents: i64,
-    pub kind: String,
     pub reason: Option<String>,
+    pub expired: bool,
+}
+
+/// A REST API token as listed by /tokens; the secret itself is never stored.
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub id: i64,
+    pub scope: String,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+/// A successfully authenticated API token.
+#[derive(Debug, Clone)]
+pub struct TokenOwner {
+    pub user_id: Uuid,
+    pub scope: String,
+}
+
+/// A savings target for the current stash, optionally with a deadline.
+#[derive(Debug, Clone)]
+pub struct Goal {
+    pub target_cents: i64,
+    pub name: Option<String>,
+    pub deadline: Option<String>,
+    /// Current total when the goal was set; pace is measured from here.
+    pub start_cents: i64,
+    pub created_at: String,
+}
+
+/// What one counterparty still owes on /loan, net of /repaid.
+#[derive(Debug, Clone)]
+pub struct Loan {
+    pub counterparty: String,
+    pub outstanding_cents: i64,
+    /// When the last loan or repayment with them was recorded.
+    pub last_at: String,
+}
+
+/// One month of a spending envelope. `carry_cents` is unused budget rolled
+/// over from the previous month; an amount of 0 means the envelope is off.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    pub category: String,
+    pub month: String,
+    pub amount_cents: i64,
+    pub rollover: bool,
+    pub carry_cents: i64,
+}
+
+/// A user whose uninvested cash is large or has been sitting for a while.
+#[derive(Debug, Clone)]
+pub struct IdleCash {
+    pub user_id: Uuid,
+    pub tg_user_id: i64,
+    pub total_cents: i64,
+    pub since: String,
+}
+
+/// A user with entries who hasn't sent a command since `last_seen_at`.
+#[derive(Debug, Clone)]
+pub struct InactiveUser {
+    pub user_id: Uuid,
+    pub tg_user_id: i64,
+    pub last_seen_at: String,
+}
+
+/// A registered user found by `@username`: someone who has recorded
+/// entries in a group for /nudge, or anyone for /split.
+#[derive(Debug, Clone)]
+pub struct ChatMember {
+    /// Whose entries they record: their /shared ledger if they are in one.
+    pub user_id: Uuid,
+    pub tg_user_id: i64,
+    pub first_name: Option<String>,
+}
+
+/// A /shared ledger and who is in it.
+#[derive(Debug, Clone)]
+pub struct SharedLedger {
+    pub ledger_id: Uuid,
+    pub name: String,
+    pub code: String,
+    /// First names, in the order they joined.
+    pub members: Vec<String>,
+}
+
+/// How far a user with a `/budget 300` saving target got in a month.
+#[derive(Debug, Clone)]
+pub struct SavingProgress {
+    pub user_id: Uuid,
+    pub tg_user_id: i64,
+    pub target_cents: i64,
+    /// /save entries in the month, invested or not.
+    pub saved_cents: i64,
+    /// The highest of the 50/90/100% alerts already sent for the month, or 0.
+    pub alerted_pct: i64,
+}
+
+/// A member on a chat's /leaderboard.
+#[derive(Debug, Clone)]
+pub struct Leader {
+    pub user_id: Uuid,
+    pub name: String,
+    pub saved_cents: i64,
+}
+
+/// A /remind or /goal checkin schedule in the user's local time. `weekday`
+/// is 0 = Monday for weekly schedules and `None` for daily ones.
+#[derive(Debug, Clone)]
+pub struct Reminder {
+    pub user_id: Uuid,
+    pub chat_id: i64,
+    pub weekday: Option<u8>,
+    pub minute_of_day: i64,
+    pub utc_offset_minutes: i64,
+    /// Local `YYYY-MM-DD` of the last ping.
+    pub last_sent_on: Option<String>,
+}
+
+/// A user whose monthly digest (or weekly /watchticker digest, or budget
+/// summary) hasn't gone out yet.
+#[derive(Debug, Clone)]
+pub struct DigestDue {
+    pub user_id: Uuid,
+    pub tg_user_id: i64,
+}
+
+/// Balances at the start of `day` (UTC), taken on the scheduler's first
+/// tick after midnight, so they are the previous day's closing totals.
+/// `invested_cents` is what was invested, at cost; `value_cents` is its
+/// market value.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub day: String,
+    pub cash_cents: i64,
+    pub invested_cents: i64,
+    /// What the invested part was worth at that day's last close, with
+    /// shares that had no price counted at cost; `None` for days from
+    /// before /networth.
+    pub value_cents: Option<i64>,
+}
+
+/// A user with a simulated savings rate whose interest hasn't been credited
+/// for the current month yet.
+#[derive(Debug, Clone)]
+pub struct InterestDue {
+    pub user_id: Uuid,
+    pub chat_id: i64,
+    pub rate_bp: i64,
+}
+
+/// Money flows over a date range, all as positive cents.
+#[derive(Debug, Clone, Default)]
+pub struct PeriodSummary {
+    pub saved: i64,
+    pub spent: i64,
+    pub invested: i64,
+    /// Every /dividend received, whether it went into the stash or not.
+    pub dividends: i64,
+}
+
+/// A stored receipt photo or voice note and its entry; `amount_cents` and
+/// `reason` are `None` once the entry was deleted.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub id: i64,
+    pub entry_id: i64,
+    pub source: String,
     pub created_at: String,
+    pub amount_cents: Option<i64>,
+    pub reason: Option<String>,
+}
+
+/// All /dividend payouts so far.
+#[derive(Debug, Clone, Default)]
+pub struct DividendTotals {
+    pub count: i64,
+    pub total_cents: i64,
+    /// The part that was added to the stash; the rest was reinvested.
+    pub to_stash_cents: i64,
+}
+
+/// Counts and extremes over a date range, for /stats.
+#[derive(Debug, Clone, Default)]
+pub struct PeriodStats {
+    /// Number of entries per kind, most frequent first.
+    pub kinds: Vec<(EntryKind, i64)>,
+    /// The entry with the largest absolute amount.
+    pub largest: Option<Entry>,
+}
+
+/// Money per 50/30/20 bucket for one month, all as positive cents.
+#[derive(Debug, Clone, Default)]
+pub struct ClassTotals {
+    pub needs: i64,
+    pub wants: i64,
+    pub savings: i64,
 }
 
 impl Db {
+    #[tracing::instrument(skip_all)]
     pub async fn new(database_url: &str) -> Result<Self> {
         // If it's a SQLite file path, ensure its parent directory exists
         if let Some(path) = sqlite_path_from_url(database_url) {
@@ -38,6 +529,18 @@ impl Db {
         Ok(db)
     }
 
+    /// Opens an existing database file without running migrations or
+    /// allowing writes, e.g. to inspect a backup.
+    #[tracing::instrument(skip_all)]
+    pub async fn open_read_only(path: &Path) -> Result<Self> {
+        let opts = SqliteConnectOptions::new().filename(path).read_only(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(opts)
+            .await?;
+        Ok(Self(pool))
+    }
+
     async fn init(&self) -> Result<()> {
         let schema = r#"
         PRAGMA journal_mode=WAL;
@@ -57,7 +560,9 @@ impl Db {
           amount_cents INTEGER NOT NULL,
           kind TEXT NOT NULL,
           reason TEXT,
+          class TEXT,
           created_at TEXT NOT NULL,
+          occurred_at TEXT,
           FOREIGN KEY(user_id) REFERENCES users(id)
         );
 
@@ -67,164 +572,5251 @@ impl Db {
           amount_cents INTEGER NOT NULL,
           kind TEXT NOT NULL,
           reason TEXT,
+          class TEXT,
           created_at TEXT NOT NULL,
+          occurred_at TEXT,
           archived_at TEXT NOT NULL,
           FOREIGN KEY(user_id) REFERENCES users(id)
         );
 
-        CREATE INDEX IF NOT EXISTS idx_entries_user ON entries(user_id);
-        CREATE INDEX IF NOT EXISTS idx_entries_history_user ON entries_history(user_id);
-        "#;
-
-        sqlx::query(schema).execute(&self.0).await?;
-        Ok(())
-    }
+        CREATE TABLE IF NOT EXISTS planned_entries(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          chat_id INTEGER NOT NULL,
+          amount_cents INTEGER NOT NULL,
+          reason TEXT,
+          due_on TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
 
-    pub async fn ensure_user(
-        &self,
-        tg_user_id: i64,
-        tg_username: Option<String>,
-        first_name: String, // <- String (not Option)
-        last_name: Option<String>,
-    ) -> Result<Uuid> {
-        if let Some(row) = sqlx::query("SELECT id FROM users WHERE tg_user_id = ?")
-            .bind(tg_user_id)
-            .fetch_optional(&self.0)
-            .await?
-        {
-            let id: String = row.get("id");
-            return Ok(Uuid::parse_str(&id)?);
-        }
+        CREATE TABLE IF NOT EXISTS recurring_entries(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          chat_id INTEGER NOT NULL,
+          amount_cents INTEGER NOT NULL,
+          reason TEXT,
+          every TEXT NOT NULL,
+          start_on TEXT NOT NULL,
+          runs INTEGER NOT NULL DEFAULT 0,
+          next_on TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
 
-        let id = Uuid::new_v4();
-        let now = OffsetDateTime::now_utc()
-            .format(&Rfc3339)
-            .unwrap_or_else(|_| "now".into());
+        -- The #hashtags of each entry's reason, kept by its entries.id (which
+        -- entries_history.entry_id carries over). Rows of deleted entries
+        -- match nothing and are ignored.
+        CREATE TABLE IF NOT EXISTS entry_tags(
+          entry_id INTEGER NOT NULL,
+          user_id TEXT NOT NULL,
+          tag TEXT NOT NULL,
+          PRIMARY KEY(entry_id, tag)
+        );
 
-        sqlx::query(
-            "INSERT INTO users(id, tg_user_id, tg_username, first_name, last_name, created_at)
-             VALUES(?, ?, ?, ?, ?, ?)",
-        )
-        .bind(id.to_string())
-        .bind(tg_user_id)
-        .bind(tg_username)
-        .bind(first_name)
-        .bind(last_name)
-        .bind(now)
-        .execute(&self.0)
-        .await?;
+        -- Words of a categorized entry's reason, for suggesting categories.
+        CREATE TABLE IF NOT EXISTS category_tokens(
+          entry_id INTEGER NOT NULL,
+          user_id TEXT NOT NULL,
+          token TEXT NOT NULL,
+          category TEXT NOT NULL,
+          PRIMARY KEY(entry_id, token)
+        );
 
-        Ok(id)
-    }
+        CREATE TABLE IF NOT EXISTS month_closes(
+          user_id TEXT NOT NULL,
+          month TEXT NOT NULL,
+          step TEXT NOT NULL,
+          statement TEXT,
+          closed_at TEXT,
+          PRIMARY KEY(user_id, month),
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
 
-    pub async fn add_entry(
-        &self,
-        user_id: Uuid,
-        amount_cents: i64,
-        kind: &str,
-        reason: Option<String>,
-    ) -> Result<()> {
-        let now = OffsetDateTime::now_utc()
-            .format(&Rfc3339)
-            .unwrap_or_else(|_| "now".into());
+        CREATE TABLE IF NOT EXISTS pending_entries(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          chat_id INTEGER NOT NULL,
+          amount_cents INTEGER NOT NULL,
+          reason TEXT,
+          source TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
 
-        sqlx::query(
-            "INSERT INTO entries(user_id, amount_cents, kind, reason, created_at)
-             VALUES(?, ?, ?, ?, ?)",
-        )
-        .bind(user_id.to_string())
-        .bind(amount_cents)
-        .bind(kind)
-        .bind(reason)
-        .bind(now)
-        .execute(&self.0)
-        .await?;
-        Ok(())
-    }
+        CREATE TABLE IF NOT EXISTS pending_purges(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          entry_ids TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
 
-    pub async fn total_cents(&self, user_id: Uuid) -> Result<i64> {
-        let row = sqlx::query(
-            "SELECT COALESCE(SUM(amount_cents),0) AS total FROM entries WHERE user_id = ?",
-        )
-        .bind(user_id.to_string())
-        .fetch_one(&self.0)
-        .await?;
-        let total: i64 = row.get("total");
-        Ok(total)
-    }
+        CREATE TABLE IF NOT EXISTS holdings(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          batch_id INTEGER NOT NULL,
+          strategy TEXT,
+          ticker TEXT NOT NULL,
+          price_cents INTEGER NOT NULL,
+          cost_cents INTEGER NOT NULL,
+          micro_shares INTEGER NOT NULL,
+          bought_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
 
+        CREATE TABLE IF NOT EXISTS dividends(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          amount_cents INTEGER NOT NULL,
+          received_on TEXT NOT NULL,
+          reason TEXT,
+          entry_id INTEGER,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS member_nudges(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          chat_id INTEGER NOT NULL,
+          sender_id TEXT NOT NULL,
+          target_id TEXT NOT NULL,
+          sent_at TEXT NOT NULL
+        );
+
+        -- Telegram file_ids of the receipt photo or voice note an entry was
+        -- confirmed from, by entries.id like entry_tags.
+        CREATE TABLE IF NOT EXISTS attachments(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          entry_id INTEGER NOT NULL,
+          file_id TEXT NOT NULL,
+          source TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS scheduler_jobs(
+          job TEXT PRIMARY KEY,
+          last_run_at TEXT NOT NULL,
+          next_run_at TEXT NOT NULL
+        );
+
+        -- /shared ledgers. Each is a pseudo-user like a group's /anonymous
+        -- pot, whose entries every member records into and sees.
+        CREATE TABLE IF NOT EXISTS shared_ledgers(
+          ledger_id TEXT PRIMARY KEY,
+          name TEXT NOT NULL,
+          code TEXT NOT NULL UNIQUE,
+          created_by TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(ledger_id) REFERENCES users(id)
+        );
+
+        -- One shared ledger per member at a time.
+        CREATE TABLE IF NOT EXISTS shared_members(
+          user_id TEXT PRIMARY KEY,
+          ledger_id TEXT NOT NULL,
+          joined_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id),
+          FOREIGN KEY(ledger_id) REFERENCES shared_ledgers(ledger_id)
+        );
+
+        -- The one running bot allowed to poll this database; see lease.rs.
+        CREATE TABLE IF NOT EXISTS instance_lease(
+          id INTEGER PRIMARY KEY CHECK(id = 1),
+          holder TEXT NOT NULL,
+          started_at TEXT NOT NULL,
+          heartbeat_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS outbox(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          chat_id INTEGER NOT NULL,
+          text TEXT NOT NULL,
+          keyboard TEXT,
+          attempts INTEGER NOT NULL DEFAULT 1,
+          next_attempt_at TEXT NOT NULL,
+          last_error TEXT,
+          created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS fx_rates(
+          base TEXT NOT NULL,
+          quote TEXT NOT NULL,
+          rate REAL NOT NULL,
+          fetched_at TEXT NOT NULL,
+          PRIMARY KEY(base, quote)
+        );
+
+        CREATE TABLE IF NOT EXISTS pending_transfers(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          from_user_id TEXT NOT NULL,
+          to_user_id TEXT NOT NULL,
+          from_tg_user_id INTEGER NOT NULL,
+          from_name TEXT NOT NULL,
+          amount_cents INTEGER NOT NULL,
+          reason TEXT,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(from_user_id) REFERENCES users(id),
+          FOREIGN KEY(to_user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS loans(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          counterparty TEXT NOT NULL COLLATE NOCASE,
+          amount_cents INTEGER NOT NULL,
+          reason TEXT,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS pending_invests(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          amount_cents INTEGER NOT NULL,
+          strategy TEXT,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS budgets(
+          user_id TEXT NOT NULL,
+          category TEXT NOT NULL,
+          month TEXT NOT NULL,
+          amount_cents INTEGER NOT NULL,
+          rollover INTEGER NOT NULL DEFAULT 0,
+          carry_cents INTEGER NOT NULL DEFAULT 0,
+          PRIMARY KEY(user_id, category, month),
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS pending_imports(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          entries TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS reminders(
+          user_id TEXT PRIMARY KEY,
+          chat_id INTEGER NOT NULL,
+          weekday INTEGER,
+          minute_of_day INTEGER NOT NULL,
+          last_sent_on TEXT,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS api_tokens(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          token_hash TEXT NOT NULL UNIQUE,
+          scope TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          expires_at TEXT,
+          last_used_at TEXT,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS goals(
+          user_id TEXT PRIMARY KEY,
+          target_cents INTEGER NOT NULL,
+          name TEXT,
+          deadline TEXT,
+          start_cents INTEGER NOT NULL,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS goal_checkins(
+          user_id TEXT PRIMARY KEY,
+          chat_id INTEGER NOT NULL,
+          weekday INTEGER,
+          minute_of_day INTEGER NOT NULL,
+          last_sent_on TEXT,
+          paused INTEGER NOT NULL DEFAULT 0,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS watched_tickers(
+          user_id TEXT NOT NULL,
+          ticker TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          PRIMARY KEY(user_id, ticker),
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS strategies(
+          user_id TEXT NOT NULL,
+          slug TEXT NOT NULL,
+          name TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          PRIMARY KEY(user_id, slug),
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS fees(
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          user_id TEXT NOT NULL,
+          amount_cents INTEGER NOT NULL,
+          reason TEXT,
+          occurred_at TEXT NOT NULL,
+          created_at TEXT NOT NULL,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        -- /flag rollouts: the share of users, 0-100, who get each feature.
+        CREATE TABLE IF NOT EXISTS feature_flags(
+          flag TEXT PRIMARY KEY,
+          percent INTEGER NOT NULL
+        );
+
+        -- Per-user /flag overrides that win over the rollout.
+        CREATE TABLE IF NOT EXISTS feature_flag_users(
+          flag TEXT NOT NULL,
+          user_id TEXT NOT NULL,
+          enabled INTEGER NOT NULL,
+          PRIMARY KEY(flag, user_id),
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS snapshots(
+          user_id TEXT NOT NULL,
+          day TEXT NOT NULL,
+          cash_cents INTEGER NOT NULL,
+          invested_cents INTEGER NOT NULL,
+          PRIMARY KEY(user_id, day),
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS allocation_targets(
+          user_id TEXT NOT NULL,
+          ticker TEXT NOT NULL,
+          percent INTEGER NOT NULL,
+          PRIMARY KEY(user_id, ticker),
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS user_settings(
+          user_id TEXT PRIMARY KEY,
+          interest_bp INTEGER NOT NULL DEFAULT 0,
+          interest_chat_id INTEGER,
+          interest_month TEXT,
+          FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS chat_settings(
+          chat_id INTEGER PRIMARY KEY,
+          language TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_entries_user ON entries(user_id);
+        CREATE INDEX IF NOT EXISTS idx_entries_history_user ON entries_history(user_id);
+        CREATE INDEX IF NOT EXISTS idx_loans_user ON loans(user_id, counterparty);
+        CREATE INDEX IF NOT EXISTS idx_planned_entries_due ON planned_entries(due_on);
+        CREATE INDEX IF NOT EXISTS idx_recurring_entries_next ON recurring_entries(next_on);
+        CREATE INDEX IF NOT EXISTS idx_entry_tags_user_tag ON entry_tags(user_id, tag);
+        CREATE INDEX IF NOT EXISTS idx_category_tokens_user ON category_tokens(user_id, token);
+        "#;
+
+        sqlx::query(schema).execute(&self.0).await?;
+
+        // Columns added after the first release; CREATE TABLE IF NOT EXISTS
+        // leaves older databases without them.
+        self.ensure_column("entries", "class", "TEXT").await?;
+        self.ensure_column("entries_history", "class", "TEXT")
+            .await?;
+        self.ensure_column("user_settings", "cash_drag_nudged_at", "TEXT")
+            .await?;
+        self.ensure_column("users", "last_seen_at", "TEXT").await?;
+        // Set while the user has the bot blocked; see outbox::send.
+        self.ensure_column("users", "blocked_at", "TEXT").await?;
+        // 'shared' for a /shared ledger's pseudo-user, 'pot' for a group's
+        // pot; NULL is a person. Only people get direct messages.
+        self.ensure_column("users", "kind", "TEXT").await?;
+        sqlx::query(
+            "UPDATE users SET kind = 'shared'
+             WHERE kind IS NULL AND id IN (SELECT ledger_id FROM shared_ledgers)",
+        )
+        .execute(&self.0)
+        .await?;
+        // Group chat ids are negative; people's never are.
+        sqlx::query("UPDATE users SET kind = 'pot' WHERE kind IS NULL AND tg_user_id < 0")
+            .execute(&self.0)
+            .await?;
+        self.ensure_column("snapshots", "value_cents", "INTEGER")
+            .await?;
+        // Where an entry was typed, and its id once archived, for /show.
+        for table in ["entries", "entries_history"] {
+            self.ensure_column(table, "source_chat_id", "INTEGER")
+                .await?;
+            self.ensure_column(table, "source_message_id", "INTEGER")
+                .await?;
+        }
+        self.ensure_column("entries_history", "entry_id", "INTEGER")
+            .await?;
+        // An entry typed in another currency, e.g. `/save 1000 JPY`, keeps
+        // what was typed next to the converted amount_cents.
+        for table in ["entries", "entries_history"] {
+            self.ensure_column(table, "original_cents", "INTEGER")
+                .await?;
+            self.ensure_column(table, "original_currency", "TEXT")
+                .await?;
+        }
+        // Both legs of a /transfer carry its id, so neither can be undone,
+        // edited or deleted on its own.
+        for table in ["entries", "entries_history"] {
+            self.ensure_column(table, "transfer_id", "INTEGER").await?;
+        }
+        // What booked an entry the user didn't type, so /undo passes over
+        // it: 'plan', 'recurring' or 'split'. Interest is told by its kind.
+        for table in ["entries", "entries_history"] {
+            self.ensure_column(table, "origin", "TEXT").await?;
+        }
+        // Slug of the /strategy an /allinvoo went into; NULL is the main bucket.
+        self.ensure_column("entries_history", "strategy", "TEXT")
+            .await?;
+        // Numbers each /allinvoo run; rows from before it are numbered by
+        // their shared archived_at.
+        self.ensure_column("entries_history", "batch_id", "INTEGER")
+            .await?;
+        sqlx::query(
+            "UPDATE entries_history SET batch_id = b.id
+             FROM (
+               SELECT user_id, archived_at, ROW_NUMBER() OVER (ORDER BY archived_at, user_id) AS id
+               FROM (SELECT DISTINCT user_id, archived_at FROM entries_history)
+             ) b
+             WHERE entries_history.batch_id IS NULL
+               AND b.user_id = entries_history.user_id AND b.archived_at = entries_history.archived_at
+               AND NOT EXISTS (SELECT 1 FROM entries_history WHERE batch_id IS NOT NULL)",
+        )
+        .execute(&self.0)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_entries_history_batch ON entries_history(user_id, batch_id)",
+        )
+        .execute(&self.0)
+        .await?;
+        // JSON array of EntryEdit, appended to by /edit.
+        for table in ["entries", "entries_history"] {
+            self.ensure_column(table, "edits", "TEXT").await?;
+        }
+        self.ensure_column("user_settings", "webhook_url", "TEXT")
+            .await?;
+        self.ensure_column("user_settings", "webhook_secret", "TEXT")
+            .await?;
+        self.ensure_column("user_settings", "nudges", "INTEGER NOT NULL DEFAULT 1")
+            .await?;
+        self.ensure_column("user_settings", "currency", "TEXT")
+            .await?;
+        // /decimal comma: `,` is the only decimal mark and `.` groups thousands.
+        self.ensure_column(
+            "user_settings",
+            "decimal_comma",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        // "comma" or "point" once the user picked one with /decimal; NULL
+        // goes by their language. It replaces decimal_comma, which can't
+        // tell "never set" from "point".
+        self.ensure_column("user_settings", "decimal_mark", "TEXT")
+            .await?;
+        sqlx::query(
+            "UPDATE user_settings SET decimal_mark = 'comma'
+             WHERE decimal_mark IS NULL AND decimal_comma = 1",
+        )
+        .execute(&self.0)
+        .await?;
+        self.ensure_column("user_settings", "inactivity_pinged_at", "TEXT")
+            .await?;
+        // USD per unit of the user's /currency on the day a holding was
+        // bought, for /portfolio fx; NULL for USD, bare numbers, or when no
+        // rate could be had.
+        self.ensure_column("holdings", "fx_rate", "REAL").await?;
+        // Holdings bought in another currency used to divide that cost by
+        // the USD price as it was; count their shares from the USD cost.
+        sqlx::query(
+            "UPDATE holdings
+             SET micro_shares = CAST(ROUND(cost_cents * fx_rate * 1000000 / price_cents) AS INTEGER)
+             WHERE fx_rate IS NOT NULL AND price_cents > 0",
+        )
+        .execute(&self.0)
+        .await?;
+        self.ensure_column("user_settings", "digest_month", "TEXT")
+            .await?;
+        // Fund expense ratio in millionths: 0.03% is 300.
+        self.ensure_column(
+            "user_settings",
+            "expense_ratio_ppm",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        // The last week (its Monday) and month whose budget summary went out.
+        self.ensure_column("user_settings", "budget_alert_week", "TEXT")
+            .await?;
+        self.ensure_column("user_settings", "budget_alert_month", "TEXT")
+            .await?;
+        // Monday `YYYY-MM-DD` of the last week the /watchticker digest went out.
+        self.ensure_column("user_settings", "ticker_digest_week", "TEXT")
+            .await?;
+        // /autocategory mode: suggest, apply or off.
+        self.ensure_column(
+            "user_settings",
+            "autocategory",
+            "TEXT NOT NULL DEFAULT 'suggest'",
+        )
+        .await?;
+        self.ensure_column("user_settings", "immutable", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        // Which photo or voice note a proposed save came from.
+        self.ensure_column("pending_entries", "file_id", "TEXT")
+            .await?;
+        // /publicpage: the secret in the page's URL; NULL while off.
+        self.ensure_column("user_settings", "public_page_token", "TEXT")
+            .await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_public_page_token
+             ON user_settings(public_page_token)",
+        )
+        .execute(&self.0)
+        .await?;
+        // `/budget 300`: what the user means to save each month, and which
+        // progress alerts and month-end review already went out for it.
+        self.ensure_column("user_settings", "saving_target_cents", "INTEGER")
+            .await?;
+        self.ensure_column("user_settings", "saving_alert_month", "TEXT")
+            .await?;
+        self.ensure_column("user_settings", "saving_alert_pct", "INTEGER")
+            .await?;
+        self.ensure_column("user_settings", "saving_review_month", "TEXT")
+            .await?;
+        // /leaderboard on: shown on the boards of chats the user saves in.
+        self.ensure_column("user_settings", "leaderboard", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        // /dividend auto: received dividends also go into the stash.
+        self.ensure_column(
+            "user_settings",
+            "dividends_to_stash",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        self.ensure_column(
+            "user_settings",
+            "utc_offset_minutes",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        // Keyed by the channel's chat id: who its posts are recorded for.
+        self.ensure_column("chat_settings", "owner_user_id", "TEXT")
+            .await?;
+        self.ensure_column(
+            "chat_settings",
+            "anonymous_pot",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        // Space-separated command names turned off with /disablecmd.
+        self.ensure_column("chat_settings", "disabled_commands", "TEXT")
+            .await?;
+        // /splitrounding: who gets leftover cents; see rounding.rs.
+        self.ensure_column("chat_settings", "split_rounding", "TEXT")
+            .await?;
+        // Who gets the next leftover cent under round-robin rounding.
+        self.ensure_column("chat_settings", "split_turn", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        for table in ["entries", "entries_history"] {
+            self.ensure_column(table, "occurred_at", "TEXT").await?;
+            sqlx::query(&format!(
+                "UPDATE {table} SET occurred_at = created_at WHERE occurred_at IS NULL"
+            ))
+            .execute(&self.0)
+            .await?;
+        }
+        self.normalize_kinds().await?;
+        self.backfill_tags().await?;
+        // /ticker set used to keep its split apart from /target's.
+        let split_table: Option<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'invest_tickers'",
+        )
+        .fetch_optional(&self.0)
+        .await?;
+        if split_table.is_some() {
+            sqlx::query(
+                "INSERT INTO allocation_targets(user_id, ticker, percent)
+                 SELECT user_id, ticker, percent FROM invest_tickers
+                 WHERE user_id NOT IN (SELECT user_id FROM allocation_targets)",
+            )
+            .execute(&self.0)
+            .await?;
+            sqlx::query("DROP TABLE invest_tickers")
+                .execute(&self.0)
+                .await?;
+        }
+        // Left by /balances, which read the ledger through it.
+        sqlx::query("DROP VIEW IF EXISTS postings")
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    /// Rewrites kinds written before [`EntryKind`] checked them: case and
+    /// spaces are dropped, and anything still unknown is filed by its sign
+    /// as an entry of no particular kind would be.
+    async fn normalize_kinds(&self) -> Result<()> {
+        let known = EntryKind::ALL
+            .iter()
+            .map(|k| format!("'{k}'"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        for table in ["entries", "entries_history"] {
+            let res = sqlx::query(&format!(
+                "UPDATE {table} SET kind = CASE
+                   WHEN lower(trim(kind)) IN ({known}) THEN lower(trim(kind))
+                   WHEN amount_cents > 0 THEN 'save'
+                   ELSE 'adjust'
+                 END
+                 WHERE kind NOT IN ({known})"
+            ))
+            .execute(&self.0)
+            .await?;
+            if res.rows_affected() > 0 {
+                eprintln!(
+                    "db: normalized the kind of {} rows in {table}",
+                    res.rows_affected()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Tags entries whose reason has a `#` but no entry_tags (or no
+    /// category_tokens) rows yet, i.e. ones recorded before those were
+    /// stored. Archived entries from before entries_history.entry_id
+    /// existed can't be keyed and stay untagged.
+    async fn backfill_tags(&self) -> Result<()> {
+        let rows = sqlx::query(
+            "SELECT id, user_id, reason FROM (
+               SELECT id, user_id, reason FROM entries
+               UNION ALL
+               SELECT entry_id, user_id, reason FROM entries_history WHERE entry_id IS NOT NULL
+             )
+             WHERE reason LIKE '%#%'
+               AND (id NOT IN (SELECT entry_id FROM entry_tags)
+                    OR id NOT IN (SELECT entry_id FROM category_tokens))",
+        )
+        .fetch_all(&self.0)
+        .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.0.begin().await?;
+        for r in rows {
+            let user_id = Uuid::parse_str(&r.get::<String, _>("user_id"))?;
+            let reason: Option<String> = r.get("reason");
+            set_tags(&mut tx, user_id, r.get("id"), reason.as_deref()).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn ensure_column(&self, table: &str, column: &str, decl: &str) -> Result<()> {
+        let exists = sqlx::query(&format!("PRAGMA table_info({table})"))
+            .fetch_all(&self.0)
+            .await?
+            .iter()
+            .any(|r| r.get::<String, _>("name") == column);
+        if !exists {
+            sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))
+                .execute(&self.0)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Writes a consistent, compacted copy of the database to `path`, which
+    /// must not exist yet, and returns the row counts the copy must have.
+    /// Writers wait while it runs, so nothing lands between the copy and
+    /// the counts.
+    #[tracing::instrument(skip_all)]
+    pub async fn backup_into(&self, path: &Path) -> Result<Vec<(String, i64)>> {
+        let mut lock = self.0.acquire().await?;
+        sqlx::query("BEGIN IMMEDIATE").execute(&mut *lock).await?;
+        let res = async {
+            // VACUUM can't run inside a transaction, so on another connection.
+            sqlx::query("VACUUM INTO ?")
+                .bind(path.to_string_lossy().into_owned())
+                .execute(&self.0)
+                .await?;
+            table_counts(&mut lock).await
+        }
+        .await;
+        sqlx::query("ROLLBACK").execute(&mut *lock).await?;
+        res
+    }
+
+    /// `PRAGMA integrity_check` output; `["ok"]` when the file is healthy.
+    #[tracing::instrument(skip_all)]
+    pub async fn integrity_check(&self) -> Result<Vec<String>> {
+        let rows = sqlx::query("PRAGMA integrity_check")
+            .fetch_all(&self.0)
+            .await?;
+        Ok(rows.iter().map(|r| r.get::<String, _>(0)).collect())
+    }
+
+    /// Row count of every user table, by name, as of one moment.
+    #[tracing::instrument(skip_all)]
+    pub async fn table_counts(&self) -> Result<Vec<(String, i64)>> {
+        let mut tx = self.0.begin().await?;
+        let counts = table_counts(&mut tx).await?;
+        tx.commit().await?;
+        Ok(counts)
+    }
+
+    /// A registered user by Telegram id or `@username`.
+    #[tracing::instrument(skip_all)]
+    pub async fn find_user(&self, who: &str) -> Result<Option<Uuid>> {
+        let row = match who.strip_prefix('@') {
+            Some(username) => {
+                sqlx::query("SELECT id FROM users WHERE tg_username = ? COLLATE NOCASE")
+                    .bind(username)
+                    .fetch_optional(&self.0)
+                    .await?
+            }
+            None => {
+                let Ok(tg_user_id) = who.parse::<i64>() else {
+                    return Ok(None);
+                };
+                sqlx::query("SELECT id FROM users WHERE tg_user_id = ?")
+                    .bind(tg_user_id)
+                    .fetch_optional(&self.0)
+                    .await?
+            }
+        };
+        row.map(|r| Ok(Uuid::parse_str(&r.get::<String, _>("id"))?))
+            .transpose()
+    }
+
+    /// Instance-wide numbers for /globalstats. Activity is measured against
+    /// the RFC 3339 cutoffs `week_ago` and `month_ago`, entries per day on
+    /// creation days since `since` (`YYYY-MM-DD`).
+    #[tracing::instrument(skip_all)]
+    pub async fn instance_stats(
+        &self,
+        week_ago: &str,
+        month_ago: &str,
+        since: &str,
+    ) -> Result<InstanceStats> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS users,
+                    COUNT(*) FILTER (WHERE COALESCE(last_seen_at, created_at) >= ?1) AS active_7d,
+                    COUNT(*) FILTER (WHERE COALESCE(last_seen_at, created_at) >= ?2) AS active_30d,
+                    (SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size())
+                      AS db_bytes
+             FROM users",
+        )
+        .bind(week_ago)
+        .bind(month_ago)
+        .fetch_one(&self.0)
+        .await?;
+        let days = sqlx::query(
+            "SELECT substr(created_at, 1, 10) AS day, COUNT(*) AS n FROM (
+               SELECT created_at FROM entries
+               UNION ALL
+               SELECT created_at FROM entries_history
+             )
+             WHERE created_at >= ?
+             GROUP BY day ORDER BY day",
+        )
+        .bind(since)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(InstanceStats {
+            users: row.get("users"),
+            active_7d: row.get("active_7d"),
+            active_30d: row.get("active_30d"),
+            db_bytes: row.get("db_bytes"),
+            entries_per_day: days
+                .into_iter()
+                .map(|r| (r.get("day"), r.get("n")))
+                .collect(),
+        })
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn ensure_user(
+        &self,
+        tg_user_id: i64,
+        tg_username: Option<String>,
+        first_name: String, // <- String (not Option)
+        last_name: Option<String>,
+    ) -> Result<Uuid> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+
+        // Every command passes through here, so this doubles as "last seen",
+        // and as proof that someone who blocked the bot is back.
+        if let Some(row) = sqlx::query(
+            "UPDATE users SET last_seen_at = ?, blocked_at = NULL WHERE tg_user_id = ? RETURNING id",
+        )
+        .bind(&now)
+        .bind(tg_user_id)
+        .fetch_optional(&self.0)
+        .await?
+        {
+            let id: String = row.get("id");
+            return Ok(Uuid::parse_str(&id)?);
+        }
+
+        let id = Uuid::new_v4();
+
+        sqlx::query(
+            "INSERT INTO users(id, tg_user_id, tg_username, first_name, last_name, created_at, last_seen_at)
+             VALUES(?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(id.to_string())
+        .bind(tg_user_id)
+        .bind(tg_username)
+        .bind(first_name)
+        .bind(last_name)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.0)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// The pot of the group chat `chat_id`, made on first use: a pseudo-user
+    /// keyed by the chat id, which gets no direct messages.
+    #[tracing::instrument(skip_all)]
+    pub async fn ensure_pot(&self, chat_id: i64, name: String) -> Result<Uuid> {
+        let id = self.ensure_user(chat_id, None, name, None).await?;
+        sqlx::query("UPDATE users SET kind = 'pot' WHERE id = ? AND kind IS NULL")
+            .bind(id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(id)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn add_entry(
+        &self,
+        user_id: Uuid,
+        amount_cents: i64,
+        kind: EntryKind,
+        reason: Option<String>,
+        class: Option<&str>,
+        occurred_at: Option<String>,
+    ) -> Result<i64> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let occurred_at = occurred_at.unwrap_or_else(|| now.clone());
+
+        let mut tx = self.0.begin().await?;
+        let res = sqlx::query(
+            "INSERT INTO entries(user_id, amount_cents, kind, reason, class, created_at, occurred_at)
+             VALUES(?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(amount_cents)
+        .bind(kind)
+        .bind(&reason)
+        .bind(class)
+        .bind(now)
+        .bind(occurred_at)
+        .execute(&mut *tx)
+        .await?;
+        let id = res.last_insert_rowid();
+        set_tags(&mut tx, user_id, id, reason.as_deref()).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Removes the most recent uninvested entry the user typed themselves,
+    /// unless it was recorded before `recorded_after`. Rows booked by the
+    /// scheduler, /split or /transfer are passed over, and so is what's left
+    /// of an entry a partial /allinvoo took part of: /undo never reaches past
+    /// an /allinvoo.
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_last_entry(
+        &self,
+        user_id: Uuid,
+        recorded_after: Option<&str>,
+    ) -> Result<Option<Entry>> {
+        let row = sqlx::query(
+            "DELETE FROM entries
+             WHERE id = (
+                 SELECT MAX(id) FROM entries
+                 WHERE user_id = ?1 AND transfer_id IS NULL AND origin IS NULL
+                   AND kind <> 'interest'
+                   AND id NOT IN (
+                     SELECT entry_id FROM entries_history
+                     WHERE user_id = ?1 AND entry_id IS NOT NULL
+                   )
+               )
+               AND user_id = ?1
+               AND (?2 IS NULL OR created_at >= ?2)
+             RETURNING id, amount_cents, kind, reason, class, occurred_at",
+        )
+        .bind(user_id.to_string())
+        .bind(recorded_after)
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| Entry {
+            id: r.get("id"),
+            amount_cents: r.get("amount_cents"),
+            kind: r.get("kind"),
+            reason: r.get("reason"),
+            class: r.get("class"),
+            occurred_at: r.get("occurred_at"),
+        }))
+    }
+
+    /// Every entry the user has, current and archived, oldest first. Rows
+    /// are streamed so a long ledger isn't held in memory twice.
+    pub fn all_entries(&self, user_id: Uuid) -> BoxStream<'_, Result<LedgerRow>> {
+        sqlx::query(
+            "SELECT occurred_at, amount_cents, kind, reason, NULL AS batch, id AS seq
+             FROM entries WHERE user_id = ?1
+             UNION ALL
+             SELECT occurred_at, amount_cents, kind, reason, archived_at, COALESCE(entry_id, 0)
+             FROM entries_history WHERE user_id = ?1
+             ORDER BY occurred_at, seq",
+        )
+        .bind(user_id.to_string())
+        .fetch(&self.0)
+        .map(|row| {
+            let r = row?;
+            Ok(LedgerRow {
+                occurred_at: r.get("occurred_at"),
+                amount_cents: r.get("amount_cents"),
+                kind: r.get("kind"),
+                reason: r.get("reason"),
+                batch: r.get("batch"),
+            })
+        })
+        .boxed()
+    }
+
+    /// The ledger rows dated in `[start, end)`, oldest first.
+    #[tracing::instrument(skip_all)]
+    pub async fn ledger_between(
+        &self,
+        user_id: Uuid,
+        start: &str,
+        end: &str,
+    ) -> Result<Vec<LedgerRow>> {
+        let rows = sqlx::query(
+            "SELECT occurred_at, amount_cents, kind, reason, batch FROM (
+               SELECT occurred_at, amount_cents, kind, reason, NULL AS batch, id AS seq
+               FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT occurred_at, amount_cents, kind, reason, archived_at, COALESCE(entry_id, 0)
+               FROM entries_history WHERE user_id = ?1
+             )
+             WHERE occurred_at >= ?2 AND occurred_at < ?3
+             ORDER BY occurred_at, seq",
+        )
+        .bind(user_id.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| LedgerRow {
+                occurred_at: r.get("occurred_at"),
+                amount_cents: r.get("amount_cents"),
+                kind: r.get("kind"),
+                reason: r.get("reason"),
+                batch: r.get("batch"),
+            })
+            .collect())
+    }
+
+    /// Entries, invested or not, whose reason contains `phrase` ignoring
+    /// case, newest first and at most `limit` of them.
+    #[tracing::instrument(skip_all)]
+    pub async fn search_entries(
+        &self,
+        user_id: Uuid,
+        phrase: &str,
+        limit: i64,
+    ) -> Result<SearchResults> {
+        let rows = sqlx::query(
+            "SELECT id, occurred_at, amount_cents, kind, reason, invested,
+                    COUNT(*) OVER () AS matches, SUM(amount_cents) OVER () AS net
+             FROM (
+               SELECT id, occurred_at, amount_cents, kind, reason, 0 AS invested
+               FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT entry_id, occurred_at, amount_cents, kind, reason, 1
+               FROM entries_history WHERE user_id = ?1
+             )
+             WHERE instr(lower(COALESCE(reason, '')), lower(?2)) > 0
+             ORDER BY occurred_at DESC, id DESC
+             LIMIT ?3",
+        )
+        .bind(user_id.to_string())
+        .bind(phrase)
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await?;
+        let (matches, net_cents) = rows
+            .first()
+            .map(|r| (r.get("matches"), r.get("net")))
+            .unwrap_or((0, 0));
+        let hits = rows
+            .into_iter()
+            .map(|r| SearchHit {
+                id: r.get("id"),
+                occurred_at: r.get("occurred_at"),
+                amount_cents: r.get("amount_cents"),
+                kind: r.get("kind"),
+                reason: r.get("reason"),
+                invested: r.get::<i64, _>("invested") != 0,
+            })
+            .collect();
+        Ok(SearchResults {
+            hits,
+            matches,
+            net_cents,
+        })
+    }
+
+    /// The user's newest `limit` invested entries, newest batch first.
+    #[tracing::instrument(skip_all)]
+    pub async fn archived_entries(&self, user_id: Uuid, limit: i64) -> Result<Vec<ArchivedEntry>> {
+        let rows = sqlx::query(
+            "SELECT batch_id, archived_at, strategy, entry_id, occurred_at, amount_cents, kind, reason,
+                    SUM(amount_cents) OVER (PARTITION BY batch_id) AS batch_cents,
+                    COUNT(*) OVER (PARTITION BY batch_id) AS batch_entries
+             FROM entries_history WHERE user_id = ?
+             ORDER BY batch_id DESC, occurred_at DESC, id DESC
+             LIMIT ?",
+        )
+        .bind(user_id.to_string())
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| ArchivedEntry {
+                batch_id: r.get("batch_id"),
+                archived_at: r.get("archived_at"),
+                strategy: r.get("strategy"),
+                id: r.get("entry_id"),
+                occurred_at: r.get("occurred_at"),
+                amount_cents: r.get("amount_cents"),
+                kind: r.get("kind"),
+                reason: r.get("reason"),
+                batch_cents: r.get("batch_cents"),
+                batch_entries: r.get("batch_entries"),
+            })
+            .collect())
+    }
+
+    /// Removes one of the user's uninvested entries by id. /transfer legs
+    /// are never removed.
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_entry(&self, user_id: Uuid, id: i64) -> Result<Option<Entry>> {
+        let row = sqlx::query(
+            "DELETE FROM entries WHERE id = ? AND user_id = ? AND transfer_id IS NULL
+             RETURNING id, amount_cents, kind, reason, class, occurred_at",
+        )
+        .bind(id)
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| Entry {
+            id: r.get("id"),
+            amount_cents: r.get("amount_cents"),
+            kind: r.get("kind"),
+            reason: r.get("reason"),
+            class: r.get("class"),
+            occurred_at: r.get("occurred_at"),
+        }))
+    }
+
+    /// Remembers the chat message an entry came from.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_entry_source(
+        &self,
+        entry_id: i64,
+        chat_id: i64,
+        message_id: i32,
+    ) -> Result<()> {
+        sqlx::query("UPDATE entries SET source_chat_id = ?, source_message_id = ? WHERE id = ?")
+            .bind(chat_id)
+            .bind(message_id)
+            .bind(entry_id)
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    /// Remembers the amount an entry was typed in before conversion.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_entry_original(
+        &self,
+        entry_id: i64,
+        original_cents: i64,
+        currency: &str,
+    ) -> Result<()> {
+        sqlx::query("UPDATE entries SET original_cents = ?, original_currency = ? WHERE id = ?")
+            .bind(original_cents)
+            .bind(currency)
+            .bind(entry_id)
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    /// When a scheduler job is next due, if it has run before.
+    #[tracing::instrument(skip_all)]
+    pub async fn job_next_run(&self, job: &str) -> Result<Option<String>> {
+        Ok(
+            sqlx::query_scalar("SELECT next_run_at FROM scheduler_jobs WHERE job = ?")
+                .bind(job)
+                .fetch_optional(&self.0)
+                .await?,
+        )
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_job_run(&self, job: &str, last_run_at: &str, next_run_at: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO scheduler_jobs(job, last_run_at, next_run_at) VALUES(?, ?, ?)
+             ON CONFLICT(job) DO UPDATE SET
+               last_run_at = excluded.last_run_at, next_run_at = excluded.next_run_at",
+        )
+        .bind(job)
+        .bind(last_run_at)
+        .bind(next_run_at)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// The /shared ledger the user records into, if any, marked as seen
+    /// since one of its members is active.
+    #[tracing::instrument(skip_all)]
+    pub async fn shared_ledger_id(&self, user_id: Uuid) -> Result<Option<Uuid>> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        let id: Option<String> = sqlx::query_scalar(
+            "UPDATE users SET last_seen_at = ?
+             WHERE id = (SELECT ledger_id FROM shared_members WHERE user_id = ?)
+             RETURNING id",
+        )
+        .bind(now)
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?;
+        id.map(|id| Ok(Uuid::parse_str(&id)?)).transpose()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn shared_ledger(&self, user_id: Uuid) -> Result<Option<SharedLedger>> {
+        let Some(row) = sqlx::query(
+            "SELECT l.ledger_id, l.name, l.code FROM shared_ledgers l
+             JOIN shared_members m ON m.ledger_id = l.ledger_id WHERE m.user_id = ?",
+        )
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let ledger_id: String = row.get("ledger_id");
+        let members = sqlx::query_scalar(
+            "SELECT COALESCE(u.first_name, u.tg_username, 'someone') FROM shared_members m
+             JOIN users u ON u.id = m.user_id WHERE m.ledger_id = ?
+             ORDER BY m.joined_at",
+        )
+        .bind(&ledger_id)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(Some(SharedLedger {
+            ledger_id: Uuid::parse_str(&ledger_id)?,
+            name: row.get("name"),
+            code: row.get("code"),
+            members,
+        }))
+    }
+
+    /// Makes a shared ledger with `creator` as its first member. Its
+    /// pseudo-user gets a Telegram id below any real chat's and is marked
+    /// blocked, so scheduled messages meant for a person skip it.
+    #[tracing::instrument(skip_all)]
+    pub async fn create_shared_ledger(
+        &self,
+        creator: Uuid,
+        name: &str,
+        code: &str,
+    ) -> Result<Uuid> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        let id = Uuid::new_v4();
+        let mut tx = self.0.begin().await?;
+        sqlx::query(
+            "INSERT INTO users(id, tg_user_id, first_name, created_at, last_seen_at, blocked_at, kind)
+             SELECT ?1, MIN(?2, COALESCE(MIN(tg_user_id), 0) - 1), ?3, ?4, ?4, ?4, 'shared' FROM users",
+        )
+        .bind(id.to_string())
+        .bind(SHARED_LEDGER_TG_IDS)
+        .bind(name)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO shared_ledgers(ledger_id, name, code, created_by, created_at)
+             VALUES(?, ?, ?, ?, ?)",
+        )
+        .bind(id.to_string())
+        .bind(name)
+        .bind(code)
+        .bind(creator.to_string())
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("INSERT INTO shared_members(user_id, ledger_id, joined_at) VALUES(?, ?, ?)")
+            .bind(creator.to_string())
+            .bind(id.to_string())
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Joins the ledger with this invite code; `None` if there is none.
+    /// The user must not be in another one.
+    #[tracing::instrument(skip_all)]
+    pub async fn join_shared_ledger(&self, user_id: Uuid, code: &str) -> Result<Option<String>> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        let row = sqlx::query(
+            "INSERT INTO shared_members(user_id, ledger_id, joined_at)
+             SELECT ?, ledger_id, ? FROM shared_ledgers WHERE code = ? COLLATE NOCASE
+             RETURNING (SELECT name FROM shared_ledgers WHERE ledger_id = shared_members.ledger_id) AS name",
+        )
+        .bind(user_id.to_string())
+        .bind(now)
+        .bind(code)
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| r.get("name")))
+    }
+
+    /// `false` if the user wasn't in a shared ledger. The ledger and its
+    /// entries stay, for the other members or for rejoining.
+    #[tracing::instrument(skip_all)]
+    pub async fn leave_shared_ledger(&self, user_id: Uuid) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM shared_members WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Replaces the invite code, so the old one stops letting people in.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_shared_code(&self, ledger_id: Uuid, code: &str) -> Result<()> {
+        sqlx::query("UPDATE shared_ledgers SET code = ? WHERE ledger_id = ?")
+            .bind(code)
+            .bind(ledger_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    /// Takes the instance lease for `holder` unless another holder renewed
+    /// it at or after `stale_before`. `false` means someone else has it.
+    #[tracing::instrument(skip_all)]
+    pub async fn acquire_lease(&self, holder: &str, now: &str, stale_before: &str) -> Result<bool> {
+        let res = sqlx::query(
+            "INSERT INTO instance_lease(id, holder, started_at, heartbeat_at) VALUES(1, ?1, ?2, ?2)
+             ON CONFLICT(id) DO UPDATE SET
+               holder = excluded.holder, started_at = excluded.started_at,
+               heartbeat_at = excluded.heartbeat_at
+             WHERE instance_lease.holder = excluded.holder OR instance_lease.heartbeat_at < ?3",
+        )
+        .bind(holder)
+        .bind(now)
+        .bind(stale_before)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Who holds the instance lease and when they last renewed it.
+    #[tracing::instrument(skip_all)]
+    pub async fn lease_holder(&self) -> Result<Option<(String, String)>> {
+        let row = sqlx::query("SELECT holder, heartbeat_at FROM instance_lease WHERE id = 1")
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.map(|r| (r.get("holder"), r.get("heartbeat_at"))))
+    }
+
+    /// `false` once the lease went to someone else.
+    #[tracing::instrument(skip_all)]
+    pub async fn renew_lease(&self, holder: &str, now: &str) -> Result<bool> {
+        let res =
+            sqlx::query("UPDATE instance_lease SET heartbeat_at = ? WHERE id = 1 AND holder = ?")
+                .bind(now)
+                .bind(holder)
+                .execute(&self.0)
+                .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn release_lease(&self, holder: &str) -> Result<()> {
+        sqlx::query("DELETE FROM instance_lease WHERE id = 1 AND holder = ?")
+            .bind(holder)
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    /// Notes that the user blocked the bot. `false` if that was known.
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_blocked(&self, tg_user_id: i64) -> Result<bool> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        let res = sqlx::query(
+            "UPDATE users SET blocked_at = ? WHERE tg_user_id = ? AND blocked_at IS NULL",
+        )
+        .bind(now)
+        .bind(tg_user_id)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Whether the user has blocked the bot and not come back since.
+    #[tracing::instrument(skip_all)]
+    pub async fn is_blocked(&self, tg_user_id: i64) -> Result<bool> {
+        let blocked: Option<Option<String>> =
+            sqlx::query_scalar("SELECT blocked_at FROM users WHERE tg_user_id = ?")
+                .bind(tg_user_id)
+                .fetch_optional(&self.0)
+                .await?;
+        Ok(blocked.flatten().is_some())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn queue_message(
+        &self,
+        chat_id: i64,
+        text: &str,
+        keyboard: Option<&str>,
+        next_attempt_at: &str,
+        error: &str,
+    ) -> Result<()> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        sqlx::query(
+            "INSERT INTO outbox(chat_id, text, keyboard, next_attempt_at, last_error, created_at)
+             VALUES(?, ?, ?, ?, ?, ?)",
+        )
+        .bind(chat_id)
+        .bind(text)
+        .bind(keyboard)
+        .bind(next_attempt_at)
+        .bind(error)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Queued messages due by `now`, oldest first.
+    #[tracing::instrument(skip_all)]
+    pub async fn due_messages(&self, now: &str, limit: i64) -> Result<Vec<QueuedMessage>> {
+        let rows = sqlx::query(
+            "SELECT id, chat_id, text, keyboard, attempts FROM outbox
+             WHERE next_attempt_at <= ?
+             ORDER BY id
+             LIMIT ?",
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| QueuedMessage {
+                id: r.get("id"),
+                chat_id: r.get("chat_id"),
+                text: r.get("text"),
+                keyboard: r.get("keyboard"),
+                attempts: r.get("attempts"),
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn retry_message(&self, id: i64, next_attempt_at: &str, error: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE outbox SET attempts = attempts + 1, next_attempt_at = ?, last_error = ?
+             WHERE id = ?",
+        )
+        .bind(next_attempt_at)
+        .bind(error)
+        .bind(id)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn remove_message(&self, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM outbox WHERE id = ?")
+            .bind(id)
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    /// A cached exchange rate fetched after `fresh_after`.
+    #[tracing::instrument(skip_all)]
+    pub async fn fx_rate(&self, base: &str, quote: &str, fresh_after: &str) -> Result<Option<f64>> {
+        Ok(sqlx::query_scalar(
+            "SELECT rate FROM fx_rates WHERE base = ? AND quote = ? AND fetched_at > ?",
+        )
+        .bind(base)
+        .bind(quote)
+        .bind(fresh_after)
+        .fetch_optional(&self.0)
+        .await?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_fx_rate(&self, base: &str, quote: &str, rate: f64) -> Result<()> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        sqlx::query(
+            "INSERT INTO fx_rates(base, quote, rate, fetched_at) VALUES(?, ?, ?, ?)
+             ON CONFLICT(base, quote) DO UPDATE SET rate = excluded.rate, fetched_at = excluded.fetched_at",
+        )
+        .bind(base)
+        .bind(quote)
+        .bind(rate)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Looks an entry up by its id, whether it is still uninvested or has
+    /// been moved to history by /allinvoo.
+    #[tracing::instrument(skip_all)]
+    pub async fn entry_detail(&self, user_id: Uuid, id: i64) -> Result<Option<EntryDetail>> {
+        let row = sqlx::query(
+            "SELECT id, amount_cents, kind, reason, class, occurred_at, created_at,
+                    NULL AS archived_at, source_chat_id, source_message_id, edits,
+                    original_cents, original_currency, transfer_id
+             FROM entries WHERE id = ? AND user_id = ?
+             UNION ALL
+             SELECT entry_id, amount_cents, kind, reason, class, occurred_at, created_at,
+                    archived_at, source_chat_id, source_message_id, edits,
+                    original_cents, original_currency, transfer_id
+             FROM entries_history WHERE entry_id = ? AND user_id = ?
+             LIMIT 1",
+        )
+        .bind(id)
+        .bind(user_id.to_string())
+        .bind(id)
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?;
+        row.map(|r| {
+            let edits = match r.get::<Option<String>, _>("edits") {
+                Some(json) => serde_json::from_str(&json)?,
+                None => Vec::new(),
+            };
+            Ok(EntryDetail {
+                entry: Entry {
+                    id: r.get("id"),
+                    amount_cents: r.get("amount_cents"),
+                    kind: r.get("kind"),
+                    reason: r.get("reason"),
+                    class: r.get("class"),
+                    occurred_at: r.get("occurred_at"),
+                },
+                created_at: r.get("created_at"),
+                archived_at: r.get("archived_at"),
+                source_chat_id: r.get("source_chat_id"),
+                source_message_id: r.get("source_message_id"),
+                edits,
+                original: r
+                    .get::<Option<i64>, _>("original_cents")
+                    .zip(r.get("original_currency")),
+                transfer_id: r.get("transfer_id"),
+            })
+        })
+        .transpose()
+    }
+
+    /// Changes the amount and reason of one of the user's uninvested
+    /// entries, keeping the previous values in its `edits` column. Returns
+    /// the entry before and after the change; /transfer legs can't change.
+    #[tracing::instrument(skip_all)]
+    pub async fn update_entry(
+        &self,
+        user_id: Uuid,
+        id: i64,
+        amount_cents: i64,
+        reason: Option<String>,
+    ) -> Result<Option<(Entry, Entry)>> {
+        let mut tx = self.0.begin().await?;
+        let Some(row) = sqlx::query(
+            "SELECT id, amount_cents, kind, reason, class, occurred_at
+             FROM entries WHERE id = ? AND user_id = ? AND transfer_id IS NULL",
+        )
+        .bind(id)
+        .bind(user_id.to_string())
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let old = Entry {
+            id: row.get("id"),
+            amount_cents: row.get("amount_cents"),
+            kind: row.get("kind"),
+            reason: row.get("reason"),
+            class: row.get("class"),
+            occurred_at: row.get("occurred_at"),
+        };
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        sqlx::query(
+            "UPDATE entries
+             SET edits = json_insert(COALESCE(edits, '[]'), '$[#]',
+                   json_object('amount_cents', amount_cents, 'reason', reason, 'edited_at', ?1)),
+                 original_cents = CASE WHEN amount_cents = ?2 THEN original_cents END,
+                 original_currency = CASE WHEN amount_cents = ?2 THEN original_currency END,
+                 amount_cents = ?2, reason = ?3
+             WHERE id = ?4",
+        )
+        .bind(now)
+        .bind(amount_cents)
+        .bind(&reason)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        set_tags(&mut tx, user_id, id, reason.as_deref()).await?;
+        tx.commit().await?;
+        let new = Entry {
+            amount_cents,
+            reason,
+            ..old.clone()
+        };
+        Ok(Some((old, new)))
+    }
+
+    /// Records the user's starting savings as a backdated `opening` entry.
+    /// Returns false if the user already has one (current or archived).
+    #[tracing::instrument(skip_all)]
+    pub async fn add_opening_balance(
+        &self,
+        user_id: Uuid,
+        amount_cents: i64,
+        occurred_at: &str,
+    ) -> Result<bool> {
+        let mut tx = self.0.begin().await?;
+        let existing: i64 = sqlx::query(
+            "SELECT (SELECT COUNT(*) FROM entries WHERE user_id = ? AND kind = 'opening')
+                  + (SELECT COUNT(*) FROM entries_history WHERE user_id = ? AND kind = 'opening') AS n",
+        )
+        .bind(user_id.to_string())
+        .bind(user_id.to_string())
+        .fetch_one(&mut *tx)
+        .await?
+        .get("n");
+        if existing > 0 {
+            return Ok(false);
+        }
+
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        sqlx::query(
+            "INSERT INTO entries(user_id, amount_cents, kind, reason, created_at, occurred_at)
+             VALUES(?, ?, ?, 'Opening balance', ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(amount_cents)
+        .bind(EntryKind::Opening)
+        .bind(now)
+        .bind(occurred_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn total_cents(&self, user_id: Uuid) -> Result<i64> {
+        let row = sqlx::query(
+            "SELECT COALESCE(SUM(amount_cents),0) AS total FROM entries WHERE user_id = ?",
+        )
+        .bind(user_id.to_string())
+        .fetch_one(&self.0)
+        .await?;
+        let total: i64 = row.get("total");
+        Ok(total)
+    }
+
+    #[tracing::instrument(skip_all)]
     pub async fn history_total_cents(&self, user_id: Uuid) -> Result<i64> {
         let row = sqlx::query(
-            "SELECT COALESCE(SUM(amount_cents),0) AS total FROM entries_history WHERE user_id = ?",
+            "SELECT COALESCE(SUM(amount_cents),0) AS total FROM entries_history WHERE user_id = ?",
+        )
+        .bind(user_id.to_string())
+        .fetch_one(&self.0)
+        .await?;
+        let total: i64 = row.get("total");
+        Ok(total)
+    }
+
+    /// (entries created since `since`, all entries) of the user, invested
+    /// ones included, for the operator's quotas.
+    #[tracing::instrument(skip_all)]
+    pub async fn entry_counts(&self, user_id: Uuid, since: &str) -> Result<(i64, i64)> {
+        let row = sqlx::query(
+            "SELECT COALESCE(SUM(created_at >= ?2), 0) AS recent, COUNT(*) AS total
+             FROM (
+               SELECT created_at FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT created_at FROM entries_history WHERE user_id = ?1
+             )",
+        )
+        .bind(user_id.to_string())
+        .bind(since)
+        .fetch_one(&self.0)
+        .await?;
+        Ok((row.get("recent"), row.get("total")))
+    }
+
+    /// Current and history totals in one round trip, for /balance and /query.
+    #[tracing::instrument(skip_all)]
+    pub async fn balances(&self, user_id: Uuid) -> Result<(i64, i64)> {
+        let row = sqlx::query(
+            "SELECT
+               (SELECT COALESCE(SUM(amount_cents),0) FROM entries WHERE user_id = ?1) AS current,
+               (SELECT COALESCE(SUM(amount_cents),0) FROM entries_history WHERE user_id = ?1) AS history",
+        )
+        .bind(user_id.to_string())
+        .fetch_one(&self.0)
+        .await?;
+        Ok((row.get("current"), row.get("history")))
+    }
+
+    /// What /allinvoo would take right now, for its preview.
+    #[tracing::instrument(skip_all)]
+    pub async fn invest_selection(
+        &self,
+        user_id: Uuid,
+        amount_cents: Option<i64>,
+    ) -> Result<InvestSelection> {
+        let mut conn = self.0.acquire().await?;
+        invest_selection(&mut conn, user_id, amount_cents).await
+    }
+
+    /// Moves uninvested entries to history, all of them or `amount_cents`
+    /// worth (see [`InvestSelection`]), and returns the amount moved.
+    #[tracing::instrument(skip_all)]
+    pub async fn archive_user_entries(
+        &self,
+        user_id: Uuid,
+        strategy: Option<&str>,
+        amount_cents: Option<i64>,
+    ) -> Result<i64> {
+        let mut tx = self.0.begin().await?;
+        let selection = invest_selection(&mut tx, user_id, amount_cents).await?;
+        if selection.cents == 0 {
+            tx.commit().await?;
+            return Ok(0);
+        }
+
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let whole = serde_json::to_string(&selection.whole)?;
+        let (split_id, split_cents) = selection.split.unzip();
+
+        sqlx::query(
+            "INSERT INTO entries_history(user_id, amount_cents, kind, reason, class, created_at, occurred_at,
+                                         archived_at, entry_id, source_chat_id, source_message_id, edits,
+                                         strategy, batch_id, original_cents, original_currency,
+                                         transfer_id, origin)
+             SELECT user_id, CASE WHEN id = ?4 THEN ?5 ELSE amount_cents END, kind, reason, class,
+                    created_at, occurred_at, ?1, id, source_chat_id, source_message_id, edits, ?2,
+                    (SELECT COALESCE(MAX(batch_id), 0) + 1 FROM entries_history),
+                    CASE WHEN id = ?4 THEN NULL ELSE original_cents END,
+                    CASE WHEN id = ?4 THEN NULL ELSE original_currency END,
+                    transfer_id, origin
+             FROM entries
+             WHERE user_id = ?3 AND (id IN (SELECT value FROM json_each(?6)) OR id = ?4)",
+        )
+        .bind(now)
+        .bind(strategy)
+        .bind(user_id.to_string())
+        .bind(split_id)
+        .bind(split_cents)
+        .bind(&whole)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "DELETE FROM entries WHERE user_id = ? AND id IN (SELECT value FROM json_each(?))",
+        )
+        .bind(user_id.to_string())
+        .bind(&whole)
+        .execute(&mut *tx)
+        .await?;
+        if let Some((id, cents)) = selection.split {
+            // Neither part of a split entry matches what was typed any more.
+            sqlx::query(
+                "UPDATE entries SET amount_cents = amount_cents - ?,
+                                    original_cents = NULL, original_currency = NULL
+                 WHERE id = ?",
+            )
+            .bind(cents)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(selection.cents)
+    }
+
+    /// The user's most recent /allinvoo: (batch id, archived_at, cents).
+    #[tracing::instrument(skip_all)]
+    pub async fn last_batch(&self, user_id: Uuid) -> Result<Option<(i64, String, i64)>> {
+        let row = sqlx::query(
+            "SELECT batch_id, archived_at, SUM(amount_cents) AS total FROM entries_history
+             WHERE user_id = ?1 AND batch_id = (
+               SELECT MAX(batch_id) FROM entries_history WHERE user_id = ?1
+             )
+             GROUP BY batch_id",
+        )
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| (r.get("batch_id"), r.get("archived_at"), r.get("total"))))
+    }
+
+    /// Moves an /allinvoo batch back into current, under the entries' old
+    /// ids, merging any part of a split entry into what stayed behind.
+    /// Returns the cents restored, or `None` unless `batch_id` is still the
+    /// user's latest batch.
+    #[tracing::instrument(skip_all)]
+    pub async fn restore_batch(&self, user_id: Uuid, batch_id: i64) -> Result<Option<i64>> {
+        let mut tx = self.0.begin().await?;
+        let latest: Option<i64> =
+            sqlx::query_scalar("SELECT MAX(batch_id) FROM entries_history WHERE user_id = ?")
+                .bind(user_id.to_string())
+                .fetch_one(&mut *tx)
+                .await?;
+        if latest != Some(batch_id) {
+            return Ok(None);
+        }
+        sqlx::query(
+            "UPDATE entries SET amount_cents = entries.amount_cents + h.amount_cents
+             FROM entries_history h
+             WHERE h.user_id = ?1 AND h.batch_id = ?2 AND h.entry_id = entries.id",
+        )
+        .bind(user_id.to_string())
+        .bind(batch_id)
+        .execute(&mut *tx)
+        .await?;
+        let restored = sqlx::query(
+            "INSERT INTO entries(id, user_id, amount_cents, kind, reason, class, created_at,
+                                 occurred_at, source_chat_id, source_message_id, edits,
+                                 original_cents, original_currency, transfer_id, origin)
+             SELECT entry_id, user_id, amount_cents, kind, reason, class, created_at,
+                    occurred_at, source_chat_id, source_message_id, edits,
+                    original_cents, original_currency, transfer_id, origin
+             FROM entries_history h
+             WHERE user_id = ?1 AND batch_id = ?2
+               AND NOT EXISTS (SELECT 1 FROM entries e WHERE e.id = h.entry_id)
+             RETURNING id, reason",
+        )
+        .bind(user_id.to_string())
+        .bind(batch_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for r in &restored {
+            let reason: Option<String> = r.get("reason");
+            set_tags(&mut tx, user_id, r.get("id"), reason.as_deref()).await?;
+        }
+        sqlx::query("DELETE FROM holdings WHERE user_id = ? AND batch_id = ?")
+            .bind(user_id.to_string())
+            .bind(batch_id)
+            .execute(&mut *tx)
+            .await?;
+        let cents: i64 = sqlx::query_scalar(
+            "DELETE FROM entries_history WHERE user_id = ? AND batch_id = ?
+             RETURNING amount_cents",
+        )
+        .bind(user_id.to_string())
+        .bind(batch_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .sum();
+        tx.commit().await?;
+        Ok(Some(cents))
+    }
+
+    /// Up to `limit` uninvested entries next to `cursor`, newest first,
+    /// optionally only those tagged `#tag`. A cursor whose entry is gone
+    /// (or belongs to someone else) gives an empty page.
+    #[tracing::instrument(skip_all)]
+    pub async fn last_entries(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+        tag: Option<&str>,
+        cursor: Cursor,
+    ) -> Result<Vec<Entry>> {
+        let cursor_row = "(SELECT occurred_at, id FROM entries WHERE id = ?4 AND user_id = ?1)";
+        let (position, order) = match cursor {
+            Cursor::Newest => (String::new(), "DESC"),
+            Cursor::Before(_) => (format!("AND (e.occurred_at, e.id) < {cursor_row}"), "DESC"),
+            Cursor::After(_) => (format!("AND (e.occurred_at, e.id) > {cursor_row}"), "ASC"),
+        };
+        let id = match cursor {
+            Cursor::Newest => None,
+            Cursor::Before(id) | Cursor::After(id) => Some(id),
+        };
+        let rows = sqlx::query(&format!(
+            "SELECT e.id, e.amount_cents, e.kind, e.reason, e.class, e.occurred_at
+             FROM entries e
+             WHERE e.user_id = ?1
+               AND (?3 IS NULL OR EXISTS(
+                 SELECT 1 FROM entry_tags t WHERE t.entry_id = e.id AND t.tag = ?3))
+               {position}
+             ORDER BY e.occurred_at {order}, e.id {order}
+             LIMIT ?2"
+        ))
+        .bind(user_id.to_string())
+        .bind(limit)
+        .bind(tag)
+        .bind(id)
+        .fetch_all(&self.0)
+        .await?;
+
+        let mut entries: Vec<Entry> = rows
+            .into_iter()
+            .map(|r| Entry {
+                id: r.get::<i64, _>("id"),
+                amount_cents: r.get::<i64, _>("amount_cents"),
+                kind: r.get("kind"),
+                reason: r.get::<Option<String>, _>("reason"),
+                class: r.get::<Option<String>, _>("class"),
+                occurred_at: r.get::<String, _>("occurred_at"),
+            })
+            .collect();
+        if matches!(cursor, Cursor::After(_)) {
+            entries.reverse();
+        }
+        Ok(entries)
+    }
+
+    /// Net cents and entry count per tag over `[start, end)`, current and
+    /// invested entries alike, largest amounts first. An entry with two
+    /// tags counts toward both.
+    #[tracing::instrument(skip_all)]
+    pub async fn tag_totals(
+        &self,
+        user_id: Uuid,
+        start: &str,
+        end: &str,
+    ) -> Result<Vec<(String, i64, i64)>> {
+        let rows = sqlx::query(
+            "SELECT t.tag, SUM(e.amount_cents) AS net, COUNT(*) AS n
+             FROM (
+               SELECT id, amount_cents, occurred_at FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT entry_id, amount_cents, occurred_at FROM entries_history
+               WHERE user_id = ?1 AND entry_id IS NOT NULL
+             ) e
+             JOIN entry_tags t ON t.entry_id = e.id
+             WHERE e.occurred_at >= ?2 AND e.occurred_at < ?3
+             GROUP BY t.tag
+             ORDER BY ABS(SUM(e.amount_cents)) DESC, t.tag",
+        )
+        .bind(user_id.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("tag"), r.get("net"), r.get("n")))
+            .collect())
+    }
+
+    /// Sums entries of a `YYYY-MM` month (current and archived) per bucket.
+    /// Unclassified saves count as savings; other unclassified entries are ignored.
+    #[tracing::instrument(skip_all)]
+    pub async fn class_totals(&self, user_id: Uuid, month: &str) -> Result<ClassTotals> {
+        let row = sqlx::query(
+            "SELECT
+               COALESCE(SUM(CASE WHEN class = 'need' THEN ABS(amount_cents) END), 0) AS needs,
+               COALESCE(SUM(CASE WHEN class = 'want' THEN ABS(amount_cents) END), 0) AS wants,
+               COALESCE(SUM(CASE WHEN class = 'savings' OR (class IS NULL AND kind = 'save')
+                                 THEN ABS(amount_cents) END), 0) AS savings
+             FROM (
+               SELECT amount_cents, kind, class, occurred_at FROM entries WHERE user_id = ?
+               UNION ALL
+               SELECT amount_cents, kind, class, occurred_at FROM entries_history WHERE user_id = ?
+             )
+             WHERE substr(occurred_at, 1, 7) = ?",
+        )
+        .bind(user_id.to_string())
+        .bind(user_id.to_string())
+        .bind(month)
+        .fetch_one(&self.0)
+        .await?;
+        Ok(ClassTotals {
+            needs: row.get("needs"),
+            wants: row.get("wants"),
+            savings: row.get("savings"),
+        })
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn add_api_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        scope: &str,
+        expires_at: Option<String>,
+    ) -> Result<i64> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO api_tokens(user_id, token_hash, scope, created_at, expires_at)
+             VALUES(?, ?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(token_hash)
+        .bind(scope)
+        .bind(now)
+        .bind(expires_at)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn api_tokens(&self, user_id: Uuid) -> Result<Vec<ApiToken>> {
+        let rows = sqlx::query(
+            "SELECT id, scope, created_at, expires_at, last_used_at
+             FROM api_tokens WHERE user_id = ? ORDER BY id",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| ApiToken {
+                id: r.get("id"),
+                scope: r.get("scope"),
+                created_at: r.get("created_at"),
+                expires_at: r.get("expires_at"),
+                last_used_at: r.get("last_used_at"),
+            })
+            .collect())
+    }
+
+    /// Returns false if the user has no token with that id.
+    #[tracing::instrument(skip_all)]
+    pub async fn revoke_api_token(&self, user_id: Uuid, id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM api_tokens WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Looks up an unexpired token by hash and stamps its last use.
+    #[tracing::instrument(skip_all)]
+    pub async fn authenticate_api_token(&self, token_hash: &str) -> Result<Option<TokenOwner>> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let row = sqlx::query(
+            "UPDATE api_tokens SET last_used_at = ?
+             WHERE token_hash = ? AND (expires_at IS NULL OR expires_at > ?)
+             RETURNING user_id, scope",
+        )
+        .bind(&now)
+        .bind(token_hash)
+        .bind(&now)
+        .fetch_optional(&self.0)
+        .await?;
+        row.map(|r| {
+            Ok(TokenOwner {
+                user_id: Uuid::parse_str(&r.get::<String, _>("user_id"))?,
+                scope: r.get("scope"),
+            })
+        })
+        .transpose()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn matching_entries(&self, user_id: Uuid, f: &EntryFilter) -> Result<Vec<Entry>> {
+        let rows = sqlx::query(
+            "SELECT id, amount_cents, kind, reason, class, occurred_at
+             FROM entries
+             WHERE user_id = ?1 AND transfer_id IS NULL
+               AND (?2 IS NULL OR instr(lower(COALESCE(reason, '')), lower(?2)) > 0)
+               AND (?3 IS NULL OR kind = ?3)
+               AND (?4 IS NULL OR occurred_at < ?4)
+               AND (?5 IS NULL OR occurred_at >= ?5)
+               AND (?6 IS NULL OR created_at >= ?6)
+             ORDER BY occurred_at DESC, id DESC",
+        )
+        .bind(user_id.to_string())
+        .bind(&f.reason)
+        .bind(f.kind)
+        .bind(&f.before)
+        .bind(&f.after)
+        .bind(&f.recorded_after)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Entry {
+                id: r.get("id"),
+                amount_cents: r.get("amount_cents"),
+                kind: r.get("kind"),
+                reason: r.get("reason"),
+                class: r.get("class"),
+                occurred_at: r.get("occurred_at"),
+            })
+            .collect())
+    }
+
+    /// Remembers exactly which entries a /purge preview showed, so entries
+    /// added before the user confirms are left alone.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_pending_purge(&self, user_id: Uuid, entry_ids: &[i64]) -> Result<i64> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO pending_purges(user_id, entry_ids, created_at) VALUES(?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(serde_json::to_string(entry_ids)?)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Deletes the previewed entries in one transaction, except any recorded
+    /// before `lock_cutoff` (/immutable) since the preview. Returns how many
+    /// were removed, `Some(None)` if the purge was made before `cutoff` and
+    /// has expired, or `None` if it isn't pending for this user.
+    #[tracing::instrument(skip_all)]
+    pub async fn run_pending_purge(
+        &self,
+        user_id: Uuid,
+        id: i64,
+        cutoff: &str,
+        lock_cutoff: Option<&str>,
+    ) -> Result<Option<Option<u64>>> {
+        let mut tx = self.0.begin().await?;
+        let Some(row) = sqlx::query(
+            "DELETE FROM pending_purges WHERE id = ? AND user_id = ?
+             RETURNING entry_ids, created_at < ? AS expired",
+        )
+        .bind(id)
+        .bind(user_id.to_string())
+        .bind(cutoff)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+        if row.get::<bool, _>("expired") {
+            tx.commit().await?;
+            return Ok(Some(None));
+        }
+        let ids: String = row.get("entry_ids");
+        let res = sqlx::query(
+            "DELETE FROM entries
+             WHERE user_id = ?1 AND id IN (SELECT value FROM json_each(?2)) AND transfer_id IS NULL
+               AND (?3 IS NULL OR created_at >= ?3)",
+        )
+        .bind(user_id.to_string())
+        .bind(ids)
+        .bind(lock_cutoff)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(Some(Some(res.rows_affected())))
+    }
+
+    /// Parks parsed /import rows until the user confirms the preview.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_pending_import(&self, user_id: Uuid, entries: &[NewEntry]) -> Result<i64> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO pending_imports(user_id, entries, created_at) VALUES(?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(serde_json::to_string(entries)?)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Inserts a pending import in one transaction. Returns how many entries
+    /// were added, or `None` if the import isn't pending for this user.
+    #[tracing::instrument(skip_all)]
+    pub async fn run_pending_import(&self, user_id: Uuid, id: i64) -> Result<Option<u64>> {
+        let mut tx = self.0.begin().await?;
+        let Some(row) = sqlx::query(
+            "DELETE FROM pending_imports WHERE id = ? AND user_id = ? RETURNING entries",
+        )
+        .bind(id)
+        .bind(user_id.to_string())
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let entries: String = row.get("entries");
+        let added = insert_entries(&mut tx, user_id, &entries).await?;
+        tx.commit().await?;
+        Ok(Some(added))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn cancel_pending_import(&self, user_id: Uuid, id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM pending_imports WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn cancel_pending_purge(&self, user_id: Uuid, id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM pending_purges WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Parks an /allinvoo of `amount_cents` until the user confirms it.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_pending_invest(
+        &self,
+        user_id: Uuid,
+        amount_cents: i64,
+        strategy: Option<&str>,
+    ) -> Result<i64> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO pending_invests(user_id, amount_cents, strategy, created_at)
+             VALUES(?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(amount_cents)
+        .bind(strategy)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Removes and returns a pending /allinvoo, whether it is confirmed or
+    /// cancelled. `None` if it isn't pending for this user.
+    #[tracing::instrument(skip_all)]
+    pub async fn take_pending_invest(
+        &self,
+        user_id: Uuid,
+        id: i64,
+        cutoff: &str,
+    ) -> Result<Option<PendingInvest>> {
+        let row = sqlx::query(
+            "DELETE FROM pending_invests WHERE id = ? AND user_id = ?
+             RETURNING amount_cents, strategy, created_at < ? AS expired",
+        )
+        .bind(id)
+        .bind(user_id.to_string())
+        .bind(cutoff)
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| PendingInvest {
+            amount_cents: r.get("amount_cents"),
+            strategy: r.get("strategy"),
+            expired: r.get("expired"),
+        }))
+    }
+
+    /// Parks a /transfer from `from` (ledger, Telegram id, name) to
+    /// `to_user_id` until the recipient accepts it.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_pending_transfer(
+        &self,
+        (from_user_id, from_tg_user_id, from_name): (Uuid, i64, &str),
+        to_user_id: Uuid,
+        amount_cents: i64,
+        reason: Option<&str>,
+    ) -> Result<i64> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        let res = sqlx::query(
+            "INSERT INTO pending_transfers(from_user_id, to_user_id, from_tg_user_id, from_name,
+                                           amount_cents, reason, created_at)
+             VALUES(?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(from_user_id.to_string())
+        .bind(to_user_id.to_string())
+        .bind(from_tg_user_id)
+        .bind(from_name)
+        .bind(amount_cents)
+        .bind(reason)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Removes and returns a pending /transfer to `to_user_id`, whether it
+    /// is accepted or declined. `None` if it isn't pending for this user.
+    #[tracing::instrument(skip_all)]
+    pub async fn take_pending_transfer(
+        &self,
+        to_user_id: Uuid,
+        id: i64,
+        cutoff: &str,
+    ) -> Result<Option<PendingTransfer>> {
+        let row = sqlx::query(
+            "DELETE FROM pending_transfers WHERE id = ? AND to_user_id = ?
+             RETURNING from_user_id, from_tg_user_id, from_name, amount_cents, reason,
+                       created_at < ? AS expired",
+        )
+        .bind(id)
+        .bind(to_user_id.to_string())
+        .bind(cutoff)
+        .fetch_optional(&self.0)
+        .await?;
+        row.map(|r| {
+            Ok(PendingTransfer {
+                from_user_id: Uuid::parse_str(&r.get::<String, _>("from_user_id"))?,
+                from_tg_user_id: r.get("from_tg_user_id"),
+                from_name: r.get("from_name"),
+                amount_cents: r.get("amount_cents"),
+                reason: r.get("reason"),
+                expired: r.get("expired"),
+            })
+        })
+        .transpose()
+    }
+
+    /// Books a /transfer as two adjustments, out of `from` and into `to`,
+    /// both or neither. Nothing is booked, and `None` returned, when the
+    /// sender's current stash no longer holds the amount; otherwise the
+    /// (sender's, recipient's) entry ids.
+    #[tracing::instrument(skip_all)]
+    pub async fn transfer(
+        &self,
+        transfer_id: i64,
+        (from_user_id, from_reason): (Uuid, &str),
+        (to_user_id, to_reason): (Uuid, &str),
+        amount_cents: i64,
+    ) -> Result<Option<(i64, i64)>> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        let mut tx = self.0.begin().await?;
+        // The balance check and the debit are one statement, so two
+        // transfers racing each other can't both spend the same money.
+        let res = sqlx::query(
+            "INSERT INTO entries(user_id, amount_cents, kind, reason, created_at, occurred_at,
+                                 transfer_id)
+             SELECT ?1, ?2, ?3, ?4, ?5, ?5, ?6
+             WHERE (SELECT COALESCE(SUM(amount_cents), 0) FROM entries WHERE user_id = ?1) >= -?2",
+        )
+        .bind(from_user_id.to_string())
+        .bind(-amount_cents)
+        .bind(EntryKind::Adjust)
+        .bind(from_reason)
+        .bind(&now)
+        .bind(transfer_id)
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let from_id = res.last_insert_rowid();
+        set_tags(&mut tx, from_user_id, from_id, Some(from_reason)).await?;
+        let to_id = sqlx::query(
+            "INSERT INTO entries(user_id, amount_cents, kind, reason, created_at, occurred_at,
+                                 transfer_id)
+             VALUES(?1, ?2, ?3, ?4, ?5, ?5, ?6)",
+        )
+        .bind(to_user_id.to_string())
+        .bind(amount_cents)
+        .bind(EntryKind::Adjust)
+        .bind(to_reason)
+        .bind(&now)
+        .bind(transfer_id)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        set_tags(&mut tx, to_user_id, to_id, Some(to_reason)).await?;
+        tx.commit().await?;
+        Ok(Some((from_id, to_id)))
+    }
+
+    /// Records money lent to `counterparty` (positive) or paid back by them
+    /// (negative). Loans are kept apart from entries, so they never move
+    /// the stash.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_loan(
+        &self,
+        user_id: Uuid,
+        counterparty: &str,
+        amount_cents: i64,
+        reason: Option<&str>,
+    ) -> Result<i64> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        let res = sqlx::query(
+            "INSERT INTO loans(user_id, counterparty, amount_cents, reason, created_at)
+             VALUES(?, ?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(counterparty)
+        .bind(amount_cents)
+        .bind(reason)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Balances per counterparty, names matched regardless of case, most
+    /// recently active first. Settled ones are left out.
+    #[tracing::instrument(skip_all)]
+    pub async fn loans(&self, user_id: Uuid) -> Result<Vec<Loan>> {
+        let rows = sqlx::query(
+            "SELECT counterparty, SUM(amount_cents) AS outstanding, MAX(created_at) AS last_at
+             FROM loans WHERE user_id = ?
+             GROUP BY counterparty
+             HAVING outstanding != 0
+             ORDER BY last_at DESC",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Loan {
+                counterparty: r.get("counterparty"),
+                outstanding_cents: r.get("outstanding"),
+                last_at: r.get("last_at"),
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn add_pending(
+        &self,
+        user_id: Uuid,
+        chat_id: i64,
+        amount_cents: i64,
+        reason: Option<String>,
+        (source, file_id): (&str, Option<&str>),
+    ) -> Result<i64> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO pending_entries(user_id, chat_id, amount_cents, reason, source, file_id, created_at)
+             VALUES(?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(chat_id)
+        .bind(amount_cents)
+        .bind(reason)
+        .bind(source)
+        .bind(file_id)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Removes and returns the user's pending entry, so a double-tapped
+    /// button can't record it twice.
+    #[tracing::instrument(skip_all)]
+    pub async fn take_pending(&self, user_id: Uuid, id: i64) -> Result<Option<Pending>> {
+        let row = sqlx::query(
+            "DELETE FROM pending_entries WHERE id = ? AND user_id = ?
+             RETURNING amount_cents, reason, source, file_id",
+        )
+        .bind(id)
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| Pending {
+            amount_cents: r.get("amount_cents"),
+            reason: r.get("reason"),
+            source: r.get("source"),
+            file_id: r.get("file_id"),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn add_plan(
+        &self,
+        user_id: Uuid,
+        chat_id: i64,
+        amount_cents: i64,
+        reason: Option<String>,
+        due_on: &str,
+    ) -> Result<i64> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO planned_entries(user_id, chat_id, amount_cents, reason, due_on, created_at)
+             VALUES(?, ?, ?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(chat_id)
+        .bind(amount_cents)
+        .bind(reason)
+        .bind(due_on)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn cancel_plan(&self, user_id: Uuid, id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM planned_entries WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn user_plans(&self, user_id: Uuid) -> Result<Vec<Plan>> {
+        let rows = sqlx::query(
+            "SELECT id, user_id, chat_id, amount_cents, reason, due_on
+             FROM planned_entries WHERE user_id = ? ORDER BY due_on, id",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter().map(plan_from_row).collect()
+    }
+
+    /// Plans of every user whose date has arrived (`due_on <= today`).
+    #[tracing::instrument(skip_all)]
+    pub async fn due_plans(&self, today: &str) -> Result<Vec<Plan>> {
+        let rows = sqlx::query(
+            "SELECT id, user_id, chat_id, amount_cents, reason, due_on
+             FROM planned_entries WHERE due_on <= ? ORDER BY due_on, id",
+        )
+        .bind(today)
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter().map(plan_from_row).collect()
+    }
+
+    /// Turns a due plan into a real entry dated `due_on`. Returns false if the
+    /// plan was cancelled in the meantime.
+    #[tracing::instrument(skip_all)]
+    pub async fn realize_plan(&self, plan: &Plan) -> Result<bool> {
+        let mut tx = self.0.begin().await?;
+        let res = sqlx::query("DELETE FROM planned_entries WHERE id = ?")
+            .bind(plan.id)
+            .execute(&mut *tx)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let kind = EntryKind::by_sign(plan.amount_cents);
+        let res = sqlx::query(
+            "INSERT INTO entries(user_id, amount_cents, kind, reason, created_at, occurred_at, origin)
+             VALUES(?, ?, ?, ?, ?, ?, 'plan')",
+        )
+        .bind(plan.user_id.to_string())
+        .bind(plan.amount_cents)
+        .bind(kind)
+        .bind(&plan.reason)
+        .bind(now)
+        .bind(format!("{}T00:00:00Z", plan.due_on))
+        .execute(&mut *tx)
+        .await?;
+        set_tags(
+            &mut tx,
+            plan.user_id,
+            res.last_insert_rowid(),
+            plan.reason.as_deref(),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Adds a standing order whose first run is on `start_on`.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_recurring(
+        &self,
+        user_id: Uuid,
+        chat_id: i64,
+        amount_cents: i64,
+        reason: Option<String>,
+        every: &str,
+        start_on: &str,
+    ) -> Result<i64> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO recurring_entries(user_id, chat_id, amount_cents, reason, every,
+                                           start_on, next_on, created_at)
+             VALUES(?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(chat_id)
+        .bind(amount_cents)
+        .bind(reason)
+        .bind(every)
+        .bind(start_on)
+        .bind(start_on)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn cancel_recurring(&self, user_id: Uuid, id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM recurring_entries WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn user_recurring(&self, user_id: Uuid) -> Result<Vec<Recurring>> {
+        let rows = sqlx::query(&format!(
+            "{RECURRING_SELECT} WHERE user_id = ? ORDER BY next_on, id"
+        ))
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter().map(recurring_from_row).collect()
+    }
+
+    /// Standing orders of every user with a run due (`next_on <= today`).
+    #[tracing::instrument(skip_all)]
+    pub async fn due_recurring(&self, today: &str) -> Result<Vec<Recurring>> {
+        let rows = sqlx::query(&format!(
+            "{RECURRING_SELECT} WHERE next_on <= ? ORDER BY next_on, id"
+        ))
+        .bind(today)
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter().map(recurring_from_row).collect()
+    }
+
+    /// Records the run due on `r.next_on` as an entry dated that day and
+    /// moves the order on to `next_on`. Returns false if the order was
+    /// cancelled or already advanced in the meantime.
+    #[tracing::instrument(skip_all)]
+    pub async fn realize_recurring(&self, r: &Recurring, next_on: &str) -> Result<bool> {
+        let mut tx = self.0.begin().await?;
+        let res = sqlx::query(
+            "UPDATE recurring_entries SET runs = runs + 1, next_on = ?
+             WHERE id = ? AND runs = ?",
+        )
+        .bind(next_on)
+        .bind(r.id)
+        .bind(r.runs)
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let kind = EntryKind::by_sign(r.amount_cents);
+        let res = sqlx::query(
+            "INSERT INTO entries(user_id, amount_cents, kind, reason, created_at, occurred_at, origin)
+             VALUES(?, ?, ?, ?, ?, ?, 'recurring')",
+        )
+        .bind(r.user_id.to_string())
+        .bind(r.amount_cents)
+        .bind(kind)
+        .bind(&r.reason)
+        .bind(now)
+        .bind(format!("{}T00:00:00Z", r.next_on))
+        .execute(&mut *tx)
+        .await?;
+        set_tags(
+            &mut tx,
+            r.user_id,
+            res.last_insert_rowid(),
+            r.reason.as_deref(),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn month_close(&self, user_id: Uuid, month: &str) -> Result<Option<MonthClose>> {
+        let row = sqlx::query(
+            "SELECT step, statement, closed_at FROM month_closes WHERE user_id = ? AND month = ?",
+        )
+        .bind(user_id.to_string())
+        .bind(month)
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| MonthClose {
+            step: r.get("step"),
+            statement: r.get("statement"),
+            closed_at: r.get("closed_at"),
+        }))
+    }
+
+    /// Moves an open /close run to `step`, starting it if needed. Returns
+    /// false if the month is already closed.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_close_step(&self, user_id: Uuid, month: &str, step: &str) -> Result<bool> {
+        let res = sqlx::query(
+            "INSERT INTO month_closes(user_id, month, step) VALUES(?, ?, ?)
+             ON CONFLICT(user_id, month) DO UPDATE SET step = excluded.step
+             WHERE closed_at IS NULL",
+        )
+        .bind(user_id.to_string())
+        .bind(month)
+        .bind(step)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Archives the monthly statement and marks the month closed.
+    #[tracing::instrument(skip_all)]
+    pub async fn finish_close(&self, user_id: Uuid, month: &str, statement: &str) -> Result<bool> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "UPDATE month_closes SET step = 'done', statement = ?, closed_at = ?
+             WHERE user_id = ? AND month = ? AND closed_at IS NULL",
+        )
+        .bind(statement)
+        .bind(now)
+        .bind(user_id.to_string())
+        .bind(month)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Drops an unfinished /close run; closed months are kept.
+    #[tracing::instrument(skip_all)]
+    pub async fn cancel_close(&self, user_id: Uuid, month: &str) -> Result<bool> {
+        let res = sqlx::query(
+            "DELETE FROM month_closes WHERE user_id = ? AND month = ? AND closed_at IS NULL",
+        )
+        .bind(user_id.to_string())
+        .bind(month)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn goal(&self, user_id: Uuid) -> Result<Option<Goal>> {
+        let row = sqlx::query(
+            "SELECT target_cents, name, deadline, start_cents, created_at
+             FROM goals WHERE user_id = ?",
+        )
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| Goal {
+            target_cents: r.get("target_cents"),
+            name: r.get("name"),
+            deadline: r.get("deadline"),
+            start_cents: r.get("start_cents"),
+            created_at: r.get("created_at"),
+        }))
+    }
+
+    /// Returns false if the user had no goal. Its check-ins go with it.
+    #[tracing::instrument(skip_all)]
+    pub async fn clear_goal(&self, user_id: Uuid) -> Result<bool> {
+        let mut tx = self.0.begin().await?;
+        let res = sqlx::query("DELETE FROM goals WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM goal_checkins WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// The user's goal check-in schedule, unless paused or without a goal.
+    #[tracing::instrument(skip_all)]
+    pub async fn goal_checkin(&self, user_id: Uuid) -> Result<Option<Reminder>> {
+        let row = sqlx::query(&format!("{GOAL_CHECKIN_SELECT} AND c.user_id = ?"))
+            .bind(user_id.to_string())
+            .fetch_optional(&self.0)
+            .await?;
+        row.map(reminder_from_row).transpose()
+    }
+
+    /// Every active goal check-in with its owner's timezone.
+    #[tracing::instrument(skip_all)]
+    pub async fn goal_checkins(&self) -> Result<Vec<Reminder>> {
+        let rows = sqlx::query(GOAL_CHECKIN_SELECT).fetch_all(&self.0).await?;
+        rows.into_iter().map(reminder_from_row).collect()
+    }
+
+    /// Sets (and resumes) the user's goal check-ins. With `only_if_new`
+    /// an existing schedule, paused or not, is left alone; returns whether
+    /// anything was written.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_goal_checkin(&self, r: &Reminder, only_if_new: bool) -> Result<bool> {
+        let conflict = if only_if_new {
+            "DO NOTHING"
+        } else {
+            "DO UPDATE SET
+               chat_id = excluded.chat_id,
+               weekday = excluded.weekday,
+               minute_of_day = excluded.minute_of_day,
+               last_sent_on = excluded.last_sent_on,
+               paused = 0"
+        };
+        let res = sqlx::query(&format!(
+            "INSERT INTO goal_checkins(user_id, chat_id, weekday, minute_of_day, last_sent_on)
+             VALUES(?, ?, ?, ?, ?)
+             ON CONFLICT(user_id) {conflict}"
+        ))
+        .bind(r.user_id.to_string())
+        .bind(r.chat_id)
+        .bind(r.weekday)
+        .bind(r.minute_of_day)
+        .bind(&r.last_sent_on)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Returns false if check-ins were already paused or never set.
+    #[tracing::instrument(skip_all)]
+    pub async fn pause_goal_checkin(&self, user_id: Uuid) -> Result<bool> {
+        let res =
+            sqlx::query("UPDATE goal_checkins SET paused = 1 WHERE user_id = ? AND paused = 0")
+                .bind(user_id.to_string())
+                .execute(&self.0)
+                .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_goal_checkin_sent(&self, user_id: Uuid, local_day: &str) -> Result<()> {
+        sqlx::query("UPDATE goal_checkins SET last_sent_on = ? WHERE user_id = ?")
+            .bind(local_day)
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    /// Replaces the user's goal; progress restarts from the current total.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_goal(
+        &self,
+        user_id: Uuid,
+        target_cents: i64,
+        name: Option<String>,
+        deadline: Option<String>,
+    ) -> Result<()> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let start_cents = self.total_cents(user_id).await?;
+        sqlx::query(
+            "INSERT INTO goals(user_id, target_cents, name, deadline, start_cents, created_at)
+             VALUES(?, ?, ?, ?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET
+               target_cents = excluded.target_cents,
+               name = excluded.name,
+               deadline = excluded.deadline,
+               start_cents = excluded.start_cents,
+               created_at = excluded.created_at",
+        )
+        .bind(user_id.to_string())
+        .bind(target_cents)
+        .bind(name)
+        .bind(deadline)
+        .bind(start_cents)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Stores every user's cash and invested-at-cost totals as they stand at
+    /// the start of `day`. Safe to call repeatedly: the first snapshot of a
+    /// day wins.
+    #[tracing::instrument(skip_all)]
+    pub async fn record_snapshots(&self, day: &str) -> Result<u64> {
+        let res = sqlx::query(
+            "INSERT OR IGNORE INTO snapshots(user_id, day, cash_cents, invested_cents)
+             SELECT u.id, ?,
+               (SELECT COALESCE(SUM(amount_cents),0) FROM entries WHERE user_id = u.id),
+               (SELECT COALESCE(SUM(amount_cents),0) FROM entries_history WHERE user_id = u.id)
+             FROM users u",
+        )
+        .bind(day)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn snapshots(&self, user_id: Uuid) -> Result<Vec<Snapshot>> {
+        let rows = sqlx::query(
+            "SELECT day, cash_cents, invested_cents, value_cents FROM snapshots
+             WHERE user_id = ? ORDER BY day",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Snapshot {
+                day: r.get("day"),
+                cash_cents: r.get("cash_cents"),
+                invested_cents: r.get("invested_cents"),
+                value_cents: r.get("value_cents"),
+            })
+            .collect())
+    }
+
+    /// The user's latest snapshot on or before `day`.
+    #[tracing::instrument(skip_all)]
+    pub async fn snapshot_on_or_before(
+        &self,
+        user_id: Uuid,
+        day: &str,
+    ) -> Result<Option<Snapshot>> {
+        let row = sqlx::query(
+            "SELECT day, cash_cents, invested_cents, value_cents FROM snapshots
+             WHERE user_id = ? AND day <= ? ORDER BY day DESC LIMIT 1",
+        )
+        .bind(user_id.to_string())
+        .bind(day)
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| Snapshot {
+            day: r.get("day"),
+            cash_cents: r.get("cash_cents"),
+            invested_cents: r.get("invested_cents"),
+            value_cents: r.get("value_cents"),
+        }))
+    }
+
+    /// Every ticker anyone holds shares of.
+    #[tracing::instrument(skip_all)]
+    pub async fn held_tickers(&self) -> Result<Vec<String>> {
+        Ok(
+            sqlx::query_scalar("SELECT DISTINCT ticker FROM holdings ORDER BY ticker")
+                .fetch_all(&self.0)
+                .await?,
+        )
+    }
+
+    /// Every currency set with /currency other than USD.
+    #[tracing::instrument(skip_all)]
+    pub async fn currencies_in_use(&self) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar(
+            "SELECT DISTINCT currency FROM user_settings
+             WHERE currency IS NOT NULL AND currency <> 'USD' ORDER BY currency",
+        )
+        .fetch_all(&self.0)
+        .await?)
+    }
+
+    /// Fills in the market value of `day`'s snapshots from (ticker, price
+    /// in USD cents) pairs: the invested total with each priced holding's
+    /// gain over its cost added. `rates` are (currency, USD per unit) pairs
+    /// to turn that into each user's currency; holdings in a currency with
+    /// no rate count at cost. Snapshots that already have one are kept.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_snapshot_values(
+        &self,
+        day: &str,
+        prices: &[(String, i64)],
+        rates: &[(String, f64)],
+    ) -> Result<u64> {
+        let prices = serde_json::to_string(
+            &prices
+                .iter()
+                .map(|(ticker, price)| serde_json::json!({ "ticker": ticker, "price": price }))
+                .collect::<Vec<_>>(),
+        )?;
+        let rates = serde_json::to_string(
+            &rates
+                .iter()
+                .map(|(code, rate)| serde_json::json!({ "code": code, "rate": rate }))
+                .chain([serde_json::json!({ "code": "USD", "rate": 1.0 })])
+                .collect::<Vec<_>>(),
+        )?;
+        // Bare numbers are valued as USD, as before /currency existed.
+        let res = sqlx::query(
+            "UPDATE snapshots SET value_cents = invested_cents + COALESCE((
+               SELECT CAST(ROUND(SUM(
+                 h.micro_shares * json_extract(p.value, '$.price') / 1000000.0 / (
+                   SELECT json_extract(r.value, '$.rate') FROM json_each(?3) r
+                   WHERE json_extract(r.value, '$.code') = COALESCE((
+                     SELECT currency FROM user_settings
+                     WHERE user_id = COALESCE((SELECT created_by FROM shared_ledgers
+                                               WHERE ledger_id = snapshots.user_id),
+                                              snapshots.user_id)
+                   ), 'USD')
+                 ) - h.cost_cents
+               )) AS INTEGER)
+               FROM holdings h JOIN json_each(?1) p
+                 ON json_extract(p.value, '$.ticker') = h.ticker
+               WHERE h.user_id = snapshots.user_id
+             ), 0)
+             WHERE day = ?2 AND value_cents IS NULL",
+        )
+        .bind(prices)
+        .bind(day)
+        .bind(rates)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// Target allocation as (ticker, percent) pairs, largest first.
+    #[tracing::instrument(skip_all)]
+    pub async fn allocation_targets(&self, user_id: Uuid) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query(
+            "SELECT ticker, percent FROM allocation_targets
+             WHERE user_id = ? ORDER BY percent DESC, ticker",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("ticker"), r.get("percent")))
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_allocation_targets(
+        &self,
+        user_id: Uuid,
+        targets: &[(String, i64)],
+    ) -> Result<()> {
+        let mut tx = self.0.begin().await?;
+        sqlx::query("DELETE FROM allocation_targets WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        for (ticker, percent) in targets {
+            sqlx::query("INSERT INTO allocation_targets(user_id, ticker, percent) VALUES(?, ?, ?)")
+                .bind(user_id.to_string())
+                .bind(ticker)
+                .bind(percent)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// The latest recorded month of each envelope, up to and including a
+    /// `YYYY-MM` month.
+    #[tracing::instrument(skip_all)]
+    pub async fn latest_budgets(&self, user_id: Uuid, month: &str) -> Result<Vec<Budget>> {
+        let rows = sqlx::query(
+            "SELECT category, month, amount_cents, rollover, carry_cents FROM budgets b
+             WHERE user_id = ?1 AND month = (
+               SELECT MAX(month) FROM budgets
+               WHERE user_id = ?1 AND category = b.category AND month <= ?2
+             )
+             ORDER BY category",
+        )
+        .bind(user_id.to_string())
+        .bind(month)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Budget {
+                category: r.get("category"),
+                month: r.get("month"),
+                amount_cents: r.get("amount_cents"),
+                rollover: r.get::<i64, _>("rollover") != 0,
+                carry_cents: r.get("carry_cents"),
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn put_budget(&self, user_id: Uuid, budget: &Budget) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO budgets(user_id, category, month, amount_cents, rollover, carry_cents)
+             VALUES(?, ?, ?, ?, ?, ?)
+             ON CONFLICT(user_id, category, month) DO UPDATE SET
+               amount_cents = excluded.amount_cents,
+               rollover = excluded.rollover,
+               carry_cents = excluded.carry_cents",
+        )
+        .bind(user_id.to_string())
+        .bind(&budget.category)
+        .bind(&budget.month)
+        .bind(budget.amount_cents)
+        .bind(budget.rollover)
+        .bind(budget.carry_cents)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Users holding at least `threshold_cents`, or any cash recorded before
+    /// `idle_before`, who haven't been nudged since `nudged_before`.
+    #[tracing::instrument(skip_all)]
+    pub async fn idle_cash(
+        &self,
+        threshold_cents: i64,
+        idle_before: &str,
+        nudged_before: &str,
+    ) -> Result<Vec<IdleCash>> {
+        let rows = sqlx::query(&format!(
+            "SELECT u.id, r.tg_user_id, SUM(e.amount_cents) AS total, MIN(e.created_at) AS since
+             FROM users u
+             JOIN {LEDGER_RECIPIENTS} r ON r.ledger_id = u.id
+             JOIN entries e ON e.user_id = u.id
+             LEFT JOIN user_settings s ON s.user_id = u.id
+             WHERE COALESCE(s.nudges, 1) = 1
+               AND (s.cash_drag_nudged_at IS NULL OR s.cash_drag_nudged_at < ?)
+             GROUP BY u.id, r.tg_user_id
+             HAVING total > 0 AND (total >= ? OR since < ?)"
+        ))
+        .bind(nudged_before)
+        .bind(threshold_cents)
+        .bind(idle_before)
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter()
+            .map(|r| {
+                Ok(IdleCash {
+                    user_id: Uuid::parse_str(&r.get::<String, _>("id"))?,
+                    tg_user_id: r.get("tg_user_id"),
+                    total_cents: r.get("total"),
+                    since: r.get("since"),
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_cash_drag_nudged(&self, user_id: Uuid, at: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, cash_drag_nudged_at) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET cash_drag_nudged_at = excluded.cash_drag_nudged_at",
+        )
+        .bind(user_id.to_string())
+        .bind(at)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Net amount per day (`YYYY-MM-DD`) from `from_day` on, current and archived.
+    /// Opening balances, interest and dividends aren't activity and are left out.
+    #[tracing::instrument(skip_all)]
+    pub async fn daily_net(&self, user_id: Uuid, from_day: &str) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query(
+            "SELECT substr(occurred_at, 1, 10) AS day, SUM(amount_cents) AS net
+             FROM (
+               SELECT amount_cents, kind, occurred_at FROM entries WHERE user_id = ?
+               UNION ALL
+               SELECT amount_cents, kind, occurred_at FROM entries_history WHERE user_id = ?
+             )
+             WHERE kind NOT IN ('opening', 'interest', 'dividend') AND occurred_at >= ?
+             GROUP BY day ORDER BY day",
+        )
+        .bind(user_id.to_string())
+        .bind(user_id.to_string())
+        .bind(from_day)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("day"), r.get("net")))
+            .collect())
+    }
+
+    /// Net change of the user's savings (cash and invested, every kind) per
+    /// day, or per month as `YYYY-MM` when `monthly`, from `from_day` on.
+    #[tracing::instrument(skip_all)]
+    pub async fn net_by_bucket(
+        &self,
+        user_id: Uuid,
+        monthly: bool,
+        from_day: &str,
+    ) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query(
+            "SELECT substr(occurred_at, 1, ?3) AS bucket, SUM(amount_cents) AS net
+             FROM (
+               SELECT amount_cents, occurred_at FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT amount_cents, occurred_at FROM entries_history WHERE user_id = ?1
+             )
+             WHERE occurred_at >= ?2
+             GROUP BY bucket ORDER BY bucket",
+        )
+        .bind(user_id.to_string())
+        .bind(from_day)
+        .bind(if monthly { 7 } else { 10 })
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("bucket"), r.get("net")))
+            .collect())
+    }
+
+    /// Cash plus invested as of the start of `day`.
+    #[tracing::instrument(skip_all)]
+    pub async fn balance_before(&self, user_id: Uuid, day: &str) -> Result<i64> {
+        let row = sqlx::query(
+            "SELECT COALESCE(SUM(amount_cents), 0) AS total FROM (
+               SELECT amount_cents, occurred_at FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT amount_cents, occurred_at FROM entries_history WHERE user_id = ?1
+             )
+             WHERE occurred_at < ?2",
+        )
+        .bind(user_id.to_string())
+        .bind(day)
+        .fetch_one(&self.0)
+        .await?;
+        Ok(row.get("total"))
+    }
+
+    /// The user's past save reasons starting with `prefix` (any case), most
+    /// used first, for inline-query completion.
+    #[tracing::instrument(skip_all)]
+    pub async fn reason_suggestions(
+        &self,
+        user_id: Uuid,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT reason, COUNT(*) AS uses, MAX(created_at) AS last_used FROM (
+               SELECT reason, kind, created_at FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT reason, kind, created_at FROM entries_history WHERE user_id = ?1
+             )
+             WHERE kind = 'save' AND reason IS NOT NULL AND instr(lower(reason), lower(?2)) = 1
+             GROUP BY reason
+             ORDER BY uses DESC, last_used DESC, reason
+             LIMIT ?3",
+        )
+        .bind(user_id.to_string())
+        .bind(prefix)
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows.into_iter().map(|r| r.get("reason")).collect())
+    }
+
+    /// Money spent (as positive cents) per distinct reason in a `YYYY-MM` month.
+    #[tracing::instrument(skip_all)]
+    pub async fn spending_by_reason(
+        &self,
+        user_id: Uuid,
+        month: &str,
+    ) -> Result<Vec<(Option<String>, i64)>> {
+        let rows = sqlx::query(
+            "SELECT reason, -SUM(amount_cents) AS spent
+             FROM (
+               SELECT amount_cents, reason, occurred_at FROM entries WHERE user_id = ?
+               UNION ALL
+               SELECT amount_cents, reason, occurred_at FROM entries_history WHERE user_id = ?
+             )
+             WHERE amount_cents < 0 AND substr(occurred_at, 1, 7) = ?
+             GROUP BY reason",
+        )
+        .bind(user_id.to_string())
+        .bind(user_id.to_string())
+        .bind(month)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("reason"), r.get("spent")))
+            .collect())
+    }
+
+    /// Like [`Db::spending_by_reason`], for the days `[start, end)`
+    /// (`YYYY-MM-DD`).
+    #[tracing::instrument(skip_all)]
+    pub async fn spending_by_reason_between(
+        &self,
+        user_id: Uuid,
+        start: &str,
+        end: &str,
+    ) -> Result<Vec<(Option<String>, i64)>> {
+        let rows = sqlx::query(
+            "SELECT reason, -SUM(amount_cents) AS spent
+             FROM (
+               SELECT amount_cents, reason, occurred_at FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT amount_cents, reason, occurred_at FROM entries_history WHERE user_id = ?1
+             )
+             WHERE amount_cents < 0
+               AND substr(occurred_at, 1, 10) >= ?2 AND substr(occurred_at, 1, 10) < ?3
+             GROUP BY reason",
+        )
+        .bind(user_id.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("reason"), r.get("spent")))
+            .collect())
+    }
+
+    /// Saved and spent by `occurred_at`, invested by `archived_at`, for
+    /// `[start, end)` where both are `YYYY-MM-DD`. Opening balances are not
+    /// counted as saving, and dividends are counted on their own.
+    #[tracing::instrument(skip_all)]
+    pub async fn period_summary(
+        &self,
+        user_id: Uuid,
+        start: &str,
+        end: &str,
+    ) -> Result<PeriodSummary> {
+        let row = sqlx::query(
+            "SELECT
+               COALESCE(SUM(CASE WHEN amount_cents > 0 THEN amount_cents END), 0) AS saved,
+               COALESCE(-SUM(CASE WHEN amount_cents < 0 THEN amount_cents END), 0) AS spent
+             FROM (
+               SELECT amount_cents, kind, occurred_at FROM entries WHERE user_id = ?
+               UNION ALL
+               SELECT amount_cents, kind, occurred_at FROM entries_history WHERE user_id = ?
+             )
+             WHERE kind NOT IN ('opening', 'dividend') AND occurred_at >= ? AND occurred_at < ?",
+        )
+        .bind(user_id.to_string())
+        .bind(user_id.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.0)
+        .await?;
+        let dividends: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(SUM(amount_cents), 0) FROM dividends
+             WHERE user_id = ? AND received_on >= ? AND received_on < ?",
+        )
+        .bind(user_id.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.0)
+        .await?;
+        let invested: i64 = sqlx::query(
+            "SELECT COALESCE(SUM(amount_cents), 0) AS invested FROM entries_history
+             WHERE user_id = ? AND archived_at >= ? AND archived_at < ?",
+        )
+        .bind(user_id.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.0)
+        .await?
+        .get("invested");
+        Ok(PeriodSummary {
+            saved: row.get("saved"),
+            spent: row.get("spent"),
+            invested,
+            dividends,
+        })
+    }
+
+    /// Entry counts per kind and the largest entry in `[start, end)`,
+    /// current and invested alike. Opening balances are left out.
+    #[tracing::instrument(skip_all)]
+    pub async fn period_stats(&self, user_id: Uuid, start: &str, end: &str) -> Result<PeriodStats> {
+        const RANGE: &str = "FROM (
+               SELECT id, amount_cents, kind, reason, class, occurred_at
+               FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT COALESCE(entry_id, id), amount_cents, kind, reason, class, occurred_at
+               FROM entries_history WHERE user_id = ?1
+             )
+             WHERE kind != 'opening' AND occurred_at >= ?2 AND occurred_at < ?3";
+        let kinds = sqlx::query(&format!(
+            "SELECT kind, COUNT(*) AS n {RANGE} GROUP BY kind ORDER BY n DESC, kind"
+        ))
+        .bind(user_id.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.0)
+        .await?
+        .into_iter()
+        .map(|r| (r.get("kind"), r.get("n")))
+        .collect();
+        let largest = sqlx::query(&format!(
+            "SELECT id, amount_cents, kind, reason, class, occurred_at {RANGE}
+             ORDER BY ABS(amount_cents) DESC, occurred_at DESC LIMIT 1"
+        ))
+        .bind(user_id.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_optional(&self.0)
+        .await?
+        .map(|r| Entry {
+            id: r.get("id"),
+            amount_cents: r.get("amount_cents"),
+            kind: r.get("kind"),
+            reason: r.get("reason"),
+            class: r.get("class"),
+            occurred_at: r.get("occurred_at"),
+        });
+        Ok(PeriodStats { kinds, largest })
+    }
+
+    /// The user's outbound webhook as (url, signing secret).
+    #[tracing::instrument(skip_all)]
+    pub async fn webhook(&self, user_id: Uuid) -> Result<Option<(String, String)>> {
+        let row = sqlx::query(
+            "SELECT webhook_url, webhook_secret FROM user_settings
+             WHERE user_id = ? AND webhook_url IS NOT NULL",
+        )
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map(|r| (r.get("webhook_url"), r.get("webhook_secret"))))
+    }
+
+    /// Where a ledger's events go: its owner's webhook, or each /shared
+    /// member's.
+    #[tracing::instrument(skip_all)]
+    pub async fn ledger_webhooks(&self, ledger_id: Uuid) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query(
+            "SELECT webhook_url, webhook_secret FROM user_settings
+             WHERE webhook_url IS NOT NULL
+               AND (user_id = ?1
+                    OR user_id IN (SELECT user_id FROM shared_members WHERE ledger_id = ?1))",
+        )
+        .bind(ledger_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("webhook_url"), r.get("webhook_secret")))
+            .collect())
+    }
+
+    /// `None` removes the webhook.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_webhook(&self, user_id: Uuid, hook: Option<(&str, &str)>) -> Result<()> {
+        let (url, secret) = hook.unzip();
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, webhook_url, webhook_secret) VALUES(?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET
+               webhook_url = excluded.webhook_url,
+               webhook_secret = excluded.webhook_secret",
+        )
+        .bind(user_id.to_string())
+        .bind(url)
+        .bind(secret)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn nudges_enabled(&self, user_id: Uuid) -> Result<bool> {
+        let row = sqlx::query("SELECT nudges FROM user_settings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.map(|r| r.get::<i64, _>("nudges") != 0).unwrap_or(true))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_nudges(&self, user_id: Uuid, enabled: bool) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, nudges) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET nudges = excluded.nudges",
+        )
+        .bind(user_id.to_string())
+        .bind(enabled as i64)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// The ISO code set with /currency, if any. A /shared ledger's is its
+    /// creator's.
+    #[tracing::instrument(skip_all)]
+    pub async fn currency(&self, user_id: Uuid) -> Result<Option<String>> {
+        let code = sqlx::query_scalar(
+            "SELECT currency FROM user_settings
+             WHERE user_id = COALESCE((SELECT created_by FROM shared_ledgers WHERE ledger_id = ?1), ?1)",
+        )
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(code.flatten())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_currency(&self, user_id: Uuid, code: Option<&str>) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, currency) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET currency = excluded.currency",
+        )
+        .bind(user_id.to_string())
+        .bind(code)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// The /decimal choice, `None` if the user never made one.
+    #[tracing::instrument(skip_all)]
+    pub async fn decimal_comma(&self, user_id: Uuid) -> Result<Option<bool>> {
+        let mark: Option<Option<String>> =
+            sqlx::query_scalar("SELECT decimal_mark FROM user_settings WHERE user_id = ?")
+                .bind(user_id.to_string())
+                .fetch_optional(&self.0)
+                .await?;
+        Ok(mark.flatten().map(|m| m == "comma"))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_decimal_comma(&self, user_id: Uuid, comma: bool) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, decimal_comma, decimal_mark) VALUES(?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE
+             SET decimal_comma = excluded.decimal_comma, decimal_mark = excluded.decimal_mark",
+        )
+        .bind(user_id.to_string())
+        .bind(comma as i64)
+        .bind(if comma { "comma" } else { "point" })
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Users with at least one entry, unseen since `seen_before`, who haven't
+    /// been pinged since they were last seen (one reminder per absence).
+    #[tracing::instrument(skip_all)]
+    pub async fn inactive_users(&self, seen_before: &str) -> Result<Vec<InactiveUser>> {
+        let rows = sqlx::query(
+            "SELECT u.id, u.tg_user_id, COALESCE(u.last_seen_at, u.created_at) AS seen
+             FROM users u
+             LEFT JOIN user_settings s ON s.user_id = u.id
+             WHERE u.kind IS NULL
+               AND COALESCE(u.last_seen_at, u.created_at) < ?
+               AND COALESCE(s.nudges, 1) = 1
+               AND (s.inactivity_pinged_at IS NULL
+                    OR s.inactivity_pinged_at < COALESCE(u.last_seen_at, u.created_at))
+               AND EXISTS(
+                 SELECT 1 FROM (
+                   SELECT user_id FROM entries UNION ALL SELECT user_id FROM entries_history
+                 )
+                 WHERE user_id = u.id
+                    OR user_id IN (SELECT ledger_id FROM shared_members WHERE user_id = u.id)
+               )",
+        )
+        .bind(seen_before)
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter()
+            .map(|r| {
+                Ok(InactiveUser {
+                    user_id: Uuid::parse_str(&r.get::<String, _>("id"))?,
+                    tg_user_id: r.get("tg_user_id"),
+                    last_seen_at: r.get("seen"),
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_inactivity_pinged(&self, user_id: Uuid, at: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, inactivity_pinged_at) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET inactivity_pinged_at = excluded.inactivity_pinged_at",
+        )
+        .bind(user_id.to_string())
+        .bind(at)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Users with entries and nudges on who haven't had the digest sent in
+    /// the `YYYY-MM` month.
+    #[tracing::instrument(skip_all)]
+    pub async fn digest_due(&self, month: &str) -> Result<Vec<DigestDue>> {
+        let rows = sqlx::query(&format!(
+            "SELECT u.id, r.tg_user_id
+             FROM users u
+             JOIN {LEDGER_RECIPIENTS} r ON r.ledger_id = u.id
+             LEFT JOIN user_settings s ON s.user_id = u.id
+             WHERE COALESCE(s.nudges, 1) = 1
+               AND (s.digest_month IS NULL OR s.digest_month < ?)
+               AND (EXISTS(SELECT 1 FROM entries e WHERE e.user_id = u.id)
+                    OR EXISTS(SELECT 1 FROM entries_history h WHERE h.user_id = u.id))"
+        ))
+        .bind(month)
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter()
+            .map(|r| {
+                Ok(DigestDue {
+                    user_id: Uuid::parse_str(&r.get::<String, _>("id"))?,
+                    tg_user_id: r.get("tg_user_id"),
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_digest_sent(&self, user_id: Uuid, month: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, digest_month) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET digest_month = excluded.digest_month",
+        )
+        .bind(user_id.to_string())
+        .bind(month)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Users with budgets and nudges on who haven't had the weekly (or
+    /// monthly) budget summary for `period`: a week's Monday or a `YYYY-MM`.
+    #[tracing::instrument(skip_all)]
+    pub async fn budget_alerts_due(&self, weekly: bool, period: &str) -> Result<Vec<DigestDue>> {
+        let column = if weekly {
+            "budget_alert_week"
+        } else {
+            "budget_alert_month"
+        };
+        let rows = sqlx::query(&format!(
+            "SELECT u.id, r.tg_user_id
+             FROM users u
+             JOIN {LEDGER_RECIPIENTS} r ON r.ledger_id = u.id
+             LEFT JOIN user_settings s ON s.user_id = u.id
+             WHERE COALESCE(s.nudges, 1) = 1
+               AND (s.{column} IS NULL OR s.{column} < ?)
+               AND EXISTS(SELECT 1 FROM budgets b WHERE b.user_id = u.id)"
+        ))
+        .bind(period)
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter()
+            .map(|r| {
+                Ok(DigestDue {
+                    user_id: Uuid::parse_str(&r.get::<String, _>("id"))?,
+                    tg_user_id: r.get("tg_user_id"),
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_budget_alert_sent(
+        &self,
+        user_id: Uuid,
+        weekly: bool,
+        period: &str,
+    ) -> Result<()> {
+        let column = if weekly {
+            "budget_alert_week"
+        } else {
+            "budget_alert_month"
+        };
+        sqlx::query(&format!(
+            "INSERT INTO user_settings(user_id, {column}) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET {column} = excluded.{column}"
+        ))
+        .bind(user_id.to_string())
+        .bind(period)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Users with /watchticker tickers and nudges on who haven't had the
+    /// price digest for the week starting `week` (a Monday `YYYY-MM-DD`).
+    #[tracing::instrument(skip_all)]
+    pub async fn ticker_digest_due(&self, week: &str) -> Result<Vec<DigestDue>> {
+        let rows = sqlx::query(&format!(
+            "SELECT u.id, r.tg_user_id
+             FROM users u
+             JOIN {LEDGER_RECIPIENTS} r ON r.ledger_id = u.id
+             LEFT JOIN user_settings s ON s.user_id = u.id
+             WHERE COALESCE(s.nudges, 1) = 1
+               AND (s.ticker_digest_week IS NULL OR s.ticker_digest_week < ?)
+               AND EXISTS(SELECT 1 FROM watched_tickers w WHERE w.user_id = u.id)"
+        ))
+        .bind(week)
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter()
+            .map(|r| {
+                Ok(DigestDue {
+                    user_id: Uuid::parse_str(&r.get::<String, _>("id"))?,
+                    tg_user_id: r.get("tg_user_id"),
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_ticker_digest_sent(&self, user_id: Uuid, week: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, ticker_digest_week) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET ticker_digest_week = excluded.ticker_digest_week",
+        )
+        .bind(user_id.to_string())
+        .bind(week)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// The user's /watchticker tickers, alphabetically.
+    #[tracing::instrument(skip_all)]
+    pub async fn watched_tickers(&self, user_id: Uuid) -> Result<Vec<String>> {
+        let rows =
+            sqlx::query("SELECT ticker FROM watched_tickers WHERE user_id = ? ORDER BY ticker")
+                .bind(user_id.to_string())
+                .fetch_all(&self.0)
+                .await?;
+        Ok(rows.into_iter().map(|r| r.get("ticker")).collect())
+    }
+
+    /// Returns false if the ticker was already watched.
+    #[tracing::instrument(skip_all)]
+    pub async fn watch_ticker(&self, user_id: Uuid, ticker: &str) -> Result<bool> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO watched_tickers(user_id, ticker, created_at) VALUES(?, ?, ?)
+             ON CONFLICT(user_id, ticker) DO NOTHING",
+        )
+        .bind(user_id.to_string())
+        .bind(ticker)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Returns false if the ticker wasn't watched.
+    #[tracing::instrument(skip_all)]
+    pub async fn unwatch_ticker(&self, user_id: Uuid, ticker: &str) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM watched_tickers WHERE user_id = ? AND ticker = ?")
+            .bind(user_id.to_string())
+            .bind(ticker)
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// How often each category went with any of `tokens` in the user's
+    /// past entries, most frequent first.
+    #[tracing::instrument(skip_all)]
+    pub async fn category_hits(
+        &self,
+        user_id: Uuid,
+        tokens: &[String],
+    ) -> Result<Vec<(String, i64)>> {
+        if tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+        let placeholders = vec!["?"; tokens.len()].join(", ");
+        let sql = format!(
+            "SELECT category, COUNT(*) AS hits FROM category_tokens
+             WHERE user_id = ? AND token IN ({placeholders})
+             GROUP BY category
+             ORDER BY hits DESC, category"
+        );
+        let mut query = sqlx::query(&sql).bind(user_id.to_string());
+        for token in tokens {
+            query = query.bind(token);
+        }
+        let rows = query.fetch_all(&self.0).await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("category"), r.get("hits")))
+            .collect())
+    }
+
+    /// Replaces the reason of an uninvested entry without recording an
+    /// edit; used when a suggested category is applied or undone. Returns
+    /// false if the entry is gone or already invested.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_entry_reason(&self, user_id: Uuid, id: i64, reason: &str) -> Result<bool> {
+        let mut tx = self.0.begin().await?;
+        let res = sqlx::query("UPDATE entries SET reason = ? WHERE id = ? AND user_id = ?")
+            .bind(reason)
+            .bind(id)
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Ok(false);
+        }
+        set_tags(&mut tx, user_id, id, Some(reason)).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn autocategory(&self, user_id: Uuid) -> Result<String> {
+        let row = sqlx::query("SELECT autocategory FROM user_settings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row
+            .map(|r| r.get("autocategory"))
+            .unwrap_or_else(|| "suggest".into()))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_autocategory(&self, user_id: Uuid, mode: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, autocategory) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET autocategory = excluded.autocategory",
+        )
+        .bind(user_id.to_string())
+        .bind(mode)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns false if the user already has a strategy with that slug.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_strategy(&self, user_id: Uuid, slug: &str, name: &str) -> Result<bool> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO strategies(user_id, slug, name, created_at) VALUES(?, ?, ?, ?)
+             ON CONFLICT(user_id, slug) DO NOTHING",
+        )
+        .bind(user_id.to_string())
+        .bind(slug)
+        .bind(name)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Returns false if there was no such strategy.
+    #[tracing::instrument(skip_all)]
+    pub async fn remove_strategy(&self, user_id: Uuid, slug: &str) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM strategies WHERE user_id = ? AND slug = ?")
+            .bind(user_id.to_string())
+            .bind(slug)
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// The display name of the user's strategy `slug`, if it exists.
+    #[tracing::instrument(skip_all)]
+    pub async fn strategy_name(&self, user_id: Uuid, slug: &str) -> Result<Option<String>> {
+        let row = sqlx::query("SELECT name FROM strategies WHERE user_id = ? AND slug = ?")
+            .bind(user_id.to_string())
+            .bind(slug)
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.map(|r| r.get("name")))
+    }
+
+    /// Every bucket's name with its invested total: the main one (`None`)
+    /// first, then each /strategy alphabetically, empty ones included.
+    #[tracing::instrument(skip_all)]
+    pub async fn strategy_totals(&self, user_id: Uuid) -> Result<Vec<(Option<String>, i64)>> {
+        let rows = sqlx::query(
+            "SELECT NULL AS name,
+                    (SELECT COALESCE(SUM(amount_cents), 0) FROM entries_history
+                     WHERE user_id = ?1 AND strategy IS NULL) AS total
+             UNION ALL
+             SELECT * FROM (
+               SELECT s.name, COALESCE(SUM(h.amount_cents), 0)
+               FROM strategies s
+               LEFT JOIN entries_history h ON h.user_id = s.user_id AND h.strategy = s.slug
+               WHERE s.user_id = ?1
+               GROUP BY s.slug
+               ORDER BY s.slug
+             )",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("name"), r.get("total")))
+            .collect())
+    }
+
+    /// One bucket's /allinvoo runs, oldest first: when, how much and how
+    /// many entries. `None` is the main bucket.
+    #[tracing::instrument(skip_all)]
+    pub async fn invest_batches(
+        &self,
+        user_id: Uuid,
+        strategy: Option<&str>,
+    ) -> Result<Vec<(String, i64, i64)>> {
+        let rows = sqlx::query(
+            "SELECT MIN(archived_at) AS archived_at, SUM(amount_cents) AS total, COUNT(*) AS entries
+             FROM entries_history
+             WHERE user_id = ? AND strategy IS ?
+             GROUP BY batch_id
+             ORDER BY batch_id",
+        )
+        .bind(user_id.to_string())
+        .bind(strategy)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("archived_at"), r.get("total"), r.get("entries")))
+            .collect())
+    }
+
+    /// Records the `cost_cents` of an /allinvoo batch that went into `ticker`
+    /// and the shares it bought at `price_cents` each, with the day's
+    /// `fx_rate` to USD if the user saves in another currency. The price is
+    /// in USD cents, so such a cost is converted to USD before dividing.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_holding(
+        &self,
+        (user_id, batch_id): (Uuid, i64),
+        ticker: &str,
+        price_cents: i64,
+        cost_cents: i64,
+        fx_rate: Option<f64>,
+    ) -> Result<()> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        sqlx::query(
+            "INSERT INTO holdings(user_id, batch_id, strategy, ticker, price_cents, cost_cents,
+                                  micro_shares, bought_at, fx_rate)
+             SELECT user_id, batch_id, MIN(strategy), ?3, ?4, ?5,
+                    CAST(ROUND(?5 * COALESCE(?7, 1.0) * 1000000 / ?4) AS INTEGER), ?6, ?7
+             FROM entries_history WHERE user_id = ?1 AND batch_id = ?2
+             GROUP BY batch_id",
+        )
+        .bind(user_id.to_string())
+        .bind(batch_id)
+        .bind(ticker)
+        .bind(price_cents)
+        .bind(cost_cents)
+        .bind(now)
+        .bind(fx_rate)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Shares held per strategy and ticker.
+    #[tracing::instrument(skip_all)]
+    pub async fn holdings(&self, user_id: Uuid) -> Result<Vec<Holding>> {
+        let rows = sqlx::query(
+            "SELECT strategy, ticker, SUM(micro_shares) AS shares, SUM(cost_cents) AS cost
+             FROM holdings WHERE user_id = ?
+             GROUP BY strategy, ticker
+             ORDER BY strategy IS NOT NULL, strategy, ticker",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Holding {
+                strategy: r.get("strategy"),
+                ticker: r.get("ticker"),
+                micro_shares: r.get("shares"),
+                cost_cents: r.get("cost"),
+            })
+            .collect())
+    }
+
+    /// Shares bought per /allinvoo run and ticker, oldest first, with
+    /// strategies folded together.
+    #[tracing::instrument(skip_all)]
+    pub async fn lots(&self, user_id: Uuid) -> Result<Vec<Lot>> {
+        let rows = sqlx::query(
+            "SELECT ticker, SUM(micro_shares) AS shares, SUM(cost_cents) AS cost,
+                    MIN(bought_at) AS bought_at, MIN(price_cents) AS price, MIN(fx_rate) AS fx_rate
+             FROM holdings WHERE user_id = ?
+             GROUP BY batch_id, ticker
+             ORDER BY MIN(bought_at), ticker",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Lot {
+                ticker: r.get("ticker"),
+                micro_shares: r.get("shares"),
+                cost_cents: r.get("cost"),
+                bought_at: r.get("bought_at"),
+                price_cents: r.get("price"),
+                fx_rate: r.get("fx_rate"),
+            })
+            .collect())
+    }
+
+    /// Every /allinvoo run across all buckets, oldest first.
+    #[tracing::instrument(skip_all)]
+    pub async fn investments(&self, user_id: Uuid) -> Result<Vec<Investment>> {
+        let rows = sqlx::query(
+            "SELECT archived_at, strategy, total, SUM(total) OVER (ORDER BY batch_id) AS running
+             FROM (
+               SELECT batch_id, MIN(archived_at) AS archived_at, MIN(strategy) AS strategy,
+                      SUM(amount_cents) AS total
+               FROM entries_history WHERE user_id = ?
+               GROUP BY batch_id
+             )
+             ORDER BY batch_id",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Investment {
+                archived_at: r.get("archived_at"),
+                strategy: r.get("strategy"),
+                cents: r.get("total"),
+                running_cents: r.get("running"),
+            })
+            .collect())
+    }
+
+    /// Records a fee paid on invested money, e.g. a broker's commission.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_fee(
+        &self,
+        user_id: Uuid,
+        amount_cents: i64,
+        reason: Option<String>,
+        occurred_at: Option<String>,
+    ) -> Result<i64> {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let res = sqlx::query(
+            "INSERT INTO fees(user_id, amount_cents, reason, occurred_at, created_at)
+             VALUES(?, ?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(amount_cents)
+        .bind(reason)
+        .bind(occurred_at.unwrap_or_else(|| now.clone()))
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn fees_total_cents(&self, user_id: Uuid) -> Result<i64> {
+        let row = sqlx::query(
+            "SELECT COALESCE(SUM(amount_cents), 0) AS total FROM fees WHERE user_id = ?",
+        )
+        .bind(user_id.to_string())
+        .fetch_one(&self.0)
+        .await?;
+        Ok(row.get("total"))
+    }
+
+    /// Per `YYYY` year, oldest first: recorded fees and the sum of the
+    /// daily invested snapshots (for the expense ratio's cost).
+    #[tracing::instrument(skip_all)]
+    pub async fn fee_years(&self, user_id: Uuid) -> Result<Vec<(String, i64, i64)>> {
+        let rows = sqlx::query(
+            "SELECT year, SUM(fees) AS fees, SUM(invested_days) AS invested_days FROM (
+               SELECT substr(occurred_at, 1, 4) AS year, amount_cents AS fees, 0 AS invested_days
+               FROM fees WHERE user_id = ?1
+               UNION ALL
+               SELECT substr(day, 1, 4), 0, invested_cents
+               FROM snapshots WHERE user_id = ?1
+             )
+             GROUP BY year
+             ORDER BY year",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("year"), r.get("fees"), r.get("invested_days")))
+            .collect())
+    }
+
+    /// The fund expense ratio in millionths (0 = none set).
+    #[tracing::instrument(skip_all)]
+    pub async fn expense_ratio_ppm(&self, user_id: Uuid) -> Result<i64> {
+        let row = sqlx::query("SELECT expense_ratio_ppm FROM user_settings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.map(|r| r.get("expense_ratio_ppm")).unwrap_or(0))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_expense_ratio_ppm(&self, user_id: Uuid, ppm: i64) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, expense_ratio_ppm) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET expense_ratio_ppm = excluded.expense_ratio_ppm",
+        )
+        .bind(user_id.to_string())
+        .bind(ppm)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Annual interest rate in basis points (0 = off).
+    #[tracing::instrument(skip_all)]
+    pub async fn interest_rate(&self, user_id: Uuid) -> Result<i64> {
+        let row = sqlx::query("SELECT interest_bp FROM user_settings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.map(|r| r.get("interest_bp")).unwrap_or(0))
+    }
+
+    /// Sets the rate. `month` is recorded as already credited so the first
+    /// accrual happens at the start of the next month, not for a partial one.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_interest_rate(
+        &self,
+        user_id: Uuid,
+        rate_bp: i64,
+        chat_id: i64,
+        month: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, interest_bp, interest_chat_id, interest_month)
+             VALUES(?, ?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET
+               interest_bp = excluded.interest_bp,
+               interest_chat_id = excluded.interest_chat_id,
+               interest_month = excluded.interest_month",
+        )
+        .bind(user_id.to_string())
+        .bind(rate_bp)
+        .bind(chat_id)
+        .bind(month)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn interest_due(&self, month: &str) -> Result<Vec<InterestDue>> {
+        let rows = sqlx::query(
+            "SELECT user_id, interest_chat_id, interest_bp FROM user_settings
+             WHERE interest_bp > 0 AND (interest_month IS NULL OR interest_month < ?)",
+        )
+        .bind(month)
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter()
+            .map(|r| {
+                Ok(InterestDue {
+                    user_id: Uuid::parse_str(&r.get::<String, _>("user_id"))?,
+                    chat_id: r.get("interest_chat_id"),
+                    rate_bp: r.get("interest_bp"),
+                })
+            })
+            .collect()
+    }
+
+    /// Credits one month of interest (if any) and marks `month` as done, atomically
+    /// so a crash can't double-credit.
+    #[tracing::instrument(skip_all)]
+    pub async fn accrue_interest(
+        &self,
+        user_id: Uuid,
+        month: &str,
+        amount_cents: i64,
+        reason: &str,
+    ) -> Result<()> {
+        let mut tx = self.0.begin().await?;
+        if amount_cents > 0 {
+            let now = OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_else(|_| "now".into());
+            sqlx::query(
+                "INSERT INTO entries(user_id, amount_cents, kind, reason, created_at, occurred_at)
+                 VALUES(?, ?, ?, ?, ?, ?)",
+            )
+            .bind(user_id.to_string())
+            .bind(amount_cents)
+            .bind(EntryKind::Interest)
+            .bind(reason)
+            .bind(&now)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query("UPDATE user_settings SET interest_month = ? WHERE user_id = ?")
+            .bind(month)
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// The user's override for `flag`, if any, and its stored rollout.
+    #[tracing::instrument(skip_all)]
+    pub async fn flag_state(
+        &self,
+        flag: &str,
+        user_id: Uuid,
+    ) -> Result<(Option<bool>, Option<i64>)> {
+        let row = sqlx::query(
+            "SELECT (SELECT enabled FROM feature_flag_users WHERE flag = ?1 AND user_id = ?2)
+                      AS forced,
+                    (SELECT percent FROM feature_flags WHERE flag = ?1) AS percent",
+        )
+        .bind(flag)
+        .bind(user_id.to_string())
+        .fetch_one(&self.0)
+        .await?;
+        Ok((row.get("forced"), row.get("percent")))
+    }
+
+    /// Stored rollouts with how many users are forced on and off.
+    #[tracing::instrument(skip_all)]
+    pub async fn flags(&self) -> Result<Vec<FlagRow>> {
+        let rows = sqlx::query(
+            "SELECT flag, MAX(percent) AS percent, SUM(forced_on) AS forced_on,
+                    SUM(forced_off) AS forced_off
+             FROM (
+               SELECT flag, percent, 0 AS forced_on, 0 AS forced_off FROM feature_flags
+               UNION ALL
+               SELECT flag, NULL, enabled, 1 - enabled FROM feature_flag_users
+             )
+             GROUP BY flag ORDER BY flag",
+        )
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| FlagRow {
+                flag: r.get("flag"),
+                percent: r.get("percent"),
+                forced_on: r.get("forced_on"),
+                forced_off: r.get("forced_off"),
+            })
+            .collect())
+    }
+
+    /// `None` drops the stored rollout so the `FEATURE_FLAGS` default applies.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_flag_rollout(&self, flag: &str, percent: Option<i64>) -> Result<()> {
+        match percent {
+            Some(percent) => {
+                sqlx::query(
+                    "INSERT INTO feature_flags(flag, percent) VALUES(?, ?)
+                     ON CONFLICT(flag) DO UPDATE SET percent = excluded.percent",
+                )
+                .bind(flag)
+                .bind(percent)
+                .execute(&self.0)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM feature_flags WHERE flag = ?")
+                    .bind(flag)
+                    .execute(&self.0)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forces `flag` on or off for one user; `None` removes the override.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_flag_override(
+        &self,
+        flag: &str,
+        user_id: Uuid,
+        enabled: Option<bool>,
+    ) -> Result<()> {
+        match enabled {
+            Some(enabled) => {
+                sqlx::query(
+                    "INSERT INTO feature_flag_users(flag, user_id, enabled) VALUES(?, ?, ?)
+                     ON CONFLICT(flag, user_id) DO UPDATE SET enabled = excluded.enabled",
+                )
+                .bind(flag)
+                .bind(user_id.to_string())
+                .bind(enabled)
+                .execute(&self.0)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM feature_flag_users WHERE flag = ? AND user_id = ?")
+                    .bind(flag)
+                    .bind(user_id.to_string())
+                    .execute(&self.0)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn chat_language(&self, chat_id: i64) -> Result<Option<String>> {
+        let row = sqlx::query("SELECT language FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.and_then(|r| r.get::<Option<String>, _>("language")))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_chat_language(&self, chat_id: i64, language: Option<&str>) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings(chat_id, language) VALUES(?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET language = excluded.language",
+        )
+        .bind(chat_id)
+        .bind(language)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// The user's /timezone as minutes east of UTC. A /shared ledger's days
+    /// follow its creator's.
+    #[tracing::instrument(skip_all)]
+    pub async fn utc_offset_minutes(&self, user_id: Uuid) -> Result<i64> {
+        let row = sqlx::query(
+            "SELECT utc_offset_minutes FROM user_settings
+             WHERE user_id = COALESCE((SELECT created_by FROM shared_ledgers WHERE ledger_id = ?1), ?1)",
+        )
+        .bind(user_id.to_string())
+        .fetch_optional(&self.0)
+        .await?;
+        Ok(row.map_or(0, |r| r.get("utc_offset_minutes")))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_utc_offset_minutes(&self, user_id: Uuid, minutes: i64) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, utc_offset_minutes) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET utc_offset_minutes = excluded.utc_offset_minutes",
+        )
+        .bind(user_id.to_string())
+        .bind(minutes)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Local `YYYY-MM-DD` days with at least one /save, newest first.
+    /// Backdated entries (stored at UTC midnight) keep the date typed.
+    #[tracing::instrument(skip_all)]
+    pub async fn save_days(&self, user_id: Uuid, offset_minutes: i64) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT
+               CASE WHEN occurred_at LIKE '%T00:00:00Z' THEN substr(occurred_at, 1, 10)
+                    ELSE date(occurred_at, ?2) END AS day
+             FROM (
+               SELECT occurred_at, kind, amount_cents FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT occurred_at, kind, amount_cents FROM entries_history WHERE user_id = ?1
+             )
+             WHERE kind = 'save' AND amount_cents > 0
+             ORDER BY day DESC",
+        )
+        .bind(user_id.to_string())
+        .bind(format!("{offset_minutes:+} minutes"))
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows.into_iter().filter_map(|r| r.get("day")).collect())
+    }
+
+    /// Replaces the user's reminder.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_reminder(&self, r: &Reminder) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO reminders(user_id, chat_id, weekday, minute_of_day, last_sent_on)
+             VALUES(?, ?, ?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET
+               chat_id = excluded.chat_id,
+               weekday = excluded.weekday,
+               minute_of_day = excluded.minute_of_day,
+               last_sent_on = excluded.last_sent_on",
+        )
+        .bind(r.user_id.to_string())
+        .bind(r.chat_id)
+        .bind(r.weekday)
+        .bind(r.minute_of_day)
+        .bind(&r.last_sent_on)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns false if the user had no reminder.
+    #[tracing::instrument(skip_all)]
+    pub async fn clear_reminder(&self, user_id: Uuid) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM reminders WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn reminder(&self, user_id: Uuid) -> Result<Option<Reminder>> {
+        let row = sqlx::query(&format!("{REMINDER_SELECT} WHERE r.user_id = ?"))
+            .bind(user_id.to_string())
+            .fetch_optional(&self.0)
+            .await?;
+        row.map(reminder_from_row).transpose()
+    }
+
+    /// Every reminder with its owner's timezone.
+    #[tracing::instrument(skip_all)]
+    pub async fn reminders(&self) -> Result<Vec<Reminder>> {
+        let rows = sqlx::query(REMINDER_SELECT).fetch_all(&self.0).await?;
+        rows.into_iter().map(reminder_from_row).collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_reminder_sent(&self, user_id: Uuid, local_day: &str) -> Result<()> {
+        sqlx::query("UPDATE reminders SET last_sent_on = ? WHERE user_id = ?")
+            .bind(local_day)
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether entries lock 24 hours after they are recorded.
+    #[tracing::instrument(skip_all)]
+    pub async fn immutable(&self, user_id: Uuid) -> Result<bool> {
+        let row = sqlx::query("SELECT immutable FROM user_settings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.is_some_and(|r| r.get::<i64, _>("immutable") != 0))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_immutable(&self, user_id: Uuid, enabled: bool) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, immutable) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET immutable = excluded.immutable",
+        )
+        .bind(user_id.to_string())
+        .bind(enabled)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Whether /dividend also adds payouts to the stash.
+    #[tracing::instrument(skip_all)]
+    pub async fn dividends_to_stash(&self, user_id: Uuid) -> Result<bool> {
+        let row = sqlx::query("SELECT dividends_to_stash FROM user_settings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.is_some_and(|r| r.get::<i64, _>("dividends_to_stash") != 0))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_dividends_to_stash(&self, user_id: Uuid, enabled: bool) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, dividends_to_stash) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET dividends_to_stash = excluded.dividends_to_stash",
+        )
+        .bind(user_id.to_string())
+        .bind(enabled)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Records a payout received on `received_on` (`YYYY-MM-DD`);
+    /// `entry_id` is the stash entry it was also booked as, if any.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_dividend(
+        &self,
+        user_id: Uuid,
+        amount_cents: i64,
+        received_on: &str,
+        reason: Option<&str>,
+        entry_id: Option<i64>,
+    ) -> Result<()> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        sqlx::query(
+            "INSERT INTO dividends(user_id, amount_cents, received_on, reason, entry_id, created_at)
+             VALUES(?, ?, ?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(amount_cents)
+        .bind(received_on)
+        .bind(reason)
+        .bind(entry_id)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn dividend_totals(&self, user_id: Uuid) -> Result<DividendTotals> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS n, COALESCE(SUM(amount_cents), 0) AS total,
+                    COALESCE(SUM(CASE WHEN entry_id IS NOT NULL THEN amount_cents END), 0) AS to_stash
+             FROM dividends WHERE user_id = ?",
         )
         .bind(user_id.to_string())
         .fetch_one(&self.0)
         .await?;
-        let total: i64 = row.get("total");
-        Ok(total)
+        Ok(DividendTotals {
+            count: row.get("n"),
+            total_cents: row.get("total"),
+            to_stash_cents: row.get("to_stash"),
+        })
     }
 
-    pub async fn archive_user_entries(&self, user_id: Uuid) -> Result<i64> {
-        let mut tx = self.0.begin().await?;
-        let current_total: i64 = sqlx::query(
-            "SELECT COALESCE(SUM(amount_cents),0) AS total FROM entries WHERE user_id = ?",
+    #[tracing::instrument(skip_all)]
+    pub async fn add_attachment(
+        &self,
+        user_id: Uuid,
+        entry_id: i64,
+        file_id: &str,
+        source: &str,
+    ) -> Result<()> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        sqlx::query(
+            "INSERT INTO attachments(user_id, entry_id, file_id, source, created_at)
+             VALUES(?, ?, ?, ?, ?)",
         )
         .bind(user_id.to_string())
-        .fetch_one(&mut *tx)
-        .await?
-        .get("total");
+        .bind(entry_id)
+        .bind(file_id)
+        .bind(source)
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
 
-        if current_total == 0 {
-            tx.commit().await?;
-            return Ok(0);
-        }
+    /// The user's attachments, newest first, with their entries where those
+    /// still exist, in the stash or invested.
+    #[tracing::instrument(skip_all)]
+    pub async fn attachments(&self, user_id: Uuid, limit: i64) -> Result<Vec<Attachment>> {
+        let rows = sqlx::query(
+            "WITH known AS (
+               SELECT entry_id, SUM(amount_cents) AS amount_cents, MAX(reason) AS reason FROM (
+                 SELECT id AS entry_id, amount_cents, reason FROM entries WHERE user_id = ?1
+                 UNION ALL
+                 SELECT entry_id, amount_cents, reason FROM entries_history
+                 WHERE user_id = ?1 AND entry_id IS NOT NULL
+               ) GROUP BY entry_id
+             )
+             SELECT a.id, a.entry_id, a.source, a.created_at, k.amount_cents, k.reason
+             FROM attachments a LEFT JOIN known k ON k.entry_id = a.entry_id
+             WHERE a.user_id = ?1
+             ORDER BY a.created_at DESC, a.id DESC LIMIT ?2",
+        )
+        .bind(user_id.to_string())
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Attachment {
+                id: r.get("id"),
+                entry_id: r.get("entry_id"),
+                source: r.get("source"),
+                created_at: r.get("created_at"),
+                amount_cents: r.get("amount_cents"),
+                reason: r.get("reason"),
+            })
+            .collect())
+    }
 
-        let now = OffsetDateTime::now_utc()
-            .format(&Rfc3339)
-            .unwrap_or_else(|_| "now".into());
+    /// (id, source) of the attachments kept with one entry, oldest first.
+    #[tracing::instrument(skip_all)]
+    pub async fn entry_attachments(
+        &self,
+        user_id: Uuid,
+        entry_id: i64,
+    ) -> Result<Vec<(i64, String)>> {
+        let rows = sqlx::query(
+            "SELECT id, source FROM attachments WHERE user_id = ? AND entry_id = ? ORDER BY id",
+        )
+        .bind(user_id.to_string())
+        .bind(entry_id)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("id"), r.get("source")))
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn attachment_count(&self, user_id: Uuid) -> Result<i64> {
+        Ok(
+            sqlx::query_scalar("SELECT COUNT(*) FROM attachments WHERE user_id = ?")
+                .bind(user_id.to_string())
+                .fetch_one(&self.0)
+                .await?,
+        )
+    }
 
+    /// Detaches one file; the entry stays. `false` if it isn't the user's.
+    #[tracing::instrument(skip_all)]
+    pub async fn remove_attachment(&self, user_id: Uuid, id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM attachments WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Forgets the user's attachments stored before `before` (RFC3339), and
+    /// any whose entry is gone. Returns how many were removed.
+    #[tracing::instrument(skip_all)]
+    pub async fn remove_attachments_before(&self, user_id: Uuid, before: &str) -> Result<u64> {
+        let res = sqlx::query(
+            "DELETE FROM attachments WHERE user_id = ?1
+             AND (created_at < ?2
+                  OR (entry_id NOT IN (SELECT id FROM entries WHERE user_id = ?1)
+                      AND entry_id NOT IN (SELECT entry_id FROM entries_history
+                                           WHERE user_id = ?1 AND entry_id IS NOT NULL)))",
+        )
+        .bind(user_id.to_string())
+        .bind(before)
+        .execute(&self.0)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// The token in the user's /publicpage URL, if the page is on.
+    #[tracing::instrument(skip_all)]
+    pub async fn public_page_token(&self, user_id: Uuid) -> Result<Option<String>> {
+        let row = sqlx::query("SELECT public_page_token FROM user_settings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.and_then(|r| r.get("public_page_token")))
+    }
+
+    /// Turns the page on with a new token, or off with `None`; either way
+    /// the old URL stops working.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_public_page_token(&self, user_id: Uuid, token: Option<&str>) -> Result<()> {
         sqlx::query(
-            "INSERT INTO entries_history(user_id, amount_cents, kind, reason, created_at, archived_at)
-             SELECT user_id, amount_cents, kind, reason, created_at, ?
-             FROM entries WHERE user_id = ?",
+            "INSERT INTO user_settings(user_id, public_page_token) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET public_page_token = excluded.public_page_token",
         )
-        .bind(now)
         .bind(user_id.to_string())
-        .execute(&mut *tx)
+        .bind(token)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Whose public page `token` is.
+    #[tracing::instrument(skip_all)]
+    pub async fn public_page_owner(&self, token: &str) -> Result<Option<Uuid>> {
+        let id: Option<String> =
+            sqlx::query_scalar("SELECT user_id FROM user_settings WHERE public_page_token = ?")
+                .bind(token)
+                .fetch_optional(&self.0)
+                .await?;
+        id.map(|id| Ok(Uuid::parse_str(&id)?)).transpose()
+    }
+
+    /// Whether anonymous posts in the chat go into its shared pot.
+    #[tracing::instrument(skip_all)]
+    pub async fn anonymous_pot(&self, chat_id: i64) -> Result<bool> {
+        let row = sqlx::query("SELECT anonymous_pot FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.is_some_and(|r| r.get::<i64, _>("anonymous_pot") != 0))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_anonymous_pot(&self, chat_id: i64, enabled: bool) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings(chat_id, anonymous_pot) VALUES(?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET anonymous_pot = excluded.anonymous_pot",
+        )
+        .bind(chat_id)
+        .bind(enabled)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// The chat's /splitrounding rule, if one was picked.
+    #[tracing::instrument(skip_all)]
+    pub async fn split_rounding(&self, chat_id: i64) -> Result<Option<String>> {
+        let row = sqlx::query("SELECT split_rounding FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row.and_then(|r| r.get("split_rounding")))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_split_rounding(&self, chat_id: i64, rule: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings(chat_id, split_rounding) VALUES(?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET split_rounding = excluded.split_rounding",
+        )
+        .bind(chat_id)
+        .bind(rule)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// Commands group admins turned off in the chat, without the slash.
+    #[tracing::instrument(skip_all)]
+    pub async fn disabled_commands(&self, chat_id: i64) -> Result<Vec<String>> {
+        let row = sqlx::query("SELECT disabled_commands FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(row
+            .and_then(|r| r.get::<Option<String>, _>("disabled_commands"))
+            .map(|s| s.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_disabled_commands(&self, chat_id: i64, commands: &[String]) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings(chat_id, disabled_commands) VALUES(?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET disabled_commands = excluded.disabled_commands",
+        )
+        .bind(chat_id)
+        .bind(Some(commands.join(" ")).filter(|s| !s.is_empty()))
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// The user with this `@username` (without the `@`) who has recorded
+    /// entries in the chat, current or invested.
+    #[tracing::instrument(skip_all)]
+    pub async fn chat_member(&self, chat_id: i64, username: &str) -> Result<Option<ChatMember>> {
+        let row = sqlx::query(
+            "SELECT COALESCE(m.ledger_id, u.id) AS id, u.tg_user_id, u.first_name
+             FROM users u LEFT JOIN shared_members m ON m.user_id = u.id
+             WHERE u.tg_username = ?1 COLLATE NOCASE
+               AND (EXISTS (SELECT 1 FROM entries
+                            WHERE user_id = COALESCE(m.ledger_id, u.id) AND source_chat_id = ?2)
+                    OR EXISTS (SELECT 1 FROM entries_history
+                               WHERE user_id = COALESCE(m.ledger_id, u.id) AND source_chat_id = ?2))",
+        )
+        .bind(username)
+        .bind(chat_id)
+        .fetch_optional(&self.0)
         .await?;
+        row.map(|r| {
+            Ok(ChatMember {
+                user_id: Uuid::parse_str(&r.get::<String, _>("id"))?,
+                tg_user_id: r.get("tg_user_id"),
+                first_name: r.get("first_name"),
+            })
+        })
+        .transpose()
+    }
 
-        sqlx::query("DELETE FROM entries WHERE user_id = ?")
+    #[tracing::instrument(skip_all)]
+    pub async fn leaderboard_opt_in(&self, user_id: Uuid) -> Result<bool> {
+        let row = sqlx::query("SELECT leaderboard FROM user_settings WHERE user_id = ?")
             .bind(user_id.to_string())
-            .execute(&mut *tx)
+            .fetch_optional(&self.0)
             .await?;
+        Ok(row.is_some_and(|r| r.get::<i64, _>("leaderboard") != 0))
+    }
 
-        tx.commit().await?;
-        Ok(current_total)
+    #[tracing::instrument(skip_all)]
+    pub async fn set_leaderboard_opt_in(&self, user_id: Uuid, enabled: bool) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, leaderboard) VALUES(?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET leaderboard = excluded.leaderboard",
+        )
+        .bind(user_id.to_string())
+        .bind(enabled as i64)
+        .execute(&self.0)
+        .await?;
+        Ok(())
     }
 
-    pub async fn last_entries(&self, user_id: Uuid, limit: i64) -> Result<Vec<Entry>> {
-        let rows = sqlx::query(
-            "SELECT amount_cents, kind, reason, created_at
-             FROM entries
-             WHERE user_id = ?
-             ORDER BY id DESC
-             LIMIT ?",
+    #[tracing::instrument(skip_all)]
+    pub async fn saving_target(&self, user_id: Uuid) -> Result<Option<i64>> {
+        let target: Option<Option<i64>> =
+            sqlx::query_scalar("SELECT saving_target_cents FROM user_settings WHERE user_id = ?")
+                .bind(user_id.to_string())
+                .fetch_optional(&self.0)
+                .await?;
+        Ok(target.flatten())
+    }
+
+    /// Sets or clears the monthly saving target. This month's progress
+    /// alerts start over, so a raised target gets its own; months up to
+    /// `last_month` count as reviewed, since the target didn't exist then.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_saving_target(
+        &self,
+        user_id: Uuid,
+        cents: Option<i64>,
+        last_month: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings(user_id, saving_target_cents, saving_review_month)
+             VALUES(?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE
+             SET saving_target_cents = excluded.saving_target_cents, saving_alert_month = NULL,
+                 saving_review_month = MAX(COALESCE(saving_review_month, ''),
+                                           excluded.saving_review_month)",
         )
         .bind(user_id.to_string())
+        .bind(cents)
+        .bind(last_month)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// /save entries in `month` (`YYYY-MM`), invested or not.
+    #[tracing::instrument(skip_all)]
+    pub async fn saved_in_month(&self, user_id: Uuid, month: &str) -> Result<i64> {
+        let saved = sqlx::query_scalar(
+            "SELECT COALESCE(SUM(amount_cents), 0) FROM (
+               SELECT amount_cents, kind, occurred_at FROM entries WHERE user_id = ?1
+               UNION ALL
+               SELECT amount_cents, kind, occurred_at FROM entries_history WHERE user_id = ?1
+             )
+             WHERE kind = ?2 AND substr(occurred_at, 1, 7) = ?3",
+        )
+        .bind(user_id.to_string())
+        .bind(EntryKind::Save)
+        .bind(month)
+        .fetch_one(&self.0)
+        .await?;
+        Ok(saved)
+    }
+
+    /// Everyone with a saving target and nudges on, with what they saved
+    /// in `month`. With `unreviewed`, only those whose month-end review for
+    /// it hasn't gone out.
+    #[tracing::instrument(skip_all)]
+    pub async fn saving_progress(
+        &self,
+        month: &str,
+        unreviewed: bool,
+    ) -> Result<Vec<SavingProgress>> {
+        let rows = sqlx::query(&format!(
+            "SELECT u.id, r.tg_user_id, s.saving_target_cents,
+               CASE WHEN s.saving_alert_month = ?1 THEN COALESCE(s.saving_alert_pct, 0) ELSE 0 END
+                 AS alerted_pct,
+               (SELECT COALESCE(SUM(amount_cents), 0) FROM entries
+                WHERE user_id = u.id AND kind = ?2 AND substr(occurred_at, 1, 7) = ?1)
+               + (SELECT COALESCE(SUM(amount_cents), 0) FROM entries_history
+                  WHERE user_id = u.id AND kind = ?2 AND substr(occurred_at, 1, 7) = ?1)
+                 AS saved_cents
+             FROM users u
+             JOIN {LEDGER_RECIPIENTS} r ON r.ledger_id = u.id
+             JOIN user_settings s ON s.user_id = u.id
+             WHERE s.saving_target_cents > 0
+               AND COALESCE(s.nudges, 1) = 1
+               AND (?3 = 0 OR s.saving_review_month IS NULL OR s.saving_review_month < ?1)"
+        ))
+        .bind(month)
+        .bind(EntryKind::Save)
+        .bind(unreviewed)
+        .fetch_all(&self.0)
+        .await?;
+        rows.into_iter()
+            .map(|r| {
+                Ok(SavingProgress {
+                    user_id: Uuid::parse_str(&r.get::<String, _>("id"))?,
+                    tg_user_id: r.get("tg_user_id"),
+                    target_cents: r.get("saving_target_cents"),
+                    saved_cents: r.get("saved_cents"),
+                    alerted_pct: r.get("alerted_pct"),
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_saving_alert(&self, user_id: Uuid, month: &str, pct: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE user_settings SET saving_alert_month = ?, saving_alert_pct = ?
+             WHERE user_id = ?",
+        )
+        .bind(month)
+        .bind(pct)
+        .bind(user_id.to_string())
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_saving_review(&self, user_id: Uuid, month: &str) -> Result<()> {
+        sqlx::query("UPDATE user_settings SET saving_review_month = ? WHERE user_id = ?")
+            .bind(month)
+            .bind(user_id.to_string())
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    /// The chat's members who opted in, by what they saved in `month`
+    /// (`YYYY-MM`) anywhere, invested or not; those who saved nothing are
+    /// left out.
+    #[tracing::instrument(skip_all)]
+    pub async fn leaderboard(&self, chat_id: i64, month: &str, limit: i64) -> Result<Vec<Leader>> {
+        let rows = sqlx::query(
+            "WITH saves AS (
+               SELECT user_id, amount_cents, occurred_at FROM entries WHERE kind = 'save'
+               UNION ALL
+               SELECT user_id, amount_cents, occurred_at FROM entries_history WHERE kind = 'save'
+             )
+             SELECT u.id, COALESCE(u.first_name, u.tg_username, 'someone') AS name,
+                    SUM(s.amount_cents) AS saved
+             FROM users u
+             JOIN user_settings st ON st.user_id = u.id AND st.leaderboard != 0
+             JOIN saves s ON s.user_id = u.id AND substr(s.occurred_at, 1, 7) = ?2
+             WHERE EXISTS (SELECT 1 FROM entries WHERE user_id = u.id AND source_chat_id = ?1)
+                OR EXISTS (SELECT 1 FROM entries_history
+                           WHERE user_id = u.id AND source_chat_id = ?1)
+             GROUP BY u.id
+             HAVING saved > 0
+             ORDER BY saved DESC, name
+             LIMIT ?3",
+        )
+        .bind(chat_id)
+        .bind(month)
         .bind(limit)
         .fetch_all(&self.0)
         .await?;
+        rows.into_iter()
+            .map(|r| {
+                Ok(Leader {
+                    user_id: Uuid::parse_str(&r.get::<String, _>("id"))?,
+                    name: r.get("name"),
+                    saved_cents: r.get("saved"),
+                })
+            })
+            .collect()
+    }
 
-        Ok(rows
-            .into_iter()
-            .map(|r| Entry {
-                amount_cents: r.get::<i64, _>("amount_cents"),
-                kind: r.get::<String, _>("kind"),
-                reason: r.get::<Option<String>, _>("reason"),
-                created_at: r.get::<String, _>("created_at"),
+    #[tracing::instrument(skip_all)]
+    pub async fn user_by_username(&self, username: &str) -> Result<Option<ChatMember>> {
+        let row = sqlx::query(
+            "SELECT COALESCE(m.ledger_id, u.id) AS id, u.tg_user_id, u.first_name
+             FROM users u LEFT JOIN shared_members m ON m.user_id = u.id
+             WHERE u.tg_username = ? COLLATE NOCASE",
+        )
+        .bind(username)
+        .fetch_optional(&self.0)
+        .await?;
+        row.map(|r| {
+            Ok(ChatMember {
+                user_id: Uuid::parse_str(&r.get::<String, _>("id"))?,
+                tg_user_id: r.get("tg_user_id"),
+                first_name: r.get("first_name"),
             })
-            .collect())
+        })
+        .transpose()
+    }
+
+    /// Books each (ledger, cents) share of a /split as an adjustment on its
+    /// owner's ledger, all or none, and moves the chat's round-robin turn on
+    /// by `turns`. Returns the new entry ids in the same order.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_split(
+        &self,
+        (chat_id, message_id): (i64, i32),
+        reason: Option<&str>,
+        shares: &[(Uuid, i64)],
+        turns: i64,
+    ) -> Result<Vec<i64>> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        let mut tx = self.0.begin().await?;
+        let mut ids = Vec::with_capacity(shares.len());
+        for (user_id, cents) in shares {
+            let res = sqlx::query(
+                "INSERT INTO entries(user_id, amount_cents, kind, reason, created_at, occurred_at,
+                                     source_chat_id, source_message_id, origin)
+                 VALUES(?1, ?2, ?3, ?4, ?5, ?5, ?6, ?7, 'split')",
+            )
+            .bind(user_id.to_string())
+            .bind(cents)
+            .bind(EntryKind::Adjust)
+            .bind(reason)
+            .bind(&now)
+            .bind(chat_id)
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await?;
+            let id = res.last_insert_rowid();
+            set_tags(&mut tx, *user_id, id, reason).await?;
+            ids.push(id);
+        }
+        sqlx::query(
+            "INSERT INTO chat_settings(chat_id, split_turn) VALUES(?1, ?2)
+             ON CONFLICT(chat_id) DO UPDATE SET split_turn = split_turn + ?2",
+        )
+        .bind(chat_id)
+        .bind(turns)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    /// Where round-robin rounding hands out the chat's next leftover cent.
+    #[tracing::instrument(skip_all)]
+    pub async fn split_turn(&self, chat_id: i64) -> Result<i64> {
+        let turn: Option<i64> =
+            sqlx::query_scalar("SELECT split_turn FROM chat_settings WHERE chat_id = ?")
+                .bind(chat_id)
+                .fetch_optional(&self.0)
+                .await?;
+        Ok(turn.unwrap_or(0))
+    }
+
+    /// When the user last recorded an entry in the chat, and how many they
+    /// recorded there since `since`.
+    #[tracing::instrument(skip_all)]
+    pub async fn chat_activity(
+        &self,
+        user_id: Uuid,
+        chat_id: i64,
+        since: &str,
+    ) -> Result<(Option<String>, i64)> {
+        let row = sqlx::query(
+            "SELECT MAX(occurred_at) AS last, COALESCE(SUM(occurred_at >= ?3), 0) AS recent
+             FROM (
+               SELECT occurred_at FROM entries WHERE user_id = ?1 AND source_chat_id = ?2
+               UNION ALL
+               SELECT occurred_at FROM entries_history WHERE user_id = ?1 AND source_chat_id = ?2
+             )",
+        )
+        .bind(user_id.to_string())
+        .bind(chat_id)
+        .bind(since)
+        .fetch_one(&self.0)
+        .await?;
+        Ok((row.get("last"), row.get("recent")))
+    }
+
+    /// When anyone last nudged `target` in the chat.
+    #[tracing::instrument(skip_all)]
+    pub async fn last_member_nudge(&self, chat_id: i64, target: Uuid) -> Result<Option<String>> {
+        Ok(sqlx::query_scalar(
+            "SELECT MAX(sent_at) FROM member_nudges WHERE chat_id = ? AND target_id = ?",
+        )
+        .bind(chat_id)
+        .bind(target.to_string())
+        .fetch_one(&self.0)
+        .await?)
+    }
+
+    /// How many nudges `sender` has sent in the chat since `since`.
+    #[tracing::instrument(skip_all)]
+    pub async fn member_nudges_since(
+        &self,
+        chat_id: i64,
+        sender: Uuid,
+        since: &str,
+    ) -> Result<i64> {
+        Ok(sqlx::query_scalar(
+            "SELECT COUNT(*) FROM member_nudges WHERE chat_id = ? AND sender_id = ? AND sent_at >= ?",
+        )
+        .bind(chat_id)
+        .bind(sender.to_string())
+        .bind(since)
+        .fetch_one(&self.0)
+        .await?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn record_member_nudge(
+        &self,
+        chat_id: i64,
+        sender: Uuid,
+        target: Uuid,
+    ) -> Result<()> {
+        let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+        sqlx::query(
+            "INSERT INTO member_nudges(chat_id, sender_id, target_id, sent_at) VALUES(?, ?, ?, ?)",
+        )
+        .bind(chat_id)
+        .bind(sender.to_string())
+        .bind(target.to_string())
+        .bind(now)
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// The user who claimed a channel with /channelowner.
+    #[tracing::instrument(skip_all)]
+    pub async fn channel_owner(&self, channel_id: i64) -> Result<Option<Uuid>> {
+        let row = sqlx::query("SELECT owner_user_id FROM chat_settings WHERE chat_id = ?")
+            .bind(channel_id)
+            .fetch_optional(&self.0)
+            .await?;
+        row.and_then(|r| r.get::<Option<String>, _>("owner_user_id"))
+            .map(|id| Ok(Uuid::parse_str(&id)?))
+            .transpose()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set_channel_owner(&self, channel_id: i64, owner: Option<Uuid>) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings(chat_id, owner_user_id) VALUES(?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET owner_user_id = excluded.owner_user_id",
+        )
+        .bind(channel_id)
+        .bind(owner.map(|u| u.to_string()))
+        .execute(&self.0)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Row count of every user table on `conn`, by name.
+async fn table_counts(conn: &mut SqliteConnection) -> Result<Vec<(String, i64)>> {
+    let tables = sqlx::query(
+        "SELECT name FROM sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+         ORDER BY name",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let mut counts = Vec::with_capacity(tables.len());
+    for t in tables {
+        let name: String = t.get("name");
+        let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM \"{name}\""))
+            .fetch_one(&mut *conn)
+            .await?;
+        counts.push((name, count));
+    }
+    Ok(counts)
+}
+
+/// Batched insert of a JSON array of [`NewEntry`] in a single statement.
+async fn insert_entries(conn: &mut SqliteConnection, user_id: Uuid, entries: &str) -> Result<u64> {
+    let now = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| "now".into());
+    let rows = sqlx::query(
+        "INSERT INTO entries(user_id, amount_cents, kind, reason, created_at, occurred_at)
+         SELECT ?, json_extract(value, '$.amount_cents'), json_extract(value, '$.kind'),
+                json_extract(value, '$.reason'), ?, json_extract(value, '$.occurred_at')
+         FROM json_each(?)
+         RETURNING id, reason",
+    )
+    .bind(user_id.to_string())
+    .bind(now)
+    .bind(entries)
+    .fetch_all(&mut *conn)
+    .await?;
+    for r in &rows {
+        let reason: Option<String> = r.get("reason");
+        set_tags(conn, user_id, r.get("id"), reason.as_deref()).await?;
+    }
+    Ok(rows.len() as u64)
+}
+
+/// Picks what investing `amount_cents` takes, oldest entries first: whole
+/// entries while the running sum stays within the amount, then just enough
+/// of the entry that crosses it. `None`, or an amount at or above the
+/// total, takes every entry.
+async fn invest_selection(
+    conn: &mut SqliteConnection,
+    user_id: Uuid,
+    amount_cents: Option<i64>,
+) -> Result<InvestSelection> {
+    let rows = sqlx::query(
+        "SELECT id, amount_cents FROM entries WHERE user_id = ? ORDER BY occurred_at, id",
+    )
+    .bind(user_id.to_string())
+    .fetch_all(&mut *conn)
+    .await?;
+    let total: i64 = rows.iter().map(|r| r.get::<i64, _>("amount_cents")).sum();
+    let target = amount_cents.filter(|a| *a < total).unwrap_or(total);
+    let mut selection = InvestSelection::default();
+    for r in rows {
+        let (id, cents): (i64, i64) = (r.get("id"), r.get("amount_cents"));
+        if target < total && selection.cents + cents > target {
+            let part = target - selection.cents;
+            if part > 0 {
+                selection.split = Some((id, part));
+            }
+            selection.cents = target;
+            break;
+        }
+        selection.whole.push(id);
+        selection.cents += cents;
+    }
+    Ok(selection)
+}
+
+/// Replaces an entry's entry_tags rows with the hashtags of `reason`, and
+/// its category_tokens rows with the words that led to its category.
+async fn set_tags(
+    conn: &mut SqliteConnection,
+    user_id: Uuid,
+    entry_id: i64,
+    reason: Option<&str>,
+) -> Result<()> {
+    for table in ["entry_tags", "category_tokens"] {
+        sqlx::query(&format!("DELETE FROM {table} WHERE entry_id = ?"))
+            .bind(entry_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    let tags = crate::tags::parse(reason.unwrap_or_default());
+    for tag in &tags {
+        sqlx::query("INSERT INTO entry_tags(entry_id, user_id, tag) VALUES(?, ?, ?)")
+            .bind(entry_id)
+            .bind(user_id.to_string())
+            .bind(tag)
+            .execute(&mut *conn)
+            .await?;
+    }
+    let Some(category) = tags.first() else {
+        return Ok(());
+    };
+    for token in crate::categorize::tokens(reason.unwrap_or_default()) {
+        sqlx::query(
+            "INSERT INTO category_tokens(entry_id, user_id, token, category) VALUES(?, ?, ?, ?)",
+        )
+        .bind(entry_id)
+        .bind(user_id.to_string())
+        .bind(token)
+        .bind(category)
+        .execute(&mut *conn)
+        .await?;
     }
+    Ok(())
+}
+
+fn plan_from_row(r: sqlx::sqlite::SqliteRow) -> Result<Plan> {
+    Ok(Plan {
+        id: r.get("id"),
+        user_id: Uuid::parse_str(&r.get::<String, _>("user_id"))?,
+        chat_id: r.get("chat_id"),
+        amount_cents: r.get("amount_cents"),
+        reason: r.get("reason"),
+        due_on: r.get("due_on"),
+    })
+}
+
+fn recurring_from_row(r: sqlx::sqlite::SqliteRow) -> Result<Recurring> {
+    Ok(Recurring {
+        id: r.get("id"),
+        user_id: Uuid::parse_str(&r.get::<String, _>("user_id"))?,
+        chat_id: r.get("chat_id"),
+        amount_cents: r.get("amount_cents"),
+        reason: r.get("reason"),
+        every: r.get("every"),
+        start_on: r.get("start_on"),
+        runs: r.get("runs"),
+        next_on: r.get("next_on"),
+    })
+}
+
+fn reminder_from_row(r: sqlx::sqlite::SqliteRow) -> Result<Reminder> {
+    Ok(Reminder {
+        user_id: Uuid::parse_str(&r.get::<String, _>("user_id"))?,
+        chat_id: r.get("chat_id"),
+        weekday: r.get("weekday"),
+        minute_of_day: r.get("minute_of_day"),
+        utc_offset_minutes: r.get("utc_offset_minutes"),
+        last_sent_on: r.get("last_sent_on"),
+    })
 }
 
 fn sqlite_path_from_url(url: &str) -> Option<String> {
@@ -0,0 +1,265 @@
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::db::Db;
+use crate::kind::EntryKind;
+
+/// The numbers a dashboard shows for one user.
+pub struct Summary {
+    pub total_cents: i64,
+    pub invested_cents: i64,
+    /// Percent of the goal reached, capped at 100; `None` without a goal.
+    pub goal_percent: Option<i64>,
+}
+
+impl Summary {
+    pub async fn load(db: &Db, user_id: Uuid) -> anyhow::Result<Self> {
+        let total_cents = db.total_cents(user_id).await?;
+        let goal_percent = db
+            .goal(user_id)
+            .await?
+            .map(|g| (total_cents.max(0) * 100 / g.target_cents).min(100));
+        Ok(Self {
+            total_cents,
+            invested_cents: db.history_total_cents(user_id).await?,
+            goal_percent,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "total": self.total_cents as f64 / 100.0,
+            "invested": self.invested_cents as f64 / 100.0,
+            "goal_percent": self.goal_percent,
+        })
+    }
+}
+
+/// Something that happened to a user's ledger, pushed to their integrations.
+pub enum Event<'a> {
+    Entry {
+        amount_cents: i64,
+        kind: EntryKind,
+        reason: Option<&'a str>,
+        total_cents: i64,
+    },
+    AllInVoo {
+        moved_cents: i64,
+        invested_cents: i64,
+    },
+    GoalReached {
+        name: Option<&'a str>,
+        target_cents: i64,
+    },
+}
+
+impl Event<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Event::Entry { .. } => "entry",
+            Event::AllInVoo { .. } => "allinvoo",
+            Event::GoalReached { .. } => "goal_reached",
+        }
+    }
+
+    fn payload(&self, user_id: Uuid) -> Value {
+        let mut body = match self {
+            Event::Entry {
+                amount_cents,
+                kind,
+                reason,
+                total_cents,
+            } => json!({
+                "amount_cents": amount_cents,
+                "kind": kind.as_str(),
+                "reason": reason,
+                "total_cents": total_cents,
+            }),
+            Event::AllInVoo {
+                moved_cents,
+                invested_cents,
+            } => json!({
+                "moved_cents": moved_cents,
+                "invested_cents": invested_cents,
+            }),
+            Event::GoalReached { name, target_cents } => json!({
+                "name": name,
+                "target_cents": target_cents,
+            }),
+        };
+        body["event"] = json!(self.name());
+        body["user_id"] = json!(user_id.to_string());
+        body["at"] = json!(OffsetDateTime::now_utc().format(&Rfc3339).ok());
+        body
+    }
+}
+
+/// Emits an `entry` event for a just-recorded entry, plus `goal_reached` if
+/// it pushed the stash over the user's goal.
+pub async fn entry_added(
+    db: &Db,
+    user_id: Uuid,
+    amount_cents: i64,
+    kind: EntryKind,
+    reason: Option<&str>,
+) {
+    let res: anyhow::Result<()> = async {
+        let total_cents = db.total_cents(user_id).await?;
+        emit(
+            db,
+            user_id,
+            Event::Entry {
+                amount_cents,
+                kind,
+                reason,
+                total_cents,
+            },
+        )
+        .await;
+        if let Some(goal) = db.goal(user_id).await? {
+            let before = total_cents - amount_cents;
+            if before < goal.target_cents && total_cents >= goal.target_cents {
+                let event = Event::GoalReached {
+                    name: goal.name.as_deref(),
+                    target_cents: goal.target_cents,
+                };
+                emit(db, user_id, event).await;
+            }
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(err) = res {
+        eprintln!("events: {err:?}");
+    }
+}
+
+/// Publishes `event` to MQTT and delivers it to the ledger's webhooks, if any,
+/// in the background so a slow endpoint never delays the bot's reply.
+pub async fn emit(db: &Db, user_id: Uuid, event: Event<'_>) {
+    let name = event.name();
+    let body = event.payload(user_id).to_string();
+    let total = match event {
+        Event::Entry { total_cents, .. } => Some(total_cents),
+        Event::AllInVoo { .. } => Some(0),
+        Event::GoalReached { .. } => None,
+    };
+    crate::mqtt::publish(user_id, &body, total);
+    if crate::mqtt::enabled() {
+        match Summary::load(db, user_id).await {
+            Ok(summary) => crate::mqtt::publish_state(user_id, &summary),
+            Err(err) => eprintln!("events: summary error: {err:?}"),
+        }
+    }
+
+    let hooks = match db.ledger_webhooks(user_id).await {
+        Ok(hooks) => hooks,
+        Err(err) => {
+            eprintln!("events: webhook lookup error: {err:?}");
+            return;
+        }
+    };
+    for (url, secret) in hooks {
+        let body = body.clone();
+        tokio::spawn(async move {
+            if let Err(err) = post_webhook(&url, &secret, name, body).await {
+                eprintln!("events: webhook delivery error: {err:?}");
+            }
+        });
+    }
+}
+
+/// Checks that `url` may receive webhooks: https, and a host that resolves
+/// only to public addresses, so a webhook can't reach the bot's own network
+/// or its cloud metadata endpoint. Returns the address to connect to.
+pub async fn webhook_target(url: &str) -> anyhow::Result<(reqwest::Url, SocketAddr)> {
+    let url = reqwest::Url::parse(url)?;
+    if url.scheme() != "https" {
+        anyhow::bail!("the webhook must be an https:// URL");
+    }
+    let port = url.port_or_known_default().unwrap_or(443);
+    let Some(host) = url.host_str() else {
+        anyhow::bail!("the webhook URL has no host");
+    };
+    let addrs: Vec<SocketAddr> = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port)).await?.collect(),
+    };
+    if let Some(addr) = addrs.iter().find(|a| !is_public(a.ip())) {
+        anyhow::bail!("{} isn't a public address", addr.ip());
+    }
+    let addr = addrs
+        .first()
+        .copied()
+        .ok_or_else(|| anyhow::anyhow!("the webhook host doesn't resolve"))?;
+    Ok((url, addr))
+}
+
+/// Whether `ip` is reachable on the internet: not loopback, private,
+/// link-local, carrier-grade NAT, multicast or otherwise reserved.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || v4.is_multicast()
+                || a == 0
+                || a >= 240
+                || a == 100 && b & 0xc0 == 64)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public(v4.into()),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// POSTs a JSON body signed with `X-Voo-Signature: sha256=<hex HMAC of the
+/// body>` so receivers can check it came from us. The host is checked again
+/// on every delivery and the request goes to the address that was checked,
+/// with redirects refused, so DNS changes can't point it inward later.
+pub async fn post_webhook(
+    url: &str,
+    secret: &str,
+    event: &str,
+    body: String,
+) -> anyhow::Result<()> {
+    let (url, addr) = webhook_target(url).await?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body.as_bytes());
+    let signature = hex::encode(mac.finalize().into_bytes());
+    let mut client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+    if let Some(host) = url.domain() {
+        client = client.resolve(host, addr);
+    }
+    client
+        .build()?
+        .post(url)
+        .timeout(Duration::from_secs(10))
+        .header("content-type", "application/json")
+        .header("x-voo-event", event)
+        .header("x-voo-signature", format!("sha256={signature}"))
+        .body(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::Db;
+use crate::format_cents;
+
+/// Parses an expense ratio in percent such as `0.03` or `0.035%` into
+/// millionths. Anything above 10% is surely a typo.
+pub fn parse_ratio(s: &str) -> Option<i64> {
+    let percent: f64 = s.trim().trim_end_matches('%').trim().parse().ok()?;
+    let ppm = (percent * 10_000.0).round();
+    (0.0..=100_000.0).contains(&ppm).then_some(ppm as i64)
+}
+
+/// `300` → `0.03%`
+pub fn format_ratio(ppm: i64) -> String {
+    format!("{}%", ppm as f64 / 10_000.0)
+}
+
+/// What the fund charges in a year on `invested_cents`.
+pub fn annual_cost(invested_cents: i64, ppm: i64) -> i64 {
+    (invested_cents.max(0) * ppm + 500_000) / 1_000_000
+}
+
+/// What the expense ratio took from a sum of daily invested balances.
+pub fn drag(invested_days: i64, ppm: i64) -> i64 {
+    (invested_days.max(0) * ppm + 182_500_000) / 365_000_000
+}
+
+/// Recorded fees and the expense ratio's cost so far, all years together.
+pub async fn totals(db: &Db, uuid: Uuid) -> Result<(i64, i64)> {
+    let ppm = db.expense_ratio_ppm(uuid).await?;
+    let invested_days: i64 = db.fee_years(uuid).await?.iter().map(|y| y.2).sum();
+    Ok((db.fees_total_cents(uuid).await?, drag(invested_days, ppm)))
+}
+
+/// One line per year: recorded fees plus the expense ratio's cost on the
+/// daily invested snapshots, at the current ratio.
+pub async fn yearly(db: &Db, uuid: Uuid, year: Option<&str>) -> Result<Vec<String>> {
+    let ppm = db.expense_ratio_ppm(uuid).await?;
+    let mut lines = Vec::new();
+    for (y, recorded, invested_days) in db.fee_years(uuid).await? {
+        if year.is_some_and(|year| year != y) {
+            continue;
+        }
+        let drag = drag(invested_days, ppm);
+        if recorded == 0 && drag == 0 {
+            continue;
+        }
+        lines.push(if ppm == 0 {
+            format!("{y}: {}", format_cents(recorded))
+        } else {
+            format!(
+                "{y}: {} = {} recorded + about {} expense ratio",
+                format_cents(recorded + drag),
+                format_cents(recorded),
+                format_cents(drag)
+            )
+        });
+    }
+    Ok(lines)
+}
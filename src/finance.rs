@@ -0,0 +1,98 @@
+//! Return math for /gains.
+
+use time::Date;
+
+/// Days per year for annualizing, as spreadsheets' XIRR uses.
+const YEAR_DAYS: f64 = 365.0;
+
+/// Annualized internal rate of return of dated cash flows, money put in
+/// negative and money out (or what is still held) positive: the rate at
+/// which they discount to zero. `None` without both signs among the flows
+/// or when no rate between -99.99% and 1,000,000% fits.
+pub fn xirr(flows: &[(Date, f64)]) -> Option<f64> {
+    let first = flows.iter().map(|(d, _)| *d).min()?;
+    let npv = |rate: f64| -> f64 {
+        flows
+            .iter()
+            .map(|(d, amount)| {
+                let years = (*d - first).whole_days() as f64 / YEAR_DAYS;
+                amount / (1.0 + rate).powf(years)
+            })
+            .sum()
+    };
+    if !flows.iter().any(|f| f.1 < 0.0) || !flows.iter().any(|f| f.1 > 0.0) {
+        return None;
+    }
+    // Bisection rather than Newton's method: slower, but it can't wander
+    // off on the flat stretches short holding periods produce.
+    let (mut low, mut high) = (-0.9999, 10_000.0);
+    let (f_low, f_high) = (npv(low), npv(high));
+    if !f_low.is_finite() || !f_high.is_finite() || f_low.signum() == f_high.signum() {
+        return None;
+    }
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+        let f_mid = npv(mid);
+        if f_mid.abs() < 1e-9 || high - low < 1e-10 {
+            return Some(mid);
+        }
+        if f_mid.signum() == f_low.signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Some((low + high) / 2.0)
+}
+
+/// Gain over cost in percent; `None` at zero cost.
+pub fn gain_percent(cost_cents: i64, value_cents: i64) -> Option<f64> {
+    (cost_cents != 0).then(|| (value_cents - cost_cents) as f64 * 100.0 / cost_cents as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    #[test]
+    fn xirr_matches_the_spreadsheet_example() {
+        // The XIRR example from spreadsheet documentation: 37.34%.
+        let flows = [
+            (date!(2008 - 01 - 01), -10_000.0),
+            (date!(2008 - 03 - 01), 2_750.0),
+            (date!(2008 - 10 - 30), 4_250.0),
+            (date!(2009 - 02 - 15), 3_250.0),
+            (date!(2009 - 04 - 01), 2_750.0),
+        ];
+        let rate = xirr(&flows).unwrap();
+        assert!((rate - 0.373_362_535).abs() < 1e-6, "{rate}");
+    }
+
+    #[test]
+    fn xirr_of_a_loss() {
+        let flows = [
+            (date!(2023 - 01 - 01), -1_000.0),
+            (date!(2024 - 01 - 01), 900.0),
+        ];
+        let rate = xirr(&flows).unwrap();
+        assert!((rate + 0.1).abs() < 1e-6, "{rate}");
+    }
+
+    #[test]
+    fn xirr_needs_money_in_and_out() {
+        let d = date!(2024 - 01 - 01);
+        assert_eq!(xirr(&[]), None);
+        assert_eq!(xirr(&[(d, -100.0)]), None);
+        assert_eq!(xirr(&[(d, -100.0), (date!(2024 - 06 - 01), -50.0)]), None);
+        assert_eq!(xirr(&[(d, 100.0), (date!(2024 - 06 - 01), 50.0)]), None);
+    }
+
+    #[test]
+    fn gain_percent_of_cost() {
+        assert_eq!(gain_percent(10_000, 12_500), Some(25.0));
+        assert_eq!(gain_percent(10_000, 8_000), Some(-20.0));
+        assert_eq!(gain_percent(0, 500), None);
+        assert_eq!(gain_percent(0, 0), None);
+    }
+}
@@ -0,0 +1,77 @@
+//! Feature flags for soft launches. Each flag has a rollout percentage:
+//! the one set with /flag, else the `FEATURE_FLAGS` default, else 100.
+//! Per-user overrides win over the rollout. A user's bucket comes from a
+//! hash of the flag and their id, so raising the percentage only ever adds
+//! users.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::env;
+use uuid::Uuid;
+
+use crate::db::Db;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    /// Voice notes: speech-to-text and parsing the amount out of it.
+    Voice,
+    /// Receipt photos read with OCR.
+    Receipts,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 2] = [Flag::Voice, Flag::Receipts];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.as_str() == s)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Flag::Voice => "voice",
+            Flag::Receipts => "receipts",
+        }
+    }
+}
+
+/// The operator's default from `FEATURE_FLAGS`, e.g. `voice=0,receipts=25`.
+fn default_percent(flag: Flag) -> i64 {
+    env::var("FEATURE_FLAGS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| name.trim() == flag.as_str())
+        .and_then(|(_, percent)| percent.trim().trim_end_matches('%').parse().ok())
+        .map_or(100, |p: i64| p.clamp(0, 100))
+}
+
+/// 0-99, stable for a user and flag.
+fn bucket(flag: Flag, user_id: Uuid) -> i64 {
+    let hash = Sha256::digest(format!("{}:{user_id}", flag.as_str()));
+    i64::from(u16::from_be_bytes([hash[0], hash[1]]) % 100)
+}
+
+pub async fn enabled(db: &Db, flag: Flag, user_id: Uuid) -> Result<bool> {
+    let (forced, percent) = db.flag_state(flag.as_str(), user_id).await?;
+    let percent = percent.unwrap_or_else(|| default_percent(flag));
+    Ok(forced.unwrap_or_else(|| bucket(flag, user_id) < percent))
+}
+
+/// One line per flag for /flag: rollout, where it comes from, overrides.
+pub async fn describe(db: &Db) -> Result<Vec<String>> {
+    let stored = db.flags().await?;
+    let mut lines = Vec::new();
+    for flag in Flag::ALL {
+        let row = stored.iter().find(|r| r.flag == flag.as_str());
+        let (on, off) = row.map_or((0, 0), |r| (r.forced_on, r.forced_off));
+        let mut line = match row.and_then(|r| r.percent) {
+            Some(p) => format!("{}: {p}%", flag.as_str()),
+            None => format!("{}: {}% (default)", flag.as_str(), default_percent(flag)),
+        };
+        if on + off > 0 {
+            line.push_str(&format!(", forced on for {on} and off for {off} users"));
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
@@ -0,0 +1,85 @@
+//! Exchange rates for entries in another currency, e.g. `/save 1000 JPY
+//! lunch`. Rates come from `FX_URL` and are kept in the fx_rates table for
+//! a while, so a burst of foreign entries costs one lookup.
+
+use anyhow::{anyhow, bail, Result};
+use std::env;
+use time::OffsetDateTime;
+
+use crate::db::Db;
+
+/// Frankfurter serves the ECB reference rates; `{from}` and `{to}` are
+/// replaced with ISO codes. Any provider answering
+/// `{"rates": {"USD": 0.0067}}` works.
+const DEFAULT_FX_URL: &str = "https://api.frankfurter.app/latest?from={from}&to={to}";
+
+/// How long a fetched rate is reused.
+const CACHE_HOURS: i64 = 12;
+
+/// ISO 4217 codes read as a currency after an amount: the ECB reference
+/// currencies and other widely used ones. Codes that are also everyday
+/// words or abbreviations (ALL, CUP, TOP, MOP) are left out, so `/save 5
+/// ALL day` keeps its reason.
+const ISO_CODES: &[&str] = &[
+    "AED", "ARS", "AUD", "BGN", "BRL", "CAD", "CHF", "CLP", "CNY", "COP", "CZK", "DKK", "EGP",
+    "EUR", "GBP", "HKD", "HUF", "IDR", "ILS", "INR", "ISK", "JPY", "KES", "KRW", "KWD", "MAD",
+    "MXN", "MYR", "NGN", "NOK", "NZD", "PEN", "PHP", "PKR", "PLN", "QAR", "RON", "RSD", "RUB",
+    "SAR", "SEK", "SGD", "THB", "TRY", "TWD", "UAH", "USD", "VND", "ZAR",
+];
+
+/// A currency code right after the amount, so `/save 1000 JPY lunch`
+/// converts while `/save 5 tea` or `/save 20 BBQ` keeps its reason.
+pub fn split_code(reason: Option<String>) -> (Option<String>, Option<String>) {
+    let Some(reason) = reason else {
+        return (None, None);
+    };
+    let (first, rest) = reason
+        .split_once(char::is_whitespace)
+        .unwrap_or((reason.as_str(), ""));
+    if !ISO_CODES.contains(&first) {
+        return (None, Some(reason));
+    }
+    let rest = rest.trim();
+    (
+        Some(first.to_string()),
+        (!rest.is_empty()).then(|| rest.to_string()),
+    )
+}
+
+/// How many `to` one `from` buys, or `None` when `FX_URL` is set to an
+/// empty string to keep the bot offline.
+pub async fn rate(db: &Db, from: &str, to: &str) -> Result<Option<f64>> {
+    let now = OffsetDateTime::now_utc();
+    let fresh_after = (now - time::Duration::hours(CACHE_HOURS))
+        .format(&time::format_description::well_known::Rfc3339)?;
+    if let Some(rate) = db.fx_rate(from, to, &fresh_after).await? {
+        return Ok(Some(rate));
+    }
+    let template = env::var("FX_URL").unwrap_or_else(|_| DEFAULT_FX_URL.into());
+    if template.is_empty() {
+        return Ok(None);
+    }
+    let rate = fetch(&template, from, to).await?;
+    db.set_fx_rate(from, to, rate).await?;
+    Ok(Some(rate))
+}
+
+#[tracing::instrument(skip_all, fields(from = from, to = to))]
+async fn fetch(template: &str, from: &str, to: &str) -> Result<f64> {
+    let url = template.replace("{from}", from).replace("{to}", to);
+    let resp = reqwest::get(&url).await?;
+    let status = resp.status();
+    let body: serde_json::Value = resp.json().await?;
+    if !status.is_success() {
+        bail!("exchange rate {status}: {body}");
+    }
+    body["rates"][to]
+        .as_f64()
+        .filter(|r| r.is_finite() && *r > 0.0)
+        .ok_or_else(|| anyhow!("no exchange rate from {from} to {to}"))
+}
+
+/// Hundredths of one currency in hundredths of another.
+pub fn convert(cents: i64, rate: f64) -> i64 {
+    (cents as f64 * rate).round() as i64
+}
@@ -0,0 +1,148 @@
+//! /gains: what the invested batches cost, what they are worth at the last
+//! close and the annualized return of buying them when they were bought,
+//! before and after /fee payments and the fund's expense ratio. Prices are
+//! in USD and valued in the user's currency at today's rate. Nothing here
+//! is ever sold, so the only realized gains are /dividend payouts.
+
+use anyhow::Result;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::currency::Currency;
+use crate::db::Db;
+use crate::{fees, finance, hedged, market};
+
+/// Below this, an annualized return mostly extrapolates noise.
+const MIN_XIRR_DAYS: i64 = 30;
+
+pub async fn report(db: &Db, uuid: Uuid, currency: Currency) -> Result<Vec<String>> {
+    let lots = db.lots(uuid).await?;
+    if lots.is_empty() {
+        return Ok(vec![
+            "No invested shares yet. /allinvoo records what each run buys.".into(),
+        ]);
+    }
+
+    let Some(rate) = hedged::value_rate(db, currency, "gains").await else {
+        return Ok(vec![format!(
+            "No {}/USD exchange rate right now to value your shares with.",
+            currency.code
+        )]);
+    };
+    let (prices, unpriced) = market::prices(lots.iter().map(|l| l.ticker.as_str()), "gains").await;
+    let price = |ticker: &str| prices.iter().find(|p| p.0 == ticker).map(|p| p.1);
+
+    // (ticker, cost, value) of the priced lots only, so the totals compare
+    // like with like.
+    let mut positions: Vec<(&str, i64, i64)> = Vec::new();
+    let mut flows = Vec::new();
+    let now = OffsetDateTime::now_utc();
+    let mut first_bought = now;
+    for lot in &lots {
+        let Some(price) = price(&lot.ticker) else {
+            continue;
+        };
+        let usd_value = lot.micro_shares as f64 * price as f64 / 1_000_000.0;
+        let value = (usd_value / rate).round() as i64;
+        match positions.iter_mut().find(|p| p.0 == lot.ticker) {
+            Some(p) => {
+                p.1 += lot.cost_cents;
+                p.2 += value;
+            }
+            None => positions.push((&lot.ticker, lot.cost_cents, value)),
+        }
+        let bought = OffsetDateTime::parse(&lot.bought_at, &Rfc3339)?;
+        first_bought = first_bought.min(bought);
+        flows.push((bought.date(), -(lot.cost_cents as f64)));
+    }
+    let cost: i64 = positions.iter().map(|p| p.1).sum();
+    let value: i64 = positions.iter().map(|p| p.2).sum();
+    let (recorded_fees, drag) = fees::totals(db, uuid).await?;
+    let net_value = value - recorded_fees - drag;
+    let mut net_flows = flows.clone();
+    flows.push((now.date(), value as f64));
+    net_flows.push((now.date(), net_value as f64));
+
+    let mut lines = vec!["📈 Gains".to_string()];
+    for (ticker, cost, value) in &positions {
+        lines.push(format!(
+            "  {ticker}: cost {}, now {} ({})",
+            currency.format(*cost),
+            currency.format(*value),
+            percent(finance::gain_percent(*cost, *value))
+        ));
+    }
+    if !positions.is_empty() {
+        lines.push(format!(
+            "Unrealized: {} on {} ({}), worth {}",
+            currency.format_signed(value - cost),
+            currency.format(cost),
+            percent(finance::gain_percent(cost, value)),
+            currency.format(value)
+        ));
+        if recorded_fees != 0 || drag != 0 {
+            lines.push(format!(
+                "Net of fees: {} ({}) after {} recorded fees and about {} expense ratio",
+                currency.format_signed(net_value - cost),
+                percent(finance::gain_percent(cost, net_value)),
+                currency.format(recorded_fees),
+                currency.format(drag)
+            ));
+        }
+        let days = (now - first_bought).whole_days();
+        if days < MIN_XIRR_DAYS {
+            lines.push(format!(
+                "Annualized return (XIRR): after {MIN_XIRR_DAYS} days of holding"
+            ));
+        } else {
+            lines.push(xirr_line("Annualized return (XIRR)", &flows));
+            if recorded_fees != 0 || drag != 0 {
+                lines.push(xirr_line("Net of fees", &net_flows));
+            }
+        }
+        let ppm = db.expense_ratio_ppm(uuid).await?;
+        if ppm > 0 {
+            lines.push(format!(
+                "At a {} expense ratio the fund costs about {}/year on this.",
+                fees::format_ratio(ppm),
+                currency.format(fees::annual_cost(value, ppm))
+            ));
+        }
+    }
+    let dividends = db.dividend_totals(uuid).await?;
+    lines.push(format!(
+        "Realized: {} in dividends; shares are never sold here.",
+        currency.format(dividends.total_cents)
+    ));
+    if !unpriced.is_empty() {
+        lines.push(format!(
+            "No price for {} right now; left out above.",
+            unpriced.join(", ")
+        ));
+    }
+    let (_, invested) = db.balances(uuid).await?;
+    let tracked: i64 = lots.iter().map(|l| l.cost_cents).sum();
+    if invested > tracked {
+        lines.push(format!(
+            "{} invested before share prices were recorded isn't included.",
+            currency.format(invested - tracked)
+        ));
+    }
+    Ok(lines)
+}
+
+/// `{label}: +7.1%`, or why there is no rate.
+fn xirr_line(label: &str, flows: &[(time::Date, f64)]) -> String {
+    match finance::xirr(flows) {
+        Some(rate) => format!("{label}: {}", percent(Some(rate * 100.0))),
+        None => format!("{label}: can't be worked out from these batches"),
+    }
+}
+
+/// `+12.3%`, `-4.0%`, or `n/a`
+fn percent(p: Option<f64>) -> String {
+    match p {
+        Some(p) if p.is_finite() => format!("{p:+.1}%"),
+        _ => "n/a".into(),
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::Result;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use time::{format_description::well_known::Rfc3339, Date, Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::db::{Db, Goal};
+use crate::i18n::{self, Lang};
+use crate::{format_cents, parse_date};
+
+/// Window for the "at your recent pace" projection.
+const PACE_DAYS: i64 = 30;
+
+/// Percentages worth a celebration when a save crosses them.
+const MILESTONES: [i64; 4] = [25, 50, 75, 100];
+
+/// New goals get weekly check-ins on Sundays at 10:00 local time.
+pub const CHECKIN_WEEKDAY: u8 = 6;
+pub const CHECKIN_MINUTE: i64 = 10 * 60;
+
+/// [`progress`] for today, with the pace taken from the last 30 days.
+pub async fn status(db: &Db, user_id: Uuid, goal: &Goal, current_cents: i64) -> Result<String> {
+    let today = OffsetDateTime::now_utc().date();
+    let start = today - Duration::days(PACE_DAYS);
+    let recent = db
+        .period_summary(
+            user_id,
+            &start.to_string(),
+            &today.next_day().unwrap_or(today).to_string(),
+        )
+        .await?;
+    let daily_pace = (recent.saved - recent.spent) / PACE_DAYS;
+    Ok(progress(goal, current_cents, today, daily_pace))
+}
+
+/// The periodic check-in, e.g. "You're 40% toward New laptop, 12 weeks
+/// left", with the amount that keeps the deadline in reach.
+pub fn checkin(lang: Lang, goal: &Goal, current_cents: i64, today: Date) -> String {
+    let name = goal.name.as_deref().unwrap_or(i18n::unnamed_goal(lang));
+    let percent = (current_cents.max(0) * 100 / goal.target_cents).min(100);
+    let remaining = goal.target_cents - current_cents;
+    if remaining <= 0 {
+        return i18n::goal_checkin_reached(lang, name);
+    }
+    let mut text = i18n::goal_checkin(lang, percent, name);
+    match weeks_left(goal, today) {
+        Some(weeks) => text.push_str(&i18n::goal_checkin_weeks(
+            lang,
+            weeks,
+            &format_cents(remaining),
+            &format_cents((remaining + weeks - 1) / weeks),
+        )),
+        None => text.push_str(&i18n::goal_checkin_remaining(
+            lang,
+            &format_cents(remaining),
+        )),
+    }
+    text
+}
+
+/// One check-in period's share of what is left before the deadline: a
+/// day's for daily check-ins, otherwise a week's. `None` without a
+/// deadline ahead or once the goal is reached.
+pub fn suggested_save(goal: &Goal, current_cents: i64, today: Date, daily: bool) -> Option<i64> {
+    let remaining = goal.target_cents - current_cents;
+    let deadline = goal.deadline.as_deref().and_then(parse_date)?;
+    let periods = if daily {
+        (deadline - today).whole_days()
+    } else {
+        weeks_left(goal, today)?
+    };
+    (remaining > 0 && periods > 0).then(|| (remaining + periods - 1) / periods)
+}
+
+/// Buttons under a check-in. Callback data is `goal:{save[:cents]|adjust|pause}`.
+pub fn checkin_keyboard(lang: Lang, save_cents: Option<i64>) -> InlineKeyboardMarkup {
+    let save = match save_cents {
+        Some(cents) => InlineKeyboardButton::callback(
+            i18n::goal_save_button(lang, Some(&format_cents(cents))),
+            format!("goal:save:{cents}"),
+        ),
+        None => InlineKeyboardButton::callback(i18n::goal_save_button(lang, None), "goal:save"),
+    };
+    InlineKeyboardMarkup::new([[
+        save,
+        InlineKeyboardButton::callback(i18n::goal_adjust_button(lang), "goal:adjust"),
+        InlineKeyboardButton::callback(i18n::goal_pause_button(lang), "goal:pause"),
+    ]])
+}
+
+/// Whole or partial weeks until a deadline that is still ahead.
+fn weeks_left(goal: &Goal, today: Date) -> Option<i64> {
+    let deadline = goal.deadline.as_deref().and_then(parse_date)?;
+    let days_left = (deadline - today).whole_days();
+    (days_left > 0).then_some((days_left + 6) / 7)
+}
+
+/// The highest milestone the stash crossed going from `before` to `after`.
+pub fn milestone(goal: &Goal, before: i64, after: i64) -> Option<i64> {
+    let percent = |cents: i64| cents.max(0) * 100 / goal.target_cents;
+    let (before, after) = (percent(before), percent(after));
+    MILESTONES
+        .into_iter()
+        .rev()
+        .find(|m| before < *m && after >= *m)
+}
+
+/// Progress line(s) for a goal: a projected completion date at the recent
+/// net saving pace (`daily_pace_cents` per day), the weekly pace needed to
+/// hit the deadline, and a warning when the user is behind a straight-line
+/// plan.
+pub fn progress(goal: &Goal, current_cents: i64, today: Date, daily_pace_cents: i64) -> String {
+    let name = goal.name.as_deref().unwrap_or("Goal");
+    let percent = (current_cents.max(0) * 100 / goal.target_cents).min(100);
+    let mut lines = vec![format!(
+        "🎯 {name}: {} / {} ({percent}%)",
+        format_cents(current_cents),
+        format_cents(goal.target_cents)
+    )];
+
+    let remaining = goal.target_cents - current_cents;
+    if remaining <= 0 {
+        lines.push("🎉 Goal reached!".into());
+        return lines.join("\n");
+    }
+
+    if daily_pace_cents > 0 {
+        let days = (remaining + daily_pace_cents - 1) / daily_pace_cents;
+        let eta = today.checked_add(Duration::days(days));
+        if let Some(eta) = eta {
+            lines.push(format!(
+                "At your recent pace ({}/day) you'll get there around {eta}.",
+                format_cents(daily_pace_cents)
+            ));
+        }
+    } else {
+        lines.push(format!(
+            "No net saving in the last {PACE_DAYS} days, so no projected date yet."
+        ));
+    }
+
+    let Some(deadline) = goal.deadline.as_deref().and_then(parse_date) else {
+        return lines.join("\n");
+    };
+    let days_left = (deadline - today).whole_days();
+    if days_left <= 0 {
+        lines.push(format!(
+            "Deadline {deadline} has passed, {} short.",
+            format_cents(remaining)
+        ));
+        return lines.join("\n");
+    }
+
+    // Partial weeks count as a whole week of saving time.
+    let weeks_left = (days_left + 6) / 7;
+    lines.push(format!(
+        "Deadline {deadline} ({weeks_left} weeks left): you need {}/week to make it.",
+        format_cents((remaining + weeks_left - 1) / weeks_left)
+    ));
+
+    if let Some(behind) = behind_pace(goal, current_cents, today, deadline) {
+        lines.push(format!("⚠️ You're {} behind pace.", format_cents(behind)));
+    }
+    lines.join("\n")
+}
+
+/// How far the stash trails a linear path from `start_cents` on the day the goal
+/// was set to the target on the deadline, if at all.
+fn behind_pace(goal: &Goal, current_cents: i64, today: Date, deadline: Date) -> Option<i64> {
+    let start = OffsetDateTime::parse(&goal.created_at, &Rfc3339)
+        .ok()?
+        .date();
+    let total_days = (deadline - start).whole_days();
+    let elapsed = (today - start).whole_days();
+    if total_days <= 0 || elapsed <= 0 {
+        return None;
+    }
+    let expected = goal.start_cents + (goal.target_cents - goal.start_cents) * elapsed / total_days;
+    (current_cents < expected).then_some(expected - current_cents)
+}
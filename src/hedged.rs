@@ -0,0 +1,123 @@
+//! /portfolio fx and /networth fx, for users who save in something other
+//! than USD: invested shares valued from their USD price at today's
+//! exchange rate, with the part of the gain that came from the exchange
+//! rate moving shown apart from the part the market made. Each /allinvoo
+//! stores that day's rate with its holdings.
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::currency::Currency;
+use crate::db::Db;
+use crate::{fx, market};
+
+/// How many USD one unit of `currency` buys, or `None` for USD itself,
+/// bare numbers, or with exchange rates switched off.
+pub async fn usd_rate(db: &Db, currency: Currency) -> Result<Option<f64>> {
+    if currency.code.is_empty() || currency.code == "USD" {
+        return Ok(None);
+    }
+    fx::rate(db, currency.code, "USD").await
+}
+
+/// How many USD one unit of `currency` buys, for valuing shares in it: 1
+/// for USD and bare numbers, `None` if the rate can't be had right now.
+/// Errors are logged under `context`.
+pub async fn value_rate(db: &Db, currency: Currency, context: &str) -> Option<f64> {
+    if currency.code.is_empty() || currency.code == "USD" {
+        return Some(1.0);
+    }
+    usd_rate(db, currency).await.unwrap_or_else(|err| {
+        eprintln!("{context}: {} rate: {err:?}", currency.code);
+        None
+    })
+}
+
+/// Lines to append to /portfolio or /networth.
+pub async fn report(db: &Db, uuid: Uuid, currency: Currency) -> Result<Vec<String>> {
+    if currency.code.is_empty() || currency.code == "USD" {
+        return Ok(vec![
+            "FX reporting is for a currency other than USD; set yours with e.g. /currency EUR."
+                .into(),
+        ]);
+    }
+    let code = currency.code;
+    let rate = usd_rate(db, currency).await.unwrap_or_else(|err| {
+        eprintln!("hedged: {code} rate: {err:?}");
+        None
+    });
+    let Some(rate) = rate else {
+        return Ok(vec![format!("No {code}/USD exchange rate right now.")]);
+    };
+    let lots = db.lots(uuid).await?;
+    if lots.is_empty() {
+        return Ok(vec![
+            "No invested shares yet. /allinvoo records what each run buys.".into(),
+        ]);
+    }
+
+    let (prices, unpriced) = market::prices(lots.iter().map(|l| l.ticker.as_str()), "hedged").await;
+
+    // (ticker, value, market gain, FX gain), all in the user's currency.
+    let mut positions: Vec<(&str, f64, f64, f64)> = Vec::new();
+    let mut without_rate = 0;
+    for lot in &lots {
+        let Some(&(_, price)) = prices.iter().find(|p| p.0 == lot.ticker) else {
+            continue;
+        };
+        if lot.price_cents <= 0 {
+            continue;
+        }
+        // Lots bought before rates were stored count as bought at today's.
+        let bought_rate = lot.fx_rate.unwrap_or_else(|| {
+            without_rate += lot.cost_cents;
+            rate
+        });
+        let cost = lot.cost_cents as f64;
+        let usd_cost = cost * bought_rate;
+        let usd_value = usd_cost * price as f64 / lot.price_cents as f64;
+        let value = usd_value / rate;
+        let market_gain = (usd_value - usd_cost) / rate;
+        let fx_gain = usd_cost / rate - cost;
+        match positions.iter_mut().find(|p| p.0 == lot.ticker) {
+            Some(p) => {
+                p.1 += value;
+                p.2 += market_gain;
+                p.3 += fx_gain;
+            }
+            None => positions.push((&lot.ticker, value, market_gain, fx_gain)),
+        }
+    }
+
+    let mut lines = vec![format!(
+        "💱 In {code} at today's rate (1 {code} = {rate:.4} USD)"
+    )];
+    for (ticker, value, market_gain, fx_gain) in &positions {
+        lines.push(format!(
+            "  {ticker}: worth {}, market {}, FX {}",
+            currency.format(value.round() as i64),
+            currency.format_signed(market_gain.round() as i64),
+            currency.format_signed(fx_gain.round() as i64)
+        ));
+    }
+    if !positions.is_empty() {
+        let market_gain: f64 = positions.iter().map(|p| p.2).sum();
+        let fx_gain: f64 = positions.iter().map(|p| p.3).sum();
+        lines.push(format!(
+            "Market: {}, FX: {}, together {}",
+            currency.format_signed(market_gain.round() as i64),
+            currency.format_signed(fx_gain.round() as i64),
+            currency.format_signed((market_gain + fx_gain).round() as i64)
+        ));
+    }
+    if without_rate > 0 {
+        lines.push(format!(
+            "{} invested before exchange rates were recorded shows no FX gain or loss.",
+            currency.format(without_rate)
+        ));
+    }
+    if !unpriced.is_empty() {
+        lines.push(format!("No price for {} right now.", unpriced.join(", ")));
+    }
+    Ok(lines)
+}
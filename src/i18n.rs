@@ -0,0 +1,508 @@
+use crate::rounding::Rounding;
+
+/// Languages the bot can reply in. Anything else falls back to English.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    De,
+}
+
+pub const SUPPORTED: &str = "en, de";
+
+impl Lang {
+    /// Accepts bare codes ("de") as well as Telegram's IETF tags ("de-AT").
+    pub fn from_code(code: &str) -> Option<Self> {
+        let primary = code.split(['-', '_']).next()?.trim().to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Lang::En),
+            "de" => Some(Lang::De),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::De => "de",
+        }
+    }
+}
+
+/// Whether people writing in the language of this IETF tag use a decimal
+/// comma, 12,50 rather than 12.50. Any language, not just the ones the bot
+/// replies in.
+pub fn uses_decimal_comma(code: &str) -> bool {
+    const COMMA: &[&str] = &[
+        "de", "fr", "es", "it", "pt", "nl", "ru", "uk", "pl", "cs", "sk", "sl", "hr", "sr", "bg",
+        "ro", "hu", "tr", "el", "sv", "da", "nb", "nn", "no", "fi", "et", "lv", "lt", "id", "vi",
+        "be", "kk", "az", "ca", "gl", "eu",
+    ];
+    let primary = code.split(['-', '_']).next().unwrap_or_default();
+    COMMA.iter().any(|c| c.eq_ignore_ascii_case(primary.trim()))
+}
+
+pub fn welcome(lang: Lang, name: &str, uuid: &str) -> String {
+    match lang {
+        Lang::En => format!(
+            "Welcome, {name}!\nYour user UUID: `{uuid}`\nUse /save, /adjust, /allinvoo, /query."
+        ),
+        Lang::De => format!(
+            "Willkommen, {name}!\nDeine Benutzer-UUID: `{uuid}`\nNutze /save, /adjust, /allinvoo, /query."
+        ),
+    }
+}
+
+pub fn reason_prefix(lang: Lang, reason: &Option<String>) -> String {
+    let label = match lang {
+        Lang::En => "Reason",
+        Lang::De => "Grund",
+    };
+    reason
+        .as_ref()
+        .map(|r| format!("{label}: {r}\n"))
+        .unwrap_or_default()
+}
+
+pub fn save_not_positive(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "Amount must be positive for /save.",
+        Lang::De => "Der Betrag für /save muss positiv sein.",
+    }
+}
+
+pub fn saved(lang: Lang, amount: &str, reason: &Option<String>, total: &str) -> String {
+    let reason = reason_prefix(lang, reason);
+    match lang {
+        Lang::En => format!("Saved {amount}\n{reason}Total now: {total}"),
+        Lang::De => format!("Gespart: {amount}\n{reason}Gesamt jetzt: {total}"),
+    }
+}
+
+pub fn adjust_zero(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "Adjustment must be non-zero.",
+        Lang::De => "Die Korrektur darf nicht null sein.",
+    }
+}
+
+pub fn adjusted(
+    lang: Lang,
+    added: bool,
+    amount: &str,
+    reason: &Option<String>,
+    total: &str,
+) -> String {
+    let reason = reason_prefix(lang, reason);
+    match lang {
+        Lang::En => {
+            let sign = if added { "added" } else { "subtracted" };
+            format!("Adjustment {sign} {amount}\n{reason}Total now: {total}")
+        }
+        Lang::De => {
+            let sign = if added { "hinzugefügt" } else { "abgezogen" };
+            format!("Korrektur: {amount} {sign}\n{reason}Gesamt jetzt: {total}")
+        }
+    }
+}
+
+pub fn withdraw_not_positive(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "Amount must be positive for /withdraw.",
+        Lang::De => "Der Betrag für /withdraw muss positiv sein.",
+    }
+}
+
+pub fn withdraw_exceeds(lang: Lang, total: &str) -> String {
+    match lang {
+        Lang::En => format!("You can't withdraw more than your current total of {total}."),
+        Lang::De => format!("Du kannst nicht mehr als deinen aktuellen Stand von {total} abheben."),
+    }
+}
+
+pub fn withdrew(lang: Lang, amount: &str, reason: &Option<String>, total: &str) -> String {
+    let reason = reason_prefix(lang, reason);
+    match lang {
+        Lang::En => format!("Withdrew {amount}\n{reason}Total now: {total}"),
+        Lang::De => format!("Abgehoben: {amount}\n{reason}Gesamt jetzt: {total}"),
+    }
+}
+
+pub fn nothing_to_invest(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "Nothing to invest yet. Your current total is 0.",
+        Lang::De => "Noch nichts zu investieren. Dein aktueller Stand ist 0.",
+    }
+}
+
+pub fn invested(lang: Lang, moved: &str, tickers: &str, current: &str, history: &str) -> String {
+    match lang {
+        Lang::En => format!(
+            "Invested {moved} into {tickers} (moved to history).\nCurrent now: {current}\nHistory total: {history}"
+        ),
+        Lang::De => format!(
+            "{moved} in {tickers} investiert (in den Verlauf verschoben).\nAktuell jetzt: {current}\nVerlauf gesamt: {history}"
+        ),
+    }
+}
+
+pub fn invest_confirm(lang: Lang, moved: &str, tickers: &str, strategy: Option<&str>) -> String {
+    match (lang, strategy) {
+        (Lang::En, None) => format!("Invest {moved} into {tickers}? This can't be undone."),
+        (Lang::En, Some(name)) => {
+            format!("Invest {moved} into {tickers} for {name}? This can't be undone.")
+        }
+        (Lang::De, None) => {
+            format!("{moved} in {tickers} investieren? Das lässt sich nicht rückgängig machen.")
+        }
+        (Lang::De, Some(name)) => format!(
+            "{moved} für {name} in {tickers} investieren? Das lässt sich nicht rückgängig machen."
+        ),
+    }
+}
+
+pub fn invest_cancelled(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "Cancelled, nothing was invested.",
+        Lang::De => "Abgebrochen, es wurde nichts investiert.",
+    }
+}
+
+pub fn invest_stale(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "Nothing was invested: that confirmation has expired or your stash changed since. Run /allinvoo again.",
+        Lang::De => "Es wurde nichts investiert: die Bestätigung ist abgelaufen oder dein Stand hat sich geändert. Starte /allinvoo erneut.",
+    }
+}
+
+pub fn strategy_total(lang: Lang, name: &str, total: &str) -> String {
+    match lang {
+        Lang::En => format!("{name} total: {total}"),
+        Lang::De => format!("{name} gesamt: {total}"),
+    }
+}
+
+pub fn invest_preview(
+    lang: Lang,
+    moved: &str,
+    entries: i64,
+    current: &str,
+    history: &str,
+    tickers: &str,
+    shares: &[(String, String, String)],
+) -> String {
+    match lang {
+        Lang::En => {
+            let mut text = format!(
+                "Preview — nothing has been changed.\n/allinvoo would invest {moved} from {entries} entries into {tickers}.\nCurrent after: {current}\nHistory total after: {history}"
+            );
+            if !shares.is_empty() {
+                let parts: Vec<String> = shares
+                    .iter()
+                    .map(|(ticker, shares, price)| format!("{shares} {ticker} at {price}"))
+                    .collect();
+                text.push_str(&format!(
+                    "\nThat buys about {} (last close).",
+                    parts.join(", ")
+                ));
+            }
+            text
+        }
+        Lang::De => {
+            let mut text = format!(
+                "Vorschau — es wurde nichts geändert.\n/allinvoo würde {moved} aus {entries} Einträgen in {tickers} investieren.\nAktuell danach: {current}\nVerlauf gesamt danach: {history}"
+            );
+            if !shares.is_empty() {
+                let parts: Vec<String> = shares
+                    .iter()
+                    .map(|(ticker, shares, price)| format!("{shares} {ticker} zu {price}"))
+                    .collect();
+                text.push_str(&format!(
+                    "\nDas reicht für etwa {} (letzter Schlusskurs).",
+                    parts.join(", ")
+                ));
+            }
+            text
+        }
+    }
+}
+
+pub fn no_entries(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "No entries yet. Use /save to start!",
+        Lang::De => "Noch keine Einträge. Starte mit /save!",
+    }
+}
+
+pub fn query_header(lang: Lang, count: usize, name: &str) -> String {
+    match lang {
+        Lang::En => format!("Last {count} entries for {name}:"),
+        Lang::De => format!("Letzte {count} Einträge für {name}:"),
+    }
+}
+
+/// Header for /query pages after the first.
+pub fn query_page_header(lang: Lang, count: usize, name: &str) -> String {
+    match lang {
+        Lang::En => format!("{count} more entries for {name}:"),
+        Lang::De => format!("{count} weitere Einträge für {name}:"),
+    }
+}
+
+pub fn attached(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "(attached)",
+        Lang::De => "(als Datei)",
+    }
+}
+
+pub fn totals(lang: Lang, current: &str, history: &str, grand: &str) -> String {
+    match lang {
+        Lang::En => {
+            format!("Current total: {current}\nHistory total: {history}\nGrand total: {grand}")
+        }
+        Lang::De => {
+            format!("Aktuell gesamt: {current}\nVerlauf gesamt: {history}\nInsgesamt: {grand}")
+        }
+    }
+}
+
+pub fn chat_language_set(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "This chat now uses English for group messages.",
+        Lang::De => "Dieser Chat verwendet jetzt Deutsch für Gruppennachrichten.",
+    }
+}
+
+/// ` — reason` after an amount, or nothing.
+fn reason_suffix(reason: &Option<String>) -> String {
+    reason
+        .as_ref()
+        .map(|r| format!(" — {r}"))
+        .unwrap_or_default()
+}
+
+pub fn plan_recorded(
+    lang: Lang,
+    due_on: &str,
+    amount: &str,
+    reason: &Option<String>,
+    total: &str,
+) -> String {
+    let reason = reason_suffix(reason);
+    match lang {
+        Lang::En => format!(
+            "📅 Planned entry for {due_on} is now recorded: {amount}{reason}\nTotal now: {total}"
+        ),
+        Lang::De => format!(
+            "📅 Geplanter Eintrag für {due_on} ist jetzt gebucht: {amount}{reason}\nGesamt jetzt: {total}"
+        ),
+    }
+}
+
+/// A /recurring run booked on `on`, with the date of the next one.
+pub fn recurring_recorded(
+    lang: Lang,
+    (id, on): (i64, &str),
+    amount: &str,
+    reason: &Option<String>,
+    total: &str,
+    next: &str,
+) -> String {
+    let reason = reason_suffix(reason);
+    match lang {
+        Lang::En => format!(
+            "🔁 Recurring #{id} for {on} is recorded: {amount}{reason}\nTotal now: {total}\nNext: {next}"
+        ),
+        Lang::De => format!(
+            "🔁 Dauerauftrag #{id} für {on} ist gebucht: {amount}{reason}\nGesamt jetzt: {total}\nNächster: {next}"
+        ),
+    }
+}
+
+pub fn reminder(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "⏰ Time to log your savings! Resisted any purchases? /save them.",
+        Lang::De => "⏰ Zeit, deine Ersparnisse einzutragen! Einem Kauf widerstanden? Trag ihn mit /save ein.",
+    }
+}
+
+pub fn reminder_footer(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "(Change this with /remind, or /remind off)",
+        Lang::De => "(Ändern mit /remind, abschalten mit /remind off)",
+    }
+}
+
+/// What a goal without a name is called in its check-ins.
+pub fn unnamed_goal(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "your goal",
+        Lang::De => "dein Ziel",
+    }
+}
+
+pub fn goal_checkin_reached(lang: Lang, name: &str) -> String {
+    match lang {
+        Lang::En => format!("🎉 Goal check-in: you've reached {name}!"),
+        Lang::De => format!("🎉 Ziel-Check-in: du hast {name} erreicht!"),
+    }
+}
+
+pub fn goal_checkin(lang: Lang, percent: i64, name: &str) -> String {
+    match lang {
+        Lang::En => format!("📅 Goal check-in: you're {percent}% toward {name}"),
+        Lang::De => format!("📅 Ziel-Check-in: du hast {percent}% von {name} erreicht"),
+    }
+}
+
+/// Continues [`goal_checkin`] when the goal has a deadline ahead.
+pub fn goal_checkin_weeks(lang: Lang, weeks: i64, remaining: &str, per_week: &str) -> String {
+    match lang {
+        Lang::En => format!(
+            ", {weeks} weeks left.\n{remaining} to go, {per_week}/week keeps you on track."
+        ),
+        Lang::De => format!(
+            ", noch {weeks} Wochen.\nEs fehlen {remaining}, mit {per_week} pro Woche bleibst du im Plan."
+        ),
+    }
+}
+
+/// Continues [`goal_checkin`] when the goal has no deadline ahead.
+pub fn goal_checkin_remaining(lang: Lang, remaining: &str) -> String {
+    match lang {
+        Lang::En => format!(", {remaining} to go."),
+        Lang::De => format!(", es fehlen {remaining}."),
+    }
+}
+
+/// The save button under a check-in, with the suggested amount if any.
+pub fn goal_save_button(lang: Lang, amount: Option<&str>) -> String {
+    match (lang, amount) {
+        (Lang::En, Some(amount)) => format!("💰 Save {amount}"),
+        (Lang::En, None) => "💰 Save now".into(),
+        (Lang::De, Some(amount)) => format!("💰 {amount} sparen"),
+        (Lang::De, None) => "💰 Jetzt sparen".into(),
+    }
+}
+
+pub fn goal_adjust_button(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "✏️ Adjust goal",
+        Lang::De => "✏️ Ziel anpassen",
+    }
+}
+
+pub fn goal_pause_button(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "⏸ Pause check-ins",
+        Lang::De => "⏸ Check-ins pausieren",
+    }
+}
+
+pub fn leaderboard_joined(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "You're on the /leaderboard of groups you save in, with your first name and what you saved this month. /leaderboard off takes you off.",
+        Lang::De => "Du stehst jetzt auf der Bestenliste (/leaderboard) der Gruppen, in denen du sparst, mit deinem Vornamen und dem, was du diesen Monat gespart hast. /leaderboard off nimmt dich wieder herunter.",
+    }
+}
+
+pub fn leaderboard_left(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "You're off every /leaderboard.",
+        Lang::De => "Du stehst auf keiner Bestenliste (/leaderboard) mehr.",
+    }
+}
+
+pub fn leaderboard_status(lang: Lang, on: bool) -> &'static str {
+    match (lang, on) {
+        (Lang::En, true) => "You're on group leaderboards. /leaderboard off takes you off.",
+        (Lang::En, false) => "You're not on group leaderboards. /leaderboard on to join them.",
+        (Lang::De, true) => {
+            "Du stehst auf den Bestenlisten deiner Gruppen. /leaderboard off nimmt dich herunter."
+        }
+        (Lang::De, false) => "Du stehst auf keiner Bestenliste. Mit /leaderboard on machst du mit.",
+    }
+}
+
+pub fn leaderboard_empty(lang: Lang, month: &str) -> String {
+    match lang {
+        Lang::En => format!(
+            "Nobody on the leaderboard has saved in {month} yet. Join with /leaderboard on."
+        ),
+        Lang::De => format!(
+            "Für {month} hat auf der Bestenliste noch niemand gespart. Mach mit /leaderboard on mit."
+        ),
+    }
+}
+
+pub fn leaderboard_header(lang: Lang, month: &str) -> String {
+    match lang {
+        Lang::En => format!("🏆 Top savers, {month}"),
+        Lang::De => format!("🏆 Die besten Sparer, {month}"),
+    }
+}
+
+pub fn leaderboard_footer(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "Only members who opted in with /leaderboard on are shown.",
+        Lang::De => "Angezeigt werden nur Mitglieder, die mit /leaderboard on zugestimmt haben.",
+    }
+}
+
+pub fn leaderboard_usage(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "Usage: /leaderboard [on|off]",
+        Lang::De => "Verwendung: /leaderboard [on|off]",
+    }
+}
+
+pub fn split_header(
+    lang: Lang,
+    payer: &str,
+    amount: &str,
+    reason: Option<&str>,
+    ways: usize,
+) -> String {
+    match lang {
+        Lang::En => {
+            let reason = reason.map(|r| format!(" for {r}")).unwrap_or_default();
+            format!("🧾 {payer} paid {amount}{reason} — split {ways} ways:")
+        }
+        Lang::De => {
+            let reason = reason.map(|r| format!(" für {r}")).unwrap_or_default();
+            format!("🧾 {payer} hat {amount}{reason} bezahlt — aufgeteilt auf {ways}:")
+        }
+    }
+}
+
+pub fn split_owes(lang: Lang, name: &str, share: &str, payer: &str) -> String {
+    match lang {
+        Lang::En => format!("• {name}: {share} — owes {payer} {share}"),
+        Lang::De => format!("• {name}: {share} — schuldet {payer} {share}"),
+    }
+}
+
+pub fn split_rounding(lang: Lang, rule: Rounding) -> String {
+    match lang {
+        Lang::En => format!("Rounding: {} (/splitrounding).", rule.describe()),
+        Lang::De => {
+            let rule = match rule {
+                Rounding::LargestRemainder => {
+                    "übrige Cents gehen an die am stärksten abgerundeten Anteile"
+                }
+                Rounding::PayerAbsorbs => "übrige Cents gehen an den, der bezahlt hat",
+                Rounding::RoundRobin => {
+                    "übrige Cents gehen reihum an alle, über mehrere Aufteilungen hinweg"
+                }
+            };
+            format!("Rundung: {rule} (/splitrounding).")
+        }
+    }
+}
+
+pub fn split_booked(lang: Lang) -> &'static str {
+    match lang {
+        Lang::En => "Each share is booked as an adjustment on its owner's ledger.",
+        Lang::De => "Jeder Anteil wird als Korrektur im Konto seines Besitzers gebucht.",
+    }
+}
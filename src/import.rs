@@ -0,0 +1,121 @@
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::db::NewEntry;
+use crate::kind::EntryKind;
+use crate::{decimal_to_cents, parse_date};
+
+/// Kinds an imported row may keep; anything else is filed by its sign.
+const KINDS: [EntryKind; 5] = [
+    EntryKind::Save,
+    EntryKind::Adjust,
+    EntryKind::Withdraw,
+    EntryKind::Interest,
+    EntryKind::Dividend,
+];
+
+/// Turns CSV records into entries. The first record is a header when it
+/// names an `amount` column (so /export files round-trip); otherwise the
+/// columns are `date,amount,reason`. Returns one message per bad row; rows
+/// /export marks with the batch they were invested in are refused, as only
+/// uninvested entries can be added back.
+pub fn entries(records: &[Vec<String>]) -> Result<Vec<NewEntry>, Vec<String>> {
+    let (columns, rows, first_line) = match records.first() {
+        Some(header)
+            if header
+                .iter()
+                .any(|h| h.trim().eq_ignore_ascii_case("amount")) =>
+        {
+            let find = |name: &str| {
+                header
+                    .iter()
+                    .position(|h| h.trim().eq_ignore_ascii_case(name))
+            };
+            let columns = Columns {
+                date: find("date"),
+                amount: find("amount"),
+                reason: find("reason"),
+                kind: find("kind"),
+                batch: find("batch"),
+            };
+            (columns, &records[1..], 2)
+        }
+        _ => (
+            Columns {
+                date: Some(0),
+                amount: Some(1),
+                reason: Some(2),
+                kind: None,
+                batch: None,
+            },
+            records,
+            1,
+        ),
+    };
+    if columns.date.is_none() || columns.amount.is_none() {
+        return Err(vec!["The header needs date and amount columns.".into()]);
+    }
+
+    let today = OffsetDateTime::now_utc().date();
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        let line = first_line + i;
+        let cell = |col: Option<usize>| {
+            col.and_then(|c| row.get(c))
+                .map(|v| v.trim())
+                .unwrap_or_default()
+        };
+        // Accept plain dates and the RFC 3339 timestamps /export writes.
+        let date = cell(columns.date);
+        let Some(day) = parse_date(date.get(..10).unwrap_or(date)).filter(|d| *d <= today) else {
+            errors.push(format!("line {line}: bad or future date {date:?}"));
+            continue;
+        };
+        let amount = cell(columns.amount);
+        let amount_cents = match decimal_to_cents(amount) {
+            Ok(0) | Err(_) => {
+                errors.push(format!("line {line}: bad amount {amount:?}"));
+                continue;
+            }
+            Ok(cents) => cents,
+        };
+        let batch = cell(columns.batch);
+        if !batch.is_empty() {
+            errors.push(format!(
+                "line {line}: already invested in the batch of {batch}; only uninvested entries can be imported, so drop the row or clear its batch"
+            ));
+            continue;
+        }
+        let kind = EntryKind::parse(cell(columns.kind))
+            .filter(|k| KINDS.contains(k))
+            .unwrap_or_else(|| EntryKind::by_sign(amount_cents));
+        let reason = Some(cell(columns.reason).to_string()).filter(|r| !r.is_empty());
+        let occurred_at = if date.len() == 10 {
+            format!("{day}T00:00:00Z")
+        } else if OffsetDateTime::parse(date, &Rfc3339).is_ok() {
+            date.to_string()
+        } else {
+            errors.push(format!("line {line}: bad date {date:?}"));
+            continue;
+        };
+        entries.push(NewEntry {
+            amount_cents,
+            kind,
+            reason,
+            occurred_at,
+        });
+    }
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(errors)
+    }
+}
+
+struct Columns {
+    date: Option<usize>,
+    amount: Option<usize>,
+    reason: Option<usize>,
+    kind: Option<usize>,
+    batch: Option<usize>,
+}
@@ -0,0 +1,95 @@
+//! What an entry is: the `kind` column of entries and entries_history.
+//! Reading or writing anything else fails at the sqlx boundary, so a typo
+//! can't quietly drop rows out of reports that filter on kind.
+
+use std::fmt;
+
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::sqlite::{Sqlite, SqliteArgumentValue, SqliteTypeInfo, SqliteValueRef};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    /// /save and positive standing orders.
+    Save,
+    /// /adjust, /reverse and negative standing orders, either sign.
+    Adjust,
+    /// /withdraw, always negative.
+    Withdraw,
+    /// /openingbalance, booked against Equity.
+    Opening,
+    /// Accrued by /interest.
+    Interest,
+    /// Payouts from what was invested, e.g. from a CSV import.
+    Dividend,
+}
+
+impl EntryKind {
+    pub const ALL: [EntryKind; 6] = [
+        EntryKind::Save,
+        EntryKind::Adjust,
+        EntryKind::Withdraw,
+        EntryKind::Opening,
+        EntryKind::Interest,
+        EntryKind::Dividend,
+    ];
+
+    /// Case and surrounding spaces are ignored, as typed in /purge or a CSV.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        Self::ALL
+            .into_iter()
+            .find(|k| k.as_str().eq_ignore_ascii_case(s))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::Save => "save",
+            EntryKind::Adjust => "adjust",
+            EntryKind::Withdraw => "withdraw",
+            EntryKind::Opening => "opening",
+            EntryKind::Interest => "interest",
+            EntryKind::Dividend => "dividend",
+        }
+    }
+
+    /// Save for money in, Adjust otherwise: what an entry of no particular
+    /// kind is booked as.
+    pub fn by_sign(amount_cents: i64) -> Self {
+        if amount_cents > 0 {
+            EntryKind::Save
+        } else {
+            EntryKind::Adjust
+        }
+    }
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl sqlx::Type<Sqlite> for EntryKind {
+    fn type_info() -> SqliteTypeInfo {
+        <&str as sqlx::Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <&str as sqlx::Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, Sqlite> for EntryKind {
+    fn encode_by_ref(&self, buf: &mut Vec<SqliteArgumentValue<'q>>) -> Result<IsNull, BoxDynError> {
+        <&str as sqlx::Encode<'q, Sqlite>>::encode(self.as_str(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Sqlite> for EntryKind {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        let s = <&str as sqlx::Decode<'r, Sqlite>>::decode(value)?;
+        EntryKind::parse(s).ok_or_else(|| format!("unknown entry kind {s:?}").into())
+    }
+}
@@ -0,0 +1,85 @@
+//! One bot per database. Two replicas polling the same bot token and
+//! database would both answer every update and both run the scheduler, so
+//! each instance takes a lease row at startup and renews it while it runs.
+//! A second instance refuses to start until the lease is released or has
+//! gone unrenewed for a while, e.g. after a crash.
+
+use anyhow::{bail, Result};
+use std::time::Duration;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::db::Db;
+
+/// How often the holder renews its lease.
+const HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// How long an unrenewed lease still blocks a new instance.
+const STALE_SECS: i64 = 60;
+
+/// This process's lease on the database.
+pub struct Lease {
+    db: Db,
+    holder: String,
+}
+
+impl Lease {
+    /// Takes the lease, or fails naming the instance that has it.
+    pub async fn acquire(db: &Db) -> Result<Self> {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into());
+        let holder = format!(
+            "{host}/{}/{}",
+            std::process::id(),
+            &Uuid::new_v4().simple().to_string()[..8]
+        );
+        let now = OffsetDateTime::now_utc();
+        let stale_before = (now - time::Duration::seconds(STALE_SECS)).format(&Rfc3339)?;
+        if !db
+            .acquire_lease(&holder, &now.format(&Rfc3339)?, &stale_before)
+            .await?
+        {
+            let (other, seen) = db.lease_holder().await?.unwrap_or_default();
+            bail!(
+                "another instance ({other}, last seen {seen}) is running against this database. \
+                 Stop it first; a crashed one's lease lapses after {STALE_SECS} seconds."
+            );
+        }
+        Ok(Self {
+            db: db.clone(),
+            holder,
+        })
+    }
+
+    /// Renews the lease in the background. Should it ever be lost, e.g.
+    /// after the process was paused long enough for another instance to
+    /// take over, this one exits rather than answer alongside it.
+    pub fn keep_alive(&self) {
+        let db = self.db.clone();
+        let holder = self.holder.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(HEARTBEAT);
+            loop {
+                tick.tick().await;
+                let renewed = match OffsetDateTime::now_utc().format(&Rfc3339) {
+                    Ok(now) => db.renew_lease(&holder, &now).await,
+                    Err(err) => Err(err.into()),
+                };
+                match renewed {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        eprintln!("lease: another instance took over the database; exiting");
+                        std::process::exit(1);
+                    }
+                    // A busy database; the next tick tries again well
+                    // before the lease goes stale.
+                    Err(err) => eprintln!("lease: renewing: {err:?}"),
+                }
+            }
+        });
+    }
+
+    /// Lets the next instance start right away.
+    pub async fn release(self) -> Result<()> {
+        self.db.release_lease(&self.holder).await
+    }
+}
@@ -0,0 +1,132 @@
+//! /loan, /repaid and /loans: money lent to people, kept per counterparty
+//! in the loans table rather than as entries, so what others owe you never
+//! shows up in the stash, reports or /allinvoo.
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::currency::Currency;
+use crate::db::Db;
+
+/// `/loan 500 to Alice [reason]` when `repaid` is false, `/repaid 100
+/// from Alice [reason]` otherwise; the reply to send either way.
+pub async fn record(
+    db: &Db,
+    uuid: Uuid,
+    currency: Currency,
+    args: &str,
+    (comma, repaid): (bool, bool),
+) -> Result<String> {
+    let usage = if repaid {
+        "Usage: /repaid {amount} from {name} [reason], e.g. /repaid 100 from Alice"
+    } else {
+        "Usage: /loan {amount} to {name} [reason], e.g. /loan 500 to Alice"
+    };
+    let Some((amount_cents, name, reason)) = parse(args, comma) else {
+        return Ok(usage.into());
+    };
+    // Spelled as the first time, so `alice` and `Alice` read as one person.
+    let existing = db
+        .loans(uuid)
+        .await?
+        .into_iter()
+        .find(|l| l.counterparty.eq_ignore_ascii_case(name));
+    let owed = existing.as_ref().map_or(0, |l| l.outstanding_cents);
+    let name = existing.as_ref().map_or(name, |l| l.counterparty.as_str());
+
+    if !repaid {
+        db.add_loan(uuid, name, amount_cents, reason).await?;
+        return Ok(format!(
+            "🤝 Lent {} to {name}. {name} owes you {} in all.",
+            currency.format(amount_cents),
+            currency.format(owed + amount_cents)
+        ));
+    }
+    if owed <= 0 {
+        return Ok(format!(
+            "{name} doesn't owe you anything. /loans lists who does."
+        ));
+    }
+    if amount_cents > owed {
+        return Ok(format!(
+            "{name} only owes you {}; nothing was recorded.",
+            currency.format(owed)
+        ));
+    }
+    db.add_loan(uuid, name, -amount_cents, reason).await?;
+    Ok(if amount_cents == owed {
+        format!(
+            "✅ {name} paid back {} and is all square.",
+            currency.format(amount_cents)
+        )
+    } else {
+        format!(
+            "💸 {name} paid back {}; {} still owed.",
+            currency.format(amount_cents),
+            currency.format(owed - amount_cents)
+        )
+    })
+}
+
+/// /loans: what each counterparty still owes, and the total.
+pub async fn report(db: &Db, uuid: Uuid, currency: Currency) -> Result<String> {
+    let loans = db.loans(uuid).await?;
+    if loans.is_empty() {
+        return Ok("Nobody owes you anything. /loan 50 to Alice records money you lent.".into());
+    }
+    let mut lines = vec!["🤝 Owed to you:".to_string()];
+    for loan in &loans {
+        lines.push(format!(
+            "  {}: {} (last on {})",
+            loan.counterparty,
+            currency.format(loan.outstanding_cents),
+            loan.last_at.get(..10).unwrap_or(&loan.last_at)
+        ));
+    }
+    let total: i64 = loans.iter().map(|l| l.outstanding_cents).sum();
+    lines.push(format!("Total: {}", currency.format(total)));
+    lines.push("This isn't counted in your stash.".into());
+    Ok(lines.join("\n"))
+}
+
+/// The amount, the counterparty and an optional reason. The "to" or "from"
+/// before the name may be left out, and a leading `@` on it is dropped.
+fn parse(args: &str, comma: bool) -> Option<(i64, &str, Option<&str>)> {
+    let (amount_end, rest) = split_amount(args);
+    let (amount_cents, _) = crate::parse_amount_and_reason(amount_end, false, comma).ok()?;
+    if amount_cents <= 0 {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let rest = match rest.split_once(char::is_whitespace) {
+        Some((first, after)) if is_preposition(first) => after.trim_start(),
+        None if is_preposition(rest) => "",
+        _ => rest,
+    };
+    let (name, reason) = rest
+        .split_once(char::is_whitespace)
+        .map_or((rest, ""), |(n, r)| (n, r.trim()));
+    let name = name.trim_start_matches('@');
+    if name.is_empty() {
+        return None;
+    }
+    Some((amount_cents, name, (!reason.is_empty()).then_some(reason)))
+}
+
+/// Splits off the amount so the name isn't read as part of it, the way
+/// "five dollars" or `3*4.50` would be: everything before `to`/`from`, or
+/// the first word when neither is there.
+fn split_amount(args: &str) -> (&str, &str) {
+    let args = args.trim();
+    for (i, _) in args.match_indices(' ') {
+        let next = args[i + 1..].split_whitespace().next().unwrap_or_default();
+        if is_preposition(next) {
+            return (&args[..i], &args[i..]);
+        }
+    }
+    args.split_once(char::is_whitespace).unwrap_or((args, ""))
+}
+
+fn is_preposition(word: &str) -> bool {
+    word.eq_ignore_ascii_case("to") || word.eq_ignore_ascii_case("from")
+}
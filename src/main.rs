@@ -1,29 +1,228 @@
 use anyhow::{anyhow, Context, Result};
 use dotenvy::dotenv;
+use futures_util::StreamExt;
 use regex::Regex;
 use std::env;
-use teloxide::{prelude::*, utils::command::BotCommands};
+use teloxide::{
+    net::Download,
+    prelude::*,
+    types::{
+        ChosenInlineResult, InlineKeyboardButton, InlineKeyboardMarkup, InlineQuery,
+        InlineQueryResult, InlineQueryResultArticle, InputFile, InputMessageContent,
+        InputMessageContentText, LabeledPrice, PhotoSize, SuccessfulPayment, Voice,
+    },
+    utils::command::BotCommands,
+};
+use time::{macros::format_description, Date, OffsetDateTime};
 
+mod allocation;
+mod api;
+mod backup;
+mod budgets;
+mod calc;
+mod categorize;
+mod chart;
+mod close;
+mod csv;
+mod currency;
 mod db;
+mod events;
+mod fees;
+mod finance;
+mod flags;
+mod fx;
+mod gains;
+mod goals;
+mod hedged;
+mod i18n;
+mod import;
+mod kind;
+mod lease;
+mod loans;
+mod market;
+mod mqtt;
+mod networth;
+mod nlp;
+mod ocr;
+mod outbox;
+mod quota;
+mod recurring;
+mod reminders;
+mod render;
+mod rounding;
+mod s3;
+mod scheduler;
+mod settings;
+mod speech;
+mod stats;
+mod streaks;
+mod tags;
+mod telemetry;
+use currency::Currency;
 use db::Db;
+use i18n::Lang;
+use kind::EntryKind;
+use render::Report;
 
 #[derive(BotCommands, Clone)]
 #[command(
     rename_rule = "lowercase",
     description = "Commands:\n\
     /start - register or show your UUID\n\
-    /save {amount} [reason] - save money with optional reason\n\
-    /adjust {+/-amount} [reason] - adjust balance with optional reason\n\
-    /allinvoo - invest current stash and reset current to 0 (moves to history)\n\
-    /query [n] - list your last n entries (default 10)\n\
+    /qr - your UUID as a QR code, for linking another device\n\
+    /save {amount} [CODE] [YYYY-MM-DD] [reason] - save money, optionally in another currency or backdated\n\
+    /adjust {+/-amount} [YYYY-MM-DD] [reason] - adjust balance, optionally backdated\n\
+    /withdraw {amount} [reason] - take money out of the stash for real-world spending\n\
+    /openingbalance {amount} [YYYY-MM-DD] - start from your real savings as of a date\n\
+    /plan [{+/-amount} {YYYY-MM-DD} [reason] | cancel {id}] - schedule a future entry, or list plans\n\
+    /recurring [add {+/-amount} daily|weekly|biweekly|monthly [YYYY-MM-DD] [reason] | cancel {id}] - standing orders, or list them\n\
+    /forecast - current total plus upcoming planned entries\n\
+    /interest [rate%|off] - simulate monthly interest on your uninvested stash\n\
+    /fee {amount} [YYYY-MM-DD] [reason] - record a fee paid when investing\n\
+    /dividend [{amount} [YYYY-MM-DD] [reason] | auto on|off] - record a dividend on invested money\n\
+    /expenseratio [percent|off] - your fund's yearly expense ratio, e.g. 0.03%\n\
+    /fees [YYYY] - fees per year, recorded plus expense ratio\n\
+    /goal [set {amount} [YYYY-MM-DD] [name] | status | clear] - savings goal with optional deadline\n\
+    /goal checkin [daily HH:MM | weekly {day} HH:MM | off] - periodic goal check-ins\n\
+    /budget [{#category} {amount} [rollover] | {#category} off | {amount} | off] - monthly spending envelopes, or a monthly saving target\n\
+    /autocategory [suggest|apply|off] - suggest #categories for new entries from similar past ones\n\
+    /stats [week|month|year] - totals, daily average, largest entry and change for this period\n\
+    /stats tags [week|month|year|all] - totals per #tag\n\
+    /compareperiods {period} {period} - saved/spent/invested side by side, e.g. 2024-05 2024-06\n\
+    /chart [week|month|year|all] - cumulative savings over time\n\
+    /chart networth|heatmap|categories [YYYY-MM] - net worth, daily activity, or spending by #category\n\
+    /ticker [set TICKER [PERCENT ...] | reset] - what /allinvoo buys (default VOO), e.g. /ticker set VTI 60 VXUS 40\n\
+    /target [TICKER PERCENT ...] - set or show target allocation, e.g. /target VOO 80 VXUS 20\n\
+    /rebalance - how to split your current stash to move toward your targets\n\
+    /close [YYYY-MM] - month-end checklist ending in an archived statement (default: last month)\n\
+    /allinvoo [preview] [amount] [strategy] - invest the current stash, or just amount of it (moves to history)\n\
+    /strategy [add {name} | remove {name}] - separate invest buckets, e.g. retirement and house\n\
+    /portfolio [strategy|fx] - invested totals, shares per ticker and unrealized gain, or one strategy's invest history\n\
+    /history - every /allinvoo with its amount and the running invested total\n\
+    /historylog [n] - your last n invested entries (default 20), grouped by /allinvoo batch\n\
+    /query [n] [#tag] - your entries in pages of n (default 10), optionally only one tag\n\
+    /balance - current, history and grand total without listing entries\n\
+    /networth [fx] - cash plus invested shares at the last close, and the change over a week\n\
+    /gains - cost, value, unrealized gain and annualized return of your invested batches\n\
+    /show {id} - everything about one entry\n\
+    /search {phrase} - find entries whose reason contains a phrase\n\
+    /watchticker [TICKER] - get a weekly price summary for a ticker, or list watched ones\n\
+    /unwatchticker {TICKER} - stop watching a ticker\n\
+    /export [settings|taxes {year}] - your whole ledger as a CSV file, your settings as JSON, or a year's realized lots\n\
+    /import [settings] - send with (or reply to) a CSV of date,amount,reason to bulk-add entries, or a settings file to restore it\n\
+    /edit {id} {amount} [reason] - fix an entry's amount or reason\n\
+    /delete {id} - remove one entry (ids are listed by /query)\n\
+    /undo - remove your most recent entry\n\
+    /unvoo - put your last /allinvoo back into current, within 24 hours\n\
+    /reverse {id} - book an entry that cancels out another one\n\
+    /immutable [on|off] - lock entries 24 hours after they are recorded\n\
+    /purge {filters} - bulk-delete entries, e.g. reason:test before:2023-01-01\n\
+    /50_30_20 [YYYY-MM] - compare this month's needs/wants/savings to the 50/30/20 rule\n\
+    /webhook [set {https url}|test|off] - POST your ledger events somewhere\n\
+    /timezone [±HH:MM] - your UTC offset, for streaks and reminders\n\
+    /currency [CODE|off] - show amounts as e.g. $12.34 or 12,34 €\n\
+    /decimal [comma|point] - read typed amounts as 1.234,56 or 1234.56\n\
+    /remind [daily HH:MM | weekly {day} HH:MM | off] - a ping to log your savings\n\
+    /nudges [on|off] - reminders when you go quiet or hold lots of cash\n\
+    /nudge @user - in groups: privately remind a member to log, at most once a day per member\n\
+    /leaderboard [on|off] - in groups: this month's top savers among members who opted in\n\
+    /chatlanguage {code|off} - group admins: language for group messages\n\
+    /disablecmd [command] - group admins: turn a command off in this chat, or list disabled ones\n\
+    /enablecmd {command} - group admins: turn a disabled command back on\n\
+    /channelowner [off] - group admins: record the linked channel's posts as yours\n\
+    /anonymous [pot|off] - group admins: shared pot for anonymous admins and channel posts\n\
+    /transfer 25 @user [reason] - move money from your stash to someone else's, once they accept\n\
+    /loan {amount} to {name} [reason] - record money you lent someone, kept apart from your stash\n\
+    /repaid {amount} from {name} [reason] - record a loan being paid back\n\
+    /loans - who owes you what\n\
+    /split 60 [reason] @user... - in groups: split what you paid evenly, each share booked on its owner's ledger\n\
+    /splitrounding [largest|payer|roundrobin] - in groups: who gets leftover cents when splitting\n\
+    /shared [create {name}|join {code}|newcode|leave] - pool your saves with others in one ledger\n\
+    /token [read|write|admin] [days] - REST API token (DM only); /token revoke {id}\n\
+    /globalstats - operators: users, activity, database size and scheduler health\n\
+    /flag [{flag} {percent} | {flag} on|off|reset {user id|@username}] - operators: feature rollouts\n\
+    /tokens - list your API tokens\n\
+    /attachments [forget {id}|cleanup {months}] - receipts and voice notes kept with your entries\n\
+    /publicpage [on|new|off] - a shareable page with just your streak and % to goal\n\
+    /donate [amount] - support whoever runs this bot\n\
     /help - this help"
 )]
 enum Command {
     Start,
+    Qr,
     Save(String),
     Adjust(String),
-    Allinvoo,
+    Withdraw(String),
+    Openingbalance(String),
+    Plan(String),
+    Recurring(String),
+    Forecast,
+    Interest(String),
+    Fee(String),
+    Dividend(String),
+    Fees(String),
+    Expenseratio(String),
+    Autocategory(String),
+    Goal(String),
+    Budget(String),
+    Chart(String),
+    Stats(String),
+    Compareperiods(String),
+    Ticker(String),
+    Target(String),
+    Rebalance,
+    Close(String),
+    Allinvoo(String),
+    Strategy(String),
+    Portfolio(String),
+    History,
+    Historylog(String),
     Query(String),
+    Balance,
+    Networth(String),
+    Gains,
+    Show(String),
+    Search(String),
+    Watchticker(String),
+    Unwatchticker(String),
+    Export(String),
+    Import(String),
+    Edit(String),
+    Delete(String),
+    Purge(String),
+    Undo,
+    Unvoo,
+    Reverse(String),
+    Immutable(String),
+    #[command(rename = "50_30_20")]
+    FiftyThirtyTwenty(String),
+    Webhook(String),
+    Timezone(String),
+    Currency(String),
+    Decimal(String),
+    Remind(String),
+    Nudges(String),
+    Nudge(String),
+    Leaderboard(String),
+    Chatlanguage(String),
+    Disablecmd(String),
+    Enablecmd(String),
+    Channelowner(String),
+    Anonymous(String),
+    Shared(String),
+    Transfer(String),
+    Loan(String),
+    Repaid(String),
+    Loans,
+    Split(String),
+    Splitrounding(String),
+    Token(String),
+    Globalstats,
+    Flag(String),
+    Tokens,
+    Publicpage(String),
+    Attachments(String),
+    Donate(String),
     Help,
 }
 
@@ -31,210 +230,5159 @@ enum Command {
 async fn main() -> Result<()> {
     dotenv().ok();
     pretty_env_logger::init();
+    let tracer = telemetry::init()?;
 
     let bot_token = env::var("BOT_TOKEN").context("BOT_TOKEN env var is required")?;
     let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:./data/bot.db".into());
 
-    let bot = Bot::new(bot_token);
+    let mut bot = Bot::new(bot_token);
+    // A self-hosted Bot API server lifts the 50 MB upload limit.
+    if let Some(url) = env::var("BOT_API_URL").ok().filter(|u| !u.is_empty()) {
+        bot = bot.set_api_url(url.parse().context("BOT_API_URL is not a URL")?);
+    }
     let me = bot.get_me().send().await?;
     let bot_name = me.user.username.as_deref().unwrap_or("").to_string();
 
     let db = Db::new(&database_url).await?;
-
-    teloxide::repl(bot, move |bot: Bot, msg: Message| {
+    let lease = lease::Lease::acquire(&db).await?;
+    lease.keep_alive();
+    scheduler::spawn(bot.clone(), db.clone());
+    mqtt::init()?;
+    if let Some(addr) = env::var("API_LISTEN").ok().filter(|a| !a.is_empty()) {
+        let addr = addr
+            .parse()
+            .context("API_LISTEN must look like 0.0.0.0:8080")?;
         let db = db.clone();
-        let bot_name = bot_name.clone();
-        async move {
-            if let Some(text) = msg.text() {
-                if let Ok(cmd) = Command::parse(text, &bot_name) {
-                    if let Err(err) = handle_command(bot.clone(), &db, &msg, cmd).await {
-                        eprintln!("handle_command error: {err:?}");
-                    }
-                } else {
-                    // Inline completion hints for /save and /adjust when typing
-                    if text.starts_with("/save ") || text.starts_with("/adjust ") {
-                        let hint = "Format: /save 12.34 [reason] or /adjust -5.50 [reason]";
-                        if let Err(err) = bot
-                            .send_message(msg.chat.id, hint)
-                            .reply_to_message_id(msg.id)
-                            .send()
-                            .await
-                        {
-                            eprintln!("hint send error: {err:?}");
-                        }
-                    }
+        tokio::spawn(async move {
+            if let Err(err) = api::serve(addr, db).await {
+                eprintln!("api server error: {err:?}");
+            }
+        });
+    }
+
+    let handler = dptree::entry()
+        .branch(Update::filter_message().endpoint(on_message))
+        .branch(Update::filter_callback_query().endpoint(on_callback))
+        .branch(Update::filter_inline_query().endpoint(on_inline_query))
+        .branch(Update::filter_chosen_inline_result().endpoint(on_chosen_inline_result))
+        .branch(Update::filter_pre_checkout_query().endpoint(on_pre_checkout));
+    Dispatcher::builder(bot, handler)
+        .dependencies(dptree::deps![db, bot_name])
+        .enable_ctrlc_handler()
+        .build()
+        .dispatch()
+        .await;
+
+    if let Err(err) = lease.release().await {
+        eprintln!("releasing the instance lease: {err:?}");
+    }
+    if let Some(tracer) = tracer {
+        tracer.shutdown()?;
+    }
+    Ok(())
+}
+
+async fn on_message(bot: Bot, msg: Message, db: Db, bot_name: String) -> ResponseResult<()> {
+    // Commands also work as photo captions, e.g. a receipt sent with "/save".
+    if let Some(text) = msg.text().or(msg.caption()) {
+        if let Ok(cmd) = Command::parse(text, &bot_name) {
+            if let Err(err) = handle_command(bot.clone(), &db, &msg, cmd).await {
+                eprintln!("handle_command error: {err:?}");
+            }
+        } else {
+            // Inline completion hints for /save and /adjust when typing
+            if text.starts_with("/save ")
+                || text.starts_with("/adjust ")
+                || text.starts_with("/withdraw ")
+            {
+                let hint = "Format: /save 12.34 [reason] or /adjust -5.50 [reason]";
+                if let Err(err) = bot
+                    .send_message(msg.chat.id, hint)
+                    .reply_to_message_id(msg.id)
+                    .send()
+                    .await
+                {
+                    eprintln!("hint send error: {err:?}");
+                }
+            }
+        }
+    } else if let (Some(voice), Some(from)) = (msg.voice(), msg.from()) {
+        // In groups a voice note only counts when replied to with /save.
+        if msg.chat.is_private() {
+            let res = async {
+                let uuid = ensure_sender(&db, from).await?;
+                propose_from_voice(&bot, &db, msg.chat.id, uuid, voice).await
+            };
+            if let Err(err) = res.await {
+                eprintln!("voice error: {err:?}");
+            }
+        }
+    } else if let (Some(payment), Some(from)) = (msg.successful_payment(), msg.from()) {
+        if let Err(err) = record_donation(&bot, &msg, from, payment).await {
+            eprintln!("donation error: {err:?}");
+        }
+    }
+    respond(())
+}
+
+async fn on_inline_query(bot: Bot, q: InlineQuery, db: Db) -> ResponseResult<()> {
+    if let Err(err) = handle_inline_query(&bot, &db, &q).await {
+        eprintln!("handle_inline_query error: {err:?}");
+    }
+    respond(())
+}
+
+async fn on_chosen_inline_result(r: ChosenInlineResult, db: Db) -> ResponseResult<()> {
+    if let Err(err) = handle_chosen_inline_result(&db, &r).await {
+        eprintln!("handle_chosen_inline_result error: {err:?}");
+    }
+    respond(())
+}
+
+/// How many past reasons an inline query suggests.
+const INLINE_SUGGESTIONS: i64 = 8;
+
+/// Parses an inline query such as `save 12 cof` into the amount and the
+/// reasons to offer: what was typed, then matching past reasons. Result
+/// ids are indexes into this list, so the chosen-result handler rebuilds
+/// it from the same query.
+async fn inline_choices(
+    db: &Db,
+    (uuid, from): (uuid::Uuid, &teloxide::types::User),
+    query: &str,
+) -> Result<Option<(i64, Vec<Option<String>>)>> {
+    let query = query.trim();
+    let query = query.strip_prefix("save").unwrap_or(query);
+    let comma = reads_decimal_comma(db, uuid, Some(from)).await?;
+    let Ok((amount_cents, typed)) = parse_amount_and_reason(query, false, comma) else {
+        return Ok(None);
+    };
+    // Over quota there is nothing to offer, so nothing can be picked either.
+    if amount_cents <= 0 || quota::exceeded(db, uuid, 1).await?.is_some() {
+        return Ok(None);
+    }
+    let typed = typed.unwrap_or_default();
+    let mut choices = vec![Some(typed.clone()).filter(|r| !r.is_empty())];
+    for reason in db
+        .reason_suggestions(uuid, &typed, INLINE_SUGGESTIONS)
+        .await?
+    {
+        if !reason.eq_ignore_ascii_case(&typed) {
+            choices.push(Some(reason));
+        }
+    }
+    Ok(Some((amount_cents, choices)))
+}
+
+/// `@bot save 12 cof` offers "12.00 — coffee" and friends. Nothing is
+/// recorded until one is picked (see [`handle_chosen_inline_result`]).
+async fn handle_inline_query(bot: &Bot, db: &Db, q: &InlineQuery) -> Result<()> {
+    let uuid = ensure_sender(db, &q.from).await?;
+    let results: Vec<InlineQueryResult> =
+        match inline_choices(db, (uuid, &q.from), &q.query).await? {
+            Some((amount_cents, choices)) => choices
+                .iter()
+                .enumerate()
+                .map(|(i, reason)| {
+                    let text = format!(
+                        "🐷 Saved {}{}",
+                        format_cents(amount_cents),
+                        reason
+                            .as_ref()
+                            .map(|r| format!(" — {r}"))
+                            .unwrap_or_default()
+                    );
+                    let article = InlineQueryResultArticle::new(
+                        i.to_string(),
+                        format!("Save {}", format_cents(amount_cents)),
+                        InputMessageContent::Text(InputMessageContentText::new(text)),
+                    )
+                    .description(reason.clone().unwrap_or_else(|| "no reason".into()));
+                    InlineQueryResult::Article(article)
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+    bot.answer_inline_query(&q.id, results)
+        .cache_time(0)
+        .is_personal(true)
+        .await?;
+    Ok(())
+}
+
+/// Records the save the user picked from an inline query. Telegram only
+/// reports choices when inline feedback is turned on in @BotFather.
+async fn handle_chosen_inline_result(db: &Db, r: &ChosenInlineResult) -> Result<()> {
+    let uuid = ensure_sender(db, &r.from).await?;
+    let Some((amount_cents, choices)) = inline_choices(db, (uuid, &r.from), &r.query).await? else {
+        return Ok(());
+    };
+    let Some(reason) = r
+        .result_id
+        .parse::<usize>()
+        .ok()
+        .and_then(|i| choices.get(i).cloned())
+    else {
+        return Ok(());
+    };
+    let (reason, class) = split_class(reason);
+    db.add_entry(
+        uuid,
+        amount_cents,
+        EntryKind::Save,
+        reason.clone(),
+        class,
+        None,
+    )
+    .await?;
+    events::entry_added(db, uuid, amount_cents, EntryKind::Save, reason.as_deref()).await;
+    Ok(())
+}
+
+/// Telegram asks before charging; only our own donation invoices are okayed.
+async fn on_pre_checkout(bot: Bot, q: PreCheckoutQuery) -> ResponseResult<()> {
+    let ok = q.invoice_payload.starts_with("donation:");
+    bot.answer_pre_checkout_query(q.id, ok).await?;
+    respond(())
+}
+
+async fn record_donation(
+    bot: &Bot,
+    msg: &Message,
+    from: &teloxide::types::User,
+    payment: &SuccessfulPayment,
+) -> Result<()> {
+    let amount = format!(
+        "{} {:?}",
+        format_cents(payment.total_amount as i64),
+        payment.currency
+    );
+    bot.send_message(msg.chat.id, format!("💚 Thank you for the {amount}!"))
+        .await?;
+    if let Some(admin) = env::var("ADMIN_CHAT_ID")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(ChatId)
+    {
+        bot.send_message(
+            admin,
+            format!(
+                "💸 Donation of {amount} from {} (charge {})",
+                display_name(from),
+                payment.telegram_payment_charge_id
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn on_callback(bot: Bot, q: CallbackQuery, db: Db) -> ResponseResult<()> {
+    if let Err(err) = handle_callback(&bot, &db, &q).await {
+        eprintln!("handle_callback error: {err:?}");
+    }
+    bot.answer_callback_query(q.id).await?;
+    respond(())
+}
+
+/// Callback data is `pending:{id}:{ok|edit|no}`, from [`confirm_keyboard`].
+async fn handle_callback(bot: &Bot, db: &Db, q: &CallbackQuery) -> Result<()> {
+    if let Some(purge) = q.data.as_deref().and_then(|d| d.strip_prefix("purge:")) {
+        return handle_purge_callback(bot, db, q, purge).await;
+    }
+    if let Some(import) = q.data.as_deref().and_then(|d| d.strip_prefix("import:")) {
+        return handle_import_callback(bot, db, q, import).await;
+    }
+    if let Some(invest) = q.data.as_deref().and_then(|d| d.strip_prefix("invest:")) {
+        return handle_invest_callback(bot, db, q, invest).await;
+    }
+    if let Some(close) = q.data.as_deref().and_then(|d| d.strip_prefix("close:")) {
+        return handle_close_callback(bot, db, q, close).await;
+    }
+    if let Some(page) = q.data.as_deref().and_then(|d| d.strip_prefix("query:")) {
+        return handle_query_callback(bot, db, q, page).await;
+    }
+    if let Some(tag) = q.data.as_deref().and_then(|d| d.strip_prefix("cat:")) {
+        return handle_category_callback(bot, db, q, tag).await;
+    }
+    if let Some(goal) = q.data.as_deref().and_then(|d| d.strip_prefix("goal:")) {
+        return handle_goal_callback(bot, db, q, goal).await;
+    }
+    if let Some(transfer) = q.data.as_deref().and_then(|d| d.strip_prefix("transfer:")) {
+        return handle_transfer_callback(bot, db, q, transfer).await;
+    }
+    let Some((id, answer)) = q.data.as_deref().and_then(|d| {
+        let (id, answer) = d.strip_prefix("pending:")?.split_once(':')?;
+        Some((id.parse::<i64>().ok()?, answer))
+    }) else {
+        return Ok(());
+    };
+    let uuid = ensure_sender(db, &q.from).await?;
+    // Someone else's button, or one that was already answered.
+    let Some(pending) = db.take_pending(uuid, id).await? else {
+        return Ok(());
+    };
+    let Some(msg) = &q.message else {
+        return Ok(());
+    };
+    if answer == "ok" {
+        if let Some(text) = quota::exceeded(db, uuid, 1).await? {
+            bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+            return Ok(());
+        }
+    }
+    let text = match answer {
+        "ok" => {
+            let (reason, class) = split_class(pending.reason);
+            let entry_id = db
+                .add_entry(
+                    uuid,
+                    pending.amount_cents,
+                    EntryKind::Save,
+                    reason.clone(),
+                    class,
+                    None,
+                )
+                .await?;
+            db.set_entry_source(entry_id, msg.chat.id.0, msg.id.0)
+                .await?;
+            if let Some(file_id) = &pending.file_id {
+                db.add_attachment(uuid, entry_id, file_id, &pending.source)
+                    .await?;
+            }
+            events::entry_added(
+                db,
+                uuid,
+                pending.amount_cents,
+                EntryKind::Save,
+                reason.as_deref(),
+            )
+            .await;
+            let total = db.total_cents(uuid).await?;
+            let lang = reply_language(db, msg, Some(&q.from)).await?;
+            let mut text = i18n::saved(
+                lang,
+                &format_cents(pending.amount_cents),
+                &reason,
+                &format_cents(total),
+            );
+            if let Some(goal) = db.goal(uuid).await? {
+                text.push_str(
+                    &goal_after_save(db, uuid, &goal, pending.amount_cents, total).await?,
+                );
+            }
+            text
+        }
+        "edit" => format!(
+            "OK, send the right numbers instead, e.g.\n/save {}{}",
+            format_cents(pending.amount_cents),
+            pending.reason.map(|r| format!(" {r}")).unwrap_or_default()
+        ),
+        _ => "Discarded, nothing was recorded.".to_string(),
+    };
+    bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+    Ok(())
+}
+
+/// `purge` is `{id}:{ok|no}` from the /purge preview.
+async fn handle_purge_callback(bot: &Bot, db: &Db, q: &CallbackQuery, purge: &str) -> Result<()> {
+    let Some((Ok(id), answer)) = purge.split_once(':').map(|(id, a)| (id.parse::<i64>(), a)) else {
+        return Ok(());
+    };
+    let uuid = ensure_sender(db, &q.from).await?;
+    let Some(msg) = &q.message else {
+        return Ok(());
+    };
+    let text = if answer == "ok" {
+        let cutoff = (OffsetDateTime::now_utc() - time::Duration::minutes(PURGE_CONFIRM_MINUTES))
+            .format(&time::format_description::well_known::Rfc3339)?;
+        let locked = lock_cutoff(db, uuid).await?;
+        match db
+            .run_pending_purge(uuid, id, &cutoff, locked.as_deref())
+            .await?
+        {
+            None => return Ok(()),
+            Some(None) => format!(
+                "This purge is older than {PURGE_CONFIRM_MINUTES} minutes; nothing was deleted. Run /purge again."
+            ),
+            Some(Some(deleted)) => {
+                let total = db.total_cents(uuid).await?;
+                format!(
+                    "🗑 Deleted {deleted} entries. Total now: {}",
+                    format_cents(total)
+                )
+            }
+        }
+    } else if db.cancel_pending_purge(uuid, id).await? {
+        "Purge cancelled, nothing was deleted.".to_string()
+    } else {
+        return Ok(());
+    };
+    bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+    Ok(())
+}
+
+/// How long a /purge preview can still be confirmed.
+const PURGE_CONFIRM_MINUTES: i64 = 10;
+
+/// Members shown on a /leaderboard.
+const LEADERBOARD_SIZE: i64 = 10;
+
+/// Attachments listed by /attachments, newest first.
+const ATTACHMENTS_SHOWN: i64 = 20;
+
+/// How long after an /allinvoo it can still be undone with /unvoo.
+const UNVOO_HOURS: i64 = 24;
+
+/// How long an /allinvoo confirmation stays valid.
+const INVEST_CONFIRM_MINUTES: i64 = 10;
+
+/// `invest` is `{pending id}:{ok|no}`. Only the amount that was shown is
+/// invested, and only while the stash still holds it.
+async fn handle_invest_callback(bot: &Bot, db: &Db, q: &CallbackQuery, invest: &str) -> Result<()> {
+    let Some((Ok(id), answer)) = invest.split_once(':').map(|(id, a)| (id.parse::<i64>(), a))
+    else {
+        return Ok(());
+    };
+    let uuid = ensure_sender(db, &q.from).await?;
+    let Some(msg) = &q.message else {
+        return Ok(());
+    };
+    let lang = reply_language(db, msg, Some(&q.from)).await?;
+    let cutoff = (OffsetDateTime::now_utc() - time::Duration::minutes(INVEST_CONFIRM_MINUTES))
+        .format(&time::format_description::well_known::Rfc3339)?;
+    let Some(pending) = db.take_pending_invest(uuid, id, &cutoff).await? else {
+        return Ok(());
+    };
+    let strategy = match &pending.strategy {
+        Some(slug) => db.strategy_name(uuid, slug).await?.map(|name| (slug, name)),
+        None => None,
+    };
+    let currency = user_currency(db, uuid).await?;
+    let (current, _) = db.balances(uuid).await?;
+    let text = if answer != "ok" {
+        i18n::invest_cancelled(lang).to_string()
+    } else if pending.expired
+        || current < pending.amount_cents
+        || pending.strategy.is_some() && strategy.is_none()
+    {
+        i18n::invest_stale(lang).to_string()
+    } else {
+        let slug = strategy.as_ref().map(|(slug, _)| slug.as_str());
+        let (moved, history) = invest_all(db, uuid, slug, Some(pending.amount_cents)).await?;
+        let tickers = allocation::label(&invest_tickers(db, uuid).await?);
+        let mut text = i18n::invested(
+            lang,
+            &currency.format(moved),
+            &tickers,
+            &currency.format(current - moved),
+            &currency.format(history),
+        );
+        if let Some((slug, name)) = &strategy {
+            let total = strategy_total(db, uuid, Some(slug)).await?;
+            text.push_str(&format!(
+                "\n{}",
+                i18n::strategy_total(lang, name, &currency.format(total))
+            ));
+        }
+        text
+    };
+    bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+    Ok(())
+}
+
+/// `transfer` is `{pending id}:{ok|no}`, pressed by the recipient. The
+/// sender hears back in a DM unless the request was posted in a group,
+/// where the edited message says it for everyone.
+async fn handle_transfer_callback(
+    bot: &Bot,
+    db: &Db,
+    q: &CallbackQuery,
+    transfer: &str,
+) -> Result<()> {
+    let Some((Ok(id), answer)) = transfer
+        .split_once(':')
+        .map(|(id, a)| (id.parse::<i64>(), a))
+    else {
+        return Ok(());
+    };
+    let uuid = ensure_sender(db, &q.from).await?;
+    let Some(msg) = &q.message else {
+        return Ok(());
+    };
+    let cutoff = (OffsetDateTime::now_utc() - time::Duration::hours(TRANSFER_CONFIRM_HOURS))
+        .format(&time::format_description::well_known::Rfc3339)?;
+    // Someone else's button, or one that was already answered.
+    let Some(pending) = db.take_pending_transfer(uuid, id, &cutoff).await? else {
+        return Ok(());
+    };
+    let currency = user_currency(db, uuid).await?;
+    let amount = currency.format(pending.amount_cents);
+    let recipient = q
+        .from
+        .username
+        .as_ref()
+        .map(|u| format!("@{u}"))
+        .unwrap_or_else(|| q.from.first_name.clone());
+    let (text, to_sender) = if answer != "ok" {
+        (
+            "Declined, nothing was moved.".to_string(),
+            format!("{recipient} declined your transfer of {amount}."),
+        )
+    } else if pending.expired {
+        (
+            format!(
+                "This transfer is older than {TRANSFER_CONFIRM_HOURS} hours; nothing was moved."
+            ),
+            format!("Your transfer of {amount} to {recipient} expired before they accepted it."),
+        )
+    } else {
+        let from_reason = pending
+            .reason
+            .clone()
+            .unwrap_or_else(|| format!("transfer to {recipient}"));
+        let to_reason = pending
+            .reason
+            .clone()
+            .unwrap_or_else(|| format!("transfer from {}", pending.from_name));
+        match db
+            .transfer(
+                id,
+                (pending.from_user_id, &from_reason),
+                (uuid, &to_reason),
+                pending.amount_cents,
+            )
+            .await?
+        {
+            Some((from_id, to_id)) => {
+                let booked = [
+                    (pending.from_user_id, -pending.amount_cents, &from_reason),
+                    (uuid, pending.amount_cents, &to_reason),
+                ];
+                for (ledger, cents, reason) in booked {
+                    events::entry_added(db, ledger, cents, EntryKind::Adjust, Some(reason)).await;
                 }
+                let total = db.total_cents(uuid).await?;
+                (
+                    format!(
+                        "✅ {amount} from {} added to your stash (#{to_id}); it's now {}.",
+                        pending.from_name,
+                        currency.format(total)
+                    ),
+                    format!("✅ {recipient} accepted your transfer of {amount} (#{from_id})."),
+                )
+            }
+            None => (
+                format!(
+                    "{}'s stash no longer holds {amount}; nothing was moved.",
+                    pending.from_name
+                ),
+                format!(
+                    "Your transfer of {amount} to {recipient} failed: your stash no longer holds it."
+                ),
+            ),
+        }
+    };
+    bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+    if msg.chat.is_private() {
+        outbox::send(bot, db, ChatId(pending.from_tg_user_id), to_sender, None).await?;
+    }
+    Ok(())
+}
+
+async fn handle_import_callback(bot: &Bot, db: &Db, q: &CallbackQuery, import: &str) -> Result<()> {
+    let Some((Ok(id), answer)) = import.split_once(':').map(|(id, a)| (id.parse::<i64>(), a))
+    else {
+        return Ok(());
+    };
+    let uuid = ensure_sender(db, &q.from).await?;
+    let Some(msg) = &q.message else {
+        return Ok(());
+    };
+    let text = if answer == "ok" {
+        let Some(added) = db.run_pending_import(uuid, id).await? else {
+            return Ok(());
+        };
+        let total = db.total_cents(uuid).await?;
+        format!(
+            "📥 Imported {added} entries. Total now: {}",
+            format_cents(total)
+        )
+    } else if db.cancel_pending_import(uuid, id).await? {
+        "Import cancelled, nothing was added.".to_string()
+    } else {
+        return Ok(());
+    };
+    bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+    Ok(())
+}
+
+/// `tag` is `{entry id}:{+|-}{category}` from [`categorize::reply`]: tag the
+/// entry with the suggested category, or undo one that was applied.
+async fn handle_category_callback(bot: &Bot, db: &Db, q: &CallbackQuery, tag: &str) -> Result<()> {
+    let Some((Ok(id), change)) = tag.split_once(':').map(|(id, c)| (id.parse::<i64>(), c)) else {
+        return Ok(());
+    };
+    let Some(msg) = &q.message else {
+        return Ok(());
+    };
+    let uuid = ensure_sender(db, &q.from).await?;
+    let Some(entry) = db.entry_detail(uuid, id).await? else {
+        return Ok(());
+    };
+    let reason = entry.entry.reason.unwrap_or_default();
+    let (reason, note) = match (change.strip_prefix('+'), change.strip_prefix('-')) {
+        (Some(category), _) => (
+            categorize::with_category(&reason, category),
+            format!("🏷 Tagged #{category}."),
+        ),
+        (_, Some(category)) => (
+            categorize::without_category(&reason, category),
+            format!("↩️ Removed #{category}."),
+        ),
+        _ => return Ok(()),
+    };
+    let text = if db.set_entry_reason(uuid, id, &reason).await? {
+        note
+    } else {
+        "That entry is already invested, so it stays as it is.".to_string()
+    };
+    bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// One /query page of `size` entries at `cursor`, with Newer/Older buttons
+/// when there is more to see. Streak and goal progress only go on the
+/// first page. `None` when the page is empty.
+async fn query_page(
+    db: &Db,
+    uuid: uuid::Uuid,
+    lang: i18n::Lang,
+    sender_name: &str,
+    size: i64,
+    tag: Option<&str>,
+    cursor: db::Cursor,
+) -> Result<Option<(Report, Option<InlineKeyboardMarkup>)>> {
+    let mut items = db.last_entries(uuid, size + 1, tag, cursor).await?;
+    let more = items.len() as i64 > size;
+    if more {
+        // The extra entry is beyond the page, at the end we're moving toward.
+        match cursor {
+            db::Cursor::After(_) => items.remove(0),
+            _ => items.remove(items.len() - 1),
+        };
+    }
+    let (Some(newest), Some(oldest)) = (items.first(), items.last()) else {
+        return Ok(None);
+    };
+    let (newer, older) = match cursor {
+        db::Cursor::Newest => (false, more),
+        db::Cursor::Before(_) => (true, more),
+        db::Cursor::After(_) => (more, true),
+    };
+    let tag_suffix = tag.map(|t| format!(":{t}")).unwrap_or_default();
+    let mut buttons = Vec::new();
+    if newer {
+        buttons.push((
+            "⬅️ Newer",
+            format!("query:{size}:a{}{tag_suffix}", newest.id),
+        ));
+    }
+    if older {
+        buttons.push((
+            "Older ➡️",
+            format!("query:{size}:b{}{tag_suffix}", oldest.id),
+        ));
+    }
+    // Telegram caps callback data at 64 bytes; a very long #tag loses the buttons.
+    let keyboard =
+        (!buttons.is_empty() && buttons.iter().all(|(_, data)| data.len() <= 64)).then(|| {
+            InlineKeyboardMarkup::new([buttons
+                .into_iter()
+                .map(|(label, data)| InlineKeyboardButton::callback(label, data))
+                .collect::<Vec<_>>()])
+        });
+
+    let currency = user_currency(db, uuid).await?;
+    let (current_total, history_total) = db.balances(uuid).await?;
+    let header = if cursor == db::Cursor::Newest {
+        i18n::query_header(lang, items.len(), sender_name)
+    } else {
+        i18n::query_page_header(lang, items.len(), sender_name)
+    };
+    let totals = i18n::totals(
+        lang,
+        &currency.format(current_total),
+        &currency.format(history_total),
+        &currency.format(current_total + history_total),
+    );
+    let mut report = Report::new("query.txt");
+    report.summary = format!("{header} {}\n\n{totals}", i18n::attached(lang));
+    report.push(header);
+    for e in items {
+        let sign = if e.amount_cents >= 0 { "+" } else { "-" };
+        let amt = e.amount_cents.abs();
+        let reason = e.reason.unwrap_or_default();
+        let kind = match &e.class {
+            Some(class) => format!("{}, {}", e.kind, class),
+            None => e.kind.to_string(),
+        };
+        report.push(format!(
+            "#{} {} {} [{}] {}{}",
+            e.id,
+            sign,
+            currency.format(amt),
+            kind,
+            e.occurred_at,
+            if reason.is_empty() {
+                "".to_string()
+            } else {
+                format!(" — {}", reason)
+            }
+        ));
+    }
+    report.push(format!("\n{totals}"));
+    if cursor == db::Cursor::Newest {
+        if let Some(streak) = streak(db, uuid).await? {
+            report.push(streak);
+        }
+        if let Some(goal) = db.goal(uuid).await? {
+            report.push(format!(
+                "\n{}",
+                goals::status(db, uuid, &goal, current_total).await?
+            ));
+        }
+    }
+    Ok(Some((report, keyboard)))
+}
+
+/// `page` is `{size}:{a|b}{entry id}[:{tag}]` from [`query_page`]: the page
+/// just newer (`a`) or older (`b`) than that entry. Pages of someone
+/// else's entries come out empty and are ignored.
+async fn handle_query_callback(bot: &Bot, db: &Db, q: &CallbackQuery, page: &str) -> Result<()> {
+    let mut parts = page.splitn(3, ':');
+    let (Some(Ok(size)), Some(at)) = (parts.next().map(str::parse::<i64>), parts.next()) else {
+        return Ok(());
+    };
+    let tag = parts.next();
+    let cursor = match (at.get(..1), at.get(1..).map(str::parse::<i64>)) {
+        (Some("a"), Some(Ok(id))) => db::Cursor::After(id),
+        (Some("b"), Some(Ok(id))) => db::Cursor::Before(id),
+        _ => return Ok(()),
+    };
+    let Some(msg) = &q.message else {
+        return Ok(());
+    };
+    let uuid = ensure_sender(db, &q.from).await?;
+    let lang = reply_language(db, msg, Some(&q.from)).await?;
+    let name = display_name(&q.from);
+    let Some((report, keyboard)) =
+        query_page(db, uuid, lang, &name, size.clamp(1, 50), tag, cursor).await?
+    else {
+        return Ok(());
+    };
+    let edit = bot.edit_message_text(msg.chat.id, msg.id, report.lines.join("\n"));
+    match keyboard {
+        Some(keyboard) => edit.reply_markup(keyboard).await?,
+        None => edit.await?,
+    };
+    Ok(())
+}
+
+/// `action` is `save[:cents]`, `adjust` or `pause` from [`goals::checkin_keyboard`].
+async fn handle_goal_callback(bot: &Bot, db: &Db, q: &CallbackQuery, action: &str) -> Result<()> {
+    let uuid = ensure_sender(db, &q.from).await?;
+    let Some(msg) = &q.message else {
+        return Ok(());
+    };
+    let Some(goal) = db.goal(uuid).await? else {
+        bot.send_message(msg.chat.id, "You have no goal anymore.")
+            .await?;
+        return Ok(());
+    };
+    let name = goal.name.clone().unwrap_or_else(|| "your goal".into());
+    match action.split_once(':').unwrap_or((action, "")) {
+        ("save", cents) => match cents.parse::<i64>().ok().filter(|c| *c > 0) {
+            Some(cents) => {
+                let intro = format!("💰 Toward {name}:");
+                propose(
+                    bot,
+                    db,
+                    msg.chat.id,
+                    uuid,
+                    (cents, goal.name),
+                    ("checkin", None),
+                    &intro,
+                )
+                .await?;
+            }
+            None => {
+                bot.send_message(
+                    msg.chat.id,
+                    format!("How much? Send /save {{amount}} and it counts toward {name}."),
+                )
+                .await?;
+            }
+        },
+        ("adjust", _) => {
+            let current = format!(
+                "/goal set {}{}{}",
+                format_cents(goal.target_cents),
+                goal.deadline.map(|d| format!(" {d}")).unwrap_or_default(),
+                goal.name.map(|n| format!(" {n}")).unwrap_or_default()
+            );
+            bot.send_message(
+                msg.chat.id,
+                format!("Send the goal with new numbers. It is currently:\n{current}"),
+            )
+            .await?;
+        }
+        ("pause", _) => {
+            db.pause_goal_checkin(uuid).await?;
+            bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+            bot.send_message(
+                msg.chat.id,
+                "⏸ Goal check-ins paused. Resume with /goal checkin weekly sun 10:00.",
+            )
+            .await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// `close` is `{YYYY-MM}:{next|invest|skip|no}` from [`close::keyboard`].
+/// Buttons from a step that is already behind the user do nothing.
+async fn handle_close_callback(bot: &Bot, db: &Db, q: &CallbackQuery, close: &str) -> Result<()> {
+    let Some((Some(month), action)) = close
+        .split_once(':')
+        .map(|(m, a)| (stats::Period::parse(m), a))
+    else {
+        return Ok(());
+    };
+    let uuid = ensure_sender(db, &q.from).await?;
+    let Some(msg) = &q.message else {
+        return Ok(());
+    };
+    let Some(step) = db
+        .month_close(uuid, &month.label)
+        .await?
+        .and_then(|c| close::Step::parse(&c.step))
+    else {
+        return Ok(());
+    };
+    let next = match (step, action) {
+        (_, "no") => {
+            if db.cancel_close(uuid, &month.label).await? {
+                bot.edit_message_text(
+                    msg.chat.id,
+                    msg.id,
+                    format!(
+                        "Closing {} cancelled. Run /close again any time.",
+                        month.label
+                    ),
+                )
+                .await?;
+            }
+            return Ok(());
+        }
+        (close::Step::Review, "next") => Some(close::Step::Budgets),
+        (close::Step::Budgets, "next") if db.total_cents(uuid).await? != 0 => {
+            Some(close::Step::Invest)
+        }
+        (close::Step::Budgets, "next") | (close::Step::Invest, "skip" | "invest") => None,
+        _ => return Ok(()),
+    };
+    if let Some(next) = next {
+        db.set_close_step(uuid, &month.label, next.as_str()).await?;
+        bot.edit_message_text(
+            msg.chat.id,
+            msg.id,
+            close::prompt(db, uuid, &month, next).await?,
+        )
+        .reply_markup(close::keyboard(&month.label, next))
+        .await?;
+        return Ok(());
+    }
+    let invested = if action == "invest" {
+        Some(invest_all(db, uuid, None, None).await?.0)
+    } else {
+        None
+    };
+    let statement = close::statement(db, uuid, &month, invested).await?;
+    if !db.finish_close(uuid, &month.label, &statement).await? {
+        return Ok(());
+    }
+    bot.edit_message_text(
+        msg.chat.id,
+        msg.id,
+        format!("✅ {} is closed. Your statement is attached.", month.label),
+    )
+    .await?;
+    render::send_file(
+        bot,
+        msg.chat.id,
+        &format!("statement-{}.txt", month.label),
+        statement.into_bytes(),
+        &format!("Monthly statement for {}", month.label),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Parses `/purge` filters such as `reason:test kind:adjust before:2023-01-01`.
+fn parse_entry_filter(args: &str) -> Option<db::EntryFilter> {
+    let mut f = db::EntryFilter::default();
+    for word in args.split_whitespace() {
+        let (key, value) = word.split_once(':')?;
+        let value = Some(value.to_string()).filter(|v| !v.is_empty())?;
+        match key {
+            "reason" => f.reason = Some(value),
+            "kind" => f.kind = Some(EntryKind::parse(&value)?),
+            "before" => f.before = Some(parse_date(&value)?.to_string()),
+            "after" => f.after = Some(parse_date(&value)?.to_string()),
+            _ => return None,
+        }
+    }
+    let any = f.reason.is_some() || f.kind.is_some() || f.before.is_some() || f.after.is_some();
+    any.then_some(f)
+}
+
+/// Largest file /import will download.
+const IMPORT_MAX_BYTES: u32 = 2 * 1024 * 1024;
+
+/// How long a new entry stays editable when /immutable is on.
+const EDIT_WINDOW_HOURS: i64 = 24;
+
+/// Most /search matches listed; the count and net cover all of them.
+const SEARCH_LIMIT: i64 = 50;
+
+/// Invested entries /historylog lists when no count is given, and at most.
+const HISTORYLOG_DEFAULT: i64 = 20;
+const HISTORYLOG_MAX: i64 = 200;
+
+/// The UTF-8 text of the document sent with (or replied to by) `msg`.
+/// Tells the user what went wrong and returns `None` otherwise.
+async fn attached_text(bot: &Bot, msg: &Message, usage: &str) -> Result<Option<String>> {
+    let Some(doc) = msg
+        .document()
+        .or_else(|| msg.reply_to_message().and_then(|m| m.document()))
+    else {
+        bot.send_message(msg.chat.id, usage).await?;
+        return Ok(None);
+    };
+    if doc.file.size > IMPORT_MAX_BYTES {
+        bot.send_message(msg.chat.id, "That file is too big to import (limit 2 MB).")
+            .await?;
+        return Ok(None);
+    }
+    let file = bot.get_file(&doc.file.id).await?;
+    let mut bytes = Vec::new();
+    bot.download_file(&file.path, &mut bytes).await?;
+    let Ok(text) = String::from_utf8(bytes) else {
+        bot.send_message(
+            msg.chat.id,
+            "That file isn't UTF-8 text; save it as UTF-8 and send it again.",
+        )
+        .await?;
+        return Ok(None);
+    };
+    Ok(Some(text))
+}
+
+/// Entries recorded before the returned time are locked, if /immutable is on.
+async fn lock_cutoff(db: &Db, uuid: uuid::Uuid) -> Result<Option<String>> {
+    if !db.immutable(uuid).await? {
+        return Ok(None);
+    }
+    let cutoff = OffsetDateTime::now_utc() - time::Duration::hours(EDIT_WINDOW_HOURS);
+    Ok(Some(
+        cutoff.format(&time::format_description::well_known::Rfc3339)?,
+    ))
+}
+
+fn is_locked(entry: &db::EntryDetail, cutoff: Option<&str>) -> bool {
+    cutoff.is_some_and(|cutoff| entry.created_at.as_str() < cutoff)
+}
+
+fn locked_text(id: i64) -> String {
+    format!(
+        "Entry #{id} is more than {EDIT_WINDOW_HOURS} hours old and your ledger is immutable. Use /reverse {id} to book a compensating entry."
+    )
+}
+
+/// Both legs of a /transfer stand or fall together, so neither is changed
+/// on its own.
+fn transfer_leg_text(id: i64) -> String {
+    format!("Entry #{id} is one side of a /transfer and can't be changed on its own. To undo it, /transfer the money back.")
+}
+
+/// Moves the stash (or `amount` of it) to history, into `strategy` or the
+/// main bucket, and announces it; returns (moved, history total). Shared
+/// by /allinvoo and /close.
+async fn invest_all(
+    db: &Db,
+    uuid: uuid::Uuid,
+    strategy: Option<&str>,
+    amount: Option<i64>,
+) -> Result<(i64, i64)> {
+    let moved = db.archive_user_entries(uuid, strategy, amount).await?;
+    if moved > 0 {
+        record_shares(db, uuid).await;
+    }
+    let history = db.history_total_cents(uuid).await?;
+    let event = events::Event::AllInVoo {
+        moved_cents: moved,
+        invested_cents: history,
+    };
+    events::emit(db, uuid, event).await;
+    Ok((moved, history))
+}
+
+/// What /allinvoo buys for the user: their /target allocation (which
+/// /ticker set also sets), else all VOO.
+async fn invest_tickers(db: &Db, uuid: uuid::Uuid) -> Result<Vec<(String, i64)>> {
+    let tickers = db.allocation_targets(uuid).await?;
+    Ok(if tickers.is_empty() {
+        vec![(allocation::DEFAULT_TICKER.to_string(), 100)]
+    } else {
+        tickers
+    })
+}
+
+/// Notes how the batch just invested was split across the user's tickers
+/// and how many shares each part bought at the last close, for /portfolio.
+/// A failed price lookup only loses that part its share count. Without an
+/// exchange rate a batch saved in another currency than USD can't be turned
+/// into shares, so none are recorded for it.
+async fn record_shares(db: &Db, uuid: uuid::Uuid) {
+    let result: Result<()> = async {
+        let Some((batch_id, _, total)) = db.last_batch(uuid).await? else {
+            return Ok(());
+        };
+        let tickers = invest_tickers(db, uuid).await?;
+        let currency = user_currency(db, uuid).await?;
+        let fx_rate = hedged::usd_rate(db, currency).await.unwrap_or_else(|err| {
+            eprintln!("allinvoo: exchange rate: {err:?}");
+            None
+        });
+        if fx_rate.is_none() && !currency.code.is_empty() && currency.code != "USD" {
+            eprintln!(
+                "allinvoo: no {}/USD rate, shares not recorded",
+                currency.code
+            );
+            return Ok(());
+        }
+        for (ticker, cost) in allocation::split_contribution(&[], &tickers, total) {
+            let price = match market::price_cents(&ticker).await {
+                Ok(Some(price)) if cost > 0 => price,
+                Ok(_) => continue,
+                Err(err) => {
+                    eprintln!("allinvoo: {ticker} price: {err:?}");
+                    continue;
+                }
+            };
+            db.add_holding((uuid, batch_id), &ticker, price, cost, fx_rate)
+                .await?;
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(err) = result {
+        eprintln!("allinvoo: recording shares: {err:?}");
+    }
+}
+
+/// The last close for /portfolio, or `None` if it can't be had right now.
+async fn market_price(ticker: &str) -> Option<i64> {
+    market::price_cents(ticker).await.unwrap_or_else(|err| {
+        eprintln!("portfolio: price error: {err:?}");
+        None
+    })
+}
+
+/// `VOO: 12.3456 shares, cost basis 4000.00, worth 4321.00 (+321.00, +8.0%)`,
+/// from a price in USD cents and `rate` as from [`hedged::value_rate`]. The worth
+/// is left out without both.
+fn holding_line(h: &db::Holding, price_cents: Option<i64>, rate: Option<f64>) -> String {
+    let mut line = format!(
+        "{}: {:.4} shares, cost basis {}",
+        h.ticker,
+        h.micro_shares as f64 / 1_000_000.0,
+        format_cents(h.cost_cents)
+    );
+    if let (Some(price), Some(rate)) = (price_cents, rate) {
+        let usd_value = h.micro_shares as f64 * price as f64 / 1_000_000.0;
+        let value = (usd_value / rate).round() as i64;
+        let gain = value - h.cost_cents;
+        line.push_str(&format!(
+            ", worth {} ({}",
+            format_cents(value),
+            format_signed_cents(gain)
+        ));
+        if h.cost_cents > 0 {
+            line.push_str(&format!(
+                ", {:+.1}%",
+                gain as f64 * 100.0 / h.cost_cents as f64
+            ));
+        }
+        line.push(')');
+    }
+    line
+}
+
+/// What /allinvoo would move right now; /allinvoo preview shows it
+/// without archiving anything.
+struct AllInVooPlan {
+    moved_cents: i64,
+    entries: i64,
+    current_after_cents: i64,
+    history_after_cents: i64,
+}
+
+impl AllInVooPlan {
+    async fn load(db: &Db, uuid: uuid::Uuid, amount: Option<i64>) -> Result<Self> {
+        let selection = db.invest_selection(uuid, amount).await?;
+        let (current, history) = db.balances(uuid).await?;
+        Ok(Self {
+            moved_cents: selection.cents,
+            entries: (selection.whole.len() + usize::from(selection.split.is_some())) as i64,
+            current_after_cents: current - selection.cents,
+            history_after_cents: history + selection.cents,
+        })
+    }
+}
+
+/// Invested total of one bucket; `None` is the main one.
+async fn strategy_total(db: &Db, uuid: uuid::Uuid, slug: Option<&str>) -> Result<i64> {
+    let batches = db.invest_batches(uuid, slug).await?;
+    Ok(batches.iter().map(|(_, cents, _)| cents).sum())
+}
+
+/// Strategy names are one word so `/allinvoo house` stays unambiguous;
+/// `main` and `preview` are taken, and amounts like `200` are not names.
+fn valid_strategy_name(name: &str) -> bool {
+    let valid = (1..=24).contains(&name.chars().count())
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    valid
+        && !["main", "preview"].contains(&name.to_lowercase().as_str())
+        && decimal_to_cents(name).is_err()
+}
+
+/// The user's /timezone offset (UTC when unset or out of range).
+async fn user_offset(db: &Db, uuid: uuid::Uuid) -> Result<time::UtcOffset> {
+    let minutes = db.utc_offset_minutes(uuid).await?;
+    Ok(time::UtcOffset::from_whole_seconds((minutes * 60) as i32).unwrap_or(time::UtcOffset::UTC))
+}
+
+/// The user's /currency, or bare numbers when unset.
+async fn user_currency(db: &Db, uuid: uuid::Uuid) -> Result<Currency> {
+    let code = db.currency(uuid).await?;
+    Ok(code
+        .as_deref()
+        .and_then(Currency::parse)
+        .unwrap_or(Currency::PLAIN))
+}
+
+/// A typed amount after any conversion from another currency.
+struct Converted {
+    amount_cents: i64,
+    reason: Option<String>,
+    /// What was typed, when it was in another currency.
+    original: Option<(i64, String)>,
+}
+
+/// `/save 1000 JPY lunch`: converts an amount followed by a currency code
+/// into the user's /currency and takes the code out of the reason. `None`
+/// means the user was told why it couldn't be done.
+async fn convert_foreign(
+    bot: &Bot,
+    chat_id: ChatId,
+    db: &Db,
+    currency: Currency,
+    amount_cents: i64,
+    reason: Option<String>,
+) -> Result<Option<Converted>> {
+    let (code, rest) = fx::split_code(reason.clone());
+    let code = match code {
+        Some(code) if code == currency.code => {
+            return Ok(Some(Converted {
+                amount_cents,
+                reason: rest,
+                original: None,
+            }));
+        }
+        Some(code) => code,
+        None => {
+            return Ok(Some(Converted {
+                amount_cents,
+                reason,
+                original: None,
+            }));
+        }
+    };
+    let typed = format!("{} {code}", format_cents(amount_cents));
+    if currency == Currency::PLAIN {
+        bot.send_message(
+            chat_id,
+            format!("Set your own currency first, e.g. /currency USD, so I know what to convert {typed} into."),
+        )
+        .await?;
+        return Ok(None);
+    }
+    let rate = match fx::rate(db, &code, currency.code).await {
+        Ok(Some(rate)) => rate,
+        Ok(None) => {
+            bot.send_message(chat_id, "Currency conversion is turned off on this bot.")
+                .await?;
+            return Ok(None);
+        }
+        Err(err) => {
+            eprintln!("fx rate {code}->{}: {err:?}", currency.code);
+            bot.send_message(
+                chat_id,
+                format!(
+                    "Couldn't get an exchange rate from {code} to {}. Try again later or enter the amount in {}.",
+                    currency.code, currency.code
+                ),
+            )
+            .await?;
+            return Ok(None);
+        }
+    };
+    Ok(Some(Converted {
+        amount_cents: fx::convert(amount_cents, rate),
+        reason: rest,
+        original: Some((amount_cents, code)),
+    }))
+}
+
+/// `💱 1000.00 JPY at 0.006700` under a converted entry's confirmation.
+fn conversion_note(converted: &Converted) -> String {
+    match &converted.original {
+        Some((cents, code)) if *cents != 0 => format!(
+            "\n💱 {} {code} at {:.6}",
+            format_cents(*cents),
+            converted.amount_cents as f64 / *cents as f64
+        ),
+        _ => String::new(),
+    }
+}
+
+/// `🧮 3*4.50 = 13.50` under an entry whose amount was worked out from
+/// arithmetic, so a slip in the expression shows.
+fn calc_note(args: &str, comma: bool) -> String {
+    match calc::leading(args, comma) {
+        Some((expr, cents, _)) => format!("\n🧮 {expr} = {}", format_cents(cents)),
+        None => String::new(),
+    }
+}
+
+/// The current saving streak in the user's timezone, if any.
+async fn streak(db: &Db, uuid: uuid::Uuid) -> Result<Option<String>> {
+    let offset = user_offset(db, uuid).await?;
+    let days: Vec<Date> = db
+        .save_days(uuid, offset.whole_minutes().into())
+        .await?
+        .iter()
+        .filter_map(|d| parse_date(d))
+        .collect();
+    let today = OffsetDateTime::now_utc().to_offset(offset).date();
+    Ok(streaks::summary(&days, today))
+}
+
+/// Goal progress to append to a save confirmation, leading with a
+/// celebration when the save crossed a milestone.
+async fn goal_after_save(
+    db: &Db,
+    uuid: uuid::Uuid,
+    goal: &db::Goal,
+    amount_cents: i64,
+    total: i64,
+) -> Result<String> {
+    let mut text = String::from("\n\n");
+    if let Some(m) = goals::milestone(goal, total - amount_cents, total) {
+        let name = goal.name.as_deref().unwrap_or("your goal");
+        text.push_str(&format!("🏁 Milestone: you just passed {m}% of {name}!\n"));
+    }
+    text.push_str(&goals::status(db, uuid, goal, total).await?);
+    Ok(text)
+}
+
+fn confirm_keyboard(pending_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback("✅ Confirm", format!("pending:{pending_id}:ok")),
+        InlineKeyboardButton::callback("✏️ Edit", format!("pending:{pending_id}:edit")),
+        InlineKeyboardButton::callback("✖️ Cancel", format!("pending:{pending_id}:no")),
+    ]])
+}
+
+/// Transcribes a voice note, looks for an amount in it and asks the user to
+/// confirm the resulting save.
+async fn propose_from_voice(
+    bot: &Bot,
+    db: &Db,
+    chat_id: ChatId,
+    uuid: uuid::Uuid,
+    voice: &Voice,
+) -> Result<()> {
+    let Some(stt) = speech::Stt::from_env() else {
+        bot.send_message(
+            chat_id,
+            "Voice notes aren't enabled on this bot (no speech-to-text provider configured).",
+        )
+        .await?;
+        return Ok(());
+    };
+    if !flags::enabled(db, flags::Flag::Voice, uuid).await? {
+        bot.send_message(
+            chat_id,
+            "Voice notes aren't available to you yet. Try /save 12.34 [reason].",
+        )
+        .await?;
+        return Ok(());
+    }
+    let file = bot.get_file(&voice.file.id).await?;
+    let mut audio = Vec::new();
+    bot.download_file(&file.path, &mut audio).await?;
+    let transcript = stt.transcribe(audio).await?;
+
+    let Some((amount_cents, reason)) = nlp::parse_entry(&transcript) else {
+        bot.send_message(
+            chat_id,
+            format!("🎙 Heard: “{transcript}”\nI couldn't find an amount in that. Try /save 12.34 [reason]."),
+        )
+        .await?;
+        return Ok(());
+    };
+    propose(
+        bot,
+        db,
+        chat_id,
+        uuid,
+        (amount_cents, reason),
+        ("voice", Some(&voice.file.id)),
+        &format!("🎙 Heard: “{transcript}”"),
+    )
+    .await
+}
+
+/// Reads the total off a receipt photo and asks the user to confirm it.
+async fn propose_from_receipt(
+    bot: &Bot,
+    db: &Db,
+    chat_id: ChatId,
+    uuid: uuid::Uuid,
+    photo: &PhotoSize,
+    reason: Option<String>,
+) -> Result<()> {
+    let Some(ocr) = ocr::Ocr::from_env() else {
+        bot.send_message(
+            chat_id,
+            "Receipt scanning isn't enabled on this bot (no OCR provider configured). Add the amount: /save 12.34 [reason]",
+        )
+        .await?;
+        return Ok(());
+    };
+    if !flags::enabled(db, flags::Flag::Receipts, uuid).await? {
+        bot.send_message(
+            chat_id,
+            "Receipt scanning isn't available to you yet. Add the amount: /save 12.34 [reason]",
+        )
+        .await?;
+        return Ok(());
+    }
+    let file = bot.get_file(&photo.file.id).await?;
+    let mut image = Vec::new();
+    bot.download_file(&file.path, &mut image).await?;
+    let text = ocr.recognize(image).await?;
+
+    let Some(amount_cents) = nlp::receipt_total(&text) else {
+        bot.send_message(
+            chat_id,
+            "🧾 I couldn't find a total on that receipt. Add the amount: /save 12.34 [reason]",
+        )
+        .await?;
+        return Ok(());
+    };
+    let reason = reason.or_else(|| Some("receipt".into()));
+    propose(
+        bot,
+        db,
+        chat_id,
+        uuid,
+        (amount_cents, reason),
+        ("receipt", Some(&photo.file.id)),
+        "🧾 Read the receipt",
+    )
+    .await
+}
+
+/// Stores a proposed save and shows it with Confirm/Edit/Cancel buttons.
+/// `source` names where it came from, with the file it was read from if
+/// any, which is attached to the entry once saved.
+async fn propose(
+    bot: &Bot,
+    db: &Db,
+    chat_id: ChatId,
+    uuid: uuid::Uuid,
+    (amount_cents, reason): (i64, Option<String>),
+    source: (&str, Option<&str>),
+    intro: &str,
+) -> Result<()> {
+    let id = db
+        .add_pending(uuid, chat_id.0, amount_cents, reason.clone(), source)
+        .await?;
+    bot.send_message(
+        chat_id,
+        format!(
+            "{intro}\nSave {}{}?",
+            format_cents(amount_cents),
+            reason.map(|r| format!(" — {r}")).unwrap_or_default()
+        ),
+    )
+    .reply_markup(confirm_keyboard(id))
+    .await?;
+    Ok(())
+}
+
+/// The ledger the sender records into: their own, or their /shared one.
+async fn ensure_sender(db: &Db, from: &teloxide::types::User) -> Result<uuid::Uuid> {
+    let uuid = personal_user(db, from).await?;
+    Ok(db.shared_ledger_id(uuid).await?.unwrap_or(uuid))
+}
+
+/// The sender's own account, whether or not they are in a /shared ledger.
+async fn personal_user(db: &Db, from: &teloxide::types::User) -> Result<uuid::Uuid> {
+    // Note: first_name is String
+    db.ensure_user(
+        from.id.0 as i64,
+        from.username.clone(),
+        from.first_name.clone(),
+        from.last_name.clone(),
+    )
+    .await
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        command = msg.text().and_then(|t| t.split_whitespace().next()),
+        chat_id = msg.chat.id.0,
+    )
+)]
+async fn handle_command(bot: Bot, db: &Db, msg: &Message, cmd: Command) -> Result<()> {
+    if !msg.chat.is_private() {
+        let name = command_name(msg.text().or(msg.caption()).unwrap_or_default());
+        if db.disabled_commands(msg.chat.id.0).await?.contains(&name) {
+            bot.send_message(msg.chat.id, format!("/{name} is turned off in this chat."))
+                .await?;
+            return Ok(());
+        }
+    }
+    let from = msg.from();
+    // Posts made as a channel (including the channel's automatic forwards
+    // into its discussion group) or by an anonymous group admin carry
+    // sender_chat. Channels count for whoever claimed them; failing that,
+    // and for anonymous admins, the group's pot if /anonymous pot is on.
+    let sender_chat = msg.sender_chat();
+    // Per-person settings and credentials belong to `person`; entries go to
+    // `uuid`, their /shared ledger if they are in one.
+    let person = match (sender_chat, from) {
+        (None, Some(from)) => Some(personal_user(db, from).await?),
+        _ => None,
+    };
+    let uuid = match (sender_chat, person) {
+        (Some(chat), _) => {
+            let owner = if chat.is_channel() {
+                db.channel_owner(chat.id.0).await?
+            } else {
+                None
+            };
+            match owner {
+                Some(owner) => owner,
+                None => match chat_pot(db, &msg.chat).await? {
+                    Some(pot) => pot,
+                    // Anonymous admins must be able to turn the pot on.
+                    None if matches!(cmd, Command::Anonymous(_)) => pot_user(db, &msg.chat).await?,
+                    None => {
+                        let hint = if chat.is_channel() {
+                            "Posts from this channel aren't linked to anyone yet. The channel owner can run /channelowner in the discussion group, or an admin can turn on /anonymous pot."
+                        } else {
+                            "Anonymous admins have no ledger of their own. Turn on /anonymous pot to record them in a shared group pot."
+                        };
+                        bot.send_message(msg.chat.id, hint).await?;
+                        return Ok(());
+                    }
+                },
+            }
+        }
+        (None, Some(person)) => db.shared_ledger_id(person).await?.unwrap_or(person),
+        (None, None) => {
+            bot.send_message(msg.chat.id, "I can only respond to user messages.")
+                .await?;
+            return Ok(());
+        }
+    };
+    // These arrive under a placeholder account; its name and language say
+    // nothing about the owner.
+    let from = from.filter(|_| sender_chat.is_none());
+    let person = person.unwrap_or(uuid);
+    let sender_name = match (sender_chat, from) {
+        (Some(chat), _) => chat.title().unwrap_or("anonymous").to_string(),
+        (None, Some(from)) => display_name(from),
+        (None, None) => String::new(),
+    };
+
+    let lang = reply_language(db, msg, from).await?;
+    let comma = reads_decimal_comma(db, uuid, from).await?;
+    let currency = user_currency(db, person).await?;
+
+    if records_entry(&cmd) {
+        if let Some(text) = quota::exceeded(db, uuid, 1).await? {
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
+    }
+
+    match cmd {
+        Command::Start => {
+            let mut text = i18n::welcome(lang, &sender_name, &uuid.to_string());
+            if let Some(streak) = streak(db, uuid).await? {
+                text.push('\n');
+                text.push_str(&teloxide::utils::markdown::escape(&streak));
+            }
+            bot.send_message(msg.chat.id, text)
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+        }
+        Command::Qr => {
+            let png = chart::qr(&uuid.to_string())?;
+            bot.send_photo(msg.chat.id, InputFile::memory(png).file_name("uuid.png"))
+                .caption(format!("Your UUID: {uuid}"))
+                .await?;
+        }
+        Command::Globalstats => {
+            if !is_operator(from) {
+                bot.send_message(
+                    msg.chat.id,
+                    "Only the bot's operators can see instance stats.",
+                )
+                .await?;
+                return Ok(());
+            }
+            let now = OffsetDateTime::now_utc();
+            let cutoff = |days: i64| {
+                (now - time::Duration::days(days))
+                    .format(&time::format_description::well_known::Rfc3339)
+            };
+            let since = (now - time::Duration::days(6)).date().to_string();
+            let stats = db.instance_stats(&cutoff(7)?, &cutoff(30)?, &since).await?;
+            let entries: i64 = stats.entries_per_day.iter().map(|(_, n)| n).sum();
+            let mut lines = vec![
+                "📊 Instance stats".to_string(),
+                format!(
+                    "Users: {} registered, {} active in the last 7 days, {} in the last 30",
+                    stats.users, stats.active_7d, stats.active_30d
+                ),
+                format!(
+                    "Entries per day (last 7 days, {:.1} on average):",
+                    entries as f64 / 7.0
+                ),
+            ];
+            lines.extend(
+                stats
+                    .entries_per_day
+                    .iter()
+                    .map(|(day, n)| format!("  {day}: {n}")),
+            );
+            lines.push(format!(
+                "Database: {:.1} MB",
+                stats.db_bytes as f64 / 1_048_576.0
+            ));
+            let health = scheduler::health();
+            lines.push(match health.last_tick {
+                Some(tick) => format!("Scheduler: last tick {}", ago(tick, now)),
+                None => "Scheduler: no tick yet".to_string(),
+            });
+            for (job, h) in &health.jobs {
+                let mut line = format!("  {job}: {} runs, {} failed", h.runs, h.failures);
+                if let Some(ok) = h.last_ok {
+                    line.push_str(&format!(", last ok {}", ago(ok, now)));
+                }
+                if let Some((at, err)) = &h.last_error {
+                    line.push_str(&format!(", last error {}: {err}", ago(*at, now)));
+                }
+                lines.push(line);
+            }
+            bot.send_message(msg.chat.id, lines.join("\n")).await?;
+        }
+        Command::Flag(args) => {
+            if !is_operator(from) {
+                bot.send_message(
+                    msg.chat.id,
+                    "Only the bot's operators can change feature flags.",
+                )
+                .await?;
+                return Ok(());
+            }
+            const USAGE: &str = "Usage: /flag | /flag {flag} {0-100}% | /flag {flag} default | /flag {flag} on|off|reset {user id|@username}";
+            let words: Vec<&str> = args.split_whitespace().collect();
+            let Some(flag) = words.first().map(|w| flags::Flag::parse(&w.to_lowercase())) else {
+                let mut lines = vec!["🚩 Feature flags".to_string()];
+                lines.extend(flags::describe(db).await?);
+                bot.send_message(msg.chat.id, lines.join("\n")).await?;
+                return Ok(());
+            };
+            let Some(flag) = flag else {
+                let names: Vec<&str> = flags::Flag::ALL.iter().map(|f| f.as_str()).collect();
+                bot.send_message(
+                    msg.chat.id,
+                    format!("Unknown flag. Flags: {}", names.join(", ")),
+                )
+                .await?;
+                return Ok(());
+            };
+            let name = flag.as_str();
+            let text = match &words[1..] {
+                ["default"] => {
+                    db.set_flag_rollout(name, None).await?;
+                    format!("{name} is back to its configured rollout.")
+                }
+                [percent] => match percent.trim_end_matches('%').parse::<i64>() {
+                    Ok(p) if (0..=100).contains(&p) => {
+                        db.set_flag_rollout(name, Some(p)).await?;
+                        format!("{name} is now on for {p}% of users.")
+                    }
+                    _ => USAGE.to_string(),
+                },
+                [action @ ("on" | "off" | "reset"), who] => match db.find_user(who).await? {
+                    Some(user) => {
+                        let enabled = match *action {
+                            "on" => Some(true),
+                            "off" => Some(false),
+                            _ => None,
+                        };
+                        db.set_flag_override(name, user, enabled).await?;
+                        match enabled {
+                            Some(true) => format!("{name} is on for {who}."),
+                            Some(false) => format!("{name} is off for {who}."),
+                            None => format!("{who} follows the {name} rollout again."),
+                        }
+                    }
+                    None => format!("No user {who} has used this bot."),
+                },
+                _ => USAGE.to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Token(args) => {
+            let words: Vec<&str> = args.split_whitespace().collect();
+            if let ["revoke", id] = words.as_slice() {
+                let text = match id.parse() {
+                    Ok(id) if db.revoke_api_token(person, id).await? => {
+                        format!("Token #{id} revoked.")
+                    }
+                    _ => format!("You have no token #{id}. See /tokens."),
+                };
+                bot.send_message(msg.chat.id, text).await?;
+                return Ok(());
+            }
+            if !msg.chat.is_private() {
+                bot.send_message(msg.chat.id, "Ask me for a token in a private chat.")
+                    .await?;
+                return Ok(());
+            }
+            let scope = match words.first() {
+                None => Some(api::Scope::Read),
+                Some(s) => api::Scope::parse(s),
+            };
+            let days = match words.get(1) {
+                None => Some(90),
+                Some(d) => d.parse::<i64>().ok().filter(|d| *d >= 0),
+            };
+            let (Some(scope), Some(days), true) = (scope, days, words.len() <= 2) else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /token [read|write|admin] [days], 0 days = never expires. /token revoke {id}",
+                )
+                .await?;
+                return Ok(());
+            };
+            if scope == api::Scope::Admin && !is_operator(from) {
+                bot.send_message(
+                    msg.chat.id,
+                    "Only the bot's operators can create admin tokens.",
+                )
+                .await?;
+                return Ok(());
+            }
+            let expires_at = (days > 0)
+                .then(|| {
+                    (OffsetDateTime::now_utc() + time::Duration::days(days))
+                        .format(&time::format_description::well_known::Rfc3339)
+                })
+                .transpose()?;
+            let (token, hash) = api::new_token();
+            let id = db
+                .add_api_token(person, &hash, scope.as_str(), expires_at.clone())
+                .await?;
+            let expiry = expires_at
+                .map(|e| format!("expires {}", &e[..10]))
+                .unwrap_or_else(|| "never expires".into());
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "🔑 Token #{id} ({}, {expiry}):\n\n{token}\n\nSend it as `Authorization: Bearer …`. I only store a hash, so keep it somewhere safe; /token revoke {id} kills it.",
+                    scope.as_str()
+                ),
+            )
+            .await?;
+        }
+        Command::Attachments(args) => {
+            let words: Vec<&str> = args.split_whitespace().collect();
+            let text = match words.as_slice() {
+                [] => {
+                    let attachments = db.attachments(uuid, ATTACHMENTS_SHOWN).await?;
+                    if attachments.is_empty() {
+                        "No attachments. Receipt photos and voice notes are kept with the saves they become.".to_string()
+                    } else {
+                        let mut lines: Vec<String> = attachments
+                            .iter()
+                            .map(|a| {
+                                let icon = if a.source == "voice" { "🎙" } else { "🧾" };
+                                let entry = match a.amount_cents {
+                                    Some(cents) => format!(
+                                        "{}{}",
+                                        currency.format(cents),
+                                        a.reason
+                                            .as_deref()
+                                            .map(|r| format!(" {r}"))
+                                            .unwrap_or_default()
+                                    ),
+                                    None => "entry deleted".into(),
+                                };
+                                format!(
+                                    "#{} {} {icon} entry #{}: {entry}",
+                                    a.id,
+                                    a.created_at.get(..10).unwrap_or(&a.created_at),
+                                    a.entry_id
+                                )
+                            })
+                            .collect();
+                        let count = db.attachment_count(uuid).await?;
+                        if count > ATTACHMENTS_SHOWN {
+                            lines.push(format!(
+                                "…and {} older. /attachments cleanup {{months}} forgets old ones.",
+                                count - ATTACHMENTS_SHOWN
+                            ));
+                        }
+                        lines.join("\n")
+                    }
+                }
+                ["forget", id] => match id.trim_start_matches('#').parse() {
+                    Ok(id) if db.remove_attachment(uuid, id).await? => {
+                        format!("Attachment #{id} forgotten; its entry is untouched.")
+                    }
+                    _ => format!("You have no attachment {id}. See /attachments."),
+                },
+                ["cleanup", months] => match months.parse::<i64>() {
+                    Ok(months) if months >= 0 => {
+                        let before = (OffsetDateTime::now_utc()
+                            - time::Duration::days(30 * months))
+                        .format(&time::format_description::well_known::Rfc3339)?;
+                        let removed = db.remove_attachments_before(uuid, &before).await?;
+                        format!("Forgot {removed} attachments older than {months} months or of deleted entries; the entries are untouched.")
+                    }
+                    _ => {
+                        "Usage: /attachments cleanup {months}, e.g. /attachments cleanup 12".into()
+                    }
+                },
+                _ => "Usage: /attachments [forget {id}|cleanup {months}]".into(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Publicpage(args) => {
+            let text = match args.trim() {
+                "off" => {
+                    db.set_public_page_token(uuid, None).await?;
+                    "Your public page is off; its link no longer works.".to_string()
+                }
+                "on" | "new" if env::var("API_LISTEN").map_or(true, |a| a.is_empty()) => {
+                    "This bot doesn't run a web server, so it can't host public pages.".into()
+                }
+                "on" | "new" if !msg.chat.is_private() => {
+                    "Ask me for your page link in a private chat.".into()
+                }
+                arg @ ("on" | "new") => {
+                    let existing = db.public_page_token(uuid).await?;
+                    let token = match existing {
+                        Some(token) if arg == "on" => token,
+                        _ => {
+                            let token = api::new_page_token();
+                            db.set_public_page_token(uuid, Some(&token)).await?;
+                            token
+                        }
+                    };
+                    format!(
+                        "🌐 Your public page: {}\n\nAnyone with the link sees your streak and % to goal, never names or amounts. /publicpage new swaps the link, /publicpage off takes it down.",
+                        api::page_url(&token)
+                    )
+                }
+                "" => match db.public_page_token(uuid).await? {
+                    Some(_) if !msg.chat.is_private() => {
+                        "Your public page is on. Ask me for the link in a private chat.".into()
+                    }
+                    Some(token) => format!(
+                        "🌐 Your public page is on: {}\n/publicpage new swaps the link, /publicpage off takes it down.",
+                        api::page_url(&token)
+                    ),
+                    None => "Your public page is off. /publicpage on makes a shareable link showing only your streak and % to goal.".into(),
+                },
+                _ => "Usage: /publicpage [on|new|off]".into(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Tokens => {
+            let tokens = db.api_tokens(person).await?;
+            let text = if tokens.is_empty() {
+                "You have no API tokens. Create one with /token.".to_string()
+            } else {
+                let day = |t: &str| t.get(..10).unwrap_or(t).to_string();
+                tokens
+                    .iter()
+                    .map(|t| {
+                        format!(
+                            "#{} {} — created {}, {}, {}",
+                            t.id,
+                            t.scope,
+                            day(&t.created_at),
+                            t.expires_at
+                                .as_deref()
+                                .map(|e| format!("expires {}", day(e)))
+                                .unwrap_or_else(|| "never expires".into()),
+                            t.last_used_at
+                                .as_deref()
+                                .map(|u| format!("last used {}", day(u)))
+                                .unwrap_or_else(|| "never used".into()),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Donate(args) => {
+            let Some(token) = env::var("PAYMENT_PROVIDER_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty())
+            else {
+                bot.send_message(msg.chat.id, "Donations aren't set up on this bot.")
+                    .await?;
+                return Ok(());
+            };
+            let currency = env::var("DONATE_CURRENCY").unwrap_or_else(|_| "USD".into());
+            let amount_cents = match args.trim() {
+                "" => 500,
+                a => match parse_cents(a, comma) {
+                    Ok(c) if c > 0 => c,
+                    _ => {
+                        bot.send_message(msg.chat.id, "Usage: /donate [amount], e.g. /donate 5")
+                            .await?;
+                        return Ok(());
+                    }
+                },
+            };
+            bot.send_invoice(
+                msg.chat.id,
+                "Support this bot",
+                "A one-off donation towards running this instance. Thank you!",
+                format!("donation:{uuid}"),
+                token,
+                currency,
+                [LabeledPrice::new("Donation", amount_cents as i32)],
+            )
+            .await?;
+        }
+        Command::Help => {
+            bot.send_message(msg.chat.id, Command::descriptions().to_string())
+                .await?;
+        }
+        Command::Save(args) => {
+            if let (true, Some(voice)) = (
+                args.trim().is_empty(),
+                msg.reply_to_message().and_then(|m| m.voice()),
+            ) {
+                return propose_from_voice(&bot, db, msg.chat.id, uuid, voice).await;
+            }
+            // A photo captioned "/save [reason]" without an amount is a receipt.
+            if let Some(photo) = msg.photo().and_then(|sizes| sizes.last()) {
+                if parse_amount_and_reason(&args, false, comma).is_err() {
+                    let reason = Some(args.trim().to_string()).filter(|r| !r.is_empty());
+                    return propose_from_receipt(&bot, db, msg.chat.id, uuid, photo, reason).await;
+                }
+            }
+            let (amount_cents, reason) = parse_amount_and_reason(&args, false, comma)?;
+            let Some(converted) =
+                convert_foreign(&bot, msg.chat.id, db, currency, amount_cents, reason).await?
+            else {
+                return Ok(());
+            };
+            let amount_cents = converted.amount_cents;
+            let (reason, class) = split_class(converted.reason.clone());
+            let Some((occurred_at, reason)) = split_date(reason) else {
+                bot.send_message(msg.chat.id, BAD_DATE).await?;
+                return Ok(());
+            };
+            let (reason, guess) = categorize::prepare(db, uuid, reason).await?;
+            if amount_cents <= 0 {
+                bot.send_message(msg.chat.id, i18n::save_not_positive(lang))
+                    .await?;
+            } else {
+                let entry_id = db
+                    .add_entry(
+                        uuid,
+                        amount_cents,
+                        EntryKind::Save,
+                        reason.clone(),
+                        class,
+                        occurred_at,
+                    )
+                    .await?;
+                db.set_entry_source(entry_id, msg.chat.id.0, msg.id.0)
+                    .await?;
+                if let Some((cents, code)) = &converted.original {
+                    db.set_entry_original(entry_id, *cents, code).await?;
+                }
+                events::entry_added(db, uuid, amount_cents, EntryKind::Save, reason.as_deref())
+                    .await;
+                let total = db.total_cents(uuid).await?;
+                let mut text = i18n::saved(
+                    lang,
+                    &currency.format(amount_cents),
+                    &reason,
+                    &currency.format(total),
+                );
+                text.push_str(&conversion_note(&converted));
+                text.push_str(&calc_note(&args, comma));
+                if let Some(goal) = db.goal(uuid).await? {
+                    text.push_str(&goal_after_save(db, uuid, &goal, amount_cents, total).await?);
+                }
+                categorize::reply(&bot, db, msg.chat.id, text, entry_id, guess).await?;
+            }
+        }
+        Command::Adjust(args) => {
+            let (delta_cents, reason) = parse_amount_and_reason(&args, true, comma)?;
+            let Some(converted) =
+                convert_foreign(&bot, msg.chat.id, db, currency, delta_cents, reason).await?
+            else {
+                return Ok(());
+            };
+            let delta_cents = converted.amount_cents;
+            let (reason, class) = split_class(converted.reason.clone());
+            let Some((occurred_at, reason)) = split_date(reason) else {
+                bot.send_message(msg.chat.id, BAD_DATE).await?;
+                return Ok(());
+            };
+            let (reason, guess) = categorize::prepare(db, uuid, reason).await?;
+            if delta_cents == 0 {
+                bot.send_message(msg.chat.id, i18n::adjust_zero(lang))
+                    .await?;
+            } else {
+                let entry_id = db
+                    .add_entry(
+                        uuid,
+                        delta_cents,
+                        EntryKind::Adjust,
+                        reason.clone(),
+                        class,
+                        occurred_at,
+                    )
+                    .await?;
+                db.set_entry_source(entry_id, msg.chat.id.0, msg.id.0)
+                    .await?;
+                if let Some((cents, code)) = &converted.original {
+                    db.set_entry_original(entry_id, *cents, code).await?;
+                }
+                events::entry_added(db, uuid, delta_cents, EntryKind::Adjust, reason.as_deref())
+                    .await;
+                let total = db.total_cents(uuid).await?;
+                let text = i18n::adjusted(
+                    lang,
+                    delta_cents > 0,
+                    &currency.format(delta_cents.abs()),
+                    &reason,
+                    &currency.format(total),
+                ) + &conversion_note(&converted)
+                    + &calc_note(&args, comma);
+                categorize::reply(&bot, db, msg.chat.id, text, entry_id, guess).await?;
+            }
+        }
+        Command::Withdraw(args) => {
+            let (amount_cents, reason) = parse_amount_and_reason(&args, false, comma)?;
+            let Some(converted) =
+                convert_foreign(&bot, msg.chat.id, db, currency, amount_cents, reason).await?
+            else {
+                return Ok(());
+            };
+            let amount_cents = converted.amount_cents;
+            let (reason, class) = split_class(converted.reason.clone());
+            let Some((occurred_at, reason)) = split_date(reason) else {
+                bot.send_message(msg.chat.id, BAD_DATE).await?;
+                return Ok(());
+            };
+            let (reason, guess) = categorize::prepare(db, uuid, reason).await?;
+            let current = db.total_cents(uuid).await?;
+            if amount_cents <= 0 {
+                bot.send_message(msg.chat.id, i18n::withdraw_not_positive(lang))
+                    .await?;
+            } else if amount_cents > current {
+                bot.send_message(
+                    msg.chat.id,
+                    i18n::withdraw_exceeds(lang, &currency.format(current)),
+                )
+                .await?;
+            } else {
+                let entry_id = db
+                    .add_entry(
+                        uuid,
+                        -amount_cents,
+                        EntryKind::Withdraw,
+                        reason.clone(),
+                        class,
+                        occurred_at,
+                    )
+                    .await?;
+                db.set_entry_source(entry_id, msg.chat.id.0, msg.id.0)
+                    .await?;
+                if let Some((cents, code)) = &converted.original {
+                    db.set_entry_original(entry_id, -cents, code).await?;
+                }
+                events::entry_added(
+                    db,
+                    uuid,
+                    -amount_cents,
+                    EntryKind::Withdraw,
+                    reason.as_deref(),
+                )
+                .await;
+                let text = i18n::withdrew(
+                    lang,
+                    &currency.format(amount_cents),
+                    &reason,
+                    &currency.format(current - amount_cents),
+                ) + &conversion_note(&converted)
+                    + &calc_note(&args, comma);
+                categorize::reply(&bot, db, msg.chat.id, text, entry_id, guess).await?;
+            }
+        }
+        Command::Openingbalance(args) => {
+            let (amount_cents, rest) = parse_amount_and_reason(&args, false, comma)?;
+            let today = OffsetDateTime::now_utc().date();
+            let date = match rest.as_deref() {
+                None => Some(today),
+                Some(s) => parse_date(s),
+            };
+            let Some(date) = date.filter(|d| *d <= today) else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /openingbalance 1523.40 [YYYY-MM-DD] (date can't be in the future)",
+                )
+                .await?;
+                return Ok(());
+            };
+            if amount_cents <= 0 {
+                bot.send_message(msg.chat.id, "Opening balance must be positive.")
+                    .await?;
+            } else if db
+                .add_opening_balance(uuid, amount_cents, &format!("{date}T00:00:00Z"))
+                .await?
+            {
+                events::entry_added(
+                    db,
+                    uuid,
+                    amount_cents,
+                    EntryKind::Opening,
+                    Some("Opening balance"),
+                )
+                .await;
+                let total = db.total_cents(uuid).await?;
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "Opening balance of {} recorded as of {date}.\nTotal now: {}",
+                        format_cents(amount_cents),
+                        format_cents(total)
+                    ),
+                )
+                .await?;
+            } else {
+                bot.send_message(
+                    msg.chat.id,
+                    "You already have an opening balance. Use /adjust to correct it.",
+                )
+                .await?;
+            }
+        }
+        Command::Plan(args) => {
+            let args = args.trim();
+            if args.is_empty() {
+                let plans = db.user_plans(uuid).await?;
+                if plans.is_empty() {
+                    bot.send_message(
+                        msg.chat.id,
+                        "No planned entries. Add one with /plan 500 2025-01-15 bonus",
+                    )
+                    .await?;
+                } else {
+                    let mut report = Report::new("plans.txt");
+                    report.summary = format!("{} planned entries (attached)", plans.len());
+                    report.push("Planned entries:");
+                    for p in plans {
+                        report.push(format!(
+                            "#{} {} {}{}",
+                            p.id,
+                            p.due_on,
+                            format_signed_cents(p.amount_cents),
+                            p.reason.map(|r| format!(" — {r}")).unwrap_or_default()
+                        ));
+                    }
+                    render::send_report(&bot, msg.chat.id, report).await?;
+                }
+            } else if let Some(id) = args.strip_prefix("cancel") {
+                match id.trim().trim_start_matches('#').parse::<i64>() {
+                    Ok(id) if db.cancel_plan(uuid, id).await? => {
+                        bot.send_message(msg.chat.id, format!("Planned entry #{id} cancelled."))
+                            .await?;
+                    }
+                    _ => {
+                        bot.send_message(msg.chat.id, "No such planned entry of yours.")
+                            .await?;
+                    }
+                }
+            } else {
+                let (amount_cents, rest) = parse_amount_and_reason(args, true, comma)?;
+                let rest = rest.unwrap_or_default();
+                let (date, reason) = rest.split_once(char::is_whitespace).unwrap_or((&rest, ""));
+                let today = OffsetDateTime::now_utc().date();
+                let Some(date) = parse_date(date).filter(|d| *d > today) else {
+                    bot.send_message(
+                        msg.chat.id,
+                        "Usage: /plan 500 2025-01-15 bonus (the date must be in the future)",
+                    )
+                    .await?;
+                    return Ok(());
+                };
+                if amount_cents == 0 {
+                    bot.send_message(msg.chat.id, "Planned amount must be non-zero.")
+                        .await?;
+                    return Ok(());
+                }
+                let reason = Some(reason.trim().to_string()).filter(|r| !r.is_empty());
+                let id = db
+                    .add_plan(uuid, msg.chat.id.0, amount_cents, reason, &date.to_string())
+                    .await?;
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "Planned #{id}: {} on {date}. It won't count toward your total until then.",
+                        format_signed_cents(amount_cents)
+                    ),
+                )
+                .await?;
+            }
+        }
+        Command::Recurring(args) => {
+            let args = args.trim();
+            if args.is_empty() {
+                let orders = db.user_recurring(uuid).await?;
+                if orders.is_empty() {
+                    bot.send_message(
+                        msg.chat.id,
+                        "No standing orders. Add one with /recurring add 50 weekly payday stash",
+                    )
+                    .await?;
+                } else {
+                    let mut report = Report::new("recurring.txt");
+                    report.summary = format!("{} standing orders (attached)", orders.len());
+                    report.push("Standing orders:");
+                    for r in orders {
+                        report.push(format!(
+                            "#{} {} {}, next on {}{}",
+                            r.id,
+                            format_signed_cents(r.amount_cents),
+                            r.every,
+                            r.next_on,
+                            r.reason.map(|r| format!(" — {r}")).unwrap_or_default()
+                        ));
+                    }
+                    render::send_report(&bot, msg.chat.id, report).await?;
+                }
+            } else if let Some(id) = args.strip_prefix("cancel") {
+                match id.trim().trim_start_matches('#').parse::<i64>() {
+                    Ok(id) if db.cancel_recurring(uuid, id).await? => {
+                        bot.send_message(msg.chat.id, format!("Standing order #{id} cancelled."))
+                            .await?;
+                    }
+                    _ => {
+                        bot.send_message(msg.chat.id, "No such standing order of yours.")
+                            .await?;
+                    }
+                }
+            } else {
+                const USAGE: &str = "Usage: /recurring add 50 weekly [YYYY-MM-DD] payday stash (daily, weekly, biweekly or monthly; the first run is today unless you give a date)";
+                let Some(rest) = args.strip_prefix("add") else {
+                    bot.send_message(msg.chat.id, USAGE).await?;
+                    return Ok(());
+                };
+                let (amount_cents, rest) = match parse_amount_and_reason(rest.trim(), true, comma) {
+                    Ok(parsed) if parsed.0 != 0 => parsed,
+                    _ => {
+                        bot.send_message(msg.chat.id, USAGE).await?;
+                        return Ok(());
+                    }
+                };
+                let rest = rest.unwrap_or_default();
+                let (every, rest) = rest.split_once(char::is_whitespace).unwrap_or((&rest, ""));
+                let Some(every) = recurring::Every::parse(every) else {
+                    bot.send_message(msg.chat.id, USAGE).await?;
+                    return Ok(());
+                };
+                let rest = rest.trim();
+                let today = OffsetDateTime::now_utc().date();
+                let (first, reason) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let (start, reason) = match parse_date(first) {
+                    Some(date) if date >= today => (date, reason),
+                    Some(_) => {
+                        bot.send_message(msg.chat.id, "The first run can't be in the past.")
+                            .await?;
+                        return Ok(());
+                    }
+                    None => (today, rest),
+                };
+                let reason = reason.trim().trim_matches(['"', '“', '”']).trim();
+                let reason = Some(reason.to_string()).filter(|r| !r.is_empty());
+                let id = db
+                    .add_recurring(
+                        uuid,
+                        msg.chat.id.0,
+                        amount_cents,
+                        reason,
+                        every.as_str(),
+                        &start.to_string(),
+                    )
+                    .await?;
+                let first = if start == today {
+                    "today".to_string()
+                } else {
+                    start.to_string()
+                };
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "Standing order #{id}: {} {}, first on {first}. I'll let you know each time it's recorded.",
+                        format_signed_cents(amount_cents),
+                        every.as_str()
+                    ),
+                )
+                .await?;
+            }
+        }
+        Command::Forecast => {
+            let current = db.total_cents(uuid).await?;
+            let plans = db.user_plans(uuid).await?;
+            let mut report = Report::new("forecast.txt");
+            report.push(format!("Now: {}", format_cents(current)));
+            let mut running = current;
+            for p in &plans {
+                running += p.amount_cents;
+                report.push(format!(
+                    "{} {} → {}{}",
+                    p.due_on,
+                    format_signed_cents(p.amount_cents),
+                    format_cents(running),
+                    p.reason
+                        .as_ref()
+                        .map(|r| format!(" — {r}"))
+                        .unwrap_or_default()
+                ));
+            }
+            if plans.is_empty() {
+                report.push("No planned entries. Add one with /plan.");
+            }
+            let invested = db.history_total_cents(uuid).await?;
+            let fees = db.fees_total_cents(uuid).await?;
+            let ratio = db.expense_ratio_ppm(uuid).await?;
+            if invested != 0 || fees != 0 {
+                report.push(format!(
+                    "\nInvested: {}, {} net of {} recorded fees",
+                    format_cents(invested),
+                    format_cents(invested - fees),
+                    format_cents(fees)
+                ));
+            }
+            if ratio > 0 && invested > 0 {
+                let yearly = fees::annual_cost(invested - fees, ratio);
+                report.push(format!(
+                    "At a {} expense ratio the fund costs about {}/year, leaving {} after a year.",
+                    fees::format_ratio(ratio),
+                    format_cents(yearly),
+                    format_cents(invested - fees - yearly)
+                ));
+            }
+            report.summary = format!(
+                "Forecast: {} now, {} after {} planned entries (attached)",
+                format_cents(current),
+                format_cents(running),
+                plans.len()
+            );
+            render::send_report(&bot, msg.chat.id, report).await?;
+        }
+        Command::Fee(args) => {
+            let (amount_cents, reason) = parse_amount_and_reason(&args, false, comma)?;
+            let Some((occurred_at, reason)) = split_date(reason) else {
+                bot.send_message(msg.chat.id, BAD_DATE).await?;
+                return Ok(());
+            };
+            if amount_cents <= 0 {
+                bot.send_message(msg.chat.id, "Usage: /fee 1.50 [YYYY-MM-DD] [reason]")
+                    .await?;
+                return Ok(());
+            }
+            db.add_fee(uuid, amount_cents, reason, occurred_at).await?;
+            let invested = db.history_total_cents(uuid).await?;
+            let fees = db.fees_total_cents(uuid).await?;
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "Fee of {} recorded. Invested: {} net of {} in fees. See /fees for yearly totals.",
+                    format_cents(amount_cents),
+                    format_cents(invested - fees),
+                    format_cents(fees)
+                ),
+            )
+            .await?;
+        }
+        Command::Dividend(args) => {
+            let arg = args.trim();
+            if let Some(mode) = arg.strip_prefix("auto") {
+                match mode.trim() {
+                    "on" => db.set_dividends_to_stash(uuid, true).await?,
+                    "off" => db.set_dividends_to_stash(uuid, false).await?,
+                    _ => {
+                        bot.send_message(msg.chat.id, "Usage: /dividend auto on|off")
+                            .await?;
+                        return Ok(());
+                    }
+                }
+            }
+            if arg.is_empty() || arg.starts_with("auto") {
+                let totals = db.dividend_totals(uuid).await?;
+                let mut text = if totals.count == 0 {
+                    "No dividends yet. Record one with /dividend 12.34 [YYYY-MM-DD].".to_string()
+                } else {
+                    format!(
+                        "Dividends received: {} from {} payouts ({} added to your stash, {} reinvested).",
+                        currency.format(totals.total_cents),
+                        totals.count,
+                        currency.format(totals.to_stash_cents),
+                        currency.format(totals.total_cents - totals.to_stash_cents)
+                    )
+                };
+                text.push_str(if db.dividends_to_stash(uuid).await? {
+                    "\nNew dividends are added to your stash; /dividend auto off treats them as reinvested."
+                } else {
+                    "\nNew dividends count as reinvested; /dividend auto on adds them to your stash instead."
+                });
+                bot.send_message(msg.chat.id, text).await?;
+                return Ok(());
+            }
+            let (amount_cents, reason) = parse_amount_and_reason(arg, false, comma)?;
+            let Some((occurred_at, reason)) = split_date(reason) else {
+                bot.send_message(msg.chat.id, BAD_DATE).await?;
+                return Ok(());
+            };
+            if amount_cents <= 0 {
+                bot.send_message(msg.chat.id, "Usage: /dividend 12.34 [YYYY-MM-DD] [reason]")
+                    .await?;
+                return Ok(());
+            }
+            let received_on = match &occurred_at {
+                Some(at) => at[..10].to_string(),
+                None => OffsetDateTime::now_utc().date().to_string(),
+            };
+            let text = if db.dividends_to_stash(uuid).await? {
+                let entry_reason = reason.clone().unwrap_or_else(|| "Dividend".into());
+                let id = db
+                    .add_entry(
+                        uuid,
+                        amount_cents,
+                        EntryKind::Dividend,
+                        Some(entry_reason.clone()),
+                        None,
+                        occurred_at,
+                    )
+                    .await?;
+                events::entry_added(
+                    db,
+                    uuid,
+                    amount_cents,
+                    EntryKind::Dividend,
+                    Some(&entry_reason),
+                )
+                .await;
+                db.add_dividend(
+                    uuid,
+                    amount_cents,
+                    &received_on,
+                    reason.as_deref(),
+                    Some(id),
+                )
+                .await?;
+                format!(
+                    "💵 Dividend of {} on {received_on} added to your stash as entry #{id}.\nTotal now: {}",
+                    currency.format(amount_cents),
+                    currency.format(db.total_cents(uuid).await?)
+                )
+            } else {
+                db.add_dividend(uuid, amount_cents, &received_on, reason.as_deref(), None)
+                    .await?;
+                format!(
+                    "💵 Dividend of {} on {received_on} recorded as reinvested; your stash is unchanged.",
+                    currency.format(amount_cents)
+                )
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Fees(args) => {
+            let year = Some(args.trim()).filter(|y| !y.is_empty());
+            if year.is_some_and(|y| y.len() != 4 || y.parse::<i32>().is_err()) {
+                bot.send_message(msg.chat.id, "Usage: /fees [YYYY]").await?;
+                return Ok(());
+            }
+            let mut lines = fees::yearly(db, uuid, year).await?;
+            if lines.is_empty() {
+                lines.push(
+                    "No fees yet. Record one with /fee, or set your fund's /expenseratio.".into(),
+                );
+            } else {
+                lines.insert(0, "💸 Fees per year".into());
+            }
+            let ratio = db.expense_ratio_ppm(uuid).await?;
+            if ratio > 0 {
+                lines.push(format!(
+                    "\nExpense ratio estimates use your current {} on each day's invested total.",
+                    fees::format_ratio(ratio)
+                ));
+            }
+            bot.send_message(msg.chat.id, lines.join("\n")).await?;
+        }
+        Command::Expenseratio(args) => {
+            let arg = args.trim();
+            let text = if arg.is_empty() {
+                match db.expense_ratio_ppm(uuid).await? {
+                    0 => "No expense ratio set. Try /expenseratio 0.03%".to_string(),
+                    ppm => format!(
+                        "Your fund's expense ratio is {} a year.",
+                        fees::format_ratio(ppm)
+                    ),
+                }
+            } else {
+                let ppm = if arg.eq_ignore_ascii_case("off") {
+                    Some(0)
+                } else {
+                    fees::parse_ratio(arg)
+                };
+                match ppm {
+                    Some(ppm) => {
+                        db.set_expense_ratio_ppm(uuid, ppm).await?;
+                        if ppm == 0 {
+                            "Expense ratio cleared.".to_string()
+                        } else {
+                            format!(
+                                "Got it: {} a year. /forecast and /fees now count it.",
+                                fees::format_ratio(ppm)
+                            )
+                        }
+                    }
+                    None => "Usage: /expenseratio 0.03% (0–10) or /expenseratio off".to_string(),
+                }
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Autocategory(args) => {
+            let arg = args.trim().to_lowercase();
+            let text = if arg.is_empty() {
+                let mode = db.autocategory(uuid).await?;
+                format!("Automatic categories: {mode}. Change with /autocategory suggest, apply or off.")
+            } else if let Some(mode) = categorize::Mode::parse(&arg) {
+                db.set_autocategory(uuid, mode.as_str()).await?;
+                match mode {
+                    categorize::Mode::Suggest => "🏷 I'll suggest a #category with a button when an entry looks like earlier ones.",
+                    categorize::Mode::Apply => "🏷 I'll add the #category myself when an entry looks like earlier ones, with an undo button.",
+                    categorize::Mode::Off => "Automatic categories are off.",
+                }
+                .to_string()
+            } else {
+                "Usage: /autocategory suggest | apply | off".to_string()
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Interest(args) => {
+            let arg = args.trim().trim_end_matches('%').trim();
+            if arg.is_empty() {
+                let rate = db.interest_rate(uuid).await?;
+                let text = if rate == 0 {
+                    "Interest simulation is off. Turn it on with /interest 4.0%".to_string()
+                } else {
+                    format!(
+                        "Your stash earns {}% APR, credited monthly as interest entries.",
+                        format_cents(rate)
+                    )
+                };
+                bot.send_message(msg.chat.id, text).await?;
+                return Ok(());
+            }
+            let rate = if arg.eq_ignore_ascii_case("off") {
+                Some(0)
+            } else {
+                parse_cents(arg, comma)
+                    .ok()
+                    .filter(|bp| (0..=10_000).contains(bp))
+            };
+            let Some(rate) = rate else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /interest 4.0% (0–100) or /interest off",
+                )
+                .await?;
+                return Ok(());
+            };
+            let month = OffsetDateTime::now_utc()
+                .format(format_description!("[year]-[month]"))
+                .unwrap_or_default();
+            db.set_interest_rate(uuid, rate, msg.chat.id.0, &month)
+                .await?;
+            let text = if rate == 0 {
+                "Interest simulation turned off.".to_string()
+            } else {
+                format!(
+                    "Got it: {}% APR. Interest on your current total is credited at the start of each month.",
+                    format_cents(rate)
+                )
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Goal(args) => {
+            let args = args.trim();
+            if args == "clear" {
+                let text = if db.clear_goal(uuid).await? {
+                    "Goal cleared."
+                } else {
+                    "You have no goal to clear."
+                };
+                bot.send_message(msg.chat.id, text).await?;
+                return Ok(());
+            } else if let Some(rest) = args.strip_prefix("checkin") {
+                let rest = rest.trim().to_lowercase();
+                let text = if db.goal(uuid).await?.is_none() {
+                    "No goal yet. Set one with /goal set 5000 2025-12-31 New laptop".to_string()
+                } else if rest == "off" || rest == "pause" {
+                    if db.pause_goal_checkin(uuid).await? {
+                        "⏸ Goal check-ins paused.".to_string()
+                    } else {
+                        "Goal check-ins are already off.".to_string()
+                    }
+                } else if rest.is_empty() {
+                    match db.goal_checkin(uuid).await? {
+                        Some(r) => format!(
+                            "📅 Goal check-ins {} ({}).",
+                            reminders::describe(r.weekday, r.minute_of_day),
+                            streaks::format_offset(user_offset(db, uuid).await?)
+                        ),
+                        None => "Goal check-ins are off. Try /goal checkin weekly sun 10:00."
+                            .to_string(),
+                    }
+                } else if let Some((weekday, minute_of_day)) = reminders::parse(&rest) {
+                    let mut checkin = db::Reminder {
+                        user_id: uuid,
+                        chat_id: msg.chat.id.0,
+                        weekday,
+                        minute_of_day,
+                        utc_offset_minutes: db.utc_offset_minutes(uuid).await?,
+                        last_sent_on: None,
+                    };
+                    checkin.last_sent_on = reminders::due_on(&checkin, OffsetDateTime::now_utc());
+                    db.set_goal_checkin(&checkin, false).await?;
+                    format!(
+                        "📅 I'll check in on your goal here {} ({}).",
+                        reminders::describe(weekday, minute_of_day),
+                        streaks::format_offset(user_offset(db, uuid).await?)
+                    )
+                } else {
+                    "Usage: /goal checkin daily 20:00 | /goal checkin weekly sun 10:00 | /goal checkin off"
+                        .to_string()
+                };
+                bot.send_message(msg.chat.id, text).await?;
+                return Ok(());
+            } else if let Some(rest) = args.strip_prefix("set") {
+                let (target_cents, rest) = parse_amount_and_reason(rest, false, comma)?;
+                let today = OffsetDateTime::now_utc().date();
+                let rest = rest.unwrap_or_default();
+                let (first, tail) = rest.split_once(char::is_whitespace).unwrap_or((&rest, ""));
+                let (deadline, name) = match parse_date(first) {
+                    Some(d) if d > today => (Some(d.to_string()), tail.trim()),
+                    Some(_) => {
+                        bot.send_message(msg.chat.id, "The goal deadline must be in the future.")
+                            .await?;
+                        return Ok(());
+                    }
+                    None => (None, rest.trim()),
+                };
+                if target_cents <= 0 {
+                    bot.send_message(msg.chat.id, "Goal amount must be positive.")
+                        .await?;
+                    return Ok(());
+                }
+                let name = Some(name.to_string()).filter(|n| !n.is_empty());
+                db.set_goal(uuid, target_cents, name, deadline).await?;
+                let mut checkin = db::Reminder {
+                    user_id: uuid,
+                    chat_id: msg.chat.id.0,
+                    weekday: Some(goals::CHECKIN_WEEKDAY),
+                    minute_of_day: goals::CHECKIN_MINUTE,
+                    utc_offset_minutes: db.utc_offset_minutes(uuid).await?,
+                    last_sent_on: None,
+                };
+                checkin.last_sent_on = reminders::due_on(&checkin, OffsetDateTime::now_utc());
+                if db.set_goal_checkin(&checkin, true).await? {
+                    let total = db.total_cents(uuid).await?;
+                    let goal = db.goal(uuid).await?.context("goal just set")?;
+                    let text = format!(
+                        "{}\n\n📅 I'll check in on it here {}. Change that with /goal checkin.",
+                        goals::status(db, uuid, &goal, total).await?,
+                        reminders::describe(checkin.weekday, checkin.minute_of_day)
+                    );
+                    bot.send_message(msg.chat.id, text).await?;
+                    return Ok(());
+                }
+            } else if !args.is_empty() && args != "status" {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /goal set 5000 [2025-12-31] [name], /goal checkin, /goal clear, or /goal status to see progress",
+                )
+                .await?;
+                return Ok(());
+            }
+            let text = match db.goal(uuid).await? {
+                Some(goal) => {
+                    let total = db.total_cents(uuid).await?;
+                    goals::status(db, uuid, &goal, total).await?
+                }
+                None => "No goal yet. Set one with /goal set 5000 2025-12-31 New laptop".into(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Budget(args) => {
+            let month = month_arg("").context("formatting current month")?;
+            let args: Vec<&str> = args.split_whitespace().collect();
+            match args.as_slice() {
+                [] => {}
+                // `/budget 300`: a saving target rather than an envelope.
+                [arg] if *arg == "off" || parse_cents(arg, comma).is_ok() => {
+                    let cents = match parse_cents(arg, comma) {
+                        Ok(cents) if cents > 0 => Some(cents),
+                        Ok(_) => {
+                            bot.send_message(msg.chat.id, "A saving target needs a positive amount, e.g. /budget 300; /budget off removes it.")
+                                .await?;
+                            return Ok(());
+                        }
+                        Err(_) => None,
+                    };
+                    let today = OffsetDateTime::now_utc().date();
+                    let last_month = stats::Span::Month
+                        .containing(today)
+                        .and_then(|m| stats::Span::Month.previous(&m))
+                        .map(|m| m.label)
+                        .unwrap_or_default();
+                    db.set_saving_target(uuid, cents, &last_month).await?;
+                }
+                [category, rest @ ..] => {
+                    let category = category.trim_start_matches('#').to_lowercase();
+                    let parsed = match rest {
+                        ["off"] => Some((Ok(0), false)),
+                        [amount] => Some((parse_cents(amount, comma), false)),
+                        [amount, "rollover"] => Some((parse_cents(amount, comma), true)),
+                        _ => None,
+                    };
+                    let (amount_cents, rollover) = match parsed {
+                        Some((Ok(cents), rollover)) if cents >= 0 && !category.is_empty() => {
+                            (cents, rollover)
+                        }
+                        _ => {
+                            bot.send_message(
+                                msg.chat.id,
+                                "Usage: /budget food 200 [rollover], /budget food off, /budget 300 for a saving target, or /budget to see this month",
+                            )
+                            .await?;
+                            return Ok(());
+                        }
+                    };
+                    // Roll earlier months forward first so this month's carry is kept.
+                    let carry_cents = budgets::for_month(db, uuid, &month)
+                        .await?
+                        .into_iter()
+                        .find(|e| e.budget.category == category)
+                        .map_or(0, |e| e.budget.carry_cents);
+                    let budget = db::Budget {
+                        category,
+                        month: month.clone(),
+                        amount_cents,
+                        rollover,
+                        carry_cents: if amount_cents == 0 { 0 } else { carry_cents },
+                    };
+                    db.put_budget(uuid, &budget).await?;
+                }
+            }
+            let envelopes = budgets::for_month(db, uuid, &month).await?;
+            let target = match db.saving_target(uuid).await? {
+                Some(target) => Some(format!(
+                    "Saving target for {month}: {}",
+                    budgets::saving_line(target, db.saved_in_month(uuid, &month).await?)
+                )),
+                None => None,
+            };
+            let text = if envelopes.is_empty() && target.is_none() {
+                "No budgets yet. Try /budget food 200 rollover, and tag spending like /adjust -12 lunch #food, or set a monthly saving target with /budget 300".into()
+            } else {
+                let mut lines: Vec<String> = target.into_iter().collect();
+                if !envelopes.is_empty() {
+                    lines.push(format!("Budgets for {month} (spent / available):"));
+                    lines.extend(envelopes.iter().map(|e| e.line()));
+                }
+                lines.join("\n")
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Chart(args) => match args.trim() {
+            arg if matches!(
+                arg.trim_start_matches("savings").trim(),
+                "" | "week" | "month" | "year" | "all"
+            ) =>
+            {
+                let span = arg.trim_start_matches("savings").trim();
+                let today = OffsetDateTime::now_utc()
+                    .to_offset(user_offset(db, uuid).await?)
+                    .date();
+                let period = stats::Span::parse(span)
+                    .filter(|_| !span.is_empty())
+                    .and_then(|s| s.containing(today));
+                let from = period.as_ref().map(|p| p.start_str()).unwrap_or_default();
+                let monthly = !matches!(span, "week" | "month");
+                let mut running = db.balance_before(uuid, &from).await?;
+                let mut points: Vec<(Date, i64)> = period
+                    .as_ref()
+                    .map(|p| (p.start, running))
+                    .into_iter()
+                    .collect();
+                for (bucket, net) in db.net_by_bucket(uuid, monthly, &from).await? {
+                    let day = if monthly {
+                        format!("{bucket}-01")
+                    } else {
+                        bucket
+                    };
+                    let Some(day) = parse_date(&day) else {
+                        continue;
+                    };
+                    running += net;
+                    // A month's point sits on its first day; don't go back
+                    // before the period's own start point.
+                    match points.last_mut() {
+                        Some(last) if last.0 >= day => last.1 = running,
+                        _ => points.push((day, running)),
+                    }
+                }
+                if points.is_empty() {
+                    bot.send_message(msg.chat.id, i18n::no_entries(lang))
+                        .await?;
+                    return Ok(());
+                }
+                if points.last().map(|p| p.0) < Some(today) {
+                    points.push((today, running));
+                }
+                let title = match &period {
+                    Some(p) => format!("Savings over time, {}", p.label),
+                    None => "Savings over time".to_string(),
+                };
+                let png = chart::line_chart(&title, &points)?;
+                bot.send_photo(msg.chat.id, InputFile::memory(png).file_name("savings.png"))
+                    .caption(format!("Total now: {}", format_cents(running)))
+                    .await?;
+            }
+            "networth" => {
+                let mut points: Vec<(Date, i64)> = db
+                    .snapshots(uuid)
+                    .await?
+                    .into_iter()
+                    .filter_map(|s| Some((parse_date(&s.day)?, s.cash_cents + s.invested_cents)))
+                    .collect();
+                // Today's snapshot is how the day started; end the line at
+                // today's live value instead.
+                let today = OffsetDateTime::now_utc().date();
+                if points.last().map(|p| p.0) == Some(today) {
+                    points.pop();
+                }
+                let live = db.total_cents(uuid).await? + db.history_total_cents(uuid).await?;
+                points.push((today, live));
+                let png = chart::line_chart("Net worth (cash + invested at cost)", &points)?;
+                bot.send_photo(
+                    msg.chat.id,
+                    InputFile::memory(png).file_name("networth.png"),
+                )
+                .await?;
+            }
+            "heatmap" => {
+                let today = OffsetDateTime::now_utc().date();
+                let from = today - time::Duration::days(371);
+                let days: Vec<(Date, i64)> = db
+                    .daily_net(uuid, &from.to_string())
+                    .await?
+                    .into_iter()
+                    .filter_map(|(day, net)| Some((parse_date(&day)?, net)))
+                    .collect();
+                let png =
+                    chart::heatmap("Saving (green) and spending (red), past year", &days, today)?;
+                bot.send_photo(msg.chat.id, InputFile::memory(png).file_name("heatmap.png"))
+                    .await?;
+            }
+            arg if arg.starts_with("categories") => {
+                let Some(month) = month_arg(arg.trim_start_matches("categories")) else {
+                    bot.send_message(msg.chat.id, "Usage: /chart categories [YYYY-MM]")
+                        .await?;
+                    return Ok(());
+                };
+                let mut by_category = budgets::spending_by_category(db, uuid, &month).await?;
+                if by_category.is_empty() {
+                    bot.send_message(
+                        msg.chat.id,
+                        format!("No spending recorded for {month}. Tag spending like /adjust -12 lunch #food"),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                by_category.sort_by_key(|c| std::cmp::Reverse(c.1));
+                // Keep the pie readable: fold the long tail into one slice.
+                if by_category.len() > 8 {
+                    let other: i64 = by_category.drain(7..).map(|c| c.1).sum();
+                    by_category.push(("other".into(), other));
+                }
+                let png = chart::pie(&format!("Spending by category, {month}"), &by_category)?;
+                let caption = budgets::for_month(db, uuid, &month)
+                    .await?
+                    .iter()
+                    .map(|e| e.line())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                bot.send_photo(
+                    msg.chat.id,
+                    InputFile::memory(png).file_name("categories.png"),
+                )
+                .caption(caption)
+                .await?;
+            }
+            _ => {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /chart [week|month|year|all], or /chart networth|heatmap|categories [YYYY-MM]",
+                )
+                .await?;
+            }
+        },
+        Command::Stats(args) => {
+            let (by_tag, span) = match args.trim().strip_prefix("tags") {
+                Some(rest) => (true, rest.trim()),
+                None => (false, args.trim()),
+            };
+            let Some(span) = stats::Span::parse(span).map(Some).or_else(|| {
+                // Tag totals can also cover all time.
+                (by_tag && span == "all").then_some(None)
+            }) else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /stats [week|month|year] or /stats tags [week|month|year|all]",
+                )
+                .await?;
+                return Ok(());
+            };
+            let today = OffsetDateTime::now_utc()
+                .to_offset(user_offset(db, uuid).await?)
+                .date();
+            let cur = span.and_then(|s| s.containing(today));
+            if by_tag {
+                let (start, end, label) = match &cur {
+                    Some(p) => (p.start_str(), p.end_str(), p.label.clone()),
+                    None => (String::new(), "9999".to_string(), "all time".to_string()),
+                };
+                let totals = db.tag_totals(uuid, &start, &end).await?;
+                let mut lines = vec![format!("🏷 Totals per tag, {label}")];
+                if totals.is_empty() {
+                    lines.push("No tagged entries. Tag them like /save 12.50 lunch #food".into());
+                }
+                for (tag, net, n) in totals {
+                    let entries = if n == 1 { "entry" } else { "entries" };
+                    lines.push(format!(
+                        "#{tag}: {} ({n} {entries})",
+                        format_signed_cents(net)
+                    ));
+                }
+                bot.send_message(msg.chat.id, lines.join("\n")).await?;
+                return Ok(());
+            }
+            let (Some(span), Some(cur)) = (span, cur) else {
+                return Ok(());
+            };
+            let Some(prev) = span.previous(&cur) else {
+                return Ok(());
+            };
+            let sc = db
+                .period_summary(uuid, &cur.start_str(), &cur.end_str())
+                .await?;
+            let counts = db
+                .period_stats(uuid, &cur.start_str(), &cur.end_str())
+                .await?;
+            let sp = db
+                .period_summary(uuid, &prev.start_str(), &prev.end_str())
+                .await?;
+            bot.send_message(
+                msg.chat.id,
+                stats::report(&cur, &sc, &counts, today, &prev, &sp),
+            )
+            .await?;
+        }
+        Command::Compareperiods(args) => {
+            let periods: Vec<stats::Period> = args
+                .split_whitespace()
+                .filter_map(stats::Period::parse)
+                .collect();
+            let [a, b] = periods.as_slice() else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /compareperiods 2024-05 2024-06 (months YYYY-MM or years YYYY)",
+                )
+                .await?;
+                return Ok(());
+            };
+            let sa = db
+                .period_summary(uuid, &a.start_str(), &a.end_str())
+                .await?;
+            let sb = db
+                .period_summary(uuid, &b.start_str(), &b.end_str())
+                .await?;
+            bot.send_message(msg.chat.id, stats::compare(a, &sa, b, &sb))
+                .await?;
+        }
+        Command::Ticker(args) => {
+            let words: Vec<&str> = args.split_whitespace().collect();
+            match words.split_first() {
+                None => {}
+                Some((&"reset", [])) => db.set_allocation_targets(uuid, &[]).await?,
+                Some((&"set", rest)) if !rest.is_empty() => {
+                    let Some(tickers) = allocation::parse_tickers(rest) else {
+                        bot.send_message(
+                            msg.chat.id,
+                            "Usage: /ticker set VTI, or /ticker set VTI 60 VXUS 40 (whole percents adding up to 100)",
+                        )
+                        .await?;
+                        return Ok(());
+                    };
+                    db.set_allocation_targets(uuid, &tickers).await?;
+                }
+                Some(_) => {
+                    bot.send_message(
+                        msg.chat.id,
+                        "Usage: /ticker, /ticker set VTI 60 VXUS 40 or /ticker reset",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+            let tickers = invest_tickers(db, uuid).await?;
+            bot.send_message(
+                msg.chat.id,
+                format!("/allinvoo invests into {}.", allocation::label(&tickers)),
+            )
+            .await?;
+        }
+        Command::Target(args) => {
+            let words: Vec<&str> = args.split_whitespace().collect();
+            if !words.is_empty() {
+                let Some(targets) = allocation::parse_targets(&words) else {
+                    bot.send_message(
+                        msg.chat.id,
+                        "Usage: /target VOO 80 VXUS 20 (whole percents adding up to 100)",
+                    )
+                    .await?;
+                    return Ok(());
+                };
+                db.set_allocation_targets(uuid, &targets).await?;
+            }
+            let targets = db.allocation_targets(uuid).await?;
+            let text = if targets.is_empty() {
+                "No targets set. Example: /target VOO 80 VXUS 20".to_string()
+            } else {
+                let parts: Vec<String> = targets.iter().map(|(t, p)| format!("{t} {p}%")).collect();
+                format!("Target allocation: {}", parts.join(", "))
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Rebalance => {
+            let targets = db.allocation_targets(uuid).await?;
+            let cash = db.total_cents(uuid).await?;
+            if targets.is_empty() {
+                bot.send_message(
+                    msg.chat.id,
+                    "Set targets first, e.g. /target VOO 80 VXUS 20",
+                )
+                .await?;
+            } else if cash <= 0 {
+                bot.send_message(msg.chat.id, i18n::nothing_to_invest(lang))
+                    .await?;
+            } else {
+                // Batches with recorded shares count per ticker at cost; what
+                // was invested before prices were recorded counts as VOO.
+                let mut holdings: Vec<(String, i64)> = Vec::new();
+                for h in db.holdings(uuid).await? {
+                    match holdings.iter_mut().find(|(t, _)| *t == h.ticker) {
+                        Some((_, cents)) => *cents += h.cost_cents,
+                        None => holdings.push((h.ticker, h.cost_cents)),
+                    }
+                }
+                let priced: i64 = holdings.iter().map(|h| h.1).sum();
+                let untracked = db.history_total_cents(uuid).await? - priced;
+                if untracked > 0 {
+                    holdings.push((allocation::DEFAULT_TICKER.to_string(), untracked));
+                }
+                let split = allocation::split_contribution(&holdings, &targets, cash);
+                let mut lines = vec![format!(
+                    "Suggested split of your next {} invest:",
+                    format_cents(cash)
+                )];
+                for (ticker, cents) in split {
+                    lines.push(format!("{ticker}: {}", format_cents(cents)));
+                }
+                bot.send_message(msg.chat.id, lines.join("\n")).await?;
+            }
+        }
+        Command::Close(args) => {
+            let today = OffsetDateTime::now_utc()
+                .to_offset(user_offset(db, uuid).await?)
+                .date();
+            let this_month = stats::Span::Month.containing(today);
+            let month = match args.trim() {
+                "" | "month" => this_month.and_then(|m| stats::Span::Month.previous(&m)),
+                arg => stats::Period::parse(arg).filter(|p| p.label.len() == 7 && p.start <= today),
+            };
+            let Some(month) = month else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /close [YYYY-MM] (a month that has started; default: last month)",
+                )
+                .await?;
+                return Ok(());
+            };
+            if let Some(closed) = db.month_close(uuid, &month.label).await? {
+                if let (Some(statement), Some(at)) = (closed.statement, closed.closed_at) {
+                    render::send_file(
+                        &bot,
+                        msg.chat.id,
+                        &format!("statement-{}.txt", month.label),
+                        statement.into_bytes(),
+                        &format!(
+                            "{} was closed on {}. Here is its statement.",
+                            month.label,
+                            at.get(..10).unwrap_or(&at)
+                        ),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+            let step = close::Step::Review;
+            db.set_close_step(uuid, &month.label, step.as_str()).await?;
+            bot.send_message(msg.chat.id, close::prompt(db, uuid, &month, step).await?)
+                .reply_markup(close::keyboard(&month.label, step))
+                .await?;
+        }
+        Command::Allinvoo(args) => {
+            let mut preview = false;
+            let mut strategy = None;
+            let mut amount = None;
+            for word in args.split_whitespace() {
+                if word == "preview" {
+                    preview = true;
+                } else if let (None, Ok(cents)) = (amount, parse_cents(word, comma)) {
+                    amount = Some(cents);
+                } else if strategy.is_none() {
+                    strategy = Some(word.to_lowercase());
+                } else {
+                    bot.send_message(
+                        msg.chat.id,
+                        "Usage: /allinvoo [preview] [amount] [strategy]",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+            if amount.is_some_and(|cents| cents <= 0) {
+                bot.send_message(msg.chat.id, "The amount to invest must be positive.")
+                    .await?;
+                return Ok(());
+            }
+            let strategy = match strategy {
+                Some(slug) => {
+                    match db.strategy_name(uuid, &slug).await? {
+                        Some(name) => Some((slug, name)),
+                        None => {
+                            bot.send_message(
+                            msg.chat.id,
+                            format!("No strategy called {slug}. Create it with /strategy add {slug}"),
+                        )
+                        .await?;
+                            return Ok(());
+                        }
+                    }
+                }
+                None => None,
+            };
+            let strategy_line = |total: i64| {
+                strategy
+                    .as_ref()
+                    .map(|(_, name)| {
+                        format!(
+                            "\n{}",
+                            i18n::strategy_total(lang, name, &currency.format(total))
+                        )
+                    })
+                    .unwrap_or_default()
+            };
+            let plan = AllInVooPlan::load(db, uuid, amount).await?;
+            if plan.moved_cents == 0 {
+                bot.send_message(msg.chat.id, i18n::nothing_to_invest(lang))
+                    .await?;
+            } else if amount.is_some_and(|cents| cents > plan.moved_cents) {
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "You only have {} uninvested. Use /allinvoo to invest all of it.",
+                        currency.format(plan.moved_cents)
+                    ),
+                )
+                .await?;
+            } else if preview {
+                let tickers = invest_tickers(db, uuid).await?;
+                let mut shares = Vec::new();
+                for (ticker, cents) in
+                    allocation::split_contribution(&[], &tickers, plan.moved_cents)
+                {
+                    match market::price_cents(&ticker).await {
+                        Ok(Some(price)) => shares.push((
+                            ticker,
+                            format!("{:.4}", cents as f64 / price as f64),
+                            format_cents(price),
+                        )),
+                        Ok(None) => {}
+                        Err(err) => eprintln!("allinvoo preview: {ticker} price: {err:?}"),
+                    }
+                }
+                let bucket = match &strategy {
+                    Some((slug, _)) => strategy_total(db, uuid, Some(slug)).await?,
+                    None => 0,
+                };
+                let text = i18n::invest_preview(
+                    lang,
+                    &currency.format(plan.moved_cents),
+                    plan.entries,
+                    &currency.format(plan.current_after_cents),
+                    &currency.format(plan.history_after_cents),
+                    &allocation::label(&tickers),
+                    &shares,
+                );
+                bot.send_message(
+                    msg.chat.id,
+                    text + &strategy_line(bucket + plan.moved_cents),
+                )
+                .await?;
+            } else {
+                let slug = strategy.as_ref().map(|(slug, _)| slug.as_str());
+                let id = db.add_pending_invest(uuid, plan.moved_cents, slug).await?;
+                let keyboard = InlineKeyboardMarkup::new([[
+                    InlineKeyboardButton::callback("✅ Invest", format!("invest:{id}:ok")),
+                    InlineKeyboardButton::callback("❌ Cancel", format!("invest:{id}:no")),
+                ]]);
+                let name = strategy.as_ref().map(|(_, name)| name.as_str());
+                bot.send_message(
+                    msg.chat.id,
+                    i18n::invest_confirm(
+                        lang,
+                        &currency.format(plan.moved_cents),
+                        &allocation::label(&invest_tickers(db, uuid).await?),
+                        name,
+                    ),
+                )
+                .reply_markup(keyboard)
+                .await?;
+            }
+        }
+        Command::Strategy(args) => {
+            let args = args.trim();
+            let (action, name) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            let name = name.trim();
+            let slug = name.to_lowercase();
+            let text = match action {
+                "" => {
+                    let totals = db.strategy_totals(uuid).await?;
+                    if totals.len() == 1 {
+                        "Everything goes into one bucket. Add another with /strategy add house, then /allinvoo house.".to_string()
+                    } else {
+                        let mut lines = vec!["Strategies:".to_string()];
+                        for (name, total) in totals {
+                            let name = name.unwrap_or_else(|| "Main".into());
+                            lines.push(format!("{name}: {}", format_cents(total)));
+                        }
+                        lines.join("\n")
+                    }
+                }
+                "add" if valid_strategy_name(name) => {
+                    if db.add_strategy(uuid, &slug, name).await? {
+                        format!("Strategy {name} added. Invest into it with /allinvoo {slug} and see it with /portfolio {slug}.")
+                    } else {
+                        format!("You already have a strategy called {name}.")
+                    }
+                }
+                "remove" if valid_strategy_name(name) => {
+                    let batches = db.invest_batches(uuid, Some(&slug)).await?;
+                    if db.strategy_name(uuid, &slug).await?.is_none() {
+                        format!("No strategy called {name}.")
+                    } else if batches.is_empty() {
+                        db.remove_strategy(uuid, &slug).await?;
+                        format!("Strategy {name} removed.")
+                    } else {
+                        let total: i64 = batches.iter().map(|(_, cents, _)| cents).sum();
+                        format!(
+                            "{name} holds {} of invested history, so it stays.",
+                            format_cents(total)
+                        )
+                    }
+                }
+                _ => "Usage: /strategy, /strategy add {name} or /strategy remove {name}. Names are one word, e.g. house".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Portfolio(args) => {
+            let slug = args.trim().to_lowercase();
+            let text = if slug.is_empty() || slug == "fx" {
+                let totals = db.strategy_totals(uuid).await?;
+                let all: i64 = totals.iter().map(|(_, total)| total).sum();
+                let mut lines = vec![format!("📊 Invested: {}", format_cents(all))];
+                for (name, total) in totals {
+                    let name = name.unwrap_or_else(|| "Main".into());
+                    let share = if all > 0 { total * 100 / all } else { 0 };
+                    lines.push(format!("{name}: {} ({share}%)", format_cents(total)));
+                }
+                let holdings = db.holdings(uuid).await?;
+                if !holdings.is_empty() {
+                    // Buckets can hold the same ticker, so add them up.
+                    let mut held: Vec<db::Holding> = Vec::new();
+                    for h in holdings {
+                        match held.iter_mut().find(|x| x.ticker == h.ticker) {
+                            Some(x) => {
+                                x.micro_shares += h.micro_shares;
+                                x.cost_cents += h.cost_cents;
+                            }
+                            None => held.push(h),
+                        }
+                    }
+                    lines.push(String::new());
+                    let rate = hedged::value_rate(db, currency, "portfolio").await;
+                    for h in &held {
+                        lines.push(holding_line(h, market_price(&h.ticker).await, rate));
+                    }
+                    let priced: i64 = held.iter().map(|h| h.cost_cents).sum();
+                    if all > priced {
+                        lines.push(format!(
+                            "{} was invested before share prices were recorded.",
+                            format_cents(all - priced)
+                        ));
+                    }
+                }
+                let dividends = db.dividend_totals(uuid).await?;
+                if dividends.count > 0 {
+                    lines.push(String::new());
+                    lines.push(format!(
+                        "Dividends: {} ({} reinvested, {} added to your stash)",
+                        format_cents(dividends.total_cents),
+                        format_cents(dividends.total_cents - dividends.to_stash_cents),
+                        format_cents(dividends.to_stash_cents)
+                    ));
+                }
+                if slug == "fx" {
+                    lines.push(String::new());
+                    lines.extend(hedged::report(db, uuid, currency).await?);
+                }
+                lines.join("\n")
+            } else {
+                let (slug, name) = if slug == "main" {
+                    (None, "Main".to_string())
+                } else {
+                    match db.strategy_name(uuid, &slug).await? {
+                        Some(name) => (Some(slug), name),
+                        None => {
+                            bot.send_message(
+                                msg.chat.id,
+                                format!("No strategy called {slug}. See yours with /strategy."),
+                            )
+                            .await?;
+                            return Ok(());
+                        }
+                    }
+                };
+                let batches = db.invest_batches(uuid, slug.as_deref()).await?;
+                let total: i64 = batches.iter().map(|(_, cents, _)| cents).sum();
+                let mut lines = vec![format!("📊 {name}: {} invested", format_cents(total))];
+                let rate = hedged::value_rate(db, currency, "portfolio").await;
+                for h in db.holdings(uuid).await? {
+                    if h.strategy == slug {
+                        lines.push(holding_line(&h, market_price(&h.ticker).await, rate));
+                    }
+                }
+                if batches.is_empty() {
+                    lines.push("Nothing invested here yet.".into());
+                }
+                for (at, cents, entries) in batches {
+                    lines.push(format!(
+                        "{} {} from {entries} entries",
+                        at.get(..10).unwrap_or(&at),
+                        format_cents(cents)
+                    ));
+                }
+                lines.join("\n")
+            };
+            let mut report = Report::new("portfolio.txt");
+            report.summary = text.lines().next().unwrap_or_default().to_string();
+            report.lines = text.lines().map(str::to_string).collect();
+            render::send_report(&bot, msg.chat.id, report).await?;
+        }
+        Command::Query(args) => {
+            // `/query 20 #food`: the count and tag may come in either order.
+            let mut n = 10;
+            let mut tag = None;
+            for word in args.split_whitespace() {
+                match word.parse::<i64>() {
+                    Ok(count) => n = count,
+                    Err(_) if word.starts_with('#') => tag = tags::normalize(word),
+                    Err(_) => {}
+                }
+            }
+            let n = n.clamp(1, 50);
+            let Some((report, keyboard)) = query_page(
+                db,
+                uuid,
+                lang,
+                &sender_name,
+                n,
+                tag.as_deref(),
+                db::Cursor::Newest,
+            )
+            .await?
+            else {
+                let text = match &tag {
+                    Some(tag) => format!("No uninvested entries tagged #{tag}."),
+                    None => i18n::no_entries(lang).to_string(),
+                };
+                bot.send_message(msg.chat.id, text).await?;
+                return Ok(());
+            };
+            let text = report.lines.join("\n");
+            match keyboard {
+                Some(keyboard) if text.chars().count() <= render::MAX_MESSAGE_CHARS => {
+                    bot.send_message(msg.chat.id, text)
+                        .reply_markup(keyboard)
+                        .await?;
+                }
+                _ => render::send_report(&bot, msg.chat.id, report).await?,
+            }
+        }
+        Command::Search(args) => {
+            let phrase = args.trim();
+            if phrase.is_empty() {
+                bot.send_message(msg.chat.id, "Usage: /search {phrase}, e.g. /search coffee")
+                    .await?;
+                return Ok(());
+            }
+            let found = db.search_entries(uuid, phrase, SEARCH_LIMIT).await?;
+            if found.hits.is_empty() {
+                bot.send_message(msg.chat.id, format!("No entries mention “{phrase}”."))
+                    .await?;
+                return Ok(());
+            }
+            let header = if found.matches > found.hits.len() as i64 {
+                format!(
+                    "🔎 {} entries mention “{phrase}”, newest {} shown:",
+                    found.matches,
+                    found.hits.len()
+                )
+            } else {
+                format!("🔎 {} entries mention “{phrase}”:", found.matches)
+            };
+            let mut report = Report::new("search.txt");
+            report.summary = format!("{header} Full list attached.");
+            report.push(header);
+            for hit in found.hits {
+                report.push(format!(
+                    "{}{} {} [{}{}]{}",
+                    hit.id.map(|id| format!("#{id} ")).unwrap_or_default(),
+                    hit.occurred_at.get(..10).unwrap_or(&hit.occurred_at),
+                    format_signed_cents(hit.amount_cents),
+                    hit.kind,
+                    if hit.invested { ", invested" } else { "" },
+                    hit.reason.map(|r| format!(" — {r}")).unwrap_or_default()
+                ));
+            }
+            report.push(format!("\nNet: {}", format_signed_cents(found.net_cents)));
+            render::send_report(&bot, msg.chat.id, report).await?;
+        }
+        Command::History => {
+            let investments = db.investments(uuid).await?;
+            let Some(last) = investments.last() else {
+                bot.send_message(msg.chat.id, "Nothing invested yet. Try /allinvoo.")
+                    .await?;
+                return Ok(());
+            };
+            let header = format!(
+                "📈 {} invested over {} /allinvoo runs:",
+                format_cents(last.running_cents),
+                investments.len()
+            );
+            let mut report = Report::new("history.txt");
+            report.summary = format!("{header} Full list attached.");
+            report.push(header);
+            for investment in &investments {
+                report.push(format!(
+                    "{} {}{} → {}",
+                    investment
+                        .archived_at
+                        .get(..10)
+                        .unwrap_or(&investment.archived_at),
+                    format_signed_cents(investment.cents),
+                    investment
+                        .strategy
+                        .as_ref()
+                        .map(|s| format!(" into {s}"))
+                        .unwrap_or_default(),
+                    format_cents(investment.running_cents)
+                ));
+            }
+            render::send_report(&bot, msg.chat.id, report).await?;
+        }
+        Command::Historylog(args) => {
+            let n = match args.trim() {
+                "" => HISTORYLOG_DEFAULT,
+                n => match n.parse::<i64>() {
+                    Ok(n) if n > 0 => n.min(HISTORYLOG_MAX),
+                    _ => {
+                        bot.send_message(
+                            msg.chat.id,
+                            "Usage: /historylog [n], e.g. /historylog 50",
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                },
+            };
+            let entries = db.archived_entries(uuid, n).await?;
+            if entries.is_empty() {
+                bot.send_message(msg.chat.id, "Nothing invested yet. Try /allinvoo.")
+                    .await?;
+                return Ok(());
+            }
+            let header = format!("📦 Your last {} invested entries:", entries.len());
+            let mut report = Report::new("historylog.txt");
+            report.summary = format!("{header} Full list attached.");
+            report.push(header);
+            let mut batch = None;
+            for entry in &entries {
+                if batch != Some(entry.batch_id) {
+                    batch = Some(entry.batch_id);
+                    let shown = entries
+                        .iter()
+                        .filter(|e| e.batch_id == entry.batch_id)
+                        .count() as i64;
+                    report.push(format!(
+                        "\n{} → {}: {} from {} entries{}",
+                        entry.archived_at.get(..10).unwrap_or(&entry.archived_at),
+                        entry.strategy.as_deref().unwrap_or("main"),
+                        format_signed_cents(entry.batch_cents),
+                        entry.batch_entries,
+                        if shown < entry.batch_entries {
+                            format!(", {shown} shown")
+                        } else {
+                            String::new()
+                        }
+                    ));
+                }
+                report.push(format!(
+                    "  {}{} {} [{}]{}",
+                    entry.id.map(|id| format!("#{id} ")).unwrap_or_default(),
+                    entry.occurred_at.get(..10).unwrap_or(&entry.occurred_at),
+                    format_signed_cents(entry.amount_cents),
+                    entry.kind,
+                    entry
+                        .reason
+                        .as_ref()
+                        .map(|r| format!(" — {r}"))
+                        .unwrap_or_default()
+                ));
+            }
+            render::send_report(&bot, msg.chat.id, report).await?;
+        }
+        Command::Watchticker(args) => {
+            let watched = db.watched_tickers(uuid).await?;
+            let text = if args.trim().is_empty() {
+                if watched.is_empty() {
+                    "You aren't watching any tickers. Try /watchticker VTI.".to_string()
+                } else {
+                    format!(
+                        "📈 Watching {}. Their prices arrive in a weekly summary.",
+                        watched.join(", ")
+                    )
+                }
+            } else if let Some(ticker) = market::normalize_ticker(&args) {
+                if watched.contains(&ticker) {
+                    format!("You're already watching {ticker}.")
+                } else if watched.len() >= market::MAX_WATCHED {
+                    format!(
+                        "You can watch up to {} tickers. Drop one with /unwatchticker first.",
+                        market::MAX_WATCHED
+                    )
+                } else {
+                    match market::price_cents(&ticker).await {
+                        Err(err) => {
+                            eprintln!("watchticker: price error: {err:?}");
+                            format!("I couldn't find a price for {ticker}. Is it a US ticker?")
+                        }
+                        Ok(price) => {
+                            db.watch_ticker(uuid, &ticker).await?;
+                            let price = price
+                                .map(|p| format!(" (last close {})", format_cents(p)))
+                                .unwrap_or_default();
+                            format!("📈 Watching {ticker}{price}. You'll get its close, week change and distance from the 52-week high every week.")
+                        }
+                    }
+                }
+            } else {
+                "Usage: /watchticker VTI".to_string()
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Unwatchticker(args) => {
+            let text = match market::normalize_ticker(&args) {
+                Some(ticker) if db.unwatch_ticker(uuid, &ticker).await? => {
+                    format!("Stopped watching {ticker}.")
+                }
+                Some(ticker) => format!("You weren't watching {ticker}."),
+                None => "Usage: /unwatchticker VTI".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Networth(args) => {
+            let mut lines = networth::report(db, uuid, currency).await?;
+            if args.trim().eq_ignore_ascii_case("fx") {
+                lines.push(String::new());
+                lines.extend(hedged::report(db, uuid, currency).await?);
+            }
+            bot.send_message(msg.chat.id, lines.join("\n")).await?;
+        }
+        Command::Gains => {
+            let lines = gains::report(db, uuid, currency).await?;
+            bot.send_message(msg.chat.id, lines.join("\n")).await?;
+        }
+        Command::Balance => {
+            let (current, history) = db.balances(uuid).await?;
+            let text = i18n::totals(
+                lang,
+                &currency.format(current),
+                &currency.format(history),
+                &currency.format(current + history),
+            );
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Show(args) => {
+            let Ok(id) = args.trim().trim_start_matches('#').parse::<i64>() else {
+                bot.send_message(msg.chat.id, "Usage: /show {id} (ids are listed by /query)")
+                    .await?;
+                return Ok(());
+            };
+            let Some(d) = db.entry_detail(uuid, id).await? else {
+                bot.send_message(msg.chat.id, format!("You have no entry #{id}."))
+                    .await?;
+                return Ok(());
+            };
+            let e = &d.entry;
+            let mut lines = vec![
+                format!("Entry #{}", e.id),
+                format!("Amount: {}", format_signed_cents(e.amount_cents)),
+                format!(
+                    "Kind: {}{}",
+                    e.kind,
+                    e.class
+                        .as_deref()
+                        .map(|c| format!(" ({c})"))
+                        .unwrap_or_default()
+                ),
+                format!("Reason: {}", e.reason.as_deref().unwrap_or("—")),
+            ];
+            if let Some((cents, code)) = &d.original {
+                lines.insert(
+                    2,
+                    format!("Entered as: {} {code}", format_signed_cents(*cents)),
+                );
+            }
+            let tags = tags::parse(e.reason.as_deref().unwrap_or_default());
+            if !tags.is_empty() {
+                let tags: Vec<String> = tags.iter().map(|t| format!("#{t}")).collect();
+                lines.push(format!("Tags: {}", tags.join(" ")));
+            }
+            let attachments = db.entry_attachments(uuid, e.id).await?;
+            if !attachments.is_empty() {
+                let attachments: Vec<String> = attachments
+                    .iter()
+                    .map(|(id, source)| {
+                        let icon = if source == "voice" { "🎙" } else { "🧾" };
+                        format!("#{id} {icon}")
+                    })
+                    .collect();
+                lines.push(format!("Attachments: {}", attachments.join(", ")));
+            }
+            lines.push(format!("Date: {}", e.occurred_at));
+            if let (Some(chat), Some(message)) = (d.source_chat_id, d.source_message_id) {
+                // Only supergroup messages have public links; elsewhere name the message.
+                let source = match chat.to_string().strip_prefix("-100") {
+                    Some(internal) => format!("https://t.me/c/{internal}/{message}"),
+                    None if chat == msg.chat.id.0 => format!("message {message} in this chat"),
+                    None => format!("message {message} in another chat"),
+                };
+                lines.push(format!("Source: {source}"));
+            }
+            lines.push(String::new());
+            lines.push("History:".into());
+            lines.push(format!("• {} recorded", d.created_at));
+            for edit in &d.edits {
+                lines.push(format!(
+                    "• {} edited; was {}{}",
+                    edit.edited_at,
+                    format_signed_cents(edit.amount_cents),
+                    edit.reason
+                        .as_deref()
+                        .map(|r| format!(" — {r}"))
+                        .unwrap_or_default()
+                ));
+            }
+            match &d.archived_at {
+                Some(at) => lines.push(format!("• {at} invested with /allinvoo")),
+                None => lines.push("• not invested yet".into()),
+            }
+            bot.send_message(msg.chat.id, lines.join("\n")).await?;
+        }
+        Command::Export(args) if args.split_whitespace().next() == Some("taxes") => {
+            let this_year = OffsetDateTime::now_utc().year();
+            let year = match args.split_whitespace().collect::<Vec<_>>()[..] {
+                [_, year] => year
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|y| (1900..=this_year).contains(y)),
+                _ => None,
+            };
+            let Some(year) = year else {
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "Usage: /export taxes {{year}}, e.g. /export taxes {}",
+                        this_year - 1
+                    ),
+                )
+                .await?;
+                return Ok(());
+            };
+            // /allinvoo only ever buys, so no lot is ever sold and the
+            // report has no rows yet; the columns are Form 8949's.
+            let mut out = csv::line(&[
+                "description",
+                "acquired",
+                "sold",
+                "proceeds",
+                "basis",
+                "gain",
+                "term",
+            ]);
+            out.push('\n');
+            render::send_file(
+                &bot,
+                msg.chat.id,
+                &format!("voo-taxes-{year}.csv"),
+                out.into_bytes(),
+                &format!("Realized lots in {year}: none. The bot records purchases (/allinvoo) but never sales; /export has every purchase if you need your cost basis."),
+            )
+            .await?;
+        }
+        Command::Export(args) if args.trim() == "settings" => {
+            let exported = settings::export(db, uuid).await?;
+            let today = OffsetDateTime::now_utc().date();
+            render::send_file(
+                &bot,
+                msg.chat.id,
+                &format!("voo-settings-{today}.json"),
+                serde_json::to_vec_pretty(&exported)?,
+                "Your settings. Restore them anywhere with /import settings.",
+            )
+            .await?;
+        }
+        Command::Export(_) => {
+            let mut out = csv::line(&["date", "amount", "kind", "reason", "batch"]);
+            out.push('\n');
+            let mut rows = db.all_entries(uuid);
+            let mut count = 0;
+            while let Some(row) = rows.next().await {
+                let row = row?;
+                out.push_str(&csv::line(&[
+                    row.occurred_at,
+                    format_cents(row.amount_cents),
+                    row.kind.to_string(),
+                    row.reason.unwrap_or_default(),
+                    row.batch.unwrap_or_default(),
+                ]));
+                out.push('\n');
+                count += 1;
+            }
+            if count == 0 {
+                bot.send_message(msg.chat.id, i18n::no_entries(lang))
+                    .await?;
+                return Ok(());
+            }
+            let today = OffsetDateTime::now_utc().date();
+            render::send_file(
+                &bot,
+                msg.chat.id,
+                &format!("voo-ledger-{today}.csv"),
+                out.into_bytes(),
+                &format!("{count} entries"),
+            )
+            .await?;
+        }
+        Command::Import(args) if args.trim() == "settings" => {
+            let Some(text) = attached_text(
+                &bot,
+                msg,
+                "Send the JSON file from /export settings with /import settings as its caption, or reply /import settings to one.",
+            )
+            .await?
+            else {
+                return Ok(());
+            };
+            let imported = match serde_json::from_str::<settings::Settings>(&text) {
+                Ok(imported) => imported,
+                Err(e) => {
+                    bot.send_message(
+                        msg.chat.id,
+                        format!("That isn't a settings file from /export settings: {e}"),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            };
+            let problems = settings::problems(&imported);
+            let text = if !problems.is_empty() {
+                let mut lines = vec![format!(
+                    "Nothing was restored; {} settings need fixing:",
+                    problems.len()
+                )];
+                lines.extend(problems);
+                lines.join("\n")
+            } else {
+                let restored = settings::restore(db, uuid, msg.chat.id.0, &imported).await?;
+                if restored.is_empty() {
+                    "That file has no settings in it.".to_string()
+                } else {
+                    format!("⚙️ Restored {}.", restored.join(", "))
+                }
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Import(_) => {
+            let Some(text) = attached_text(
+                &bot,
+                msg,
+                "Send a CSV file with /import as its caption, or reply /import to one. Columns: date,amount,reason (a /export file works too).",
+            )
+            .await?
+            else {
+                return Ok(());
+            };
+            let entries = match import::entries(&csv::parse(&text)) {
+                Ok(entries) if entries.is_empty() => {
+                    bot.send_message(msg.chat.id, "That file has no rows to import.")
+                        .await?;
+                    return Ok(());
+                }
+                Ok(entries) => entries,
+                Err(errors) => {
+                    let mut lines = vec![format!(
+                        "Nothing was imported; {} rows need fixing:",
+                        errors.len()
+                    )];
+                    lines.extend(errors.iter().take(10).cloned());
+                    if errors.len() > 10 {
+                        lines.push(format!("…and {} more", errors.len() - 10));
+                    }
+                    bot.send_message(msg.chat.id, lines.join("\n")).await?;
+                    return Ok(());
+                }
+            };
+            if let Some(text) = quota::exceeded_total(db, uuid, entries.len() as i64).await? {
+                bot.send_message(msg.chat.id, text).await?;
+                return Ok(());
+            }
+            const PREVIEW: usize = 5;
+            let sum: i64 = entries.iter().map(|e| e.amount_cents).sum();
+            let first = entries.iter().map(|e| &e.occurred_at).min();
+            let last = entries.iter().map(|e| &e.occurred_at).max();
+            let day = |at: Option<&String>| {
+                at.map(|at| at.get(..10).unwrap_or(at).to_string())
+                    .unwrap_or_default()
+            };
+            let mut lines = vec![format!(
+                "Import {} entries ({} in total) dated {} to {}?",
+                entries.len(),
+                format_signed_cents(sum),
+                day(first),
+                day(last)
+            )];
+            for e in entries.iter().take(PREVIEW) {
+                lines.push(format!(
+                    "{} {} [{}]{}",
+                    day(Some(&e.occurred_at)),
+                    format_signed_cents(e.amount_cents),
+                    e.kind,
+                    e.reason
+                        .as_deref()
+                        .map(|r| format!(" — {r}"))
+                        .unwrap_or_default()
+                ));
+            }
+            if entries.len() > PREVIEW {
+                lines.push(format!("…and {} more", entries.len() - PREVIEW));
+            }
+            let id = db.add_pending_import(uuid, &entries).await?;
+            let keyboard = InlineKeyboardMarkup::new([[
+                InlineKeyboardButton::callback("📥 Import them", format!("import:{id}:ok")),
+                InlineKeyboardButton::callback("✖️ Cancel", format!("import:{id}:no")),
+            ]]);
+            bot.send_message(msg.chat.id, lines.join("\n"))
+                .reply_markup(keyboard)
+                .await?;
+        }
+        Command::Edit(args) => {
+            let usage = "Usage: /edit {id} {amount} [reason] (ids are listed by /query)";
+            let (id, rest) = args
+                .trim()
+                .split_once(char::is_whitespace)
+                .unwrap_or((args.trim(), ""));
+            let (Ok(id), Ok((amount_cents, reason))) = (
+                id.trim_start_matches('#').parse::<i64>(),
+                parse_amount_and_reason(rest, true, comma),
+            ) else {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            };
+            if amount_cents == 0 {
+                bot.send_message(msg.chat.id, "Amount must not be zero.")
+                    .await?;
+                return Ok(());
+            }
+            let Some(current) = db.entry_detail(uuid, id).await? else {
+                bot.send_message(msg.chat.id, format!("You have no entry #{id}."))
+                    .await?;
+                return Ok(());
+            };
+            if current.archived_at.is_some() {
+                bot.send_message(
+                    msg.chat.id,
+                    format!("Entry #{id} was already invested with /allinvoo and can't be edited."),
+                )
+                .await?;
+                return Ok(());
+            }
+            if current.transfer_id.is_some() {
+                bot.send_message(msg.chat.id, transfer_leg_text(id)).await?;
+                return Ok(());
+            }
+            if is_locked(&current, lock_cutoff(db, uuid).await?.as_deref()) {
+                bot.send_message(msg.chat.id, locked_text(id)).await?;
+                return Ok(());
+            }
+            // A bare amount keeps the entry's direction, so "/edit 12 30" on a
+            // withdrawal still takes money out.
+            let explicit_sign = rest.trim_start().starts_with(['+', '-']);
+            let amount_cents = if !explicit_sign && current.entry.amount_cents < 0 {
+                -amount_cents
+            } else {
+                amount_cents
+            };
+            let reason = reason.or(current.entry.reason);
+            let Some((old, new)) = db.update_entry(uuid, id, amount_cents, reason).await? else {
+                bot.send_message(msg.chat.id, format!("You have no entry #{id}."))
+                    .await?;
+                return Ok(());
+            };
+            let describe = |e: &db::Entry| {
+                format!(
+                    "{}{}",
+                    format_signed_cents(e.amount_cents),
+                    e.reason
+                        .as_deref()
+                        .map(|r| format!(" — {r}"))
+                        .unwrap_or_default()
+                )
+            };
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "✏️ Edited #{id}\nWas: {}\nNow: {}\nTotal now: {}",
+                    describe(&old),
+                    describe(&new),
+                    format_cents(db.total_cents(uuid).await?)
+                ),
+            )
+            .await?;
+        }
+        Command::Delete(args) => {
+            let Ok(id) = args.trim().trim_start_matches('#').parse::<i64>() else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /delete {id} (ids are listed by /query)",
+                )
+                .await?;
+                return Ok(());
+            };
+            if let Some(current) = db.entry_detail(uuid, id).await? {
+                if is_locked(&current, lock_cutoff(db, uuid).await?.as_deref()) {
+                    bot.send_message(msg.chat.id, locked_text(id)).await?;
+                    return Ok(());
+                }
+            }
+            let text = match db.delete_entry(uuid, id).await? {
+                Some(e) => format!(
+                    "🗑 Deleted #{} {} [{}]{}\nTotal now: {}",
+                    e.id,
+                    format_signed_cents(e.amount_cents),
+                    e.kind,
+                    e.reason.map(|r| format!(" — {r}")).unwrap_or_default(),
+                    format_cents(db.total_cents(uuid).await?)
+                ),
+                None => match db.entry_detail(uuid, id).await? {
+                    Some(d) if d.transfer_id.is_some() => transfer_leg_text(id),
+                    Some(_) => format!(
+                        "Entry #{id} was already invested with /allinvoo and can't be deleted."
+                    ),
+                    None => format!("You have no entry #{id}."),
+                },
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Reverse(args) => {
+            let Ok(id) = args.trim().trim_start_matches('#').parse::<i64>() else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /reverse {id} (ids are listed by /query)",
+                )
+                .await?;
+                return Ok(());
+            };
+            let Some(d) = db.entry_detail(uuid, id).await? else {
+                bot.send_message(msg.chat.id, format!("You have no entry #{id}."))
+                    .await?;
+                return Ok(());
+            };
+            if d.transfer_id.is_some() {
+                bot.send_message(msg.chat.id, transfer_leg_text(id)).await?;
+                return Ok(());
+            }
+            let amount_cents = -d.entry.amount_cents;
+            let reason = format!(
+                "Reversal of #{id}{}",
+                d.entry
+                    .reason
+                    .as_deref()
+                    .map(|r| format!(": {r}"))
+                    .unwrap_or_default()
+            );
+            let entry_id = db
+                .add_entry(
+                    uuid,
+                    amount_cents,
+                    EntryKind::Adjust,
+                    Some(reason.clone()),
+                    None,
+                    None,
+                )
+                .await?;
+            db.set_entry_source(entry_id, msg.chat.id.0, msg.id.0)
+                .await?;
+            events::entry_added(db, uuid, amount_cents, EntryKind::Adjust, Some(&reason)).await;
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "🔁 Booked #{entry_id} {} — {reason}\nTotal now: {}",
+                    format_signed_cents(amount_cents),
+                    format_cents(db.total_cents(uuid).await?)
+                ),
+            )
+            .await?;
+        }
+        Command::Immutable(args) => {
+            match args.trim() {
+                "on" => db.set_immutable(uuid, true).await?,
+                "off" => db.set_immutable(uuid, false).await?,
+                "" => {}
+                _ => {
+                    bot.send_message(msg.chat.id, "Usage: /immutable on|off")
+                        .await?;
+                    return Ok(());
+                }
+            }
+            let text = if db.immutable(uuid).await? {
+                "Immutable ledger is on: entries can't be edited or deleted once they are 24 hours old. Correct them with /reverse {id}."
+            } else {
+                "Immutable ledger is off: /edit, /delete, /undo and /purge work on any uninvested entry."
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Undo => {
+            let cutoff = lock_cutoff(db, uuid).await?;
+            let text = match db.delete_last_entry(uuid, cutoff.as_deref()).await? {
+                Some(e) => format!(
+                    "↩️ Removed #{} {} [{}]{}\nTotal now: {}",
+                    e.id,
+                    format_signed_cents(e.amount_cents),
+                    e.kind,
+                    e.reason.map(|r| format!(" — {r}")).unwrap_or_default(),
+                    format_cents(db.total_cents(uuid).await?)
+                ),
+                None => match db
+                    .last_entries(uuid, 1, None, db::Cursor::Newest)
+                    .await?
+                    .first()
+                {
+                    Some(e) if cutoff.is_some() => locked_text(e.id),
+                    _ => "Nothing to undo: you haven't typed an entry since your last /allinvoo."
+                        .into(),
+                },
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Unvoo => {
+            let cutoff = (OffsetDateTime::now_utc() - time::Duration::hours(UNVOO_HOURS))
+                .format(&time::format_description::well_known::Rfc3339)?;
+            let text = match db.last_batch(uuid).await? {
+                None => "Nothing to undo: you haven't invested anything yet.".to_string(),
+                Some((_, archived_at, _)) if archived_at < cutoff => format!(
+                    "Your last /allinvoo was on {}; only one from the last {UNVOO_HOURS} hours can be undone.",
+                    archived_at.get(..10).unwrap_or(&archived_at)
+                ),
+                Some((batch_id, _, _)) => match db.restore_batch(uuid, batch_id).await? {
+                    Some(cents) => format!(
+                        "↩️ Put {} back into current.\nCurrent now: {}",
+                        format_cents(cents),
+                        format_cents(db.total_cents(uuid).await?)
+                    ),
+                    None => "Your investments changed meanwhile; try /unvoo again.".to_string(),
+                },
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Purge(args) => {
+            let Some(mut filter) = parse_entry_filter(&args) else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /purge reason:{text} kind:{kind} before:YYYY-MM-DD after:YYYY-MM-DD (at least one)",
+                )
+                .await?;
+                return Ok(());
+            };
+            filter.recorded_after = lock_cutoff(db, uuid).await?;
+            let matches = db.matching_entries(uuid, &filter).await?;
+            if matches.is_empty() {
+                let text = if filter.recorded_after.is_some() {
+                    "No uninvested entries from the last 24 hours match; older ones are locked by /immutable."
+                } else {
+                    "No uninvested entries match."
+                };
+                bot.send_message(msg.chat.id, text).await?;
+                return Ok(());
+            }
+            const PREVIEW: usize = 10;
+            let sum: i64 = matches.iter().map(|e| e.amount_cents).sum();
+            let mut lines = vec![format!(
+                "These {} entries ({} in total) will be deleted:",
+                matches.len(),
+                format_signed_cents(sum)
+            )];
+            for e in matches.iter().take(PREVIEW) {
+                lines.push(format!(
+                    "#{} {} {} [{}]{}",
+                    e.id,
+                    e.occurred_at.get(..10).unwrap_or(&e.occurred_at),
+                    format_signed_cents(e.amount_cents),
+                    e.kind,
+                    e.reason
+                        .as_deref()
+                        .map(|r| format!(" — {r}"))
+                        .unwrap_or_default()
+                ));
+            }
+            if matches.len() > PREVIEW {
+                lines.push(format!("…and {} more", matches.len() - PREVIEW));
+            }
+            lines.push(format!("Confirm within {PURGE_CONFIRM_MINUTES} minutes."));
+            let ids: Vec<i64> = matches.iter().map(|e| e.id).collect();
+            let id = db.add_pending_purge(uuid, &ids).await?;
+            let keyboard = InlineKeyboardMarkup::new([[
+                InlineKeyboardButton::callback("🗑 Delete them", format!("purge:{id}:ok")),
+                InlineKeyboardButton::callback("✖️ Cancel", format!("purge:{id}:no")),
+            ]]);
+            bot.send_message(msg.chat.id, lines.join("\n"))
+                .reply_markup(keyboard)
+                .await?;
+        }
+        Command::FiftyThirtyTwenty(args) => {
+            let Some(month) = month_arg(&args) else {
+                bot.send_message(msg.chat.id, "Usage: /50_30_20 [YYYY-MM]")
+                    .await?;
+                return Ok(());
+            };
+            let t = db.class_totals(uuid, &month).await?;
+            let sum = t.needs + t.wants + t.savings;
+            if sum == 0 {
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "Nothing classified for {month}. Mark spending with !need or !want, e.g. /adjust -30 !want cinema"
+                    ),
+                )
+                .await?;
+            } else {
+                let line = |label: &str, cents: i64, target: i64| {
+                    format!(
+                        "{label}: {} ({}%, target {target}%)",
+                        format_cents(cents),
+                        cents * 100 / sum
+                    )
+                };
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "50/30/20 for {month}:\n{}\n{}\n{}",
+                        line("Needs", t.needs, 50),
+                        line("Wants", t.wants, 30),
+                        line("Savings", t.savings, 20),
+                    ),
+                )
+                .await?;
+            }
+        }
+        Command::Webhook(args) => {
+            let words: Vec<&str> = args.split_whitespace().collect();
+            let text = match words.as_slice() {
+                [] => match db.webhook(person).await? {
+                    Some((url, _)) => {
+                        format!("Webhook: {url}\nEvents: entry, allinvoo, goal_reached.")
+                    }
+                    None => "No webhook set. Use /webhook set https://…".to_string(),
+                },
+                ["set", url] if msg.chat.is_private() => {
+                    if let Err(err) = events::webhook_target(url).await {
+                        format!("Can't use {url} as a webhook: {err}.")
+                    } else {
+                        let secret = uuid::Uuid::new_v4().simple().to_string();
+                        db.set_webhook(person, Some((url, &secret))).await?;
+                        format!(
+                            "Webhook set. Each POST carries X-Voo-Signature: sha256=HMAC-SHA256(body) keyed with this secret:\n\n{secret}\n\nTry /webhook test."
+                        )
+                    }
+                }
+                ["set", _] => {
+                    "Set webhooks in a private chat, the signing secret is shown once.".to_string()
+                }
+                ["test"] => match db.webhook(person).await? {
+                    Some((url, secret)) => {
+                        let body = serde_json::json!({
+                            "event": "test",
+                            "user_id": uuid.to_string(),
+                        })
+                        .to_string();
+                        match events::post_webhook(&url, &secret, "test", body).await {
+                            Ok(()) => "✅ Test event delivered.".to_string(),
+                            Err(err) => format!("❌ Delivery failed: {err}"),
+                        }
+                    }
+                    None => "No webhook set.".to_string(),
+                },
+                ["off"] => {
+                    db.set_webhook(person, None).await?;
+                    "Webhook removed.".to_string()
+                }
+                _ => "Usage: /webhook [set {https url}|test|off]".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Timezone(args) => {
+            if !args.trim().is_empty() {
+                let Some(offset) = streaks::parse_offset(&args) else {
+                    bot.send_message(
+                        msg.chat.id,
+                        "Usage: /timezone +02:00 (your UTC offset, e.g. -5, +5:30, UTC)",
+                    )
+                    .await?;
+                    return Ok(());
+                };
+                db.set_utc_offset_minutes(person, offset.whole_minutes().into())
+                    .await?;
+            }
+            let offset = user_offset(db, person).await?;
+            let now = OffsetDateTime::now_utc().to_offset(offset);
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "Your timezone is {}; it's {} there. Streaks and reminders follow it.",
+                    streaks::format_offset(offset),
+                    now.format(format_description!("[year]-[month]-[day] [hour]:[minute]"))?
+                ),
+            )
+            .await?;
+        }
+        Command::Remind(args) => {
+            let args = args.trim().to_lowercase();
+            let text = if args == "off" {
+                if db.clear_reminder(person).await? {
+                    "Reminder turned off.".to_string()
+                } else {
+                    "You have no reminder set.".to_string()
+                }
+            } else if args.is_empty() {
+                match db.reminder(person).await? {
+                    Some(r) => format!(
+                        "I'll remind you {} ({}).",
+                        reminders::describe(r.weekday, r.minute_of_day),
+                        streaks::format_offset(user_offset(db, person).await?)
+                    ),
+                    None => "No reminder set. Try /remind daily 20:00 or /remind weekly sun 10:00."
+                        .to_string(),
+                }
+            } else if let Some((weekday, minute_of_day)) = reminders::parse(&args) {
+                let mut reminder = db::Reminder {
+                    user_id: person,
+                    chat_id: msg.chat.id.0,
+                    weekday,
+                    minute_of_day,
+                    utc_offset_minutes: db.utc_offset_minutes(person).await?,
+                    last_sent_on: None,
+                };
+                // Setting 20:00 at 21:00 shouldn't fire right away.
+                reminder.last_sent_on = reminders::due_on(&reminder, OffsetDateTime::now_utc());
+                db.set_reminder(&reminder).await?;
+                let offset = user_offset(db, person).await?;
+                let mut text = format!(
+                    "⏰ I'll remind you here {} ({}).",
+                    reminders::describe(weekday, minute_of_day),
+                    streaks::format_offset(offset)
+                );
+                if offset == time::UtcOffset::UTC {
+                    text.push_str(" Not your timezone? Set it with /timezone.");
+                }
+                text
+            } else {
+                "Usage: /remind daily 20:00 | /remind weekly sun 10:00 | /remind off".to_string()
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Currency(args) => {
+            let code = args.trim();
+            let text = if code.is_empty() {
+                let known: Vec<&str> = Currency::KNOWN.iter().map(|c| c.code).collect();
+                let now = if currency == Currency::PLAIN {
+                    "Amounts are shown without a currency.".to_string()
+                } else {
+                    format!("Amounts are shown in {}.", currency.code)
+                };
+                format!(
+                    "{now} Set one with /currency {{code}}: {}.",
+                    known.join(", ")
+                )
+            } else if code.eq_ignore_ascii_case("off") {
+                db.set_currency(person, None).await?;
+                "Amounts are shown without a currency again.".to_string()
+            } else if let Some(currency) = Currency::parse(code) {
+                db.set_currency(person, Some(currency.code)).await?;
+                format!(
+                    "Amounts are now shown in {}, e.g. {}. Nothing is converted.",
+                    currency.code,
+                    currency.format(123_456)
+                )
+            } else {
+                format!("I don't know the currency {code}. Try /currency for the list.")
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Decimal(args) => {
+            match args.trim() {
+                "comma" => db.set_decimal_comma(person, true).await?,
+                "point" => db.set_decimal_comma(person, false).await?,
+                "" => {}
+                _ => {
+                    bot.send_message(msg.chat.id, "Usage: /decimal comma|point")
+                        .await?;
+                    return Ok(());
+                }
+            }
+            let chosen = db.decimal_comma(person).await?;
+            let mut text = if reads_decimal_comma(db, uuid, from).await? {
+                "Amounts are read with a decimal comma: 1.234,56 is one thousand two hundred thirty-four and 56 cents, and 12.34 is refused.".to_string()
+            } else {
+                "Amounts are read with a decimal point, though 12,34 works too, and 1,234.56 has a thousands separator. Use /decimal comma if you write 1.234,56.".to_string()
+            };
+            if chosen.is_none() {
+                text.push_str(" (Going by your Telegram language until you pick one.)");
+            }
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Nudges(args) => {
+            match args.trim() {
+                "on" => db.set_nudges(person, true).await?,
+                "off" => db.set_nudges(person, false).await?,
+                "" => {}
+                _ => {
+                    bot.send_message(msg.chat.id, "Usage: /nudges on|off")
+                        .await?;
+                    return Ok(());
+                }
+            }
+            let text = if db.nudges_enabled(person).await? {
+                "Nudges are on: I'll ping you if you go quiet or hold a lot of uninvested cash."
+            } else {
+                "Nudges are off. Turn them back on with /nudges on."
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Nudge(args) => {
+            nudge_member(&bot, db, msg, uuid, &sender_name, &args).await?;
+        }
+        Command::Leaderboard(args) => {
+            let text = match args.trim() {
+                "on" => {
+                    db.set_leaderboard_opt_in(uuid, true).await?;
+                    i18n::leaderboard_joined(lang).to_string()
+                }
+                "off" => {
+                    db.set_leaderboard_opt_in(uuid, false).await?;
+                    i18n::leaderboard_left(lang).into()
+                }
+                "" if msg.chat.is_private() => {
+                    i18n::leaderboard_status(lang, db.leaderboard_opt_in(uuid).await?).into()
+                }
+                "" => {
+                    let month = month_arg("").unwrap_or_default();
+                    let leaders = db
+                        .leaderboard(msg.chat.id.0, &month, LEADERBOARD_SIZE)
+                        .await?;
+                    if leaders.is_empty() {
+                        i18n::leaderboard_empty(lang, &month)
+                    } else {
+                        let mut lines = vec![i18n::leaderboard_header(lang, &month)];
+                        for (rank, leader) in leaders.iter().enumerate() {
+                            let medal = ["🥇", "🥈", "🥉"].get(rank).copied().unwrap_or("  ");
+                            let currency = user_currency(db, leader.user_id).await?;
+                            lines.push(format!(
+                                "{medal} {}. {} — {}",
+                                rank + 1,
+                                leader.name,
+                                currency.format(leader.saved_cents)
+                            ));
+                        }
+                        lines.push(i18n::leaderboard_footer(lang).into());
+                        lines.join("\n")
+                    }
+                }
+                _ => i18n::leaderboard_usage(lang).into(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Shared(args) => {
+            let Some(from) = from else {
+                bot.send_message(msg.chat.id, "Shared ledgers are for people, not channels.")
+                    .await?;
+                return Ok(());
+            };
+            shared_command(&bot, db, msg, from, &args).await?;
+        }
+        Command::Channelowner(args) => {
+            let linked = if msg.chat.is_private() {
+                None
+            } else {
+                bot.get_chat(msg.chat.id).await?.linked_chat_id()
+            };
+            let Some(channel_id) = linked else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Use /channelowner in the discussion group linked to your channel.",
+                )
+                .await?;
+                return Ok(());
+            };
+            if !is_group_admin(&bot, msg, from).await? {
+                bot.send_message(
+                    msg.chat.id,
+                    "Only group admins can claim the linked channel.",
+                )
+                .await?;
+                return Ok(());
             }
-            respond(())
+            let text = if args.trim().eq_ignore_ascii_case("off") {
+                db.set_channel_owner(channel_id, None).await?;
+                "Channel posts are no longer recorded for anyone.".to_string()
+            } else {
+                db.set_channel_owner(channel_id, Some(uuid)).await?;
+                format!(
+                    "Commands posted as the linked channel now count for {sender_name}. /channelowner off undoes this."
+                )
+            };
+            bot.send_message(msg.chat.id, text).await?;
         }
-    })
-    .await;
-
-    Ok(())
-}
-
-async fn handle_command(bot: Bot, db: &Db, msg: &Message, cmd: Command) -> Result<()> {
-    let from = match msg.from() {
-        Some(u) => u,
-        None => {
-            bot.send_message(msg.chat.id, "I can only respond to user messages.")
+        Command::Anonymous(args) => {
+            if msg.chat.is_private() {
+                bot.send_message(msg.chat.id, "Use /anonymous in a group.")
+                    .await?;
+                return Ok(());
+            }
+            if !is_group_admin(&bot, msg, from).await? {
+                bot.send_message(
+                    msg.chat.id,
+                    "Only group admins can change how anonymous posts are recorded.",
+                )
                 .await?;
-            return Ok(());
+                return Ok(());
+            }
+            let text = match args.trim() {
+                "pot" => {
+                    db.set_anonymous_pot(msg.chat.id.0, true).await?;
+                    if let Some(pot) = chat_pot(db, &msg.chat).await? {
+                        // The pot's "user id" is the group; keep pings out of it.
+                        db.set_nudges(pot, false).await?;
+                    }
+                    "Anonymous admins and unclaimed channel posts now go into this group's shared pot."
+                }
+                "off" => {
+                    db.set_anonymous_pot(msg.chat.id.0, false).await?;
+                    "Anonymous posts are no longer recorded. The pot's entries are kept."
+                }
+                _ if db.anonymous_pot(msg.chat.id.0).await? => {
+                    "Anonymous posts go into this group's shared pot. /anonymous off stops that."
+                }
+                _ => "Anonymous posts aren't recorded. /anonymous pot collects them in a shared group pot.",
+            };
+            bot.send_message(msg.chat.id, text).await?;
         }
-    };
-
-    // Ensure user exists (note: first_name is String)
-    let uuid = db
-        .ensure_user(
-            from.id.0 as i64,
-            from.username.clone(),
-            from.first_name.clone(),
-            from.last_name.clone(),
-        )
-        .await?;
-
-    match cmd {
-        Command::Start => {
-            bot.send_message(
-                msg.chat.id,
-                format!(
-                    "Welcome, {}!\nYour user UUID: `{}`\nUse /save, /adjust, /allinvoo, /query.",
-                    display_name(from),
-                    uuid
-                ),
-            )
-            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
-            .await?;
+        Command::Transfer(args) => {
+            transfer_to(&bot, db, msg, uuid, &sender_name, &args).await?;
         }
-        Command::Help => {
-            bot.send_message(msg.chat.id, Command::descriptions().to_string())
+        Command::Loan(args) => {
+            let text = loans::record(db, uuid, currency, &args, (comma, false)).await?;
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Repaid(args) => {
+            let text = loans::record(db, uuid, currency, &args, (comma, true)).await?;
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Loans => {
+            bot.send_message(msg.chat.id, loans::report(db, uuid, currency).await?)
                 .await?;
         }
-        Command::Save(args) => {
-            let (amount_cents, reason) = parse_amount_and_reason(&args, false)?;
-            if amount_cents <= 0 {
-                bot.send_message(msg.chat.id, "Amount must be positive for /save.")
+        Command::Split(args) => {
+            split_expense(&bot, db, msg, uuid, &sender_name, &args).await?;
+        }
+        Command::Splitrounding(args) => {
+            if msg.chat.is_private() {
+                bot.send_message(msg.chat.id, "Use /splitrounding in a group.")
                     .await?;
+                return Ok(());
+            }
+            let arg = args.trim();
+            let text = if arg.is_empty() {
+                let rule = chat_rounding(db, msg.chat.id.0).await?;
+                let example = rounding::split(10_000, &[1, 1, 1], rule, 0, 0)
+                    .iter()
+                    .map(|c| format_cents(*c))
+                    .collect::<Vec<_>>()
+                    .join(" + ");
+                format!(
+                    "Splits here round with {}: {}. E.g. 100.00 three ways, paid by the first: {example}.\nAdmins change it with /splitrounding largest|payer|roundrobin.",
+                    rule.as_str(),
+                    rule.describe()
+                )
+            } else if !is_group_admin(&bot, msg, from).await? {
+                "Only group admins can change how splits are rounded.".to_string()
             } else {
-                db.add_entry(uuid, amount_cents, "save", reason.clone())
-                    .await?;
-                let total = db.total_cents(uuid).await?;
+                match rounding::Rounding::parse(arg) {
+                    Some(rule) => {
+                        db.set_split_rounding(msg.chat.id.0, rule.as_str()).await?;
+                        format!(
+                            "Splits here now round with {}: {}.",
+                            rule.as_str(),
+                            rule.describe()
+                        )
+                    }
+                    None => "Usage: /splitrounding largest|payer|roundrobin".into(),
+                }
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        Command::Disablecmd(args) => {
+            toggle_command(&bot, db, msg, from, &args, false).await?;
+        }
+        Command::Enablecmd(args) => {
+            toggle_command(&bot, db, msg, from, &args, true).await?;
+        }
+        Command::Chatlanguage(args) => {
+            if msg.chat.is_private() {
                 bot.send_message(
                     msg.chat.id,
-                    format!(
-                        "Saved {}.{}\n{}Total now: {}.{}",
-                        cents_to_major(amount_cents),
-                        cents_to_minor(amount_cents),
-                        reason_prefix(&reason),
-                        cents_to_major(total),
-                        cents_to_minor(total),
-                    ),
+                    "Use /chatlanguage in a group. In private chats I follow your Telegram language.",
                 )
                 .await?;
+                return Ok(());
             }
-        }
-        Command::Adjust(args) => {
-            let (delta_cents, reason) = parse_amount_and_reason(&args, true)?;
-            if delta_cents == 0 {
-                bot.send_message(msg.chat.id, "Adjustment must be non-zero.")
-                    .await?;
-            } else {
-                db.add_entry(uuid, delta_cents, "adjust", reason.clone())
-                    .await?;
-                let total = db.total_cents(uuid).await?;
-                let sign = if delta_cents > 0 {
-                    "added"
-                } else {
-                    "subtracted"
-                };
+            if !is_group_admin(&bot, msg, from).await? {
                 bot.send_message(
                     msg.chat.id,
-                    format!(
-                        "Adjustment {} {}.{}\n{}Total now: {}.{}",
-                        sign,
-                        cents_to_major(delta_cents.abs()),
-                        cents_to_minor(delta_cents.abs()),
-                        reason_prefix(&reason),
-                        cents_to_major(total),
-                        cents_to_minor(total),
-                    ),
+                    "Only group admins can change the chat language.",
                 )
                 .await?;
+                return Ok(());
             }
-        }
-        Command::Allinvoo => {
-            let current = db.total_cents(uuid).await?;
-            if current == 0 {
+            let code = args.trim();
+            if code.is_empty() || code.eq_ignore_ascii_case("off") {
+                db.set_chat_language(msg.chat.id.0, None).await?;
                 bot.send_message(
                     msg.chat.id,
-                    "Nothing to invest yet. Your current total is 0.",
+                    "Chat language cleared. Replies follow each member's Telegram language.",
                 )
                 .await?;
+            } else if let Some(chat_lang) = i18n::Lang::from_code(code) {
+                db.set_chat_language(msg.chat.id.0, Some(chat_lang.code()))
+                    .await?;
+                bot.send_message(msg.chat.id, i18n::chat_language_set(chat_lang))
+                    .await?;
             } else {
-                let moved = db.archive_user_entries(uuid).await?;
-                let history = db.history_total_cents(uuid).await?;
                 bot.send_message(
                     msg.chat.id,
-                    format!(
-                        "Invested {}.{} into VOO (moved to history).\nCurrent now: 0.00\nHistory total: {}.{}",
-                        cents_to_major(moved),
-                        cents_to_minor(moved),
-                        cents_to_major(history),
-                        cents_to_minor(history),
-                    ),
+                    format!("Unsupported language. Available: {}", i18n::SUPPORTED),
                 )
                 .await?;
             }
         }
-        Command::Query(args) => {
-            let n = args.trim().parse::<i64>().unwrap_or(10).clamp(1, 50);
-            let items = db.last_entries(uuid, n).await?;
-            let current_total = db.total_cents(uuid).await?;
-            let history_total = db.history_total_cents(uuid).await?;
-            if items.is_empty() {
-                bot.send_message(msg.chat.id, "No entries yet. Use /save to start!")
-                    .await?;
-            } else {
-                let mut lines = Vec::new();
-                lines.push(format!(
-                    "Last {} entries for {}:",
-                    items.len(),
-                    display_name(from)
-                ));
-                for e in items {
-                    let sign = if e.amount_cents >= 0 { "+" } else { "-" };
-                    let amt = e.amount_cents.abs();
-                    let reason = e.reason.unwrap_or_default();
-                    lines.push(format!(
-                        "{} {}.{} [{}] {}{}",
-                        sign,
-                        cents_to_major(amt),
-                        cents_to_minor(amt),
-                        e.kind,
-                        e.created_at,
-                        if reason.is_empty() {
-                            "".to_string()
-                        } else {
-                            format!(" — {}", reason)
-                        }
-                    ));
-                }
-                lines.push(format!(
-                    "\nCurrent total: {}.{}\nHistory total: {}.{}\nGrand total: {}.{}",
-                    cents_to_major(current_total),
-                    cents_to_minor(current_total),
-                    cents_to_major(history_total),
-                    cents_to_minor(history_total),
-                    cents_to_major(current_total + history_total),
-                    cents_to_minor(current_total + history_total),
-                ));
-                bot.send_message(msg.chat.id, lines.join("\n")).await?;
+    }
+    Ok(())
+}
+
+/// How long a member can't be nudged again in the same group.
+const NUDGE_COOLDOWN_HOURS: i64 = 24;
+
+/// Nudges one member may send per group in a day.
+const NUDGES_PER_DAY: i64 = 3;
+
+/// How long the recipient of a /transfer has to accept it.
+const TRANSFER_CONFIRM_HOURS: i64 = 24;
+
+/// /transfer 25 @user [reason]: asks the recipient to accept money from
+/// the sender's current stash; nothing moves until they press ✅ (see
+/// [`handle_transfer_callback`]). In a group the request goes to the group,
+/// otherwise to the recipient's DM.
+async fn transfer_to(
+    bot: &Bot,
+    db: &Db,
+    msg: &Message,
+    uuid: uuid::Uuid,
+    sender_name: &str,
+    args: &str,
+) -> Result<()> {
+    const USAGE: &str =
+        "Usage: /transfer 25 @user [reason], e.g. /transfer 25 @alice concert tickets";
+    let Some(from) = msg.from() else {
+        return Ok(());
+    };
+    let comma = reads_decimal_comma(db, uuid, msg.from()).await?;
+    let Ok((amount_cents, rest)) = parse_amount_and_reason(args, false, comma) else {
+        bot.send_message(msg.chat.id, USAGE).await?;
+        return Ok(());
+    };
+    let rest = rest.unwrap_or_default();
+    let mut words = rest.split_whitespace();
+    let (Some(mention), true) = (
+        words.next().filter(|w| w.starts_with('@')),
+        amount_cents > 0,
+    ) else {
+        bot.send_message(msg.chat.id, USAGE).await?;
+        return Ok(());
+    };
+    let reason = Some(words.collect::<Vec<_>>().join(" ")).filter(|r| !r.is_empty());
+    let username = mention.trim_start_matches('@');
+
+    let Some(member) = db.user_by_username(username).await? else {
+        bot.send_message(
+            msg.chat.id,
+            format!("@{username} hasn't used this bot yet, so there's nowhere to send it."),
+        )
+        .await?;
+        return Ok(());
+    };
+    if member.user_id == uuid {
+        bot.send_message(msg.chat.id, "That's your own ledger; nothing to move.")
+            .await?;
+        return Ok(());
+    }
+    let currency = user_currency(db, uuid).await?;
+    if user_currency(db, member.user_id).await? != currency {
+        bot.send_message(
+            msg.chat.id,
+            format!("@{username} keeps their ledger in another /currency, so a transfer can't be booked for both of you."),
+        )
+        .await?;
+        return Ok(());
+    }
+    let current = db.total_cents(uuid).await?;
+    if current < amount_cents {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Your current stash only holds {}.",
+                currency.format(current)
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let id = db
+        .add_pending_transfer(
+            (uuid, from.id.0 as i64, sender_name),
+            member.user_id,
+            amount_cents,
+            reason.as_deref(),
+        )
+        .await?;
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("✅ Accept", format!("transfer:{id}:ok")),
+        InlineKeyboardButton::callback("✖️ Decline", format!("transfer:{id}:no")),
+    ]]);
+    let request = format!(
+        "💸 {sender_name} wants to send {}{} to your stash. Accept within {TRANSFER_CONFIRM_HOURS} hours?",
+        currency.format(amount_cents),
+        reason
+            .as_deref()
+            .map(|r| format!(" for {r}"))
+            .unwrap_or_default()
+    );
+    if !msg.chat.is_private() {
+        bot.send_message(msg.chat.id, format!("@{username}: {request}"))
+            .reply_markup(keyboard)
+            .await?;
+        return Ok(());
+    }
+    let dm = ChatId(member.tg_user_id);
+    let sent = match bot.send_message(dm, request).reply_markup(keyboard).await {
+        Ok(_) => true,
+        Err(err) => {
+            if !outbox::note_blocked(db, dm, &err).await? {
+                eprintln!("transfer: request to {dm} failed: {err:?}");
+            }
+            false
+        }
+    };
+    let text = if sent {
+        format!("Asked @{username} to accept it; their stash gets it when they do.")
+    } else {
+        // Nobody can press the buttons, so don't leave it pending.
+        db.take_pending_transfer(member.user_id, id, "").await?;
+        format!("Couldn't reach @{username} in private; try /transfer in a group you share.")
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Most people one /split can be shared between, the payer included.
+const SPLIT_MAX_PEOPLE: usize = 20;
+
+/// /split 60 dinner @alice @bob: the sender paid, and the amount is split
+/// evenly between them and everyone mentioned, who must be registered and
+/// in the group. Each share is booked at once as an adjustment on its
+/// owner's ledger, and the group sees who owes the payer what.
+async fn split_expense(
+    bot: &Bot,
+    db: &Db,
+    msg: &Message,
+    uuid: uuid::Uuid,
+    sender_name: &str,
+    args: &str,
+) -> Result<()> {
+    const USAGE: &str =
+        "Usage: /split 60 [reason] @user [@user...], e.g. /split 60 dinner @alice @bob";
+    if msg.chat.is_private() {
+        bot.send_message(
+            msg.chat.id,
+            "Use /split in the group you're splitting with.",
+        )
+        .await?;
+        return Ok(());
+    }
+    let comma = reads_decimal_comma(db, uuid, msg.from()).await?;
+    let Ok((amount_cents, rest)) = parse_amount_and_reason(args, false, comma) else {
+        bot.send_message(msg.chat.id, USAGE).await?;
+        return Ok(());
+    };
+    let rest = rest.unwrap_or_default();
+    let (mentions, words): (Vec<&str>, Vec<&str>) =
+        rest.split_whitespace().partition(|w| w.starts_with('@'));
+    let reason = (!words.is_empty()).then(|| words.join(" "));
+    if amount_cents <= 0 || mentions.is_empty() {
+        bot.send_message(msg.chat.id, USAGE).await?;
+        return Ok(());
+    }
+
+    // (ledger, name) of everyone paying a share, the payer first.
+    let mut people = vec![(uuid, sender_name.to_string())];
+    let mut seen: Vec<i64> = msg.from().map(|f| f.id.0 as i64).into_iter().collect();
+    let mut problems = Vec::new();
+    for mention in mentions {
+        let username = mention.trim_start_matches('@');
+        let Some(member) = db.user_by_username(username).await? else {
+            problems.push(format!("@{username} hasn't used this bot yet"));
+            continue;
+        };
+        let present = bot
+            .get_chat_member(msg.chat.id, UserId(member.tg_user_id as u64))
+            .await
+            .is_ok_and(|m| m.is_present());
+        if !present {
+            problems.push(format!("@{username} isn't in this group"));
+        } else if !seen.contains(&member.tg_user_id) {
+            seen.push(member.tg_user_id);
+            people.push((member.user_id, format!("@{username}")));
+        }
+    }
+    if !problems.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            format!("Nothing was split: {}.", problems.join(", ")),
+        )
+        .await?;
+        return Ok(());
+    }
+    if people.len() < 2 || people.len() > SPLIT_MAX_PEOPLE {
+        bot.send_message(
+            msg.chat.id,
+            format!("A split needs someone besides you, and at most {SPLIT_MAX_PEOPLE} people."),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let chat_id = msg.chat.id.0;
+    let rule = chat_rounding(db, chat_id).await?;
+    let n = people.len();
+    let turn = db.split_turn(chat_id).await?.rem_euclid(n as i64) as usize;
+    let shares = rounding::split(amount_cents, &vec![1; n], rule, 0, turn);
+    let leftover = amount_cents % n as i64;
+    let booked: Vec<(uuid::Uuid, i64)> = people
+        .iter()
+        .zip(&shares)
+        .map(|((ledger, _), cents)| (*ledger, -cents))
+        .collect();
+    let turns = if rule == rounding::Rounding::RoundRobin {
+        leftover
+    } else {
+        0
+    };
+    db.add_split((chat_id, msg.id.0), reason.as_deref(), &booked, turns)
+        .await?;
+    for (ledger, cents) in &booked {
+        events::entry_added(db, *ledger, *cents, EntryKind::Adjust, reason.as_deref()).await;
+    }
+
+    let currency = user_currency(db, uuid).await?;
+    let lang = reply_language(db, msg, msg.from()).await?;
+    let mut lines = vec![i18n::split_header(
+        lang,
+        sender_name,
+        &currency.format(amount_cents),
+        reason.as_deref(),
+        n,
+    )];
+    for (i, ((_, name), cents)) in people.iter().zip(&shares).enumerate() {
+        lines.push(if i == 0 {
+            format!("• {name}: {}", currency.format(*cents))
+        } else {
+            i18n::split_owes(lang, name, &currency.format(*cents), sender_name)
+        });
+    }
+    if leftover != 0 {
+        lines.push(i18n::split_rounding(lang, rule));
+    }
+    lines.push(i18n::split_booked(lang).into());
+    bot.send_message(msg.chat.id, lines.join("\n")).await?;
+    Ok(())
+}
+
+/// /nudge @user: privately reminds a group member who has logged here
+/// before how long it has been, unless they turned /nudges off or were
+/// nudged recently.
+async fn nudge_member(
+    bot: &Bot,
+    db: &Db,
+    msg: &Message,
+    uuid: uuid::Uuid,
+    sender_name: &str,
+    args: &str,
+) -> Result<()> {
+    if msg.chat.is_private() {
+        bot.send_message(msg.chat.id, "Use /nudge @user in a group you share.")
+            .await?;
+        return Ok(());
+    }
+    let Some(username) = args.trim().strip_prefix('@').filter(|u| !u.is_empty()) else {
+        bot.send_message(msg.chat.id, "Usage: /nudge @user").await?;
+        return Ok(());
+    };
+    let chat_id = msg.chat.id.0;
+    let member = db
+        .chat_member(chat_id, username)
+        .await?
+        .filter(|m| m.user_id != uuid);
+    let Some(member) = member else {
+        bot.send_message(
+            msg.chat.id,
+            format!("@{username} hasn't logged anything in this group, so there's nothing to nudge them about."),
+        )
+        .await?;
+        return Ok(());
+    };
+    let now = OffsetDateTime::now_utc();
+    let rfc3339 = &time::format_description::well_known::Rfc3339;
+    let day_ago = (now - time::Duration::days(1)).format(rfc3339)?;
+    let cooldown = (now - time::Duration::hours(NUDGE_COOLDOWN_HOURS)).format(rfc3339)?;
+    let refusal = if !db.nudges_enabled(member.user_id).await? {
+        Some(format!("@{username} has turned nudges off."))
+    } else if db
+        .last_member_nudge(chat_id, member.user_id)
+        .await?
+        .is_some_and(|at| at >= cooldown)
+    {
+        Some(format!(
+            "@{username} was nudged in the last {NUDGE_COOLDOWN_HOURS} hours. Give them a break."
+        ))
+    } else if db.member_nudges_since(chat_id, uuid, &day_ago).await? >= NUDGES_PER_DAY {
+        Some(format!(
+            "You've sent {NUDGES_PER_DAY} nudges here today. Try again tomorrow."
+        ))
+    } else {
+        None
+    };
+    if let Some(text) = refusal {
+        bot.send_message(msg.chat.id, text).await?;
+        return Ok(());
+    }
+
+    let week_ago = (now - time::Duration::days(7)).format(rfc3339)?;
+    let (last, recent) = db.chat_activity(member.user_id, chat_id, &week_ago).await?;
+    let group = msg.chat.title().unwrap_or("your group");
+    let mut text = format!("👋 {sender_name} nudged you from {group}.");
+    if let Some(last) = last {
+        let days = OffsetDateTime::parse(&last, rfc3339)
+            .map(|at| (now - at).whole_days())
+            .unwrap_or_default();
+        text.push_str(&format!(
+            "\nYour last entry there was {days} days ago ({}), and you logged {recent} in the past week.",
+            last.get(..10).unwrap_or(&last)
+        ));
+    }
+    let currency = user_currency(db, member.user_id).await?;
+    text.push_str(&format!(
+        "\nYour stash is at {}. Resisted anything lately? /save it. (/nudges off stops these.)",
+        currency.format(db.total_cents(member.user_id).await?)
+    ));
+    // The member has to have started a private chat with the bot.
+    if let Err(err) = bot.send_message(ChatId(member.tg_user_id), text).await {
+        if !outbox::note_blocked(db, ChatId(member.tg_user_id), &err).await? {
+            eprintln!("nudge: to {}: {err:?}", member.tg_user_id);
+        }
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "I can't message @{username} privately; they need to start a chat with me first."
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+    db.record_member_nudge(chat_id, uuid, member.user_id)
+        .await?;
+    let name = member.first_name.unwrap_or_else(|| format!("@{username}"));
+    bot.send_message(msg.chat.id, format!("Nudged {name} privately."))
+        .await?;
+    Ok(())
+}
+
+/// /disablecmd and /enablecmd: edits the chat's list of turned-off commands,
+/// or lists it when no command is given.
+async fn toggle_command(
+    bot: &Bot,
+    db: &Db,
+    msg: &Message,
+    from: Option<&teloxide::types::User>,
+    args: &str,
+    enable: bool,
+) -> Result<()> {
+    if msg.chat.is_private() {
+        bot.send_message(msg.chat.id, "Commands can only be turned off in groups.")
+            .await?;
+        return Ok(());
+    }
+    let mut disabled = db.disabled_commands(msg.chat.id.0).await?;
+    let name = command_name(args);
+    if name.is_empty() {
+        let text = if disabled.is_empty() {
+            "All commands are on in this chat.".to_string()
+        } else {
+            let list: Vec<String> = disabled.iter().map(|c| format!("/{c}")).collect();
+            format!("Turned off in this chat: {}", list.join(", "))
+        };
+        bot.send_message(msg.chat.id, text).await?;
+        return Ok(());
+    }
+    if !is_group_admin(bot, msg, from).await? {
+        bot.send_message(
+            msg.chat.id,
+            "Only group admins can turn commands on or off.",
+        )
+        .await?;
+        return Ok(());
+    }
+    let known = Command::bot_commands()
+        .iter()
+        .any(|c| c.command.trim_start_matches('/') == name);
+    let text = if !known {
+        format!("There is no /{name} command.")
+    } else if name == "disablecmd" || name == "enablecmd" {
+        "That one has to stay on.".to_string()
+    } else if enable {
+        disabled.retain(|c| *c != name);
+        db.set_disabled_commands(msg.chat.id.0, &disabled).await?;
+        format!("/{name} is back on in this chat.")
+    } else {
+        if !disabled.contains(&name) {
+            disabled.push(name.clone());
+        }
+        db.set_disabled_commands(msg.chat.id.0, &disabled).await?;
+        format!("/{name} is now off in this chat. /enablecmd {name} turns it back on.")
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// `save` for "/save@AllinvooBot 12" or "save", as /disablecmd stores it.
+fn command_name(text: &str) -> String {
+    let word = text.split_whitespace().next().unwrap_or_default();
+    let word = word.trim_start_matches('/');
+    word.split('@').next().unwrap_or_default().to_lowercase()
+}
+
+/// Groups may pin a language with /chatlanguage; otherwise we follow the
+/// sender's Telegram client language.
+async fn reply_language(
+    db: &Db,
+    msg: &Message,
+    from: Option<&teloxide::types::User>,
+) -> Result<Lang> {
+    if !msg.chat.is_private() {
+        if let Some(code) = db.chat_language(msg.chat.id.0).await? {
+            if let Some(lang) = Lang::from_code(&code) {
+                return Ok(lang);
             }
         }
     }
+    Ok(from
+        .and_then(|u| u.language_code.as_deref())
+        .and_then(Lang::from_code)
+        .unwrap_or(Lang::En))
+}
+
+/// /shared: everything members record, and every /allinvoo, goes to one
+/// pooled ledger while they are in it; their own stash waits untouched
+/// until they leave.
+async fn shared_command(
+    bot: &Bot,
+    db: &Db,
+    msg: &Message,
+    from: &teloxide::types::User,
+    args: &str,
+) -> Result<()> {
+    let me = personal_user(db, from).await?;
+    let current = db.shared_ledger(me).await?;
+    let (verb, rest) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+    let rest = rest.trim();
+    let text = match (verb, current) {
+        ("", None) => "You're not in a shared ledger. /shared create {name} starts one, /shared join {code} joins one. While you're in one, everything you record and /allinvoo goes to the shared stash, and your own waits until you leave.".to_string(),
+        ("", Some(ledger)) => {
+            let mut text = format!(
+                "👪 {}: {} ({} members). Your totals, /allinvoo and reports are the shared ones.",
+                ledger.name,
+                ledger.members.join(", "),
+                ledger.members.len()
+            );
+            if msg.chat.is_private() {
+                text.push_str(&format!(
+                    "\nInvite code: {} (/shared join {}). /shared newcode replaces it.",
+                    ledger.code, ledger.code
+                ));
+            }
+            text.push_str("\n/shared leave takes you back to your own stash.");
+            text
+        }
+        ("create" | "join", Some(ledger)) => format!(
+            "You're already in {}. /shared leave first.",
+            ledger.name
+        ),
+        ("create", None) if !msg.chat.is_private() => {
+            "Create a shared ledger in a private chat, so the invite code stays private.".into()
+        }
+        ("create", None) => {
+            let name = if rest.is_empty() {
+                format!("{}'s household", from.first_name)
+            } else {
+                rest.to_string()
+            };
+            let code = shared_code();
+            db.create_shared_ledger(me, &name, &code).await?;
+            format!(
+                "👪 Created {name}. Others join with /shared join {code}. From now on what you record counts toward it; your own stash is kept aside until you /shared leave."
+            )
+        }
+        ("join", None) if rest.is_empty() => "Usage: /shared join {code}".into(),
+        ("join", None) => match db.join_shared_ledger(me, rest).await? {
+            Some(name) => format!(
+                "👪 You joined {name}. What you record now counts toward its total, and /allinvoo invests the pooled stash. Your own stash is kept aside until you /shared leave."
+            ),
+            None => "No shared ledger has that code.".into(),
+        },
+        ("newcode", Some(_)) if !msg.chat.is_private() => {
+            "Ask me for a new invite code in a private chat.".into()
+        }
+        ("newcode", Some(ledger)) => {
+            let code = shared_code();
+            db.set_shared_code(ledger.ledger_id, &code).await?;
+            format!("New invite code: {code}. The old one no longer works; members stay in.")
+        }
+        ("leave", Some(ledger)) => {
+            db.leave_shared_ledger(me).await?;
+            format!(
+                "You left {}. Its entries stay with the other members; you're back on your own stash.",
+                ledger.name
+            )
+        }
+        ("newcode" | "leave", None) => "You're not in a shared ledger.".into(),
+        _ => "Usage: /shared [create {name}|join {code}|newcode|leave]".into(),
+    };
+    bot.send_message(msg.chat.id, text).await?;
     Ok(())
 }
 
+/// An invite code for /shared join, easy to read out.
+fn shared_code() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase()
+}
+
+/// The chat's /splitrounding rule; largest remainder until one is picked.
+async fn chat_rounding(db: &Db, chat_id: i64) -> Result<rounding::Rounding> {
+    Ok(db
+        .split_rounding(chat_id)
+        .await?
+        .as_deref()
+        .and_then(rounding::Rounding::parse)
+        .unwrap_or(rounding::Rounding::LargestRemainder))
+}
+
+/// The group's shared ledger, when /anonymous pot is on. It is a
+/// pseudo-user keyed by the chat id, which never clashes with a real user id.
+async fn chat_pot(db: &Db, chat: &teloxide::types::Chat) -> Result<Option<uuid::Uuid>> {
+    if chat.is_private() || !db.anonymous_pot(chat.id.0).await? {
+        return Ok(None);
+    }
+    Ok(Some(pot_user(db, chat).await?))
+}
+
+async fn pot_user(db: &Db, chat: &teloxide::types::Chat) -> Result<uuid::Uuid> {
+    let name = chat.title().unwrap_or("group pot").to_string();
+    db.ensure_pot(chat.id.0, name).await
+}
+
+/// Whether the sender is one of the bot's operators in `ADMIN_USER_IDS`.
+fn is_operator(from: Option<&teloxide::types::User>) -> bool {
+    env::var("ADMIN_USER_IDS")
+        .unwrap_or_default()
+        .split(',')
+        .any(|id| from.is_some_and(|u| id.trim() == u.id.0.to_string()))
+}
+
+/// `3h`-style age of `then`, for /globalstats.
+fn ago(then: OffsetDateTime, now: OffsetDateTime) -> String {
+    let secs = (now - then).whole_seconds().max(0);
+    match secs {
+        0..=119 => format!("{secs}s ago"),
+        120..=7199 => format!("{}m ago", secs / 60),
+        7200..=172_799 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+/// Whether the sender administers the group. Anonymous admins post as the
+/// group itself, which only admins can do.
+async fn is_group_admin(
+    bot: &Bot,
+    msg: &Message,
+    from: Option<&teloxide::types::User>,
+) -> Result<bool> {
+    if msg.sender_chat().is_some_and(|c| c.id == msg.chat.id) {
+        return Ok(true);
+    }
+    let Some(from) = from else {
+        return Ok(false);
+    };
+    Ok(bot
+        .get_chat_member(msg.chat.id, from.id)
+        .await?
+        .is_privileged())
+}
+
 fn display_name(u: &teloxide::types::User) -> String {
     if let Some(username) = &u.username {
         format!("@{}", username)
@@ -243,32 +5391,218 @@ fn display_name(u: &teloxide::types::User) -> String {
     }
 }
 
+/// Commands that book entries on the sender's ledger, which the operator's
+/// [`quota`] can refuse. /import checks its own row count.
+fn records_entry(cmd: &Command) -> bool {
+    matches!(
+        cmd,
+        Command::Save(_)
+            | Command::Adjust(_)
+            | Command::Withdraw(_)
+            | Command::Openingbalance(_)
+            | Command::Dividend(_)
+            | Command::Reverse(_)
+            | Command::Split(_)
+            | Command::Transfer(_)
+    )
+}
+
+/// Whether amounts from this user are read like 1.234,56: their /decimal
+/// choice, or failing that the decimal mark of their Telegram language.
+async fn reads_decimal_comma(
+    db: &Db,
+    uuid: uuid::Uuid,
+    from: Option<&teloxide::types::User>,
+) -> Result<bool> {
+    // How someone types is theirs, not their /shared ledger's.
+    let uuid = match from {
+        Some(from) => personal_user(db, from).await?,
+        None => uuid,
+    };
+    Ok(match db.decimal_comma(uuid).await? {
+        Some(comma) => comma,
+        None => from
+            .and_then(|u| u.language_code.as_deref())
+            .is_some_and(i18n::uses_decimal_comma),
+    })
+}
+
+/// Currency symbols allowed around a typed amount, e.g. `$12` or `12,50€`.
+/// They are only stripped: the amount is in the user's own currency, and a
+/// code after it (`12 GBP`) is what asks for a conversion.
+const AMOUNT_SYMBOLS: [char; 5] = ['$', '€', '£', '¥', '₹'];
+
 /// Parses "amount [reason...]" where:
 /// - for /save: amount must be positive "12" or "12.34"
 /// - for /adjust: amount may be signed: "+5", "-3.50"
-fn parse_amount_and_reason(input: &str, allow_signed: bool) -> Result<(i64, Option<String>)> {
+///
+/// With `comma` (see /decimal) the amount is read like `1.234,56`;
+/// otherwise `.` and `,` are both decimal marks, though `1,234.56` groups
+/// thousands. A currency symbol may come before or after the amount.
+/// Arithmetic such as `3*4.50` is worked out first (see [`calc`]), and
+/// without digits a spelled amount such as "five dollars for coffee" is
+/// tried instead.
+fn parse_amount_and_reason(
+    input: &str,
+    allow_signed: bool,
+    comma: bool,
+) -> Result<(i64, Option<String>)> {
     let s = input.trim();
     if s.is_empty() {
         return Err(anyhow!("Missing amount"));
     }
 
-    let re = if allow_signed {
-        Regex::new(r#"^\s*([+-]?\d+(?:[.,]\d{1,2})?)\s*(.*)$"#).unwrap()
+    if let Some((_, cents, rest)) = calc::leading(s, comma) {
+        if cents < 0 && !allow_signed {
+            return Err(anyhow!("Bad amount format"));
+        }
+        return Ok((cents, Some(rest.to_string()).filter(|r| !r.is_empty())));
+    }
+
+    let sign = if allow_signed { "[+-]?" } else { "" };
+    let number = if comma {
+        r"\d[\d.]*(?:,\d{1,2})?"
     } else {
-        Regex::new(r#"^\s*(\d+(?:[.,]\d{1,2})?)\s*(.*)$"#).unwrap()
+        r"\d{1,3}(?:,\d{3})+(?:\.\d{1,2})?|\d+(?:[.,]\d{1,2})?"
     };
+    let symbol: String = AMOUNT_SYMBOLS.iter().collect();
+    // The amount ends at a space or symbol, so `1,2345` is refused rather
+    // than read as 1.23 with a reason of "45".
+    let re = Regex::new(&format!(
+        r#"^\s*(?:[{symbol}]\s*)?({sign})(?:[{symbol}]\s*)?({number})\s*[{symbol}]?(?:\s+(.*))?$"#
+    ))
+    .unwrap();
 
-    let caps = re.captures(s).ok_or_else(|| anyhow!("Bad amount format"))?;
-    let amount_str = caps.get(1).unwrap().as_str().replace(',', ".");
+    let Some(caps) = re.captures(s) else {
+        // No digits: maybe the amount is spelled out, e.g. "five dollars".
+        let (cents, rest) =
+            nlp::spelled_amount(s, allow_signed).ok_or_else(|| anyhow!("Bad amount format"))?;
+        return Ok((cents, Some(rest.to_string()).filter(|r| !r.is_empty())));
+    };
     let reason = caps
-        .get(2)
+        .get(3)
         .map(|m| m.as_str().trim().to_string())
         .filter(|t| !t.is_empty());
 
-    let cents = decimal_to_cents(&amount_str)?;
+    let cents = parse_cents(&format!("{}{}", &caps[1], &caps[2]), comma)?;
     Ok((cents, reason))
 }
 
+/// A typed amount on its own, read as parse_amount_and_reason does.
+fn parse_cents(s: &str, comma: bool) -> Result<i64> {
+    let s = s.trim().trim_start_matches(AMOUNT_SYMBOLS).trim_start();
+    let (sign, rest) = s.split_at(if s.starts_with(['+', '-']) { 1 } else { 0 });
+    let rest = rest.trim_matches(|c: char| AMOUNT_SYMBOLS.contains(&c) || c.is_whitespace());
+    let signed = format!("{sign}{rest}");
+    let s = signed.as_str();
+    if !comma {
+        // `1,234` and `1,234.56` group thousands; `12,5` and `12,34` are
+        // decimal commas.
+        let grouped = rest.contains('.')
+            || rest.matches(',').count() > 1
+            || rest
+                .split_once(',')
+                .is_some_and(|(_, frac)| frac.len() == 3);
+        if !grouped || !rest.contains(',') {
+            return decimal_to_cents(&s.replace(',', "."));
+        }
+        let whole = rest.split('.').next().unwrap_or_default();
+        let mut groups = whole.split(',');
+        let first = groups.next().unwrap_or_default();
+        if !(1..=3).contains(&first.len()) || !groups.all(|g| g.len() == 3) {
+            return Err(anyhow!("Bad amount format"));
+        }
+        return decimal_to_cents(&s.replace(',', ""));
+    }
+    let (whole, frac) = match s.split_once(',') {
+        Some((whole, frac)) => (whole, Some(frac)),
+        None => (s, None),
+    };
+    if whole.contains('.') {
+        // Thousands separators must group by three: 1.234.567 but not 12.34.
+        let mut groups = whole.trim_start_matches(['+', '-']).split('.');
+        let first = groups.next().unwrap_or_default();
+        if !(1..=3).contains(&first.len()) || !groups.all(|g| g.len() == 3) {
+            return Err(anyhow!("Bad amount format"));
+        }
+    }
+    let whole = whole.replace('.', "");
+    match frac {
+        Some(frac) => decimal_to_cents(&format!("{whole}.{frac}")),
+        None => decimal_to_cents(&whole),
+    }
+}
+
+/// Pulls a `!need`, `!want` or `!savings` marker out of the reason text.
+fn split_class(reason: Option<String>) -> (Option<String>, Option<&'static str>) {
+    let Some(reason) = reason else {
+        return (None, None);
+    };
+    let mut class = None;
+    let rest: Vec<&str> = reason
+        .split_whitespace()
+        .filter(|word| {
+            let found = match word.to_ascii_lowercase().as_str() {
+                "!need" | "!needs" => Some("need"),
+                "!want" | "!wants" => Some("want"),
+                "!saving" | "!savings" => Some("savings"),
+                _ => None,
+            };
+            if found.is_some() {
+                class = found;
+            }
+            found.is_none()
+        })
+        .collect();
+    let rest = rest.join(" ");
+    ((!rest.is_empty()).then_some(rest), class)
+}
+
+/// An optional `YYYY-MM` argument, defaulting to the current month.
+fn month_arg(arg: &str) -> Option<String> {
+    match arg.trim() {
+        "" => OffsetDateTime::now_utc()
+            .format(format_description!("[year]-[month]"))
+            .ok(),
+        m if Regex::new(r"^\d{4}-(0[1-9]|1[0-2])$").unwrap().is_match(m) => Some(m.to_string()),
+        _ => None,
+    }
+}
+
+/// The first `#hashtag` in a reason names its category.
+fn category_of(reason: Option<&str>) -> String {
+    tags::parse(reason.unwrap_or_default())
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| "uncategorized".into())
+}
+
+fn parse_date(s: &str) -> Option<Date> {
+    Date::parse(s.trim(), format_description!("[year]-[month]-[day]")).ok()
+}
+
+const BAD_DATE: &str = "Dates must be YYYY-MM-DD and not in the future.";
+
+/// Splits a leading `YYYY-MM-DD` off the reason for backdated entries.
+/// Returns None when the date is malformed or in the future.
+fn split_date(reason: Option<String>) -> Option<(Option<String>, Option<String>)> {
+    let Some(reason) = reason else {
+        return Some((None, None));
+    };
+    let (first, rest) = reason
+        .split_once(char::is_whitespace)
+        .unwrap_or((reason.as_str(), ""));
+    if !Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap().is_match(first) {
+        return Some((None, Some(reason)));
+    }
+    let date = parse_date(first).filter(|d| *d <= OffsetDateTime::now_utc().date())?;
+    let rest = rest.trim();
+    Some((
+        Some(format!("{date}T00:00:00Z")),
+        (!rest.is_empty()).then(|| rest.to_string()),
+    ))
+}
+
 fn decimal_to_cents(s: &str) -> Result<i64> {
     // Accept "12", "12.3", "12.34", "+5", "-3.5"
     let neg = s.starts_with('-');
@@ -297,9 +5631,15 @@ fn cents_to_major(cents: i64) -> i64 {
 fn cents_to_minor(cents: i64) -> String {
     format!("{:02}", (cents.abs() % 100))
 }
-fn reason_prefix(reason: &Option<String>) -> String {
-    reason
-        .as_ref()
-        .map(|r| format!("Reason: {}\n", r))
-        .unwrap_or_default()
+fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!(
+        "{sign}{}.{}",
+        cents_to_major(cents.abs()),
+        cents_to_minor(cents)
+    )
+}
+fn format_signed_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "+" };
+    format!("{sign}{}", format_cents(cents.abs()))
 }
@@ -0,0 +1,169 @@
+use anyhow::{anyhow, bail, Result};
+use std::env;
+use time::{macros::format_description, Date, Duration};
+
+/// Stooq's free delayed quote CSV; `{ticker}` is replaced with e.g. `voo`.
+const DEFAULT_PRICE_URL: &str = "https://stooq.com/q/l/?s={ticker}.us&f=sd2t2ohlcv&h&e=csv";
+
+/// Stooq's daily bars between `{from}` and `{to}` (`YYYYMMDD`).
+const DEFAULT_PRICE_HISTORY_URL: &str =
+    "https://stooq.com/q/d/l/?s={ticker}.us&d1={from}&d2={to}&i=d";
+
+/// Most tickers one user can /watchticker.
+pub const MAX_WATCHED: usize = 10;
+
+/// Last close of a US ticker in cents, or `None` when `PRICE_URL` is set
+/// to an empty string to keep the bot offline.
+#[tracing::instrument(skip_all, fields(ticker = ticker))]
+pub async fn price_cents(ticker: &str) -> Result<Option<i64>> {
+    let template = env::var("PRICE_URL").unwrap_or_else(|_| DEFAULT_PRICE_URL.into());
+    if template.is_empty() {
+        return Ok(None);
+    }
+    let url = template.replace("{ticker}", &ticker.to_lowercase());
+    let records = fetch_csv(&url).await?;
+    let [header, row, ..] = records.as_slice() else {
+        bail!("price lookup returned no quote for {ticker}");
+    };
+    let close = column(header, row, "close")
+        .ok_or_else(|| anyhow!("price lookup has no close for {ticker}"))?;
+    let price = cents(close).ok_or_else(|| anyhow!("no price for {ticker}: {close}"))?;
+    Ok(Some(price))
+}
+
+/// Last closes of `tickers`, each looked up once, and the tickers with no
+/// price right now. Failed lookups are logged under `context` and count as
+/// having no price.
+pub async fn prices<'a>(
+    tickers: impl IntoIterator<Item = &'a str>,
+    context: &str,
+) -> (Vec<(&'a str, i64)>, Vec<&'a str>) {
+    let mut tickers: Vec<&str> = tickers.into_iter().collect();
+    tickers.sort_unstable();
+    tickers.dedup();
+    let mut prices = Vec::new();
+    let mut unpriced = Vec::new();
+    for ticker in tickers {
+        match price_cents(ticker).await {
+            Ok(Some(price)) => prices.push((ticker, price)),
+            Ok(None) => unpriced.push(ticker),
+            Err(err) => {
+                eprintln!("{context}: {ticker} price: {err:?}");
+                unpriced.push(ticker);
+            }
+        }
+    }
+    (prices, unpriced)
+}
+
+/// Uppercases a ticker as typed, e.g. `vti` or `BRK-B`.
+pub fn normalize_ticker(s: &str) -> Option<String> {
+    let s = s.trim().to_uppercase();
+    let valid = !s.is_empty()
+        && s.len() <= 10
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    valid.then_some(s)
+}
+
+/// A ticker's latest close against a week earlier and its 52-week high.
+#[derive(Debug, Clone)]
+pub struct Week {
+    pub close_cents: i64,
+    pub week_ago_cents: Option<i64>,
+    pub high_cents: i64,
+}
+
+impl Week {
+    /// `VTI 245.10 +1.8% this week, 3.2% below the 52-week high`
+    pub fn line(&self, ticker: &str) -> String {
+        let change = |from: i64| (self.close_cents - from) as f64 * 100.0 / from as f64;
+        let mut line = format!("{ticker} {}", crate::format_cents(self.close_cents));
+        if let Some(ago) = self.week_ago_cents.filter(|c| *c > 0) {
+            line.push_str(&format!(" {:+.1}% this week", change(ago)));
+        }
+        if self.close_cents >= self.high_cents {
+            line.push_str(", at its 52-week high");
+        } else {
+            line.push_str(&format!(
+                ", {:.1}% below the 52-week high",
+                -change(self.high_cents)
+            ));
+        }
+        line
+    }
+}
+
+/// The last year of daily closes for a ticker up to `today`, or `None`
+/// when `PRICE_HISTORY_URL` is set to an empty string.
+#[tracing::instrument(skip_all, fields(ticker = ticker))]
+pub async fn week(ticker: &str, today: Date) -> Result<Option<Week>> {
+    let template =
+        env::var("PRICE_HISTORY_URL").unwrap_or_else(|_| DEFAULT_PRICE_HISTORY_URL.into());
+    if template.is_empty() {
+        return Ok(None);
+    }
+    let compact = format_description!("[year][month][day]");
+    let from = today - Duration::days(365);
+    let url = template
+        .replace("{ticker}", &ticker.to_lowercase())
+        .replace("{from}", &from.format(compact)?)
+        .replace("{to}", &today.format(compact)?);
+    let records = fetch_csv(&url).await?;
+    let Some((header, rows)) = records.split_first() else {
+        bail!("price history for {ticker} is empty");
+    };
+    // (day, close, high), oldest first as Stooq sends them.
+    let mut bars: Vec<(Date, i64, i64)> = rows
+        .iter()
+        .filter_map(|row| {
+            let day = crate::parse_date(column(header, row, "date")?)?;
+            let close = cents(column(header, row, "close")?)?;
+            let high = column(header, row, "high").and_then(cents).unwrap_or(close);
+            (day >= from).then_some((day, close, high))
+        })
+        .collect();
+    bars.sort_by_key(|(day, _, _)| *day);
+    let Some(&(last_day, close_cents, _)) = bars.last() else {
+        bail!("no price history for {ticker}");
+    };
+    let week_ago_cents = bars
+        .iter()
+        .rev()
+        .find(|(day, _, _)| *day <= last_day - Duration::weeks(1))
+        .map(|(_, close, _)| *close);
+    let high_cents = bars
+        .iter()
+        .map(|(_, _, high)| *high)
+        .max()
+        .unwrap_or(close_cents);
+    Ok(Some(Week {
+        close_cents,
+        week_ago_cents,
+        high_cents,
+    }))
+}
+
+async fn fetch_csv(url: &str) -> Result<Vec<Vec<String>>> {
+    let resp = reqwest::get(url).await?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("price lookup {status}");
+    }
+    Ok(crate::csv::parse(&resp.text().await?))
+}
+
+fn column<'a>(header: &[String], row: &'a [String], name: &str) -> Option<&'a str> {
+    header
+        .iter()
+        .position(|h| h.eq_ignore_ascii_case(name))
+        .and_then(|i| row.get(i))
+        .map(String::as_str)
+}
+
+/// Stooq answers unknown tickers with "N/D" rather than an error. Prices
+/// can have more than two decimals, hence f64 rather than decimal_to_cents.
+fn cents(price: &str) -> Option<i64> {
+    let price: f64 = price.parse().ok()?;
+    (price.is_finite() && price > 0.0).then(|| (price * 100.0).round() as i64)
+}
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use serde_json::json;
+use std::{
+    collections::HashSet,
+    env,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+use uuid::Uuid;
+
+use crate::events::Summary;
+
+struct Broker {
+    client: AsyncClient,
+    prefix: String,
+    /// Currency for Home Assistant discovery; `None` when discovery is off.
+    discovery: Option<String>,
+    /// Users whose discovery configs were sent since startup.
+    announced: Mutex<HashSet<Uuid>>,
+}
+
+/// Connected broker, set once at startup when `MQTT_URL` is configured.
+static CLIENT: OnceLock<Broker> = OnceLock::new();
+
+/// Connects to `MQTT_URL` (e.g. `mqtt://homeassistant.local:1883`) and keeps
+/// the connection alive in the background. Does nothing when it is unset.
+pub fn init() -> Result<()> {
+    let Some(url) = env::var("MQTT_URL").ok().filter(|u| !u.is_empty()) else {
+        return Ok(());
+    };
+    let url = if url.contains("client_id=") {
+        url
+    } else {
+        let sep = if url.contains('?') { '&' } else { '?' };
+        format!("{url}{sep}client_id=telegram-all-in-voo")
+    };
+    let mut opts = MqttOptions::parse_url(url).context("MQTT_URL is not a valid mqtt:// URL")?;
+    if let Ok(user) = env::var("MQTT_USERNAME") {
+        opts.set_credentials(user, env::var("MQTT_PASSWORD").unwrap_or_default());
+    }
+    let prefix = env::var("MQTT_TOPIC_PREFIX").unwrap_or_else(|_| "voo".into());
+    let discovery = env::var("MQTT_DISCOVERY")
+        .is_ok_and(|v| v == "1" || v == "true")
+        .then(|| env::var("MQTT_DISCOVERY_CURRENCY").unwrap_or_else(|_| "USD".into()));
+    let (client, mut eventloop) = AsyncClient::new(opts, 64);
+    CLIENT.get_or_init(|| Broker {
+        client,
+        prefix,
+        discovery,
+        announced: Mutex::new(HashSet::new()),
+    });
+    tokio::spawn(async move {
+        loop {
+            // Polling drives the connection; rumqttc reconnects on the next poll.
+            if let Err(err) = eventloop.poll().await {
+                eprintln!("mqtt: {err}");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Publishes an event to `{prefix}/{user}/events` and, when the event carries
+/// a new total, the retained `{prefix}/{user}/total` that dashboards read.
+/// Never waits: while the broker is unreachable, events beyond the client's
+/// queue are dropped.
+pub fn publish(user_id: Uuid, body: &str, total_cents: Option<i64>) {
+    let Some(Broker { client, prefix, .. }) = CLIENT.get() else {
+        return;
+    };
+    let events = format!("{prefix}/{user_id}/events");
+    if let Err(err) = client.try_publish(events, QoS::AtLeastOnce, false, body.to_string()) {
+        eprintln!("mqtt: publish error: {err:?}");
+    }
+    if let Some(total) = total_cents {
+        let topic = format!("{prefix}/{user_id}/total");
+        let total = crate::format_cents(total);
+        if let Err(err) = client.try_publish(topic, QoS::AtLeastOnce, true, total) {
+            eprintln!("mqtt: publish error: {err:?}");
+        }
+    }
+}
+
+pub fn enabled() -> bool {
+    CLIENT.get().is_some()
+}
+
+/// Publishes the retained `{prefix}/{user}/state` JSON that Home Assistant
+/// sensors read, announcing the sensors first if discovery is on.
+pub fn publish_state(user_id: Uuid, summary: &Summary) {
+    let Some(broker) = CLIENT.get() else {
+        return;
+    };
+    let state_topic = format!("{}/{user_id}/state", broker.prefix);
+    if let Some(currency) = &broker.discovery {
+        let first = broker
+            .announced
+            .lock()
+            .map(|mut seen| seen.insert(user_id))
+            .unwrap_or(false);
+        if first {
+            announce(broker, user_id, &state_topic, currency);
+        }
+    }
+    let state = summary.to_json().to_string();
+    if let Err(err) = broker
+        .client
+        .try_publish(state_topic, QoS::AtLeastOnce, true, state)
+    {
+        eprintln!("mqtt: publish error: {err:?}");
+    }
+}
+
+/// Home Assistant MQTT discovery: one retained config per sensor, grouped
+/// under a device per user.
+fn announce(broker: &Broker, user_id: Uuid, state_topic: &str, currency: &str) {
+    let short = &user_id.simple().to_string()[..8];
+    let device = json!({
+        "identifiers": [format!("voo_{user_id}")],
+        "name": format!("VOO savings {short}"),
+    });
+    let sensors = [
+        ("total", "Uninvested total", Some(currency)),
+        ("invested", "Invested total", Some(currency)),
+        ("goal_percent", "Goal progress", None),
+    ];
+    for (key, name, currency) in sensors {
+        let mut config = json!({
+            "name": name,
+            "unique_id": format!("voo_{user_id}_{key}"),
+            "state_topic": state_topic,
+            "value_template": format!("{{{{ value_json.{key} }}}}"),
+            "device": device,
+        });
+        match currency {
+            Some(c) => {
+                config["device_class"] = json!("monetary");
+                config["unit_of_measurement"] = json!(c);
+            }
+            None => config["unit_of_measurement"] = json!("%"),
+        }
+        let topic = format!("homeassistant/sensor/voo_{user_id}/{key}/config");
+        if let Err(err) =
+            broker
+                .client
+                .try_publish(topic, QoS::AtLeastOnce, true, config.to_string())
+        {
+            eprintln!("mqtt: discovery error: {err:?}");
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! /networth: the uninvested stash plus what the invested batches are worth
+//! at the last close, against the daily snapshot from a week ago. The
+//! scheduler stores each day's market value next to its snapshot so the
+//! comparison is value against value, not against cost.
+
+use anyhow::Result;
+use time::{Date, Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::currency::Currency;
+use crate::db::Db;
+use crate::{hedged, market};
+
+/// Last closes of every ticker anyone holds and today's rate for every
+/// currency saved in, then the day's snapshot values from them. Tickers
+/// without a price, or savers without a rate, just count at cost.
+pub async fn record_values(db: &Db, day: Date) -> Result<u64> {
+    let tickers = db.held_tickers().await?;
+    let (prices, _) = market::prices(tickers.iter().map(String::as_str), "networth").await;
+    let prices: Vec<(String, i64)> = prices
+        .into_iter()
+        .map(|(t, p)| (t.to_string(), p))
+        .collect();
+    let mut rates = Vec::new();
+    for code in db.currencies_in_use().await? {
+        let Some(currency) = Currency::parse(&code) else {
+            continue;
+        };
+        match hedged::usd_rate(db, currency).await {
+            Ok(Some(rate)) => rates.push((code, rate)),
+            Ok(None) => {}
+            Err(err) => eprintln!("networth: {code} rate: {err:?}"),
+        }
+    }
+    db.set_snapshot_values(&day.to_string(), &prices, &rates)
+        .await
+}
+
+pub async fn report(db: &Db, uuid: Uuid, currency: Currency) -> Result<Vec<String>> {
+    let (cash, invested) = db.balances(uuid).await?;
+
+    // Shares of the same ticker in different strategies are priced once.
+    let mut positions: Vec<(String, i64, i64)> = Vec::new();
+    for h in db.holdings(uuid).await? {
+        match positions.iter_mut().find(|p| p.0 == h.ticker) {
+            Some(p) => {
+                p.1 += h.micro_shares;
+                p.2 += h.cost_cents;
+            }
+            None => positions.push((h.ticker, h.micro_shares, h.cost_cents)),
+        }
+    }
+    // Prices are in USD; bare numbers are taken to be USD too.
+    let usd = match currency.code {
+        "" => currency,
+        _ => Currency::parse("USD").unwrap_or(Currency::PLAIN),
+    };
+    let rate = hedged::value_rate(db, currency, "networth").await;
+    let (prices, unpriced) = match rate {
+        Some(_) => market::prices(positions.iter().map(|p| p.0.as_str()), "networth").await,
+        None => (Vec::new(), Vec::new()),
+    };
+    let mut value = invested;
+    let mut holding_lines = Vec::new();
+    for (ticker, micro_shares, cost) in &positions {
+        let (Some(&(_, price)), Some(rate)) = (prices.iter().find(|p| p.0 == ticker), rate) else {
+            continue;
+        };
+        let usd_worth = *micro_shares as f64 * price as f64 / 1_000_000.0;
+        let worth = (usd_worth / rate).round() as i64;
+        value += worth - cost;
+        holding_lines.push(format!(
+            "  {ticker}: {:.4} × {} = {}",
+            *micro_shares as f64 / 1_000_000.0,
+            usd.format(price),
+            currency.format(worth)
+        ));
+    }
+    let total = cash + value;
+
+    let mut lines = vec![
+        format!("💰 Net worth: {}", currency.format(total)),
+        format!("Cash (uninvested): {}", currency.format(cash)),
+        format!(
+            "Invested: {} (cost {}, {})",
+            currency.format(value),
+            currency.format(invested),
+            currency.format_signed(value - invested)
+        ),
+    ];
+    lines.extend(holding_lines);
+    let priced_cost: i64 = positions.iter().map(|p| p.2).sum();
+    if invested > priced_cost {
+        lines.push(format!(
+            "{} invested before share prices were recorded counts at cost.",
+            currency.format(invested - priced_cost)
+        ));
+    }
+    if rate.is_none() && !positions.is_empty() {
+        lines.push(format!(
+            "No {}/USD exchange rate right now; shares counted at cost.",
+            currency.code
+        ));
+    }
+    if !unpriced.is_empty() {
+        lines.push(format!(
+            "No price for {} right now; counted at cost.",
+            unpriced.join(", ")
+        ));
+    }
+
+    let week_ago = OffsetDateTime::now_utc().date() - Duration::days(7);
+    match db
+        .snapshot_on_or_before(uuid, &week_ago.to_string())
+        .await?
+    {
+        Some(snap) => {
+            let then = snap.cash_cents + snap.value_cents.unwrap_or(snap.invested_cents);
+            lines.push(format!(
+                "Since {}: {} (was {})",
+                snap.day,
+                currency.format_signed(total - then),
+                currency.format(then)
+            ));
+        }
+        None => lines.push("No snapshot from a week ago yet to compare with.".into()),
+    }
+    Ok(lines)
+}
@@ -0,0 +1,463 @@
+use regex::Regex;
+
+/// Words around the amount that carry no meaning for the reason, e.g. the
+/// "skipped a" and "for" in "skipped a coffee for 4.50".
+const FILLER: &[&str] = &[
+    "i", "i'd", "i've", "just", "saved", "save", "skipped", "skip", "didn't", "did", "not", "buy",
+    "bought", "spent", "resisted", "instead", "of", "on", "for", "a", "an", "the", "about", "and",
+    "by",
+];
+
+const CURRENCY: &[&str] = &[
+    "$", "€", "£", "usd", "eur", "dollar", "dollars", "euro", "euros", "buck", "bucks", "pounds",
+];
+
+/// Receipt lines that name the amount actually paid, most specific first.
+const TOTAL_LABELS: &[&str] = &["grand total", "amount due", "total due", "total", "summe"];
+
+/// Finds the amount paid in OCR'd receipt text: the last amount on the first
+/// line matching the most specific total label, or failing that the largest
+/// amount on the receipt.
+pub fn receipt_total(text: &str) -> Option<i64> {
+    let re = Regex::new(r"\d{1,3}(?:[.,]\d{3})*[.,]\d{2}\b").unwrap();
+    let amount = |s: &str| {
+        let digits: String = s.chars().filter(|c| c.is_ascii_digit()).collect();
+        digits.parse::<i64>().ok()
+    };
+    for label in TOTAL_LABELS {
+        for line in text.lines() {
+            let lower = line.to_lowercase();
+            if lower.contains(label) && !lower.contains("subtotal") && !lower.contains("sub total")
+            {
+                if let Some(m) = re.find_iter(line).last() {
+                    return amount(m.as_str()).filter(|c| *c > 0);
+                }
+            }
+        }
+    }
+    re.find_iter(text).filter_map(|m| amount(m.as_str())).max()
+}
+
+/// Pulls an amount and a reason out of free text such as a voice-note
+/// transcript: "Skipped a latte, saved $4.50" → (450, "latte").
+pub fn parse_entry(text: &str) -> Option<(i64, Option<String>)> {
+    let re = Regex::new(r"\d{1,3}(?:,\d{3})+(?:\.\d{1,2})?|\d+(?:[.,]\d{1,2})?").unwrap();
+    let m = re.find(text)?;
+    let amount = match m.as_str().split_once(',') {
+        // "4,50": a decimal comma, not a thousands separator.
+        Some((_, frac)) if frac.len() <= 2 => m.as_str().replace(',', "."),
+        _ => m.as_str().replace(',', ""),
+    };
+    let cents = crate::decimal_to_cents(&amount).ok().filter(|c| *c > 0)?;
+
+    let rest = format!("{} {}", &text[..m.start()], &text[m.end()..]);
+    let words: Vec<&str> = rest
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| c.is_ascii_punctuation() && c != '#' && c != '!'))
+        .filter(|w| !w.is_empty() && !CURRENCY.contains(&w.to_lowercase().as_str()))
+        .collect();
+    let is_filler = |w: &&str| FILLER.contains(&w.to_lowercase().as_str());
+    let start = words.iter().position(|w| !is_filler(w));
+    let end = words.iter().rposition(|w| !is_filler(w));
+    let reason = match (start, end) {
+        (Some(s), Some(e)) => Some(words[s..=e].join(" ")),
+        _ => None,
+    };
+    Some((cents, reason))
+}
+
+/// Number words from zero to ninety in the languages the bot speaks, with
+/// the spellings people type without umlauts or ß.
+const NUMBER_WORDS: &[(&str, i64)] = &[
+    ("zero", 0),
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+    ("ten", 10),
+    ("eleven", 11),
+    ("twelve", 12),
+    ("thirteen", 13),
+    ("fourteen", 14),
+    ("fifteen", 15),
+    ("sixteen", 16),
+    ("seventeen", 17),
+    ("eighteen", 18),
+    ("nineteen", 19),
+    ("twenty", 20),
+    ("thirty", 30),
+    ("forty", 40),
+    ("fifty", 50),
+    ("sixty", 60),
+    ("seventy", 70),
+    ("eighty", 80),
+    ("ninety", 90),
+    ("null", 0),
+    ("ein", 1),
+    ("eins", 1),
+    ("eine", 1),
+    ("einen", 1),
+    ("zwei", 2),
+    ("drei", 3),
+    ("vier", 4),
+    ("fünf", 5),
+    ("fuenf", 5),
+    ("sechs", 6),
+    ("sieben", 7),
+    ("acht", 8),
+    ("neun", 9),
+    ("zehn", 10),
+    ("elf", 11),
+    ("zwölf", 12),
+    ("zwoelf", 12),
+    ("dreizehn", 13),
+    ("vierzehn", 14),
+    ("fünfzehn", 15),
+    ("fuenfzehn", 15),
+    ("sechzehn", 16),
+    ("siebzehn", 17),
+    ("achtzehn", 18),
+    ("neunzehn", 19),
+    ("zwanzig", 20),
+    ("dreißig", 30),
+    ("dreissig", 30),
+    ("vierzig", 40),
+    ("fünfzig", 50),
+    ("fuenfzig", 50),
+    ("sechzig", 60),
+    ("siebzig", 70),
+    ("achtzig", 80),
+    ("neunzig", 90),
+];
+
+/// Currency names that may follow a spelled amount, e.g. "five dollars".
+const CURRENCY_WORDS: &[&str] = &[
+    "dollar", "dollars", "buck", "bucks", "euro", "euros", "pound", "pounds", "quid", "franken",
+];
+
+const CENT_WORDS: &[&str] = &["cent", "cents"];
+
+/// One word of a spelled amount.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Word {
+    Number(i64),
+    Hundred,
+    Thousand,
+    /// "point" or "komma", before the digits after the decimal mark.
+    Point,
+    /// "and" or "und" between parts of a number.
+    And,
+}
+
+fn word(w: &str) -> Option<Word> {
+    match w {
+        "hundred" | "hundert" => Some(Word::Hundred),
+        "thousand" | "tausend" => Some(Word::Thousand),
+        "point" | "komma" => Some(Word::Point),
+        "and" | "und" => Some(Word::And),
+        _ => german_compound(w).map(Word::Number),
+    }
+}
+
+fn small_number(w: &str) -> Option<i64> {
+    NUMBER_WORDS.iter().find(|(n, _)| *n == w).map(|(_, v)| *v)
+}
+
+/// German writes numbers below a million as one word:
+/// "zweihundertfünfundzwanzig" is 225. Plain number words parse too.
+fn german_compound(w: &str) -> Option<i64> {
+    fn below_100(w: &str) -> Option<i64> {
+        if let Some(v) = small_number(w) {
+            return Some(v);
+        }
+        // "fünfundzwanzig": the unit comes before the tens.
+        let (unit, tens) = w.split_once("und")?;
+        let unit = small_number(unit).filter(|u| (1..=9).contains(u))?;
+        let tens = small_number(tens).filter(|t| *t >= 20 && t % 10 == 0)?;
+        Some(tens + unit)
+    }
+    fn below_1000(w: &str) -> Option<i64> {
+        let Some((hundreds, rest)) = w.split_once("hundert") else {
+            return below_100(w);
+        };
+        let hundreds = match hundreds {
+            "" => 1,
+            h => below_100(h).filter(|h| (1..=9).contains(h))?,
+        };
+        let rest = match rest.strip_prefix("und").unwrap_or(rest) {
+            "" => 0,
+            r => below_100(r)?,
+        };
+        Some(hundreds * 100 + rest)
+    }
+    let Some((thousands, rest)) = w.split_once("tausend") else {
+        return below_1000(w);
+    };
+    let thousands = match thousands {
+        "" => 1,
+        t => below_1000(t).filter(|t| *t > 0)?,
+    };
+    let rest = match rest.strip_prefix("und").unwrap_or(rest) {
+        "" => 0,
+        r => below_1000(r)?,
+    };
+    Some(thousands * 1000 + rest)
+}
+
+/// Reads a spelled-out amount at the start of `text`, for /save and
+/// friends when no digits were typed: "five dollars for coffee",
+/// "twenty-five fifty", "one hundred and five", "zwölf Euro fünfzig",
+/// "fifty cents", "minus three" (only with `allow_signed`). Returns the
+/// cents and the rest of the text, with a leading "for" or "für" dropped.
+pub fn spelled_amount(text: &str, allow_signed: bool) -> Option<(i64, &str)> {
+    let tokens: Vec<(usize, String)> = Regex::new(r"\S+")
+        .unwrap()
+        .find_iter(text)
+        .map(|m| {
+            let w = m.as_str().trim_end_matches([',', '.', '!', '?', ':', ';']);
+            (m.start(), w.to_lowercase())
+        })
+        .collect();
+    let mut i = 0;
+    let negative = allow_signed && tokens.first().is_some_and(|(_, w)| w == "minus");
+    if negative {
+        i += 1;
+    }
+
+    // Whole units first: every word up to the currency, cents or decimal
+    // mark must be part of the number. "twenty-five" counts as two words.
+    let mut words = Vec::new();
+    while let Some((_, token)) = tokens.get(i) {
+        let parts: Option<Vec<Word>> = token.split('-').map(word).collect();
+        match parts {
+            Some(parts) if !parts.is_empty() => words.extend(parts),
+            _ => break,
+        }
+        i += 1;
+    }
+    // A trailing "and" belongs to what follows, as in "five dollars and ...".
+    while words.last() == Some(&Word::And) {
+        words.pop();
+        i -= 1;
+    }
+    let point = words.iter().position(|w| *w == Word::Point);
+    let (whole, decimals) = match point {
+        Some(p) => (&words[..p], Some(&words[p + 1..])),
+        None => (&words[..], None),
+    };
+    let mut cents = match (whole, decimals) {
+        ([], None) => return None,
+        ([], Some(_)) => 0,
+        (whole, _) => match units(whole) {
+            Some(units) => units.checked_mul(100)?,
+            // "twenty-five fifty", said the way 25.50 is.
+            None if decimals.is_none() => match whole.split_last() {
+                Some((Word::Number(c @ 10..=99), units_part)) if !units_part.is_empty() => {
+                    units(units_part)?.checked_mul(100)? + c
+                }
+                _ => return None,
+            },
+            None => return None,
+        },
+    };
+    if let Some(decimals) = decimals {
+        // "four point five" is 4.50, "four point zero five" 4.05.
+        let digits: Option<Vec<i64>> = decimals
+            .iter()
+            .map(|w| match w {
+                Word::Number(d @ 0..=9) => Some(*d),
+                _ => None,
+            })
+            .collect();
+        match digits?.as_slice() {
+            [d] => cents += d * 10,
+            [d, e] => cents += d * 10 + e,
+            _ => return None,
+        }
+    }
+
+    let next = |i: usize| tokens.get(i).map(|(_, w)| w.as_str());
+    if decimals.is_none() && next(i).is_some_and(|w| CENT_WORDS.contains(&w)) {
+        // "fifty cents": the number was cents all along.
+        cents /= 100;
+        i += 1;
+    } else if next(i).is_some_and(|w| CURRENCY_WORDS.contains(&w)) {
+        i += 1;
+        // "five dollars [and] fifty [cents]"
+        let mut j = i;
+        if next(j).is_some_and(|w| matches!(word(w), Some(Word::And))) {
+            j += 1;
+        }
+        if decimals.is_none() {
+            if let Some(Some(Word::Number(c @ 0..=99))) = next(j).map(word) {
+                cents += c;
+                j += 1;
+                if next(j).is_some_and(|w| CENT_WORDS.contains(&w)) {
+                    j += 1;
+                }
+                i = j;
+            }
+        }
+    }
+
+    let mut rest = tokens.get(i).map_or("", |(start, _)| &text[*start..]);
+    if let Some((_, w)) = tokens.get(i) {
+        if w == "for" || w == "für" || w == "fuer" {
+            rest = tokens.get(i + 1).map_or("", |(start, _)| &text[*start..]);
+        }
+    }
+    Some((if negative { -cents } else { cents }, rest.trim()))
+}
+
+/// Whole units from number words: "one hundred and five" is 105. Words
+/// that don't make one number, like "five twenty", are refused.
+fn units(words: &[Word]) -> Option<i64> {
+    let mut total: i64 = 0;
+    let mut current: i64 = 0;
+    // What the last word was, so "five five" or "twenty thirty" is refused
+    // rather than added up.
+    let mut last: Option<i64> = None;
+    for w in words {
+        match *w {
+            Word::Number(v) => {
+                let fits = match last {
+                    None => true,
+                    // "twenty five", "hundred five" but not "five twenty".
+                    Some(l) => l >= 100 || l >= 20 && l % 10 == 0 && v < 10,
+                };
+                if !fits {
+                    return None;
+                }
+                current += v;
+                last = Some(if v >= 100 { v } else { current % 100 });
+            }
+            Word::Hundred => {
+                current = current.max(1).checked_mul(100)?;
+                last = Some(100);
+            }
+            Word::Thousand => {
+                total = total.checked_add(current.max(1).checked_mul(1000)?)?;
+                current = 0;
+                last = Some(1000);
+            }
+            Word::And if last.is_some_and(|l| l >= 100) => {}
+            Word::And | Word::Point => return None,
+        }
+    }
+    total.checked_add(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn german_compounds() {
+        assert_eq!(german_compound("fünfundzwanzig"), Some(25));
+        assert_eq!(german_compound("fuenfundzwanzig"), Some(25));
+        assert_eq!(german_compound("zweihundertfünfundzwanzig"), Some(225));
+        assert_eq!(german_compound("hundertundeins"), Some(101));
+        assert_eq!(german_compound("dreitausendvierhundert"), Some(3400));
+        assert_eq!(german_compound("zwölf"), Some(12));
+        // Tens before the unit, or a unit that isn't one, aren't numbers.
+        assert_eq!(german_compound("zwanzigundfünf"), None);
+        assert_eq!(german_compound("zwölfundzwanzig"), None);
+        assert_eq!(german_compound("kaffee"), None);
+    }
+
+    #[test]
+    fn spelled_whole_amounts() {
+        assert_eq!(
+            spelled_amount("five dollars for coffee", false),
+            Some((500, "coffee"))
+        );
+        assert_eq!(spelled_amount("twenty-five", false), Some((2500, "")));
+        assert_eq!(
+            spelled_amount("one hundred and five books", false),
+            Some((10500, "books"))
+        );
+        assert_eq!(
+            spelled_amount("fünfundzwanzig Euro für Kino", false),
+            Some((2500, "Kino"))
+        );
+        assert_eq!(spelled_amount("fifty cents gum", false), Some((50, "gum")));
+    }
+
+    #[test]
+    fn spelled_decimals() {
+        assert_eq!(spelled_amount("four point five", false), Some((450, "")));
+        assert_eq!(
+            spelled_amount("four point zero five tea", false),
+            Some((405, "tea"))
+        );
+        assert_eq!(spelled_amount("drei komma fünf", false), Some((350, "")));
+        assert_eq!(spelled_amount("four point five six seven", false), None);
+    }
+
+    #[test]
+    fn spelled_dollars_and_cents() {
+        assert_eq!(
+            spelled_amount("five dollars and fifty cents for lunch", false),
+            Some((550, "lunch"))
+        );
+        assert_eq!(
+            spelled_amount("zwölf Euro fünfzig", false),
+            Some((1250, ""))
+        );
+        // "and" not followed by cents stays in the reason.
+        assert_eq!(
+            spelled_amount("five dollars and a cookie", false),
+            Some((500, "and a cookie"))
+        );
+    }
+
+    #[test]
+    fn spelled_like_a_price() {
+        assert_eq!(
+            spelled_amount("twenty-five fifty shoes", false),
+            Some((2550, "shoes"))
+        );
+        assert_eq!(spelled_amount("five twenty", false), Some((520, "")));
+        assert_eq!(spelled_amount("twenty thirty", false), Some((2030, "")));
+        assert_eq!(spelled_amount("five five five", false), None);
+    }
+
+    #[test]
+    fn spelled_signs() {
+        assert_eq!(spelled_amount("minus three", true), Some((-300, "")));
+        assert_eq!(spelled_amount("minus three", false), None);
+    }
+
+    #[test]
+    fn digits_are_not_spelled() {
+        // Typed digits go through the numeric parser instead.
+        assert_eq!(spelled_amount("12.50 lunch", false), None);
+        assert_eq!(spelled_amount("coffee for five", false), None);
+        assert_eq!(spelled_amount("", false), None);
+        assert_eq!(spelled_amount("and", false), None);
+    }
+
+    #[test]
+    fn entries_from_transcripts() {
+        assert_eq!(
+            parse_entry("Skipped a latte, saved $4.50"),
+            Some((450, Some("latte".into())))
+        );
+        assert_eq!(
+            parse_entry("4,50 for a sandwich"),
+            Some((450, Some("sandwich".into())))
+        );
+        assert_eq!(
+            parse_entry("bought 1,200 worth of nothing"),
+            Some((120000, Some("worth of nothing".into())))
+        );
+        assert_eq!(parse_entry("saved 20"), Some((2000, None)));
+        assert_eq!(parse_entry("skipped coffee"), None);
+        assert_eq!(parse_entry("spent 0 today"), None);
+    }
+}
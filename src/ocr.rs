@@ -0,0 +1,46 @@
+use anyhow::{anyhow, bail, Result};
+use std::env;
+
+/// An OCR.space-compatible text recognition endpoint. Off unless
+/// `OCR_API_KEY` is set.
+pub struct Ocr {
+    url: String,
+    api_key: String,
+}
+
+impl Ocr {
+    pub fn from_env() -> Option<Self> {
+        let api_key = env::var("OCR_API_KEY").ok().filter(|k| !k.is_empty())?;
+        Some(Self {
+            url: env::var("OCR_URL").unwrap_or_else(|_| "https://api.ocr.space/parse/image".into()),
+            api_key,
+        })
+    }
+
+    /// Plain text of a JPEG photo, line breaks preserved.
+    #[tracing::instrument(skip_all, fields(bytes = image.len()))]
+    pub async fn recognize(&self, image: Vec<u8>) -> Result<String> {
+        let file = reqwest::multipart::Part::bytes(image)
+            .file_name("receipt.jpg")
+            .mime_str("image/jpeg")?;
+        let form = reqwest::multipart::Form::new()
+            .text("OCREngine", "2")
+            .text("scale", "true")
+            .part("file", file);
+        let resp = reqwest::Client::new()
+            .post(&self.url)
+            .header("apikey", &self.api_key)
+            .multipart(form)
+            .send()
+            .await?;
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await?;
+        if !status.is_success() || body["IsErroredOnProcessing"].as_bool() == Some(true) {
+            bail!("OCR {status}: {}", body["ErrorMessage"]);
+        }
+        body["ParsedResults"][0]["ParsedText"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("OCR response has no text"))
+    }
+}
@@ -0,0 +1,141 @@
+//! Messages that fail to send for a passing reason (a network blip, flood
+//! control) wait in the outbox table and are retried by the scheduler with
+//! growing gaps, so confirmations and reminders aren't lost. A user who
+//! blocked the bot gets nothing at all until they write to it again.
+
+use anyhow::Result;
+use teloxide::prelude::*;
+use teloxide::types::InlineKeyboardMarkup;
+use teloxide::{ApiError, RequestError};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+use crate::db::Db;
+
+/// Tries per message, counting the first send, before giving up on it.
+const MAX_ATTEMPTS: i64 = 8;
+
+/// Wait before the first retry; it doubles with every failure after that.
+const FIRST_RETRY_SECS: i64 = 30;
+const MAX_RETRY_SECS: i64 = 60 * 60;
+
+/// Messages retried per scheduler tick, so a long outage drains slowly
+/// rather than in one flood.
+const FLUSH_BATCH: i64 = 20;
+
+/// Sends a message now, or queues it for later if Telegram can't be reached
+/// right now. A user who blocked the bot is skipped without trying; other
+/// failures are only logged.
+pub async fn send(
+    bot: &Bot,
+    db: &Db,
+    chat_id: ChatId,
+    text: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> Result<()> {
+    if db.is_blocked(chat_id.0).await? {
+        return Ok(());
+    }
+    let Err(err) = try_send(bot, chat_id, &text, keyboard.clone()).await else {
+        return Ok(());
+    };
+    match retry_in(&err, 0) {
+        Some(wait) => {
+            eprintln!("outbox: queued message to {chat_id}: {err}");
+            let keyboard = keyboard.map(|k| serde_json::to_string(&k)).transpose()?;
+            db.queue_message(
+                chat_id.0,
+                &text,
+                keyboard.as_deref(),
+                &next_attempt(wait)?,
+                &err.to_string(),
+            )
+            .await?;
+        }
+        None if note_blocked(db, chat_id, &err).await? => {}
+        None => eprintln!("outbox: send to {chat_id} failed: {err:?}"),
+    }
+    Ok(())
+}
+
+/// Whether `err` says the user blocked the bot or deleted their account, in
+/// which case they are marked so nothing more is sent until they are back.
+/// Logged once, not on every attempt.
+pub async fn note_blocked(db: &Db, chat_id: ChatId, err: &RequestError) -> Result<bool> {
+    if !matches!(
+        err,
+        RequestError::Api(ApiError::BotBlocked | ApiError::UserDeactivated)
+    ) {
+        return Ok(false);
+    }
+    if db.mark_blocked(chat_id.0).await? {
+        eprintln!("outbox: {chat_id} blocked the bot; pausing messages to them");
+    }
+    Ok(true)
+}
+
+/// Retries queued messages that are due; run from the scheduler.
+pub async fn flush(bot: &Bot, db: &Db) -> Result<()> {
+    let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+    for msg in db.due_messages(&now, FLUSH_BATCH).await? {
+        let keyboard = msg
+            .keyboard
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()?;
+        let chat_id = ChatId(msg.chat_id);
+        if db.is_blocked(chat_id.0).await? {
+            db.remove_message(msg.id).await?;
+            continue;
+        }
+        match try_send(bot, chat_id, &msg.text, keyboard).await {
+            Ok(()) => db.remove_message(msg.id).await?,
+            Err(err) => match retry_in(&err, msg.attempts) {
+                Some(wait) if msg.attempts + 1 < MAX_ATTEMPTS => {
+                    db.retry_message(msg.id, &next_attempt(wait)?, &err.to_string())
+                        .await?;
+                }
+                _ if note_blocked(db, chat_id, &err).await? => db.remove_message(msg.id).await?,
+                _ => {
+                    eprintln!(
+                        "outbox: giving up on message #{} to {chat_id} after {} tries: {err:?}",
+                        msg.id,
+                        msg.attempts + 1
+                    );
+                    db.remove_message(msg.id).await?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+async fn try_send(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> Result<(), RequestError> {
+    let request = bot.send_message(chat_id, text);
+    match keyboard {
+        Some(keyboard) => request.reply_markup(keyboard).await?,
+        None => request.await?,
+    };
+    Ok(())
+}
+
+/// How long to wait after a failure that is worth retrying, given how many
+/// retries came before it.
+fn retry_in(err: &RequestError, retries: i64) -> Option<Duration> {
+    match err {
+        RequestError::RetryAfter(wait) => Some(Duration::seconds(wait.as_secs() as i64)),
+        RequestError::Network(_) | RequestError::Io(_) => {
+            let secs = FIRST_RETRY_SECS.saturating_mul(1 << retries.clamp(0, 20));
+            Some(Duration::seconds(secs.min(MAX_RETRY_SECS)))
+        }
+        _ => None,
+    }
+}
+
+fn next_attempt(wait: Duration) -> Result<String> {
+    Ok((OffsetDateTime::now_utc() + wait).format(&Rfc3339)?)
+}
@@ -0,0 +1,63 @@
+//! Operator caps on how much one user can record, so a single account
+//! can't fill up a small instance: entries created per day (UTC) and
+//! entries kept in all, invested ones included. `MAX_ENTRIES_PER_DAY` and
+//! `MAX_ENTRIES_PER_USER` set them; 0 turns a cap off. Entries the
+//! scheduler books, like standing orders and interest, are never refused.
+
+use anyhow::Result;
+use std::env;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::db::Db;
+
+const DEFAULT_PER_DAY: i64 = 500;
+const DEFAULT_PER_USER: i64 = 100_000;
+
+fn cap(var: &str, default: i64) -> i64 {
+    env::var(var)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Why `adding` more entries would be refused right now, if they would.
+pub async fn exceeded(db: &Db, user_id: Uuid, adding: i64) -> Result<Option<String>> {
+    check(db, user_id, adding, true).await
+}
+
+/// Like [`exceeded`] for a bulk /import, which only the overall cap limits.
+pub async fn exceeded_total(db: &Db, user_id: Uuid, adding: i64) -> Result<Option<String>> {
+    check(db, user_id, adding, false).await
+}
+
+async fn check(db: &Db, user_id: Uuid, adding: i64, daily: bool) -> Result<Option<String>> {
+    let per_day = if daily {
+        cap("MAX_ENTRIES_PER_DAY", DEFAULT_PER_DAY)
+    } else {
+        0
+    };
+    let per_user = cap("MAX_ENTRIES_PER_USER", DEFAULT_PER_USER);
+    if per_day <= 0 && per_user <= 0 {
+        return Ok(None);
+    }
+    let today = OffsetDateTime::now_utc().date().to_string();
+    let (created_today, total) = db.entry_counts(user_id, &today).await?;
+    if per_user > 0 && total + adding > per_user {
+        return Ok(Some(if adding > 1 && total < per_user {
+            format!(
+                "That's {adding} entries, but this bot keeps at most {per_user} per person and you have {total}. Import a smaller file, or /purge entries you don't need."
+            )
+        } else {
+            format!(
+                "Your ledger is full: this bot keeps at most {per_user} entries per person. /purge entries you don't need to make room, or ask whoever runs the bot for more."
+            )
+        }));
+    }
+    if per_day > 0 && created_today + adding > per_day {
+        return Ok(Some(format!(
+            "That's all for today: this bot takes at most {per_day} entries per person a day. Try again tomorrow (UTC)."
+        )));
+    }
+    Ok(None)
+}
@@ -0,0 +1,48 @@
+use time::{Date, Duration, Month};
+
+/// How often a /recurring standing order repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Every {
+    Daily,
+    Weekly,
+    Biweekly,
+    Monthly,
+}
+
+impl Every {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "daily" => Some(Every::Daily),
+            "weekly" => Some(Every::Weekly),
+            "biweekly" | "fortnightly" => Some(Every::Biweekly),
+            "monthly" => Some(Every::Monthly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Every::Daily => "daily",
+            Every::Weekly => "weekly",
+            Every::Biweekly => "biweekly",
+            Every::Monthly => "monthly",
+        }
+    }
+
+    /// The `n`th run counting from `start` (run 0). Monthly runs stay on
+    /// the start's day of month, moving to the last day in shorter months.
+    pub fn nth(self, start: Date, n: u32) -> Option<Date> {
+        match self {
+            Every::Daily => start.checked_add(Duration::days(n.into())),
+            Every::Weekly => start.checked_add(Duration::weeks(n.into())),
+            Every::Biweekly => start.checked_add(Duration::weeks(2 * i64::from(n))),
+            Every::Monthly => {
+                let months = i64::from(start.month() as u8 - 1) + i64::from(n);
+                let year = start.year() + i32::try_from(months / 12).ok()?;
+                let month = Month::try_from(u8::try_from(months % 12 + 1).ok()?).ok()?;
+                let day = start.day().min(month.length(year));
+                Date::from_calendar_date(year, month, day).ok()
+            }
+        }
+    }
+}
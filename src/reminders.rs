@@ -0,0 +1,75 @@
+use time::{Duration, OffsetDateTime, UtcOffset, Weekday};
+
+use crate::db::Reminder;
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Parses `daily 20:00` or `weekly sun 10:00` into `(weekday, minute_of_day)`,
+/// with weekday 0 = Monday and `None` for daily.
+pub fn parse(args: &str) -> Option<(Option<u8>, i64)> {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    match parts.as_slice() {
+        ["daily", at] => Some((None, parse_time(at)?)),
+        ["weekly", day, at] => {
+            let day = day.to_lowercase();
+            let weekday = WEEKDAYS.iter().position(|d| day.starts_with(d))?;
+            Some((Some(weekday as u8), parse_time(at)?))
+        }
+        _ => None,
+    }
+}
+
+fn parse_time(s: &str) -> Option<i64> {
+    let (h, m) = s.split_once(':').unwrap_or((s, "0"));
+    let h: i64 = h.parse().ok()?;
+    let m: i64 = m.parse().ok()?;
+    if !(0..24).contains(&h) || !(0..60).contains(&m) {
+        return None;
+    }
+    Some(h * 60 + m)
+}
+
+pub fn describe(weekday: Option<u8>, minute_of_day: i64) -> String {
+    let at = format!("{:02}:{:02}", minute_of_day / 60, minute_of_day % 60);
+    match weekday {
+        Some(d) => format!("every {} at {at}", WEEKDAYS[usize::from(d) % 7]),
+        None => format!("every day at {at}"),
+    }
+}
+
+/// How late a missed reminder may still go out, e.g. after the bot was
+/// down at the time.
+const CATCH_UP_HOURS: i64 = 24;
+
+/// The local `YYYY-MM-DD` of the reminder due at `now`: its latest time at
+/// or before `now`, unless that was already sent or is more than
+/// CATCH_UP_HOURS ago.
+pub fn due_on(r: &Reminder, now: OffsetDateTime) -> Option<String> {
+    let offset = UtcOffset::from_whole_seconds((r.utc_offset_minutes * 60) as i32).ok()?;
+    let local = now.to_offset(offset);
+    let minute = i64::from(local.hour()) * 60 + i64::from(local.minute());
+    let mut day = local.date();
+    if minute < r.minute_of_day {
+        day = day.previous_day()?;
+    }
+    if let Some(weekday) = r.weekday {
+        while day.weekday() != weekday_from_monday(weekday) {
+            day = day.previous_day()?;
+        }
+    }
+    let at = day.midnight().assume_offset(offset) + Duration::minutes(r.minute_of_day);
+    let day = day.to_string();
+    let sent = r
+        .last_sent_on
+        .as_deref()
+        .is_some_and(|last| last >= day.as_str());
+    (!sent && now - at <= Duration::hours(CATCH_UP_HOURS)).then_some(day)
+}
+
+fn weekday_from_monday(n: u8) -> Weekday {
+    let mut day = Weekday::Monday;
+    for _ in 0..n % 7 {
+        day = day.next();
+    }
+    day
+}
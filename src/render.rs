@@ -0,0 +1,96 @@
+use anyhow::Result;
+use std::env;
+use teloxide::{prelude::*, types::InputFile};
+
+/// Telegram rejects text messages longer than this (in characters).
+pub const MAX_MESSAGE_CHARS: usize = 4096;
+/// Listings longer than this are easier to read as a file than as a chat bubble.
+const MAX_MESSAGE_LINES: usize = 20;
+/// Upload limits for bots: 50 MB on the public Bot API, 2000 MB through a
+/// self-hosted Bot API server. A little headroom is left for the multipart
+/// envelope.
+const PUBLIC_UPLOAD_LIMIT: usize = 49 * 1024 * 1024;
+const LOCAL_UPLOAD_LIMIT: usize = 1990 * 1024 * 1024;
+
+/// A multi-line command result. Sent inline when it is small enough,
+/// otherwise as a text attachment with `summary` as the inline message.
+pub struct Report {
+    pub file_name: String,
+    pub summary: String,
+    pub lines: Vec<String>,
+}
+
+impl Report {
+    pub fn new(file_name: impl Into<String>) -> Self {
+        Self {
+            file_name: file_name.into(),
+            summary: String::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+    }
+
+    fn fits_inline(&self, text: &str) -> bool {
+        text.chars().count() <= MAX_MESSAGE_CHARS && text.lines().count() <= MAX_MESSAGE_LINES
+    }
+}
+
+pub async fn send_report(bot: &Bot, chat_id: ChatId, report: Report) -> Result<()> {
+    let text = report.lines.join("\n");
+    if report.fits_inline(&text) {
+        bot.send_message(chat_id, text).await?;
+    } else {
+        send_file(
+            bot,
+            chat_id,
+            &report.file_name,
+            text.into_bytes(),
+            &report.summary,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+fn upload_limit() -> usize {
+    match env::var("BOT_API_URL") {
+        Ok(url) if !url.is_empty() => LOCAL_UPLOAD_LIMIT,
+        _ => PUBLIC_UPLOAD_LIMIT,
+    }
+}
+
+/// Sends `bytes` as a document, split into `name.partNofM` pieces when it is
+/// over the Bot API upload limit. Rejoin with `cat name.part* > name`.
+pub async fn send_file(
+    bot: &Bot,
+    chat_id: ChatId,
+    name: &str,
+    bytes: Vec<u8>,
+    caption: &str,
+) -> Result<()> {
+    let limit = upload_limit();
+    if bytes.len() <= limit {
+        let file = InputFile::memory(bytes).file_name(name.to_string());
+        bot.send_document(chat_id, file).caption(caption).await?;
+        return Ok(());
+    }
+    let parts = bytes.len().div_ceil(limit);
+    // Zero-padded so the shell glob sorts part 10 after part 9.
+    let width = parts.to_string().len();
+    for (i, chunk) in bytes.chunks(limit).enumerate() {
+        let part_name = format!("{name}.part{:0width$}of{parts}", i + 1);
+        let part_caption = if i == 0 {
+            format!("{caption}\n(split in {parts} parts; rejoin with `cat {name}.part* > {name}`)")
+        } else {
+            format!("{name}: part {} of {parts}", i + 1)
+        };
+        let file = InputFile::memory(chunk.to_vec()).file_name(part_name);
+        bot.send_document(chat_id, file)
+            .caption(part_caption)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! Dividing cents into parts that add back up to exactly the amount. A
+//! 100.00 bill split three ways leaves a cent over after 33.33 each; the
+//! group's /splitrounding rule decides whose share it goes into.
+
+/// Who gets the cents left over after every share is rounded down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// The shares that lost the most to rounding down, one cent each.
+    LargestRemainder,
+    /// All of them to whoever paid.
+    PayerAbsorbs,
+    /// One cent each in turn, continuing from the group's last split so
+    /// nobody always ends up with the extra cent.
+    RoundRobin,
+}
+
+impl Rounding {
+    pub const ALL: [Rounding; 3] = [
+        Rounding::LargestRemainder,
+        Rounding::PayerAbsorbs,
+        Rounding::RoundRobin,
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        Self::ALL
+            .into_iter()
+            .find(|r| r.as_str().eq_ignore_ascii_case(s))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Rounding::LargestRemainder => "largest",
+            Rounding::PayerAbsorbs => "payer",
+            Rounding::RoundRobin => "roundrobin",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Rounding::LargestRemainder => "leftover cents go to the shares rounded down the most",
+            Rounding::PayerAbsorbs => "leftover cents go to whoever paid",
+            Rounding::RoundRobin => "leftover cents go to each participant in turn across splits",
+        }
+    }
+}
+
+/// Divides `total` cents in proportion to `weights` (equal weights for an
+/// even split). `payer` indexes whoever paid, for [`Rounding::PayerAbsorbs`];
+/// `turn` is where [`Rounding::RoundRobin`] hands out the first leftover
+/// cent. The parts always sum to `total`, negative totals included.
+pub fn split(total: i64, weights: &[i64], rule: Rounding, payer: usize, turn: usize) -> Vec<i64> {
+    let n = weights.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let even = vec![1; n];
+    let weights = if weights.iter().any(|w| *w < 0) || weights.iter().sum::<i64>() == 0 {
+        &even
+    } else {
+        weights
+    };
+    let sum = weights.iter().sum::<i64>() as i128;
+    let amount = total.unsigned_abs() as i128;
+    let mut parts: Vec<i64> = weights
+        .iter()
+        .map(|w| (amount * *w as i128 / sum) as i64)
+        .collect();
+    let left = (amount as i64 - parts.iter().sum::<i64>()) as usize;
+
+    match rule {
+        Rounding::LargestRemainder => {
+            let mut order: Vec<usize> = (0..n).collect();
+            // Stable, so equal remainders go to earlier participants.
+            order.sort_by_key(|&i| std::cmp::Reverse(amount * weights[i] as i128 % sum));
+            for &i in order.iter().take(left) {
+                parts[i] += 1;
+            }
+        }
+        Rounding::PayerAbsorbs => parts[payer.min(n - 1)] += left as i64,
+        Rounding::RoundRobin => {
+            for k in 0..left {
+                parts[(turn + k) % n] += 1;
+            }
+        }
+    }
+    if total < 0 {
+        parts.iter_mut().for_each(|p| *p = -*p);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_rule_adds_up_to_the_total() {
+        let cases: [(i64, &[i64]); 6] = [
+            (10_000, &[1, 1, 1]),
+            (9_999, &[1, 1, 1]),
+            (-10_000, &[1, 1, 1]),
+            (10_001, &[0, 0, 0]),
+            (1_000, &[2, 1, 0]),
+            (-7, &[3, 5]),
+        ];
+        for rule in Rounding::ALL {
+            for (total, weights) in cases {
+                let parts = split(total, weights, rule, 1, 2);
+                assert_eq!(parts.len(), weights.len());
+                assert_eq!(
+                    parts.iter().sum::<i64>(),
+                    total,
+                    "{rule:?} {total} {weights:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn even_splits_need_no_rounding() {
+        for rule in Rounding::ALL {
+            assert_eq!(split(9_000, &[1, 1, 1], rule, 0, 1), [3_000, 3_000, 3_000]);
+        }
+    }
+
+    #[test]
+    fn leftover_cents_follow_the_rule() {
+        assert_eq!(
+            split(10_000, &[1, 1, 1], Rounding::LargestRemainder, 2, 0),
+            [3_334, 3_333, 3_333]
+        );
+        assert_eq!(
+            split(10_000, &[1, 1, 1], Rounding::PayerAbsorbs, 2, 0),
+            [3_333, 3_333, 3_334]
+        );
+        assert_eq!(
+            split(-10_000, &[1, 1, 1], Rounding::PayerAbsorbs, 2, 0),
+            [-3_333, -3_333, -3_334]
+        );
+        // 1000 by 2:1 is 666.67 and 333.33; the first lost more rounding down.
+        assert_eq!(
+            split(1_000, &[2, 1], Rounding::LargestRemainder, 1, 0),
+            [667, 333]
+        );
+    }
+
+    #[test]
+    fn zero_or_negative_weights_split_evenly() {
+        let rule = Rounding::LargestRemainder;
+        assert_eq!(split(900, &[0, 0, 0], rule, 0, 0), [300, 300, 300]);
+        assert_eq!(split(900, &[5, -1, 2], rule, 0, 0), [300, 300, 300]);
+        assert!(split(900, &[], rule, 0, 0).is_empty());
+    }
+
+    #[test]
+    fn round_robin_starts_at_the_turn() {
+        let rule = Rounding::RoundRobin;
+        assert_eq!(split(10_000, &[1, 1, 1], rule, 0, 0), [3_334, 3_333, 3_333]);
+        assert_eq!(split(10_000, &[1, 1, 1], rule, 0, 1), [3_333, 3_334, 3_333]);
+        assert_eq!(split(10_000, &[1, 1, 1], rule, 0, 2), [3_333, 3_333, 3_334]);
+        assert_eq!(split(10_000, &[1, 1, 1], rule, 0, 3), [3_334, 3_333, 3_333]);
+        // Two cents over from the last participant wrap around to the first.
+        assert_eq!(split(10_001, &[1, 1, 1], rule, 0, 2), [3_334, 3_333, 3_334]);
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::env;
+use time::{macros::format_description, OffsetDateTime};
+
+/// An S3-compatible bucket (AWS, MinIO, R2, ...) addressed path-style, with
+/// just enough of the API for backups: put, list and delete.
+pub struct Bucket {
+    endpoint: reqwest::Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    /// Value of `x-amz-server-side-encryption`, e.g. `AES256`; `None` = off.
+    sse: Option<String>,
+    client: reqwest::Client,
+}
+
+impl Bucket {
+    /// Reads `S3_*` settings; `None` unless endpoint, bucket and credentials
+    /// are all set.
+    pub fn from_env() -> Result<Option<Self>> {
+        let var = |k: &str| env::var(k).ok().filter(|v| !v.is_empty());
+        let (Some(endpoint), Some(bucket), Some(access_key), Some(secret_key)) = (
+            var("S3_ENDPOINT"),
+            var("S3_BUCKET"),
+            var("S3_ACCESS_KEY_ID"),
+            var("S3_SECRET_ACCESS_KEY"),
+        ) else {
+            return Ok(None);
+        };
+        let sse = match var("S3_SSE").as_deref() {
+            None => Some("AES256".to_string()),
+            Some("off") => None,
+            Some(v) => Some(v.to_string()),
+        };
+        Ok(Some(Self {
+            endpoint: endpoint.parse().context("S3_ENDPOINT is not a URL")?,
+            bucket,
+            region: var("S3_REGION").unwrap_or_else(|| "us-east-1".into()),
+            access_key,
+            secret_key,
+            sse,
+            client: reqwest::Client::new(),
+        }))
+    }
+
+    pub async fn put(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        let mut extra = Vec::new();
+        if let Some(sse) = &self.sse {
+            extra.push(("x-amz-server-side-encryption", sse.clone()));
+        }
+        self.send(reqwest::Method::PUT, key, &[], &extra, body)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        self.send(reqwest::Method::DELETE, key, &[], &[], Vec::new())
+            .await?;
+        Ok(())
+    }
+
+    /// Keys under `prefix`, in S3's (lexicographic) order. Only the first
+    /// page of 1000 is read, which is plenty for a rotating backup set.
+    pub async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let body = self
+            .send(
+                reqwest::Method::GET,
+                "",
+                &[("list-type", "2"), ("prefix", prefix)],
+                &[],
+                Vec::new(),
+            )
+            .await?;
+        let key = Regex::new(r"<Key>([^<]*)</Key>")?;
+        Ok(key
+            .captures_iter(&body)
+            .map(|c| c[1].replace("&amp;", "&"))
+            .collect())
+    }
+
+    /// Signs (SigV4) and sends one request, returning the response body.
+    #[tracing::instrument(skip(self, query, extra_headers, body), fields(%method, bucket = %self.bucket))]
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        key: &str,
+        query: &[(&str, &str)],
+        extra_headers: &[(&'static str, String)],
+        body: Vec<u8>,
+    ) -> Result<String> {
+        let now = OffsetDateTime::now_utc();
+        let amz_date = now.format(format_description!(
+            "[year][month][day]T[hour][minute][second]Z"
+        ))?;
+        let day = &amz_date[..8];
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let mut path = format!(
+            "{}/{}",
+            self.endpoint.path().trim_end_matches('/'),
+            self.bucket
+        );
+        if !key.is_empty() {
+            path.push('/');
+            path.push_str(&uri_encode(key, false));
+        }
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(k, v)| (uri_encode(k, true), uri_encode(v, true)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let host = match self.endpoint.port() {
+            Some(port) => format!("{}:{port}", self.endpoint.host_str().unwrap_or_default()),
+            None => self.endpoint.host_str().unwrap_or_default().to_string(),
+        };
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        headers.extend(extra_headers.iter().cloned());
+        headers.sort();
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{k}:{}\n", v.trim()))
+            .collect();
+        let signed_headers = headers.iter().map(|h| h.0).collect::<Vec<_>>().join(";");
+        let canonical_request = format!(
+            "{method}\n{path}\n{query}\n{canonical_headers}\n{signed_headers}\n{payload_hash}"
+        );
+
+        let scope = format!("{day}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut signing_key = hmac(format!("AWS4{}", self.secret_key).as_bytes(), day);
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            signing_key = hmac(&signing_key, part);
+        }
+        let signature = hex::encode(hmac(&signing_key, &string_to_sign));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key
+        );
+
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+        url.set_query((!query.is_empty()).then_some(query.as_str()));
+        let mut req = self
+            .client
+            .request(method, url)
+            .header("authorization", authorization)
+            .body(body);
+        for (k, v) in headers.into_iter().filter(|h| h.0 != "host") {
+            req = req.header(k, v);
+        }
+        let resp = req.send().await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        if !status.is_success() {
+            bail!("S3 {status}: {text}");
+        }
+        Ok(text)
+    }
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// RFC 3986 encoding as SigV4 wants it: everything but unreserved characters,
+/// and `/` only when `encode_slash` (query values, not object keys).
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
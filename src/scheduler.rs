@@ -0,0 +1,578 @@
+use anyhow::Result;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    sync::Mutex,
+    time::Duration as StdDuration,
+};
+use teloxide::prelude::*;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Duration, Month,
+    OffsetDateTime,
+};
+
+use crate::db::Db;
+use crate::i18n::{self, Lang};
+use crate::kind::EntryKind;
+use crate::stats::{Period, Span};
+
+/// How often background jobs wake up. Jobs are idempotent, so a tick that
+/// finds nothing to do is cheap.
+const TICK: StdDuration = StdDuration::from_secs(60);
+
+/// The once-a-day jobs' row in scheduler_jobs.
+const DAILY_JOB: &str = "daily";
+
+/// Minimum gap between two cash-drag nudges to the same user.
+const CASH_DRAG_REPEAT_DAYS: i64 = 7;
+
+/// Operator settings for the "you're holding a lot of cash" nudge. Either
+/// trigger can be disabled with 0.
+struct CashDrag {
+    threshold_cents: i64,
+    idle_days: i64,
+}
+
+impl CashDrag {
+    fn from_env() -> Self {
+        let threshold_cents = env::var("CASH_DRAG_THRESHOLD")
+            .ok()
+            .and_then(|v| crate::decimal_to_cents(&v).ok())
+            .unwrap_or(100_000);
+        let idle_days = env::var("CASH_DRAG_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+        Self {
+            threshold_cents,
+            idle_days,
+        }
+    }
+}
+
+/// Runs periodic jobs for the lifetime of the bot.
+pub fn spawn(bot: Bot, db: Db) {
+    let cash_drag = CashDrag::from_env();
+    // Days without any command before a one-off "we miss you" ping; 0 disables.
+    let inactivity_days: i64 = env::var("INACTIVITY_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(14);
+    let backups = crate::backup::Config::from_env().unwrap_or_else(|err| {
+        eprintln!("scheduler: backups disabled: {err:?}");
+        None
+    });
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(TICK);
+        // When the daily jobs are next due, kept across restarts so they
+        // neither run twice in a day nor wait for tomorrow. Missing or past
+        // means they run on the first tick.
+        let mut next_daily = match db.job_next_run(DAILY_JOB).await {
+            Ok(at) => at.and_then(|at| OffsetDateTime::parse(&at, &Rfc3339).ok()),
+            Err(err) => {
+                eprintln!("scheduler: loading job state: {err:?}");
+                None
+            }
+        };
+        loop {
+            tick.tick().await;
+            let now = OffsetDateTime::now_utc();
+            lock_health().last_tick = Some(now);
+            let today = now.date();
+            if next_daily.is_none_or(|at| now >= at) {
+                if record("snapshot", db.record_snapshots(&today.to_string()).await) {
+                    record("networth", crate::networth::record_values(&db, today).await);
+                    let next = today.next_day().unwrap_or(today).midnight().assume_utc();
+                    record("schedule", daily_done(&db, now, next).await);
+                    next_daily = Some(next);
+                }
+                record("cash drag", nudge_idle_cash(&bot, &db, &cash_drag).await);
+                record(
+                    "inactivity",
+                    ping_inactive(&bot, &db, inactivity_days).await,
+                );
+                record("digest", send_digests(&bot, &db).await);
+                record("ticker digest", send_ticker_digests(&bot, &db).await);
+                record("budget alerts", send_budget_alerts(&bot, &db).await);
+                record("saving review", send_saving_reviews(&bot, &db).await);
+                if let Some(cfg) = &backups {
+                    record("backup", crate::backup::run_weekly(&bot, &db, cfg).await);
+                }
+            }
+            record("outbox", crate::outbox::flush(&bot, &db).await);
+            record("planned entries", realize_due_plans(&bot, &db).await);
+            record("recurring entries", run_recurring(&bot, &db).await);
+            record("interest", accrue_interest(&bot, &db).await);
+            record("reminder", send_reminders(&bot, &db).await);
+            record("goal check-in", send_goal_checkins(&bot, &db).await);
+            record("saving target", send_saving_alerts(&bot, &db).await);
+        }
+    });
+}
+
+async fn daily_done(db: &Db, now: OffsetDateTime, next: OffsetDateTime) -> Result<()> {
+    db.set_job_run(DAILY_JOB, &now.format(&Rfc3339)?, &next.format(&Rfc3339)?)
+        .await
+}
+
+/// Runs, failures and the last outcome of one job since the bot started.
+#[derive(Debug, Clone, Default)]
+pub struct JobHealth {
+    pub runs: u64,
+    pub failures: u64,
+    pub last_ok: Option<OffsetDateTime>,
+    /// When the last failure happened and its error.
+    pub last_error: Option<(OffsetDateTime, String)>,
+}
+
+/// Counters for /globalstats, kept in memory only.
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    pub last_tick: Option<OffsetDateTime>,
+    pub jobs: BTreeMap<&'static str, JobHealth>,
+}
+
+static HEALTH: Mutex<Health> = Mutex::new(Health {
+    last_tick: None,
+    jobs: BTreeMap::new(),
+});
+
+fn lock_health() -> std::sync::MutexGuard<'static, Health> {
+    HEALTH
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn health() -> Health {
+    lock_health().clone()
+}
+
+/// Logs a failed job and counts the run either way; returns whether it
+/// succeeded.
+fn record<T>(job: &'static str, result: Result<T>) -> bool {
+    let now = OffsetDateTime::now_utc();
+    let mut health = lock_health();
+    let stats = health.jobs.entry(job).or_default();
+    stats.runs += 1;
+    match result {
+        Ok(_) => {
+            stats.last_ok = Some(now);
+            true
+        }
+        Err(err) => {
+            eprintln!("scheduler: {job} error: {err:?}");
+            stats.failures += 1;
+            // Kept short so a dozen of them fit in one /globalstats message.
+            let message: String = format!("{err:#}").chars().take(200).collect();
+            stats.last_error = Some((now, message));
+            false
+        }
+    }
+}
+
+/// The /language set for the chat a scheduled message goes to. Without one
+/// there is no sender whose Telegram language could stand in, so English.
+async fn chat_lang(db: &Db, chat_id: i64) -> Result<Lang> {
+    Ok(db
+        .chat_language(chat_id)
+        .await?
+        .as_deref()
+        .and_then(Lang::from_code)
+        .unwrap_or(Lang::En))
+}
+
+#[tracing::instrument(skip_all)]
+async fn realize_due_plans(bot: &Bot, db: &Db) -> Result<()> {
+    let today = OffsetDateTime::now_utc().date().to_string();
+    for plan in db.due_plans(&today).await? {
+        if !db.realize_plan(&plan).await? {
+            continue;
+        }
+        crate::events::entry_added(
+            db,
+            plan.user_id,
+            plan.amount_cents,
+            EntryKind::by_sign(plan.amount_cents),
+            plan.reason.as_deref(),
+        )
+        .await;
+        let total = db.total_cents(plan.user_id).await?;
+        let text = i18n::plan_recorded(
+            chat_lang(db, plan.chat_id).await?,
+            &plan.due_on,
+            &crate::format_signed_cents(plan.amount_cents),
+            &plan.reason,
+            &crate::format_cents(total),
+        );
+        crate::outbox::send(bot, db, ChatId(plan.chat_id), text, None).await?;
+    }
+    Ok(())
+}
+
+/// Books every /recurring run that has come due, catching up on runs
+/// missed while the bot was down.
+#[tracing::instrument(skip_all)]
+async fn run_recurring(bot: &Bot, db: &Db) -> Result<()> {
+    let today = OffsetDateTime::now_utc().date();
+    for mut order in db.due_recurring(&today.to_string()).await? {
+        let (Some(every), Some(start)) = (
+            crate::recurring::Every::parse(&order.every),
+            crate::parse_date(&order.start_on),
+        ) else {
+            eprintln!("scheduler: recurring #{} is malformed", order.id);
+            continue;
+        };
+        while crate::parse_date(&order.next_on).is_some_and(|d| d <= today) {
+            let Some(next) = u32::try_from(order.runs + 1)
+                .ok()
+                .and_then(|n| every.nth(start, n))
+            else {
+                break;
+            };
+            if !db.realize_recurring(&order, &next.to_string()).await? {
+                break;
+            }
+            crate::events::entry_added(
+                db,
+                order.user_id,
+                order.amount_cents,
+                EntryKind::by_sign(order.amount_cents),
+                order.reason.as_deref(),
+            )
+            .await;
+            let total = db.total_cents(order.user_id).await?;
+            let text = i18n::recurring_recorded(
+                chat_lang(db, order.chat_id).await?,
+                (order.id, &order.next_on),
+                &crate::format_signed_cents(order.amount_cents),
+                &order.reason,
+                &crate::format_cents(total),
+                &next.to_string(),
+            );
+            crate::outbox::send(bot, db, ChatId(order.chat_id), text, None).await?;
+            order.runs += 1;
+            order.next_on = next.to_string();
+        }
+    }
+    Ok(())
+}
+
+/// Pings users whose /remind time has come in their /timezone. Daily
+/// reminders stay quiet if something was already saved that day.
+#[tracing::instrument(skip_all)]
+async fn send_reminders(bot: &Bot, db: &Db) -> Result<()> {
+    let now = OffsetDateTime::now_utc();
+    for reminder in db.reminders().await? {
+        let Some(day) = crate::reminders::due_on(&reminder, now) else {
+            continue;
+        };
+        // Reminders are the person's; what they saved is in their ledger.
+        let ledger = db
+            .shared_ledger_id(reminder.user_id)
+            .await?
+            .unwrap_or(reminder.user_id);
+        let saved_today = db
+            .save_days(ledger, reminder.utc_offset_minutes)
+            .await?
+            .first()
+            .is_some_and(|d| *d == day);
+        if reminder.weekday.is_some() || !saved_today {
+            let lang = chat_lang(db, reminder.chat_id).await?;
+            let mut text = String::from(i18n::reminder(lang));
+            if let Some(streak) = crate::streak(db, ledger).await? {
+                text.push_str(&format!("\n{streak}"));
+            }
+            text.push_str("\n\n");
+            text.push_str(i18n::reminder_footer(lang));
+            crate::outbox::send(bot, db, ChatId(reminder.chat_id), text, None).await?;
+        }
+        db.mark_reminder_sent(reminder.user_id, &day).await?;
+    }
+    Ok(())
+}
+
+/// Sends each active goal's check-in when its /goal checkin time has come,
+/// with buttons to save toward it, adjust it or pause the check-ins.
+#[tracing::instrument(skip_all)]
+async fn send_goal_checkins(bot: &Bot, db: &Db) -> Result<()> {
+    let now = OffsetDateTime::now_utc();
+    for checkin in db.goal_checkins().await? {
+        let Some(day) = crate::reminders::due_on(&checkin, now) else {
+            continue;
+        };
+        db.mark_goal_checkin_sent(checkin.user_id, &day).await?;
+        let Some(goal) = db.goal(checkin.user_id).await? else {
+            continue;
+        };
+        let total = db.total_cents(checkin.user_id).await?;
+        let today = crate::parse_date(&day).unwrap_or(now.date());
+        let save = crate::goals::suggested_save(&goal, total, today, checkin.weekday.is_none());
+        let lang = chat_lang(db, checkin.chat_id).await?;
+        let text = crate::goals::checkin(lang, &goal, total, today);
+        let keyboard = crate::goals::checkin_keyboard(lang, save);
+        crate::outbox::send(bot, db, ChatId(checkin.chat_id), text, Some(keyboard)).await?;
+    }
+    Ok(())
+}
+
+/// Credits last month's simulated interest on the uninvested stash once a
+/// new month starts.
+#[tracing::instrument(skip_all)]
+async fn accrue_interest(bot: &Bot, db: &Db) -> Result<()> {
+    let today = OffsetDateTime::now_utc().date();
+    let month = today.format(format_description!("[year]-[month]"))?;
+    let (prev_year, prev_month) = match today.month() {
+        Month::January => (today.year() - 1, Month::December),
+        m => (today.year(), m.previous()),
+    };
+    let label = format!("{prev_year}-{:02}", prev_month as u8);
+
+    for due in db.interest_due(&month).await? {
+        let total = db.total_cents(due.user_id).await?;
+        // Simple monthly compounding: APR / 12, rounded to the nearest cent.
+        let interest = if total > 0 {
+            (total * due.rate_bp + 60_000) / 120_000
+        } else {
+            0
+        };
+        let reason = format!(
+            "Interest for {label} at {}% APR",
+            crate::format_cents(due.rate_bp)
+        );
+        db.accrue_interest(due.user_id, &month, interest, &reason)
+            .await?;
+        if interest > 0 {
+            crate::events::entry_added(
+                db,
+                due.user_id,
+                interest,
+                EntryKind::Interest,
+                Some(&reason),
+            )
+            .await;
+            let text = format!(
+                "💰 {reason}: {}\nTotal now: {}",
+                crate::format_signed_cents(interest),
+                crate::format_cents(total + interest)
+            );
+            crate::outbox::send(bot, db, ChatId(due.chat_id), text, None).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Gently suggests /allinvoo to users whose stash is large or has sat
+/// uninvested for a while. Runs once a day.
+#[tracing::instrument(skip_all)]
+async fn nudge_idle_cash(bot: &Bot, db: &Db, cfg: &CashDrag) -> Result<()> {
+    if cfg.threshold_cents <= 0 && cfg.idle_days <= 0 {
+        return Ok(());
+    }
+    let now = OffsetDateTime::now_utc();
+    let threshold = if cfg.threshold_cents > 0 {
+        cfg.threshold_cents
+    } else {
+        i64::MAX
+    };
+    // An empty string sorts before any timestamp, i.e. "never idle".
+    let idle_before = if cfg.idle_days > 0 {
+        (now - Duration::days(cfg.idle_days)).format(&Rfc3339)?
+    } else {
+        String::new()
+    };
+    let nudged_before = (now - Duration::days(CASH_DRAG_REPEAT_DAYS)).format(&Rfc3339)?;
+    let now = now.format(&Rfc3339)?;
+
+    for idle in db
+        .idle_cash(threshold, &idle_before, &nudged_before)
+        .await?
+    {
+        let since = idle.since.get(..10).unwrap_or(&idle.since);
+        let text = format!(
+            "💤 You're holding {} in cash, some of it since {since}. Time to /allinvoo?",
+            crate::format_cents(idle.total_cents)
+        );
+        crate::outbox::send(bot, db, ChatId(idle.tg_user_id), text, None).await?;
+        db.mark_cash_drag_nudged(idle.user_id, &now).await?;
+    }
+    Ok(())
+}
+
+/// Once a month, sums up the month that just ended for everyone with
+/// entries. /nudges off opts out.
+#[tracing::instrument(skip_all)]
+async fn send_digests(bot: &Bot, db: &Db) -> Result<()> {
+    let today = OffsetDateTime::now_utc().date();
+    let month = today.format(format_description!("[year]-[month]"))?;
+    let Some(this) = Period::month(today.year(), today.month()) else {
+        return Ok(());
+    };
+    let Some(last) = Span::Month.previous(&this) else {
+        return Ok(());
+    };
+    let Some(before) = Span::Month.previous(&last) else {
+        return Ok(());
+    };
+    for due in db.digest_due(&month).await? {
+        let cur = db
+            .period_summary(due.user_id, &last.start_str(), &last.end_str())
+            .await?;
+        let prev = db
+            .period_summary(due.user_id, &before.start_str(), &before.end_str())
+            .await?;
+        // Nothing happened last month: skip quietly rather than send zeros.
+        if cur.saved + cur.spent + cur.invested > 0 {
+            let text = crate::stats::digest(&before, &prev, &last, &cur);
+            crate::outbox::send(bot, db, ChatId(due.tg_user_id), text, None).await?;
+        }
+        db.mark_digest_sent(due.user_id, &month).await?;
+    }
+    Ok(())
+}
+
+/// After each week and each month, sends users with budgets what they
+/// spent per envelope against what was budgeted. /nudges off opts out.
+#[tracing::instrument(skip_all)]
+async fn send_budget_alerts(bot: &Bot, db: &Db) -> Result<()> {
+    let today = OffsetDateTime::now_utc().date();
+    for span in [Span::Week, Span::Month] {
+        let Some(last) = span.containing(today).and_then(|this| span.previous(&this)) else {
+            continue;
+        };
+        let weekly = span == Span::Week;
+        let key = if weekly {
+            last.start_str()
+        } else {
+            last.label.clone()
+        };
+        for due in db.budget_alerts_due(weekly, &key).await? {
+            if let Some(mut text) = crate::budgets::variance(db, due.user_id, span, &last).await? {
+                text.push_str("\n\n(Change budgets with /budget; stop these with /nudges off)");
+                crate::outbox::send(bot, db, ChatId(due.tg_user_id), text, None).await?;
+            }
+            db.mark_budget_alert_sent(due.user_id, weekly, &key).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Tells users with a `/budget 300` saving target when this month's saves
+/// reach 50, 90 and 100% of it; only the highest step reached is sent when
+/// several are crossed at once.
+#[tracing::instrument(skip_all)]
+async fn send_saving_alerts(bot: &Bot, db: &Db) -> Result<()> {
+    let Some(month) = Span::Month.containing(OffsetDateTime::now_utc().date()) else {
+        return Ok(());
+    };
+    for p in db.saving_progress(&month.label, false).await? {
+        let Some(pct) = crate::budgets::saving_alert_due(&p) else {
+            continue;
+        };
+        db.mark_saving_alert(p.user_id, &month.label, pct).await?;
+        let line = crate::budgets::saving_line(p.target_cents, p.saved_cents);
+        let text = if pct >= 100 {
+            format!("🎉 Saving target for {} reached: {line}.", month.label)
+        } else {
+            format!(
+                "🎯 {pct}% of your saving target for {}: {line}, {} to go.",
+                month.label,
+                crate::format_cents(p.target_cents - p.saved_cents)
+            )
+        };
+        crate::outbox::send(bot, db, ChatId(p.tg_user_id), text, None).await?;
+    }
+    Ok(())
+}
+
+/// Once a month has ended, tells users who saved less than their target
+/// how far short they came.
+#[tracing::instrument(skip_all)]
+async fn send_saving_reviews(bot: &Bot, db: &Db) -> Result<()> {
+    let Some(last) = Span::Month
+        .containing(OffsetDateTime::now_utc().date())
+        .and_then(|this| Span::Month.previous(&this))
+    else {
+        return Ok(());
+    };
+    for p in db.saving_progress(&last.label, true).await? {
+        db.mark_saving_review(p.user_id, &last.label).await?;
+        if p.saved_cents >= p.target_cents {
+            continue;
+        }
+        let text = format!(
+            "📉 {} ended under your saving target: {}, {} short.\n(Change it with /budget {{amount}}; stop these with /nudges off)",
+            last.label,
+            crate::budgets::saving_line(p.target_cents, p.saved_cents),
+            crate::format_cents(p.target_cents - p.saved_cents)
+        );
+        crate::outbox::send(bot, db, ChatId(p.tg_user_id), text, None).await?;
+    }
+    Ok(())
+}
+
+/// Once a week, sends each user a line per /watchticker ticker: the last
+/// close, the week's change and the distance from the 52-week high.
+/// Quotes are fetched once per ticker for everyone watching it.
+#[tracing::instrument(skip_all)]
+async fn send_ticker_digests(bot: &Bot, db: &Db) -> Result<()> {
+    let today = OffsetDateTime::now_utc().date();
+    let Some(week) = Period::week(today) else {
+        return Ok(());
+    };
+    let mut quotes: HashMap<String, Option<crate::market::Week>> = HashMap::new();
+    for due in db.ticker_digest_due(&week.start_str()).await? {
+        let mut lines = vec!["📈 Your watched tickers this week:".to_string()];
+        for ticker in db.watched_tickers(due.user_id).await? {
+            if !quotes.contains_key(&ticker) {
+                let quote = match crate::market::week(&ticker, today).await {
+                    Ok(None) => return Ok(()),
+                    Ok(quote) => quote,
+                    Err(err) => {
+                        eprintln!("scheduler: price history for {ticker}: {err:?}");
+                        None
+                    }
+                };
+                quotes.insert(ticker.clone(), quote);
+            }
+            lines.push(match &quotes[&ticker] {
+                Some(quote) => quote.line(&ticker),
+                None => format!("{ticker}: no price this week"),
+            });
+        }
+        lines.push("\n(Change the list with /watchticker and /unwatchticker)".into());
+        crate::outbox::send(bot, db, ChatId(due.tg_user_id), lines.join("\n"), None).await?;
+        db.mark_ticker_digest_sent(due.user_id, &week.start_str())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Sends a single friendly reminder to users who logged things before but
+/// have gone quiet. Runs once a day; /nudges off opts out.
+#[tracing::instrument(skip_all)]
+async fn ping_inactive(bot: &Bot, db: &Db, days: i64) -> Result<()> {
+    if days <= 0 {
+        return Ok(());
+    }
+    let now = OffsetDateTime::now_utc();
+    let seen_before = (now - Duration::days(days)).format(&Rfc3339)?;
+    for user in db.inactive_users(&seen_before).await? {
+        let away = OffsetDateTime::parse(&user.last_seen_at, &Rfc3339)
+            .map(|seen| (now - seen).whole_days())
+            .unwrap_or(days);
+        let total = db.total_cents(user.user_id).await?;
+        let mut text = format!(
+            "👋 Haven't seen you in {away} days! Your stash is at {}. Resisted any purchases lately? /save them.",
+            crate::format_cents(total)
+        );
+        if let Some(goal) = db.goal(user.user_id).await? {
+            text.push_str("\n\n");
+            text.push_str(&crate::goals::status(db, user.user_id, &goal, total).await?);
+        }
+        text.push_str("\n\n(Turn these off with /nudges off)");
+        crate::outbox::send(bot, db, ChatId(user.tg_user_id), text, None).await?;
+        db.mark_inactivity_pinged(user.user_id, &now.format(&Rfc3339)?)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,467 @@
+//! `/export settings` and `/import settings`: a user's configuration as
+//! JSON, for moving to another instance of the bot. Entries travel in the
+//! CSV export instead; webhooks and API tokens are left out on purpose.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use time::{macros::format_description, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::currency::Currency;
+use crate::db::{self, Db};
+use crate::stats::Span;
+use crate::{
+    allocation, categorize, market, parse_date, recurring, reminders, valid_strategy_name,
+};
+
+pub const VERSION: u32 = 1;
+
+/// Every field is optional on import so a hand-trimmed file only restores
+/// what it still mentions.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub utc_offset_minutes: Option<i64>,
+    pub nudges: Option<bool>,
+    pub decimal_comma: Option<bool>,
+    pub currency: Option<String>,
+    pub autocategory: Option<String>,
+    pub expense_ratio_ppm: Option<i64>,
+    pub interest_bp: Option<i64>,
+    /// /dividend auto: payouts also go into the stash.
+    pub dividends_to_stash: Option<bool>,
+    /// /leaderboard on: shown on group leaderboards.
+    pub leaderboard: Option<bool>,
+    pub budgets: Vec<BudgetSetting>,
+    /// `/budget 300`: what to save each month.
+    pub saving_target_cents: Option<i64>,
+    pub goal: Option<GoalSetting>,
+    pub reminder: Option<Schedule>,
+    pub recurring: Vec<RecurringSetting>,
+    pub allocation: Vec<AllocationSetting>,
+    /// Older files' /ticker split, which is now the allocation; restored as
+    /// that when the file has none.
+    #[serde(skip_serializing)]
+    pub invest_tickers: Vec<AllocationSetting>,
+    pub watched_tickers: Vec<String>,
+    pub strategies: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BudgetSetting {
+    pub category: String,
+    pub amount_cents: i64,
+    #[serde(default)]
+    pub rollover: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GoalSetting {
+    pub target_cents: i64,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub deadline: Option<String>,
+    /// `None` when check-ins are off.
+    #[serde(default)]
+    pub checkin: Option<Schedule>,
+}
+
+/// A /remind or goal check-in time; `weekday` 0 is Monday, none is daily.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Schedule {
+    #[serde(default)]
+    pub weekday: Option<u8>,
+    pub minute_of_day: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecurringSetting {
+    pub amount_cents: i64,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub every: String,
+    /// The next run; it starts the schedule over on import.
+    pub next_on: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AllocationSetting {
+    pub ticker: String,
+    pub percent: i64,
+}
+
+impl Schedule {
+    fn from_reminder(r: db::Reminder) -> Self {
+        Schedule {
+            weekday: r.weekday,
+            minute_of_day: r.minute_of_day,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.weekday.is_none_or(|d| d < 7) && (0..24 * 60).contains(&self.minute_of_day)
+    }
+
+    /// Marked as sent for today if its time has passed, like /remind does.
+    fn reminder(&self, user_id: Uuid, chat_id: i64, utc_offset_minutes: i64) -> db::Reminder {
+        let mut r = db::Reminder {
+            user_id,
+            chat_id,
+            weekday: self.weekday,
+            minute_of_day: self.minute_of_day,
+            utc_offset_minutes,
+            last_sent_on: None,
+        };
+        r.last_sent_on = reminders::due_on(&r, OffsetDateTime::now_utc());
+        r
+    }
+}
+
+fn this_month() -> String {
+    OffsetDateTime::now_utc()
+        .format(format_description!("[year]-[month]"))
+        .unwrap_or_default()
+}
+
+pub async fn export(db: &Db, uuid: Uuid) -> Result<Settings> {
+    let goal = match db.goal(uuid).await? {
+        Some(g) => Some(GoalSetting {
+            target_cents: g.target_cents,
+            name: g.name,
+            deadline: g.deadline,
+            checkin: db.goal_checkin(uuid).await?.map(Schedule::from_reminder),
+        }),
+        None => None,
+    };
+    Ok(Settings {
+        version: VERSION,
+        utc_offset_minutes: Some(db.utc_offset_minutes(uuid).await?),
+        nudges: Some(db.nudges_enabled(uuid).await?),
+        decimal_comma: db.decimal_comma(uuid).await?,
+        currency: db.currency(uuid).await?,
+        autocategory: Some(db.autocategory(uuid).await?),
+        expense_ratio_ppm: Some(db.expense_ratio_ppm(uuid).await?),
+        interest_bp: Some(db.interest_rate(uuid).await?),
+        dividends_to_stash: Some(db.dividends_to_stash(uuid).await?),
+        leaderboard: Some(db.leaderboard_opt_in(uuid).await?),
+        budgets: db
+            .latest_budgets(uuid, &this_month())
+            .await?
+            .into_iter()
+            .filter(|b| b.amount_cents > 0)
+            .map(|b| BudgetSetting {
+                category: b.category,
+                amount_cents: b.amount_cents,
+                rollover: b.rollover,
+            })
+            .collect(),
+        saving_target_cents: db.saving_target(uuid).await?,
+        goal,
+        reminder: db.reminder(uuid).await?.map(Schedule::from_reminder),
+        recurring: db
+            .user_recurring(uuid)
+            .await?
+            .into_iter()
+            .map(|r| RecurringSetting {
+                amount_cents: r.amount_cents,
+                reason: r.reason,
+                every: r.every,
+                next_on: r.next_on,
+            })
+            .collect(),
+        allocation: db
+            .allocation_targets(uuid)
+            .await?
+            .into_iter()
+            .map(|(ticker, percent)| AllocationSetting { ticker, percent })
+            .collect(),
+        invest_tickers: Vec::new(),
+        watched_tickers: db.watched_tickers(uuid).await?,
+        strategies: db
+            .strategy_totals(uuid)
+            .await?
+            .into_iter()
+            .filter_map(|(name, _)| name)
+            .collect(),
+    })
+}
+
+/// Everything in `s` that this bot would refuse; empty when it can be
+/// restored as a whole.
+pub fn problems(s: &Settings) -> Vec<String> {
+    let mut problems = Vec::new();
+    if s.version > VERSION {
+        problems.push(format!(
+            "version {} is newer than this bot understands ({VERSION})",
+            s.version
+        ));
+    }
+    if s.utc_offset_minutes.is_some_and(|m| m.abs() > 14 * 60) {
+        problems.push("utc_offset_minutes must be within ±14 hours".into());
+    }
+    if let Some(mode) = &s.autocategory {
+        if categorize::Mode::parse(mode).is_none() {
+            problems.push(format!(
+                "autocategory {mode:?} is not suggest, apply or off"
+            ));
+        }
+    }
+    if let Some(code) = &s.currency {
+        if Currency::parse(code).is_none() {
+            problems.push(format!("currency {code:?} is not one /currency knows"));
+        }
+    }
+    if s.expense_ratio_ppm
+        .is_some_and(|p| !(0..=100_000).contains(&p))
+    {
+        problems.push("expense_ratio_ppm must be between 0 and 100000 (10%)".into());
+    }
+    if s.interest_bp.is_some_and(|bp| !(0..=10_000).contains(&bp)) {
+        problems.push("interest_bp must be between 0 and 10000 (100%)".into());
+    }
+    for b in &s.budgets {
+        if crate::tags::normalize(&b.category).is_none() || b.amount_cents <= 0 {
+            problems.push(format!(
+                "budget {:?} needs a one-word category and a positive amount",
+                b.category
+            ));
+        }
+    }
+    if s.saving_target_cents.is_some_and(|c| c <= 0) {
+        problems.push("saving_target_cents must be positive".into());
+    }
+    if let Some(g) = &s.goal {
+        if g.target_cents <= 0 {
+            problems.push("goal target_cents must be positive".into());
+        }
+        if g.deadline
+            .as_deref()
+            .is_some_and(|d| parse_date(d).is_none())
+        {
+            problems.push("goal deadline must be YYYY-MM-DD".into());
+        }
+        if g.checkin.as_ref().is_some_and(|c| !c.valid()) {
+            problems.push("goal checkin needs weekday 0-6 and minute_of_day 0-1439".into());
+        }
+    }
+    if s.reminder.as_ref().is_some_and(|r| !r.valid()) {
+        problems.push("reminder needs weekday 0-6 and minute_of_day 0-1439".into());
+    }
+    for r in &s.recurring {
+        if r.amount_cents == 0
+            || recurring::Every::parse(&r.every).is_none()
+            || parse_date(&r.next_on).is_none()
+        {
+            problems.push(format!(
+                "recurring {:?} needs a non-zero amount, every of daily/weekly/biweekly/monthly and next_on as YYYY-MM-DD",
+                r.reason.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+    if !s.allocation.is_empty() {
+        let words: Vec<String> = s
+            .allocation
+            .iter()
+            .flat_map(|a| [a.ticker.clone(), a.percent.to_string()])
+            .collect();
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        if allocation::parse_targets(&words).is_none() {
+            problems
+                .push("allocation needs distinct tickers with percents adding up to 100".into());
+        }
+    }
+    if !s.invest_tickers.is_empty() {
+        let words: Vec<String> = s
+            .invest_tickers
+            .iter()
+            .flat_map(|a| [a.ticker.clone(), a.percent.to_string()])
+            .collect();
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        if allocation::parse_targets(&words).is_none() {
+            problems.push(
+                "invest_tickers needs distinct tickers with percents adding up to 100".into(),
+            );
+        }
+    }
+    if s.watched_tickers.len() > market::MAX_WATCHED {
+        problems.push(format!("at most {} watched_tickers", market::MAX_WATCHED));
+    }
+    for t in &s.watched_tickers {
+        if market::normalize_ticker(t).is_none() {
+            problems.push(format!("watched ticker {t:?} is not a ticker"));
+        }
+    }
+    for name in &s.strategies {
+        if !valid_strategy_name(name) {
+            problems.push(format!("strategy {name:?} is not a valid name"));
+        }
+    }
+    problems
+}
+
+/// Applies settings that passed [`problems`]. Lists replace what the user
+/// had, except standing orders, which are only added when no identical one
+/// exists yet. Returns one line per restored part.
+pub async fn restore(db: &Db, uuid: Uuid, chat_id: i64, s: &Settings) -> Result<Vec<String>> {
+    let mut done = Vec::new();
+    if let Some(minutes) = s.utc_offset_minutes {
+        db.set_utc_offset_minutes(uuid, minutes).await?;
+        done.push("timezone".to_string());
+    }
+    if let Some(on) = s.nudges {
+        db.set_nudges(uuid, on).await?;
+        done.push("nudges".into());
+    }
+    if let Some(on) = s.dividends_to_stash {
+        db.set_dividends_to_stash(uuid, on).await?;
+        done.push("dividend mode".into());
+    }
+    if let Some(on) = s.leaderboard {
+        db.set_leaderboard_opt_in(uuid, on).await?;
+        done.push("leaderboard".into());
+    }
+    if let Some(comma) = s.decimal_comma {
+        db.set_decimal_comma(uuid, comma).await?;
+        done.push("decimal mark".into());
+    }
+    if let Some(currency) = s.currency.as_deref().and_then(Currency::parse) {
+        db.set_currency(uuid, Some(currency.code)).await?;
+        done.push("currency".into());
+    }
+    if let Some(mode) = s.autocategory.as_deref().and_then(categorize::Mode::parse) {
+        db.set_autocategory(uuid, mode.as_str()).await?;
+        done.push("automatic categories".into());
+    }
+    if let Some(ppm) = s.expense_ratio_ppm {
+        db.set_expense_ratio_ppm(uuid, ppm).await?;
+        done.push("expense ratio".into());
+    }
+    let month = this_month();
+    if let Some(bp) = s.interest_bp {
+        db.set_interest_rate(uuid, bp, chat_id, &month).await?;
+        done.push("interest rate".into());
+    }
+    let offset = db.utc_offset_minutes(uuid).await?;
+
+    if !s.budgets.is_empty() {
+        for old in db.latest_budgets(uuid, &month).await? {
+            if !s.budgets.iter().any(|b| {
+                crate::tags::normalize(&b.category).as_deref() == Some(old.category.as_str())
+            }) {
+                db.put_budget(
+                    uuid,
+                    &db::Budget {
+                        month: month.clone(),
+                        amount_cents: 0,
+                        carry_cents: 0,
+                        ..old
+                    },
+                )
+                .await?;
+            }
+        }
+        for b in &s.budgets {
+            let budget = db::Budget {
+                category: crate::tags::normalize(&b.category).unwrap_or_default(),
+                month: month.clone(),
+                amount_cents: b.amount_cents,
+                rollover: b.rollover,
+                carry_cents: 0,
+            };
+            db.put_budget(uuid, &budget).await?;
+        }
+        done.push(format!("budgets ({})", s.budgets.len()));
+    }
+    if let Some(cents) = s.saving_target_cents {
+        let last_month = Span::Month
+            .containing(OffsetDateTime::now_utc().date())
+            .and_then(|m| Span::Month.previous(&m))
+            .map(|m| m.label)
+            .unwrap_or_default();
+        db.set_saving_target(uuid, Some(cents), &last_month).await?;
+        done.push("saving target".into());
+    }
+
+    if let Some(g) = &s.goal {
+        db.set_goal(uuid, g.target_cents, g.name.clone(), g.deadline.clone())
+            .await?;
+        match &g.checkin {
+            Some(c) => {
+                db.set_goal_checkin(&c.reminder(uuid, chat_id, offset), false)
+                    .await?;
+            }
+            None => {
+                db.pause_goal_checkin(uuid).await?;
+            }
+        }
+        done.push("goal".into());
+    }
+
+    if let Some(r) = &s.reminder {
+        db.set_reminder(&r.reminder(uuid, chat_id, offset)).await?;
+        done.push("reminder".into());
+    }
+
+    if !s.recurring.is_empty() {
+        let existing = db.user_recurring(uuid).await?;
+        let mut added = 0;
+        for r in &s.recurring {
+            let every = recurring::Every::parse(&r.every).map_or("", |e| e.as_str());
+            let duplicate = existing.iter().any(|e| {
+                e.amount_cents == r.amount_cents && e.reason == r.reason && e.every == every
+            });
+            if !duplicate {
+                db.add_recurring(
+                    uuid,
+                    chat_id,
+                    r.amount_cents,
+                    r.reason.clone(),
+                    every,
+                    &r.next_on,
+                )
+                .await?;
+                added += 1;
+            }
+        }
+        done.push(format!("standing orders ({added} new)"));
+    }
+
+    let allocation = if s.allocation.is_empty() {
+        &s.invest_tickers
+    } else {
+        &s.allocation
+    };
+    if !allocation.is_empty() {
+        let targets: Vec<(String, i64)> = allocation
+            .iter()
+            .map(|a| (a.ticker.to_ascii_uppercase(), a.percent))
+            .collect();
+        db.set_allocation_targets(uuid, &targets).await?;
+        done.push("allocation".into());
+    }
+
+    if !s.watched_tickers.is_empty() {
+        for old in db.watched_tickers(uuid).await? {
+            db.unwatch_ticker(uuid, &old).await?;
+        }
+        for t in s
+            .watched_tickers
+            .iter()
+            .filter_map(|t| market::normalize_ticker(t))
+        {
+            db.watch_ticker(uuid, &t).await?;
+        }
+        done.push(format!("watched tickers ({})", s.watched_tickers.len()));
+    }
+
+    if !s.strategies.is_empty() {
+        // Strategies hold invest history, so existing ones are kept.
+        for name in &s.strategies {
+            db.add_strategy(uuid, &name.to_lowercase(), name).await?;
+        }
+        done.push(format!("strategies ({})", s.strategies.len()));
+    }
+    Ok(done)
+}
@@ -0,0 +1,49 @@
+use anyhow::{anyhow, bail, Result};
+use std::env;
+
+/// An OpenAI-compatible `/audio/transcriptions` endpoint (OpenAI Whisper,
+/// Groq, a self-hosted faster-whisper server, ...). Off unless
+/// `STT_API_KEY` is set.
+pub struct Stt {
+    url: String,
+    api_key: String,
+    model: String,
+}
+
+impl Stt {
+    pub fn from_env() -> Option<Self> {
+        let api_key = env::var("STT_API_KEY").ok().filter(|k| !k.is_empty())?;
+        Some(Self {
+            url: env::var("STT_URL")
+                .unwrap_or_else(|_| "https://api.openai.com/v1/audio/transcriptions".into()),
+            api_key,
+            model: env::var("STT_MODEL").unwrap_or_else(|_| "whisper-1".into()),
+        })
+    }
+
+    /// Transcribes a Telegram voice note (OGG/Opus).
+    #[tracing::instrument(skip_all, fields(bytes = audio.len()))]
+    pub async fn transcribe(&self, audio: Vec<u8>) -> Result<String> {
+        let file = reqwest::multipart::Part::bytes(audio)
+            .file_name("voice.ogg")
+            .mime_str("audio/ogg")?;
+        let form = reqwest::multipart::Form::new()
+            .text("model", self.model.clone())
+            .part("file", file);
+        let resp = reqwest::Client::new()
+            .post(&self.url)
+            .bearer_auth(&self.api_key)
+            .multipart(form)
+            .send()
+            .await?;
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await?;
+        if !status.is_success() {
+            bail!("speech-to-text {status}: {body}");
+        }
+        body["text"]
+            .as_str()
+            .map(|t| t.trim().to_string())
+            .ok_or_else(|| anyhow!("speech-to-text response has no text"))
+    }
+}
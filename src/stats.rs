@@ -0,0 +1,230 @@
+use time::{Date, Duration, Month};
+
+use crate::db::{PeriodStats, PeriodSummary};
+use crate::{format_cents, format_signed_cents};
+
+/// A calendar range `[start, end)` with the label the user typed.
+#[derive(Debug, Clone)]
+pub struct Period {
+    pub label: String,
+    pub start: Date,
+    pub end: Date,
+}
+
+impl Period {
+    /// Accepts `YYYY-MM` (a month) or `YYYY` (a year).
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        match s.split_once('-') {
+            Some((year, month)) if month.len() == 2 => Self::month(
+                year.parse().ok()?,
+                Month::try_from(month.parse::<u8>().ok()?).ok()?,
+            ),
+            None if s.len() == 4 => Self::year(s.parse().ok()?),
+            _ => None,
+        }
+    }
+
+    pub fn year(year: i32) -> Option<Self> {
+        Some(Self {
+            label: year.to_string(),
+            start: Date::from_calendar_date(year, Month::January, 1).ok()?,
+            end: Date::from_calendar_date(year + 1, Month::January, 1).ok()?,
+        })
+    }
+
+    /// The ISO week (Monday to Sunday) containing `day`.
+    pub fn week(day: Date) -> Option<Self> {
+        let start = day.checked_sub(Duration::days(
+            day.weekday().number_days_from_monday().into(),
+        ))?;
+        Some(Self {
+            label: format!("week of {start}"),
+            start,
+            end: start.checked_add(Duration::weeks(1))?,
+        })
+    }
+
+    pub fn month(year: i32, month: Month) -> Option<Self> {
+        let start = Date::from_calendar_date(year, month, 1).ok()?;
+        let next_year = if month == Month::December {
+            year + 1
+        } else {
+            year
+        };
+        Some(Self {
+            label: format!("{year}-{:02}", month as u8),
+            start,
+            end: Date::from_calendar_date(next_year, month.next(), 1).ok()?,
+        })
+    }
+
+    pub fn start_str(&self) -> String {
+        self.start.to_string()
+    }
+
+    pub fn end_str(&self) -> String {
+        self.end.to_string()
+    }
+}
+
+/// The granularity of /stats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Span {
+    Week,
+    Month,
+    Year,
+}
+
+impl Span {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "week" => Some(Span::Week),
+            "" | "month" => Some(Span::Month),
+            "year" => Some(Span::Year),
+            _ => None,
+        }
+    }
+
+    pub fn containing(self, day: Date) -> Option<Period> {
+        match self {
+            Span::Week => Period::week(day),
+            Span::Month => Period::month(day.year(), day.month()),
+            Span::Year => Period::year(day.year()),
+        }
+    }
+
+    /// The period of this span just before `p`.
+    pub fn previous(self, p: &Period) -> Option<Period> {
+        self.containing(p.start.previous_day()?)
+    }
+}
+
+/// Percentage change from `old` to `new`, e.g. "+25%", or "new" when there
+/// was nothing before.
+pub fn delta(old: i64, new: i64) -> String {
+    match (old, new) {
+        (0, 0) => "±0%".into(),
+        (0, _) => "new".into(),
+        _ => {
+            let pct = (new - old) as f64 * 100.0 / old.abs() as f64;
+            format!("{pct:+.0}%")
+        }
+    }
+}
+
+/// Share of the period's money flow that was saved rather than spent, in
+/// percent; `None` when nothing moved.
+pub fn savings_rate(s: &PeriodSummary) -> Option<i64> {
+    let flow = s.saved + s.spent;
+    (flow > 0).then(|| (s.saved * 100 + flow / 2) / flow)
+}
+
+/// The monthly digest for `cur`, with `prev` (the month before) for trends.
+pub fn digest(prev: &Period, sp: &PeriodSummary, cur: &Period, sc: &PeriodSummary) -> String {
+    let row = |label: &str, x: i64, y: i64| {
+        format!(
+            "{label}: {} ({} vs {})",
+            format_cents(y),
+            delta(x, y),
+            prev.label
+        )
+    };
+    let mut lines = vec![
+        format!("🗓 Your {} digest", cur.label),
+        row("Saved", sp.saved, sc.saved),
+        row("Spent", sp.spent, sc.spent),
+        row("Invested", sp.invested, sc.invested),
+    ];
+    if let Some(rate) = savings_rate(sc) {
+        let trend = match savings_rate(sp) {
+            Some(old) if rate > old => format!(" ↑ (was {old}%)"),
+            Some(old) if rate < old => format!(" ↓ (was {old}%)"),
+            Some(_) => " → (unchanged)".into(),
+            None => String::new(),
+        };
+        lines.push(format!("Savings rate: {rate}%{trend}"));
+    }
+    lines.join("\n")
+}
+
+pub fn compare(a: &Period, sa: &PeriodSummary, b: &Period, sb: &PeriodSummary) -> String {
+    let row = |label: &str, x: i64, y: i64| {
+        format!(
+            "{label}: {} → {} ({})",
+            format_cents(x),
+            format_cents(y),
+            delta(x, y)
+        )
+    };
+    [
+        format!("{} vs {}", a.label, b.label),
+        row("Saved", sa.saved, sb.saved),
+        row("Spent", sa.spent, sb.spent),
+        row("Invested", sa.invested, sb.invested),
+    ]
+    .join("\n")
+}
+
+/// The /stats report for `cur`, which runs up to `today`, with `prev` (the
+/// period before) for the change.
+pub fn report(
+    cur: &Period,
+    sc: &PeriodSummary,
+    stats: &PeriodStats,
+    today: Date,
+    prev: &Period,
+    sp: &PeriodSummary,
+) -> String {
+    let net = sc.saved - sc.spent;
+    let days = (today.min(cur.end.previous_day().unwrap_or(today)) - cur.start).whole_days() + 1;
+    let mut lines = vec![
+        format!("📊 Stats for {}", cur.label),
+        format!(
+            "Net: {} (saved {}, spent {}, invested {})",
+            format_signed_cents(net),
+            format_cents(sc.saved),
+            format_cents(sc.spent),
+            format_cents(sc.invested)
+        ),
+        format!(
+            "Average per day: {} over {days} days",
+            format_signed_cents(net / days.max(1))
+        ),
+    ];
+    if sc.dividends > 0 {
+        lines.push(format!(
+            "Dividends: {} (not counted as saved)",
+            format_cents(sc.dividends)
+        ));
+    }
+    if stats.kinds.is_empty() {
+        lines.push("No entries yet.".into());
+    } else {
+        let counts: Vec<String> = stats
+            .kinds
+            .iter()
+            .map(|(kind, n)| format!("{n} × {kind}"))
+            .collect();
+        lines.push(format!("Entries: {}", counts.join(", ")));
+    }
+    if let Some(e) = &stats.largest {
+        lines.push(format!(
+            "Largest: {} on {}{}",
+            format_signed_cents(e.amount_cents),
+            e.occurred_at.get(..10).unwrap_or(&e.occurred_at),
+            e.reason
+                .as_ref()
+                .map(|r| format!(" — {r}"))
+                .unwrap_or_default()
+        ));
+    }
+    lines.push(format!(
+        "Net vs {}: {} → {} ({})",
+        prev.label,
+        format_signed_cents(sp.saved - sp.spent),
+        format_signed_cents(net),
+        delta(sp.saved - sp.spent, net)
+    ));
+    lines.join("\n")
+}
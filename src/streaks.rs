@@ -0,0 +1,88 @@
+use time::{Date, Duration, UtcOffset};
+
+/// Parses a UTC offset such as `+2`, `+02:00`, `-5:30` or `UTC+1`.
+pub fn parse_offset(s: &str) -> Option<UtcOffset> {
+    let s = s.trim();
+    let s = s
+        .strip_prefix("UTC")
+        .or_else(|| s.strip_prefix("utc"))
+        .or_else(|| s.strip_prefix("GMT"))
+        .unwrap_or(s);
+    if s.is_empty() {
+        return Some(UtcOffset::UTC);
+    }
+    let (sign, rest) = match s.as_bytes()[0] {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: i8 = hours.parse().ok()?;
+    let minutes: i8 = minutes.parse().ok()?;
+    if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+pub fn format_offset(offset: UtcOffset) -> String {
+    let (h, m, _) = offset.as_hms();
+    let sign = if h < 0 || m < 0 { '-' } else { '+' };
+    format!("UTC{sign}{:02}:{:02}", h.abs(), m.abs())
+}
+
+/// Consecutive days with a save, ending today or, if nothing is saved yet
+/// today, yesterday. `days` must be newest first without duplicates.
+pub fn daily(days: &[Date], today: Date) -> u32 {
+    count_consecutive(days.iter().copied(), today, |d| d.previous_day())
+}
+
+/// Consecutive ISO weeks with a save, ending this week or last week.
+pub fn weekly(days: &[Date], today: Date) -> u32 {
+    let mut weeks: Vec<Date> = days.iter().map(|d| week_start(*d)).collect();
+    weeks.dedup();
+    count_consecutive(weeks.into_iter(), week_start(today), |w| {
+        w.checked_sub(Duration::weeks(1))
+    })
+}
+
+fn week_start(d: Date) -> Date {
+    d - Duration::days(d.weekday().number_days_from_monday().into())
+}
+
+fn count_consecutive(
+    mut periods: impl Iterator<Item = Date>,
+    current: Date,
+    previous: impl Fn(Date) -> Option<Date>,
+) -> u32 {
+    let Some(mut expected) = periods.next() else {
+        return 0;
+    };
+    // The current period may still be empty without breaking the streak.
+    if expected != current && Some(expected) != previous(current) {
+        return 0;
+    }
+    let mut streak = 1;
+    for p in periods {
+        match previous(expected) {
+            Some(prev) if prev == p => {
+                streak += 1;
+                expected = p;
+            }
+            _ => break,
+        }
+    }
+    streak
+}
+
+/// "🔥 12-day streak", with the weekly streak when that is the longer
+/// story; `None` without a current streak.
+pub fn summary(days: &[Date], today: Date) -> Option<String> {
+    let daily = daily(days, today);
+    let weekly = weekly(days, today);
+    match (daily, weekly) {
+        (0, 0) => None,
+        (d, w) if w >= 2 && w * 7 > d => Some(format!("🔥 {d}-day streak, 📅 {w} weeks in a row")),
+        (d, _) => Some(format!("🔥 {d}-day streak")),
+    }
+}
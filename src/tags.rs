@@ -0,0 +1,21 @@
+/// The `#hashtags` of a reason, lowercased and without the `#`, in order
+/// of first appearance. `/save 12.50 lunch #food #work` has `food` and `work`.
+pub fn parse(reason: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for word in reason.split_whitespace() {
+        let Some(tag) = word.strip_prefix('#').filter(|t| !t.is_empty()) else {
+            continue;
+        };
+        let tag = tag.to_lowercase();
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Accepts `#food` or `food` as typed after /query or /stats.
+pub fn normalize(arg: &str) -> Option<String> {
+    let tag = arg.trim().strip_prefix('#').unwrap_or(arg.trim());
+    (!tag.is_empty() && !tag.contains(char::is_whitespace)).then(|| tag.to_lowercase())
+}
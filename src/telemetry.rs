@@ -0,0 +1,32 @@
+use anyhow::Result;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use std::env;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Starts exporting tracing spans over OTLP/gRPC when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set (e.g. `http://jaeger:4317`). Plain
+/// logging keeps going through `pretty_env_logger` either way.
+///
+/// Keep the returned provider and call `shutdown` on exit so buffered spans
+/// are flushed.
+pub fn init() -> Result<Option<TracerProvider>> {
+    if env::var("OTEL_EXPORTER_OTLP_ENDPOINT").map_or(true, |v| v.is_empty()) {
+        return Ok(None);
+    }
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .build()?;
+    let service = env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "telegram-all-in-voo".into());
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", service)]))
+        .build();
+    let filter = EnvFilter::try_from_env("OTEL_TRACES_FILTER")
+        .unwrap_or_else(|_| EnvFilter::new("telegram_all_in_voo=debug,sqlx=info"));
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("telegram-all-in-voo")))
+        .with(filter)
+        .try_init()?;
+    Ok(Some(provider))
+}